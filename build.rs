@@ -0,0 +1,9 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // A vendored protoc so that building doesn't require protobuf to be installed
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+
+    // The generated client needs the 2021 edition prelude, and doks only serves the API anyway
+    tonic_build::configure().build_client(false).compile(&["proto/doks.proto"], &["proto"])?;
+
+    Ok(())
+}
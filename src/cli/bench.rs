@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use tokio_stream::StreamExt;
+
+use crate::cli::config::{DoksConfig, SearchEngineConfig};
+use crate::cli::select_sources;
+use crate::cli::stats::human_size;
+use crate::model::Document;
+use crate::search::{SearchEngine, SearchRequest};
+use crate::sources::DocumentSource;
+use crate::utils::table::format_table;
+
+/// Namespace of the benchmark indexes, so that the actual index is never touched.
+const BENCH_NAMESPACE: &str = "bench";
+
+/// Words of the synthetic documents and queries.
+const VOCABULARY: &[&str] = &[
+    "database", "restart", "replication", "backup", "deploy", "rollback", "incident", "runbook", "alert",
+    "latency", "cluster", "node", "kubernetes", "service", "ingress", "certificate", "rotate", "secret",
+    "vault", "token", "access", "permission", "onboarding", "handbook", "review", "release", "pipeline",
+    "build", "cache", "artifact", "docker", "image", "registry", "network", "firewall", "dns", "proxy",
+    "queue", "kafka", "consumer", "producer", "topic", "partition", "schema", "migration", "index",
+    "query", "timeout", "retry", "budget", "oncall", "escalation", "postmortem", "dashboard", "metric",
+    "log", "trace", "storage", "volume", "snapshot",
+];
+
+pub struct BenchOptions {
+    /// Engine configuration files to compare, the configured engine when empty.
+    pub engines: Vec<PathBuf>,
+    /// Index the documents of this configured source rather than a synthetic corpus.
+    pub source: Option<String>,
+    /// Size of the synthetic corpus.
+    pub documents: usize,
+    /// Queries to measure, sampled from the vocabulary when empty.
+    pub queries: Vec<String>,
+    /// Times each query is run.
+    pub iterations: usize,
+}
+
+#[derive(Debug)]
+struct BenchReport {
+    engine: String,
+    documents: usize,
+    bytes: u64,
+    indexing: Duration,
+    latencies: Vec<Duration>,
+}
+
+/// Indexes a corpus in each engine and measures the indexing throughput and the query latencies.
+/// The engines index into a `bench` namespace which is deleted afterwards.
+pub async fn bench(config: DoksConfig, options: &BenchOptions) -> anyhow::Result<()> {
+    let corpus = match &options.source {
+        Some(id) => fetch_corpus(&config, id).await?,
+        None => synthetic_corpus(options.documents),
+    };
+
+    let queries = match options.queries.is_empty() {
+        true => synthetic_queries(10),
+        false => options.queries.clone(),
+    };
+
+    let mut reports = vec![];
+
+    if options.engines.is_empty() {
+        let name = config.engine.kind();
+        reports.push(bench_engine(name, config.engine, &corpus, &queries, options.iterations).await?);
+    }
+
+    for path in &options.engines {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Couldn't read engine config: {:?}", path))?;
+        let engine: SearchEngineConfig = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid engine config: {:?}", path))?;
+
+        let name = format!("{} ({})", engine.kind(), path.display());
+        reports.push(bench_engine(&name, engine, &corpus, &queries, options.iterations).await?);
+    }
+
+    print_reports(&reports);
+
+    Ok(())
+}
+
+async fn fetch_corpus(config: &DoksConfig, id: &str) -> anyhow::Result<Vec<Document>> {
+    let source: Box<dyn DocumentSource> = select_sources(config, std::slice::from_ref(&id.to_string()))?[0].try_into()?;
+
+    source.fetch().collect::<anyhow::Result<Vec<_>>>().await
+}
+
+async fn bench_engine(
+    name: &str,
+    engine: SearchEngineConfig,
+    corpus: &[Document],
+    queries: &[String],
+    iterations: usize,
+) -> anyhow::Result<BenchReport> {
+    let engine = engine.namespaced(BENCH_NAMESPACE);
+    let search: Box<dyn SearchEngine> = (&engine).try_into()?;
+
+    let result = measure(name, search.as_ref(), corpus, queries, iterations).await;
+
+    // Cleaned up even when the measures failed
+    if let Err(err) = search.purge().await {
+        log::debug!("Couldn't purge the benchmark index: {}", err);
+    }
+    drop(search);
+
+    for path in engine.storage_paths() {
+        if path.is_dir() {
+            std::fs::remove_dir_all(&path)?;
+        } else if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+
+        // The namespace directory, only removed once empty
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::remove_dir(parent);
+        }
+    }
+
+    result
+}
+
+async fn measure(
+    name: &str,
+    search: &dyn SearchEngine,
+    corpus: &[Document],
+    queries: &[String],
+    iterations: usize,
+) -> anyhow::Result<BenchReport> {
+    let started = Instant::now();
+
+    for batch in corpus.chunks(100) {
+        search.index(batch.to_vec()).await?;
+    }
+
+    let indexing = started.elapsed();
+    let mut latencies = vec![];
+
+    for query in queries {
+        for _ in 0..iterations {
+            let started = Instant::now();
+            search.search(&SearchRequest::new(query)).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+            latencies.push(started.elapsed());
+        }
+    }
+
+    latencies.sort();
+
+    Ok(BenchReport {
+        engine: name.to_string(),
+        documents: corpus.len(),
+        bytes: corpus.iter().map(|document| document.content.len() as u64).sum(),
+        indexing,
+        latencies,
+    })
+}
+
+fn print_reports(reports: &[BenchReport]) {
+    let header = ["ENGINE", "DOCUMENTS", "SIZE", "DOCS/S", "MB/S", "P50", "P90", "P99"].map(String::from);
+    let rows = reports.iter().map(|report| {
+        let seconds = report.indexing.as_secs_f64().max(f64::EPSILON);
+        let latency = |p: f64| percentile(&report.latencies, p).map_or("-".to_string(), |d| format!("{:.1}ms", d.as_secs_f64() * 1000.0));
+
+        [
+            report.engine.clone(),
+            report.documents.to_string(),
+            human_size(report.bytes),
+            format!("{:.0}", report.documents as f64 / seconds),
+            format!("{:.2}", report.bytes as f64 / 1_000_000.0 / seconds),
+            latency(50.0),
+            latency(90.0),
+            latency(99.0),
+        ]
+    });
+
+    for line in format_table(&std::iter::once(header).chain(rows).collect::<Vec<_>>()) {
+        println!("{}", line);
+    }
+}
+
+/// Nearest-rank percentile of sorted values.
+fn percentile(sorted: &[Duration], p: f64) -> Option<Duration> {
+    if sorted.is_empty() {
+        return None;
+    }
+
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+/// Deterministic pseudo random numbers (a linear congruential generator) so that runs are comparable.
+fn pseudo_random(seed: u64) -> impl Iterator<Item=usize> {
+    std::iter::successors(Some(seed), |state| Some(state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407)))
+        .skip(1)
+        .map(|state| (state >> 33) as usize)
+}
+
+fn synthetic_corpus(documents: usize) -> Vec<Document> {
+    let mut random = pseudo_random(42);
+
+    (0..documents)
+        .map(|i| {
+            let length = 50 + random.next().unwrap() % 450;
+            let words = (0..length).map(|_| VOCABULARY[random.next().unwrap() % VOCABULARY.len()]).collect::<Vec<_>>();
+
+            Document {
+                id: format!("bench-{}", i),
+                source: "bench".to_string(),
+                title: words[..3].join(" "),
+                link: format!("bench://{}", i),
+                content: words.join(" "),
+                metadata: HashMap::new(),
+                tags: vec![],
+                modified: None,
+            }
+        })
+        .collect()
+}
+
+fn synthetic_queries(count: usize) -> Vec<String> {
+    let mut random = pseudo_random(7);
+
+    (0..count)
+        .map(|_| {
+            let first = VOCABULARY[random.next().unwrap() % VOCABULARY.len()];
+            let second = VOCABULARY[random.next().unwrap() % VOCABULARY.len()];
+            format!("{} {}", first, second)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::cli::bench::{percentile, synthetic_corpus, synthetic_queries};
+
+    #[test]
+    fn test_bench_helpers() {
+        let latencies = (1..=100).map(Duration::from_millis).collect::<Vec<_>>();
+
+        assert_eq!(percentile(&latencies, 50.0), Some(Duration::from_millis(50)));
+        assert_eq!(percentile(&latencies, 99.0), Some(Duration::from_millis(99)));
+        assert_eq!(percentile(&latencies[..1], 90.0), Some(Duration::from_millis(1)));
+        assert_eq!(percentile(&[], 50.0), None);
+
+        let corpus = synthetic_corpus(20);
+        assert_eq!(corpus.len(), 20);
+        assert_eq!(corpus, synthetic_corpus(20));
+        assert!(corpus.iter().all(|document| document.content.split(' ').count() >= 50));
+
+        assert_eq!(synthetic_queries(3), synthetic_queries(3));
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+use crate::cli::config::DoksConfig;
+use crate::cli::queryable_engine;
+use crate::cli::state::data_dir;
+use crate::model::Document;
+use crate::utils::table::format_table;
+
+#[derive(Debug, StructOpt)]
+pub enum BookmarkCommand {
+    /// Bookmarks an indexed document, given its id (the `id` of the search results).
+    Add { id: String },
+    /// Removes a bookmark.
+    Remove { id: String },
+    /// Lists the bookmarked documents.
+    List,
+}
+
+/// The bookmarked documents, by id. The ids of the documents don't change when they are indexed
+/// again, so bookmarks survive reindexing.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Bookmarks {
+    #[serde(default)]
+    pub documents: BTreeMap<String, Bookmark>,
+}
+
+/// What's shown by `doks bookmark list`, as of when the bookmark was added.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub source: String,
+    pub title: String,
+    pub link: String,
+    pub added: DateTime<Utc>,
+}
+
+/// Persists the bookmarks of a namespace in `<data dir>/<namespace>.bookmarks.json`.
+pub struct BookmarkStore {
+    path: PathBuf,
+}
+
+impl BookmarkStore {
+    pub fn new<T: AsRef<Path>>(path: T) -> Self {
+        Self { path: path.as_ref().to_path_buf() }
+    }
+
+    pub fn for_namespace(namespace: &str) -> anyhow::Result<Self> {
+        Ok(Self::new(data_dir()?.join(format!("{}.bookmarks.json", namespace))))
+    }
+
+    pub async fn load(&self) -> anyhow::Result<Bookmarks> {
+        if !self.path.exists() {
+            return Ok(Bookmarks::default());
+        }
+
+        let content = tokio::fs::read_to_string(&self.path).await?;
+
+        serde_json::from_str(&content).with_context(|| format!("Invalid bookmarks file: {:?}", self.path))
+    }
+
+    async fn store(&self, bookmarks: &Bookmarks) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        tokio::fs::write(&self.path, serde_json::to_vec_pretty(bookmarks)?)
+            .await
+            .with_context(|| format!("Couldn't write bookmarks file: {:?}", self.path))
+    }
+
+    pub async fn add(&self, document: &Document) -> anyhow::Result<()> {
+        let mut bookmarks = self.load().await?;
+        let bookmark = Bookmark {
+            source: document.source.clone(),
+            title: document.title.clone(),
+            link: document.link.clone(),
+            added: Utc::now(),
+        };
+        bookmarks.documents.insert(document.id.clone(), bookmark);
+
+        self.store(&bookmarks).await
+    }
+
+    /// Returns whether the document was bookmarked.
+    pub async fn remove(&self, id: &str) -> anyhow::Result<bool> {
+        let mut bookmarks = self.load().await?;
+        let removed = bookmarks.documents.remove(id).is_some();
+
+        if removed {
+            self.store(&bookmarks).await?;
+        }
+
+        Ok(removed)
+    }
+
+    /// The ids of the bookmarked documents, failing when there are none as filtering a search by
+    /// an empty list of ids wouldn't filter anything.
+    pub async fn ids(&self) -> anyhow::Result<Vec<String>> {
+        let ids = self.load().await?.documents.into_keys().collect::<Vec<_>>();
+
+        if ids.is_empty() {
+            bail!("No bookmarks yet (add one with `doks bookmark add <id>`)");
+        }
+
+        Ok(ids)
+    }
+}
+
+pub async fn bookmark_main(config: &DoksConfig, namespace: &str, command: &BookmarkCommand) -> anyhow::Result<()> {
+    let store = BookmarkStore::for_namespace(namespace)?;
+
+    match command {
+        BookmarkCommand::Add { id } => {
+            // Only documents actually indexed can be bookmarked, so that typos don't go unnoticed
+            let search = queryable_engine(config).await?;
+            let document = search
+                .document(id)
+                .await
+                .context("Couldn't look up the document")?
+                .with_context(|| format!("No indexed document with the id: {}", id))?;
+
+            store.add(&document).await?;
+            println!("Bookmarked: {}", document.link);
+        }
+        BookmarkCommand::Remove { id } => {
+            if !store.remove(id).await? {
+                bail!("No bookmark for the document: {}", id);
+            }
+        }
+        BookmarkCommand::List => {
+            let header = ["ID", "SOURCE", "TITLE", "LINK", "ADDED"].map(String::from);
+            let rows = store.load().await?.documents.into_iter().map(|(id, bookmark)| {
+                [id, bookmark.source, bookmark.title, bookmark.link, bookmark.added.format("%Y-%m-%d").to_string()]
+            });
+
+            for line in format_table(&std::iter::once(header).chain(rows).collect::<Vec<_>>()) {
+                println!("{}", line);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tempdir::TempDir;
+
+    use crate::cli::bookmarks::BookmarkStore;
+    use crate::model::Document;
+
+    #[tokio::test]
+    async fn test_bookmark_store() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let store = BookmarkStore::new(root.path().join("doks/default.bookmarks.json"));
+
+        assert!(store.ids().await.is_err());
+
+        let document = |id: &str| Document {
+            id: id.to_string(),
+            source: "docs".to_string(),
+            title: "Runbook".to_string(),
+            link: format!("/docs/{}", id),
+            content: "Restart the database".to_string(),
+            metadata: HashMap::new(),
+            tags: vec![],
+            modified: None,
+        };
+
+        store.add(&document("runbook.md")).await?;
+        store.add(&document("setup.md")).await?;
+        assert_eq!(store.ids().await?, vec!["runbook.md", "setup.md"]);
+        assert_eq!(store.load().await?.documents["setup.md"].link, "/docs/setup.md");
+
+        assert!(store.remove("runbook.md").await?);
+        assert!(!store.remove("runbook.md").await?);
+        assert_eq!(store.ids().await?, vec!["setup.md"]);
+
+        Ok(())
+    }
+}
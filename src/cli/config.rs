@@ -1,24 +1,190 @@
+use std::collections::BTreeMap;
 use std::convert::TryInto;
 use std::path::PathBuf;
+use std::sync::Arc;
 
-use anyhow::bail;
+use anyhow::{bail, Context};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
-use crate::search::SearchEngine;
-use crate::search::tantivy_impl::TantivySearchEngine;
+use crate::cli::state::cache_dir;
+use crate::search::algolia_impl::AlgoliaSearchEngine;
+use crate::search::chunked_impl::ChunkedSearchEngine;
+use crate::search::deduped_impl::DedupedSearchEngine;
+use crate::search::embeddings::EmbeddingProvider;
+use crate::search::embeddings::local::LocalEmbedder;
+use crate::search::embeddings::onnx::OnnxEmbedder;
+use crate::search::embeddings::openai::OpenAiEmbedder;
+use crate::search::es_impl::ElasticSearchEngine;
+use crate::search::hybrid_impl::HybridSearchEngine;
+use crate::search::meili_impl::MeiliSearchEngine;
+use crate::search::multi_impl::MultiSearchEngine;
+use crate::search::opensearch_impl::{AwsCredentials, OpenSearchAuth, OpenSearchEngine};
+use crate::search::postgres_impl::PostgresSearchEngine;
+use crate::search::qdrant_impl::QdrantSearchEngine;
+use crate::search::redis_impl::RedisSearchEngine;
+use crate::search::remote_impl::RemoteSearchEngine;
+use crate::search::{default_limit, SearchEngine};
+use crate::search::semantic_impl::SemanticSearchEngine;
+use crate::search::sonic_impl::SonicSearchEngine;
+use crate::search::sqlite_impl::SqliteSearchEngine;
+use crate::search::language;
+use crate::search::tantivy_impl::{Analyzer, Boosts, CustomField, FieldKind, StopWords, TantivySearchEngine};
+use crate::search::typesense_impl::TypesenseSearchEngine;
+use crate::sources::airtable::{AirtableSource, AirtableTable};
+use crate::sources::asana::AsanaSource;
+use crate::sources::backstage::BackstageSource;
+use crate::sources::discord::{DiscordApiChannels, DiscordChannelLoader, DiscordExportFiles, DiscordSource};
 use crate::sources::DocumentSource;
 use crate::sources::fs::FileSystemDocumentSource;
+use crate::sources::gdocs::GoogleDocsSource;
 use crate::sources::gh::{GithubRepoStaticList, GithubSource, GitRepositoryLister, RepositoryInfo};
+use crate::sources::tagged::TaggedSource;
+use crate::utils::glob::glob_to_regex;
 
-#[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
+/// `$XDG_CONFIG_HOME/doks/config.json` (`~/.config/doks/config.json` by default).
+pub fn default_config_file() -> anyhow::Result<PathBuf> {
+    let config_home = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME").context("HOME is not set")?).join(".config"),
+    };
+
+    Ok(config_home.join("doks").join("config.json"))
+}
+
+/// Reads the config files in order and merges them. Directories stand for the `.json` files they
+/// contain, sorted by name. Sources are added up (their ids must be unique across the files) while
+/// the engine is taken from the last file defining one.
+pub async fn load_config(paths: &[PathBuf]) -> anyhow::Result<DoksConfig> {
+    let mut files = vec![];
+
+    for path in paths {
+        if path.is_dir() {
+            let mut entries = tokio::fs::read_dir(path)
+                .await
+                .with_context(|| format!("Couldn't list config directory: {:?}", path))?;
+            let mut found = vec![];
+
+            while let Some(entry) = entries.next_entry().await? {
+                if entry.path().extension().is_some_and(|extension| extension == "json") {
+                    found.push(entry.path());
+                }
+            }
+
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    let mut config = DoksConfig {
+        sources: vec![],
+        engine: SearchEngineConfig::default(),
+        daemon: DaemonConfig::default(),
+        namespaces: BTreeMap::new(),
+    };
+    let mut defined_in = BTreeMap::new();
+
+    for file in &files {
+        let content = tokio::fs::read_to_string(file)
+            .await
+            .with_context(|| format!("Couldn't read config file: {:?} (run `doks config init` to create one)", file))?;
+        let partial: ConfigFile = serde_json::from_str(&content).with_context(|| format!("Invalid config file: {:?}", file))?;
+
+        for source in partial.sources {
+            if let Some(previous) = defined_in.insert(source.id().to_string(), file) {
+                bail!("Source '{}' is defined in both {:?} and {:?}", source.id(), previous, file)
+            }
+
+            config.sources.push(source);
+        }
+
+        if let Some(engine) = partial.engine {
+            config.engine = engine;
+        }
+
+        config.daemon.schedules.extend(partial.daemon.schedules);
+        config.daemon.default_schedule = partial.daemon.default_schedule.or(config.daemon.default_schedule);
+        config.daemon.webhook_secret_file = partial.daemon.webhook_secret_file.or(config.daemon.webhook_secret_file);
+        config.namespaces.extend(partial.namespaces);
+    }
+
+    Ok(config)
+}
+
+/// One of the files merged by `load_config`: everything is optional so that a file can for example
+/// only add personal sources to a shared config.
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    sources: Vec<SourceConfig>,
+    engine: Option<SearchEngineConfig>,
+    #[serde(default)]
+    daemon: DaemonConfig,
+    #[serde(default)]
+    namespaces: BTreeMap<String, NamespaceConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct DoksConfig {
     pub sources: Vec<SourceConfig>,
     #[serde(default)]
     pub engine: SearchEngineConfig,
+    #[serde(default)]
+    pub daemon: DaemonConfig,
+    /// Sections overriding the sources or the engine when running in a namespace (`-n`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub namespaces: BTreeMap<String, NamespaceConfig>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+impl DoksConfig {
+    /// The config of a namespace: its section (if any) replaces the sources and the engine, and
+    /// the indexes of the top level engine are kept apart from the ones of the other namespaces.
+    pub fn for_namespace(mut self, namespace: &str) -> anyhow::Result<Self> {
+        if !namespace.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') || namespace.is_empty() {
+            bail!("Invalid namespace: '{}' (only letters, digits, '-' and '_' are allowed)", namespace)
+        }
+
+        let section = self.namespaces.remove(namespace).unwrap_or_default();
+
+        if let Some(sources) = section.sources {
+            self.sources = sources;
+        }
+
+        self.engine = match section.engine {
+            Some(engine) => engine,
+            None if namespace == DEFAULT_NAMESPACE => self.engine,
+            None => self.engine.namespaced(namespace),
+        };
+
+        Ok(self)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct NamespaceConfig {
+    pub sources: Option<Vec<SourceConfig>>,
+    /// Used as is, without being isolated from the other namespaces.
+    pub engine: Option<SearchEngineConfig>,
+}
+
+/// Schedules of `doks daemon` by source id, and its webhooks. A schedule is either an interval
+/// (`30m`, `6h`, `1d`...) or a cron expression (`0 3 * * *`).
+#[derive(Debug, Serialize, Deserialize, Default, Eq, PartialEq)]
+pub struct DaemonConfig {
+    #[serde(default)]
+    pub schedules: BTreeMap<String, String>,
+    /// Schedule of the sources that don't have their own.
+    pub default_schedule: Option<String>,
+    /// File containing the secret of the push webhooks (`POST /hooks/<source id>`), which are
+    /// disabled when not set.
+    pub webhook_secret_file: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "source")]
 pub enum SourceConfig {
     #[serde(alias = "github")]
@@ -29,6 +195,32 @@ pub enum SourceConfig {
         include: Vec<String>,
         #[serde(default)]
         exclude: Vec<String>,
+        /// The syntax of the include/exclude patterns (regex by default).
+        #[serde(default)]
+        patterns: PatternSyntax,
+        /// Drops the code blocks of the markdown and AsciiDoc files from the indexed content.
+        #[serde(default)]
+        strip_code_blocks: bool,
+        /// Drops the outputs of the code cells of the Jupyter notebooks from the indexed content.
+        #[serde(default)]
+        strip_notebook_outputs: bool,
+        /// Indexes each section of the markdown and HTML files as its own document, linked to the
+        /// anchor of its heading (`README.md#deployment`).
+        #[serde(default)]
+        sections: bool,
+        /// Files larger than this number of bytes are skipped (no limit by default).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_file_size: Option<u64>,
+        /// Skips the files matched by the `.gitignore` and `.doksignore` files of the indexed
+        /// directories (true by default).
+        #[serde(default = "enabled")]
+        ignore_files: bool,
+        /// Multiplies the scores of the documents of the source at query time (1 by default).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        boost: Option<f32>,
+        /// Tags added to all the documents of the source.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tags: Vec<String>,
     },
     #[serde(alias = "fs")]
     FileSystem {
@@ -38,6 +230,97 @@ pub enum SourceConfig {
         include: Vec<String>,
         #[serde(default)]
         exclude: Vec<String>,
+        /// The syntax of the include/exclude patterns (regex by default).
+        #[serde(default)]
+        patterns: PatternSyntax,
+        /// Drops the code blocks of the markdown and AsciiDoc files from the indexed content.
+        #[serde(default)]
+        strip_code_blocks: bool,
+        /// Drops the outputs of the code cells of the Jupyter notebooks from the indexed content.
+        #[serde(default)]
+        strip_notebook_outputs: bool,
+        /// Indexes each section of the markdown and HTML files as its own document, linked to the
+        /// anchor of its heading (`README.md#deployment`).
+        #[serde(default)]
+        sections: bool,
+        /// Files larger than this number of bytes are skipped (no limit by default).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_file_size: Option<u64>,
+        /// Skips the files matched by the `.gitignore` and `.doksignore` files of the indexed
+        /// directories (true by default).
+        #[serde(default = "enabled")]
+        ignore_files: bool,
+        /// Multiplies the scores of the documents of the source at query time (1 by default).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        boost: Option<f32>,
+        /// Tags added to all the documents of the source.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tags: Vec<String>,
+    },
+    #[serde(alias = "asana")]
+    Asana {
+        id: String,
+        projects: Vec<String>,
+        token_file: String,
+        endpoint: Option<String>,
+        /// Multiplies the scores of the documents of the source at query time (1 by default).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        boost: Option<f32>,
+        /// Tags added to all the documents of the source.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tags: Vec<String>,
+    },
+    #[serde(alias = "discord")]
+    Discord {
+        id: String,
+        channels: DiscordChannelsConfig,
+        /// Multiplies the scores of the documents of the source at query time (1 by default).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        boost: Option<f32>,
+        /// Tags added to all the documents of the source.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tags: Vec<String>,
+    },
+    #[serde(alias = "gdocs")]
+    GoogleDocs {
+        id: String,
+        documents: Vec<String>,
+        token_file: String,
+        endpoint: Option<String>,
+        /// Multiplies the scores of the documents of the source at query time (1 by default).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        boost: Option<f32>,
+        /// Tags added to all the documents of the source.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tags: Vec<String>,
+    },
+    #[serde(alias = "airtable")]
+    Airtable {
+        id: String,
+        tables: Vec<AirtableTableConfig>,
+        token_file: String,
+        endpoint: Option<String>,
+        /// Multiplies the scores of the documents of the source at query time (1 by default).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        boost: Option<f32>,
+        /// Tags added to all the documents of the source.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tags: Vec<String>,
+    },
+    #[serde(alias = "backstage")]
+    Backstage {
+        id: String,
+        endpoint: String,
+        frontend: Option<String>,
+        #[serde(default)]
+        kinds: Vec<String>,
+        token_file: Option<String>,
+        /// Multiplies the scores of the documents of the source at query time (1 by default).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        boost: Option<f32>,
+        /// Tags added to all the documents of the source.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tags: Vec<String>,
     },
 }
 
@@ -46,6 +329,110 @@ impl SourceConfig {
         match self {
             SourceConfig::Github { ref id, .. } => id.as_str(),
             SourceConfig::FileSystem { ref id, .. } => id.as_str(),
+            SourceConfig::Asana { ref id, .. } => id.as_str(),
+            SourceConfig::Discord { ref id, .. } => id.as_str(),
+            SourceConfig::GoogleDocs { ref id, .. } => id.as_str(),
+            SourceConfig::Airtable { ref id, .. } => id.as_str(),
+            SourceConfig::Backstage { ref id, .. } => id.as_str(),
+        }
+    }
+
+    pub fn boost(&self) -> Option<f32> {
+        match self {
+            SourceConfig::Github { boost, .. }
+            | SourceConfig::FileSystem { boost, .. }
+            | SourceConfig::Asana { boost, .. }
+            | SourceConfig::Discord { boost, .. }
+            | SourceConfig::GoogleDocs { boost, .. }
+            | SourceConfig::Airtable { boost, .. }
+            | SourceConfig::Backstage { boost, .. } => *boost,
+        }
+    }
+
+    pub fn tags(&self) -> &[String] {
+        match self {
+            SourceConfig::Github { tags, .. }
+            | SourceConfig::FileSystem { tags, .. }
+            | SourceConfig::Asana { tags, .. }
+            | SourceConfig::Discord { tags, .. }
+            | SourceConfig::GoogleDocs { tags, .. }
+            | SourceConfig::Airtable { tags, .. }
+            | SourceConfig::Backstage { tags, .. } => tags,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SourceConfig::Github { .. } => "github",
+            SourceConfig::FileSystem { .. } => "fs",
+            SourceConfig::Asana { .. } => "asana",
+            SourceConfig::Discord { .. } => "discord",
+            SourceConfig::GoogleDocs { .. } => "gdocs",
+            SourceConfig::Airtable { .. } => "airtable",
+            SourceConfig::Backstage { .. } => "backstage",
+        }
+    }
+
+    /// The same source restricted to one of its repositories (`owner/name`), `None` if the source
+    /// doesn't list the repository.
+    pub fn for_repository(&self, repository: &str) -> Option<SourceConfig> {
+        match self {
+            SourceConfig::Github {
+                id,
+                repositories: GithubRepositoriesConfig::FromList { server, transport, list, endpoint, token_file },
+                include,
+                exclude,
+                patterns,
+                strip_code_blocks,
+                strip_notebook_outputs,
+                sections,
+                max_file_size,
+                ignore_files,
+                boost,
+                tags,
+            } => {
+                let repo = list.iter().find(|repo| repo.name.eq_ignore_ascii_case(repository))?;
+
+                Some(SourceConfig::Github {
+                    id: id.clone(),
+                    repositories: GithubRepositoriesConfig::FromList {
+                        server: server.clone(),
+                        transport: transport.clone(),
+                        list: vec![repo.clone()],
+                        endpoint: endpoint.clone(),
+                        token_file: token_file.clone(),
+                    },
+                    include: include.clone(),
+                    exclude: exclude.clone(),
+                    patterns: *patterns,
+                    strip_code_blocks: *strip_code_blocks,
+                    strip_notebook_outputs: *strip_notebook_outputs,
+                    sections: *sections,
+                    max_file_size: *max_file_size,
+                    ignore_files: *ignore_files,
+                    boost: *boost,
+                    tags: tags.clone(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Base url of the API the source fetches documents from (if any).
+    pub fn endpoint(&self) -> Option<String> {
+        let endpoint = |endpoint: &Option<String>, default: &str| {
+            Some(endpoint.as_deref().unwrap_or(default).to_string())
+        };
+
+        match self {
+            SourceConfig::Asana { endpoint: e, .. } => endpoint(e, "https://app.asana.com/api/1.0"),
+            SourceConfig::GoogleDocs { endpoint: e, .. } => endpoint(e, "https://docs.googleapis.com/v1"),
+            SourceConfig::Airtable { endpoint: e, .. } => endpoint(e, "https://api.airtable.com/v0"),
+            SourceConfig::Discord { channels: DiscordChannelsConfig::FromApi { endpoint: e, .. }, .. } => {
+                endpoint(e, "https://discord.com/api/v10")
+            }
+            SourceConfig::Backstage { endpoint, .. } => Some(endpoint.to_string()),
+            _ => None,
         }
     }
 }
@@ -59,6 +446,10 @@ pub enum GithubRepositoriesConfig {
         #[serde(default)]
         transport: GitCloneTransport,
         list: Vec<GithubRepo>,
+        /// The REST API the topics of the repositories are fetched from. Defaults to the one of
+        /// the server.
+        endpoint: Option<String>,
+        token_file: Option<String>,
     },
 
     #[serde(alias = "api")]
@@ -71,6 +462,33 @@ pub enum GithubRepositoriesConfig {
 }
 
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "from")]
+pub enum DiscordChannelsConfig {
+    #[serde(alias = "export")]
+    FromExport {
+        paths: Vec<String>,
+    },
+
+    #[serde(alias = "api")]
+    FromApi {
+        channels: Vec<String>,
+        token_file: String,
+        endpoint: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct AirtableTableConfig {
+    base: String,
+    table: String,
+    view: Option<String>,
+    title_field: String,
+    content_fields: Vec<String>,
+    #[serde(default)]
+    metadata_fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub struct GithubRepo {
     name: String,
     folder: Option<String>,
@@ -81,12 +499,41 @@ pub struct GithubRepo {
     exclude: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
+impl GithubRepo {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), folder: None, branch: None, include: vec![], exclude: vec![] }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub enum GitCloneTransport {
     Ssh,
     Https,
 }
 
+/// How the include/exclude patterns of the filesystem and GitHub sources match the files.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PatternSyntax {
+    /// Regexes searched in the absolute path of the files (`.*\.md$`).
+    #[default]
+    Regex,
+    /// Globs matching the path of the files relative to the indexed directory (`**/*.md`, `docs/**`).
+    Glob,
+}
+
+impl PatternSyntax {
+    pub fn compile(&self, patterns: &[String]) -> anyhow::Result<Vec<Regex>> {
+        patterns
+            .iter()
+            .map(|pattern| match self {
+                PatternSyntax::Regex => Regex::new(pattern),
+                PatternSyntax::Glob => Regex::new(&format!("^{}$", glob_to_regex(pattern))),
+            }.with_context(|| format!("Invalid pattern: {}", pattern)))
+            .collect()
+    }
+}
+
 impl Default for GitCloneTransport {
     fn default() -> Self {
         GitCloneTransport::Ssh
@@ -97,22 +544,660 @@ impl Default for GitCloneTransport {
 #[serde(tag = "use")]
 pub enum SearchEngineConfig {
     #[serde(alias = "tantivy")]
-    Tantivy { path: PathBuf }
+    Tantivy {
+        path: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        analyzer: Option<AnalyzerConfig>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        boosts: Option<BoostsConfig>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        limits: Option<LimitsConfig>,
+        /// Fields added to the schema, filled with the metadata values of the same name.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        fields: Vec<FieldConfig>,
+    },
+    #[serde(alias = "in-memory")]
+    InMemory,
+    #[serde(alias = "elasticsearch")]
+    Elasticsearch {
+        endpoint: String,
+        index: Option<String>,
+        username: Option<String>,
+        password_file: Option<String>,
+    },
+    #[serde(alias = "opensearch")]
+    OpenSearch {
+        endpoint: String,
+        index: Option<String>,
+        auth: Option<OpenSearchAuthConfig>,
+    },
+    #[serde(alias = "meilisearch")]
+    Meilisearch {
+        endpoint: String,
+        index: Option<String>,
+        api_key_file: Option<String>,
+    },
+    #[serde(alias = "sqlite")]
+    Sqlite { path: PathBuf },
+    #[serde(alias = "postgres")]
+    Postgres {
+        url: String,
+        table: Option<String>,
+        language: Option<String>,
+    },
+    #[serde(alias = "semantic")]
+    Semantic {
+        path: PathBuf,
+        embeddings: EmbeddingsConfig,
+        chunk_size: Option<usize>,
+    },
+    #[serde(alias = "qdrant")]
+    Qdrant {
+        endpoint: String,
+        collection: Option<String>,
+        api_key_file: Option<String>,
+        embeddings: EmbeddingsConfig,
+        chunk_size: Option<usize>,
+    },
+    #[serde(alias = "hybrid")]
+    Hybrid {
+        keyword: Box<SearchEngineConfig>,
+        vector: Box<SearchEngineConfig>,
+        k: Option<u32>,
+    },
+    #[serde(alias = "typesense")]
+    Typesense {
+        endpoint: String,
+        collection: Option<String>,
+        api_key_file: String,
+    },
+    #[serde(alias = "algolia")]
+    Algolia {
+        application_id: String,
+        api_key_file: String,
+        index: Option<String>,
+    },
+    #[serde(alias = "sonic")]
+    Sonic {
+        address: String,
+        password_file: String,
+        collection: Option<String>,
+        store: PathBuf,
+    },
+    #[serde(alias = "remote")]
+    Remote {
+        endpoint: String,
+        token_file: Option<String>,
+    },
+    #[serde(alias = "redis")]
+    Redis {
+        url: String,
+        index: Option<String>,
+    },
+    /// Splits the documents larger than `size` characters (4000 by default) in chunks overlapping
+    /// by `overlap` characters (400 by default) before indexing them in `engine`.
+    #[serde(alias = "chunked")]
+    Chunked {
+        engine: Box<SearchEngineConfig>,
+        size: Option<usize>,
+        overlap: Option<usize>,
+    },
+    /// Indexes a single copy of the documents with the same content in `engine`, the links of the
+    /// other copies being listed in its `duplicate_links` metadata.
+    #[serde(alias = "deduplicated")]
+    Deduplicated {
+        engine: Box<SearchEngineConfig>,
+    },
+    /// Writes to all the engines and queries the one at the `primary` position (first by default).
+    #[serde(alias = "multi")]
+    Multi {
+        engines: Vec<SearchEngineConfig>,
+        primary: Option<usize>,
+    },
+}
+
+/// How the tantivy engine tokenizes the title and content of the documents. The documents must be
+/// indexed again after changing it.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct AnalyzerConfig {
+    /// Language of the stemmer (`english`, `french`...), no stemming by default.
+    pub stemmer: Option<String>,
+    #[serde(default = "enabled")]
+    pub lowercase: bool,
+    /// Replaces the accented letters by their ASCII equivalent, so that `cafe` finds `café`.
+    #[serde(default)]
+    pub ascii_folding: bool,
+    /// Words left out of the index and the queries, none by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_words: Option<StopWordsConfig>,
+    /// Words of the queries also searched as other words or phrases, e.g. `k8s: [kubernetes]`.
+    /// Applied to the queries only: changing them doesn't require indexing again.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub synonyms: BTreeMap<String, Vec<String>>,
+}
+
+/// How much the matches in each field weigh in the score of the tantivy engine: with a title
+/// boost of 3, a word found in the title counts as much as found three times in the content.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct BoostsConfig {
+    #[serde(default = "no_boost")]
+    pub title: f32,
+    #[serde(default = "no_boost")]
+    pub content: f32,
+}
+
+// The boosts are compared like the rest of the config, NaN isn't a valid boost anyway
+impl Eq for BoostsConfig {}
+
+fn no_boost() -> f32 {
+    1.0
+}
+
+impl TryInto<Boosts> for &BoostsConfig {
+    type Error = anyhow::Error;
+
+    fn try_into(self) -> Result<Boosts, Self::Error> {
+        for (field, boost) in [("title", self.title), ("content", self.content)] {
+            if !boost.is_finite() || boost <= 0.0 {
+                bail!("Invalid {} boost: {} (expected a positive number)", field, boost)
+            }
+        }
+
+        Ok(Boosts { title: self.title, content: self.content })
+    }
+}
+
+/// The number of results of the searches of the tantivy engine: `default` when the search doesn't
+/// give one (10 by default), and at most `max` (no maximum by default) to cap the expensive queries
+/// of a shared server.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub struct LimitsConfig {
+    #[serde(default = "default_limit")]
+    pub default: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<usize>,
+}
+
+impl LimitsConfig {
+    pub fn check(&self) -> anyhow::Result<()> {
+        match self.max {
+            _ if self.default == 0 => bail!("Invalid default limit: 0 (expected a positive number)"),
+            Some(0) => bail!("Invalid max limit: 0 (expected a positive number)"),
+            Some(max) if max < self.default => bail!("The default limit ({}) is above the max limit ({})", self.default, max),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum StopWordsConfig {
+    /// The common words of a language (only `english` is built in).
+    Language(String),
+    Words(Vec<String>),
+}
+
+/// A field of the tantivy schema declared in the config (e.g. `team`, `confidentiality`), filled
+/// with the metadata value of the same name that the sources give (frontmatter, Backstage...). The
+/// documents must be indexed again after changing the fields.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct FieldConfig {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: FieldTypeConfig,
+    #[serde(default = "enabled")]
+    pub stored: bool,
+    #[serde(default = "enabled")]
+    pub indexed: bool,
+    /// Column-oriented storage of the numeric fields, for sorting and aggregating.
+    #[serde(default)]
+    pub fast: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldTypeConfig {
+    /// Words, searched along with the title and content.
+    Text,
+    /// A single value, such as an identifier or a team name.
+    String,
+    I64,
+    U64,
+    F64,
+}
+
+impl From<&FieldConfig> for CustomField {
+    fn from(config: &FieldConfig) -> Self {
+        CustomField {
+            name: config.name.clone(),
+            kind: match config.kind {
+                FieldTypeConfig::Text => FieldKind::Text,
+                FieldTypeConfig::String => FieldKind::String,
+                FieldTypeConfig::I64 => FieldKind::I64,
+                FieldTypeConfig::U64 => FieldKind::U64,
+                FieldTypeConfig::F64 => FieldKind::F64,
+            },
+            stored: config.stored,
+            indexed: config.indexed,
+            fast: config.fast,
+        }
+    }
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl TryInto<Analyzer> for &AnalyzerConfig {
+    type Error = anyhow::Error;
+
+    fn try_into(self) -> Result<Analyzer, Self::Error> {
+        let stemmer = self.stemmer
+            .as_deref()
+            .map(|name| language::stemmer(name).with_context(|| format!("Unknown stemmer language: {}", name)))
+            .transpose()?;
+
+        let stop_words = match &self.stop_words {
+            Some(StopWordsConfig::Language(language)) if language.eq_ignore_ascii_case("english") => Some(StopWords::English),
+            Some(StopWordsConfig::Language(language)) => bail!("No built-in stop words for {}: list them instead", language),
+            Some(StopWordsConfig::Words(words)) if words.is_empty() => None,
+            Some(StopWordsConfig::Words(words)) => Some(StopWords::Custom(words.clone())),
+            None => None,
+        };
+
+        Ok(Analyzer {
+            stemmer,
+            lowercase: self.lowercase,
+            ascii_folding: self.ascii_folding,
+            stop_words,
+            synonyms: self.synonyms.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "type")]
+pub enum OpenSearchAuthConfig {
+    #[serde(alias = "basic")]
+    Basic {
+        username: String,
+        password_file: String,
+    },
+    /// Credentials are read from the standard `AWS_*` environment variables.
+    #[serde(alias = "sigv4")]
+    SigV4 {
+        region: String,
+        service: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "provider")]
+pub enum EmbeddingsConfig {
+    /// BERT-like model in the safetensors format, run with candle.
+    #[serde(alias = "local")]
+    Local { model_dir: PathBuf },
+    /// ONNX export of a BERT-like model, run with the ONNX runtime.
+    #[serde(alias = "onnx")]
+    Onnx { model_dir: PathBuf },
+    /// Any endpoint compatible with the OpenAI embeddings API.
+    #[serde(alias = "openai")]
+    OpenAi {
+        endpoint: Option<String>,
+        model: String,
+        api_key_file: Option<String>,
+        dimensions: Option<usize>,
+    },
+}
+
+impl SearchEngineConfig {
+    /// The number of results of the searches not giving one.
+    pub fn default_limit(&self) -> usize {
+        match self {
+            SearchEngineConfig::Tantivy { limits: Some(limits), .. } => limits.default,
+            SearchEngineConfig::Chunked { engine, .. } | SearchEngineConfig::Deduplicated { engine } => engine.default_limit(),
+            SearchEngineConfig::Multi { engines, primary } => {
+                engines.get(primary.unwrap_or(0)).map_or_else(default_limit, SearchEngineConfig::default_limit)
+            }
+            _ => default_limit(),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SearchEngineConfig::Tantivy { .. } => "tantivy",
+            SearchEngineConfig::InMemory => "in-memory",
+            SearchEngineConfig::Elasticsearch { .. } => "elasticsearch",
+            SearchEngineConfig::OpenSearch { .. } => "opensearch",
+            SearchEngineConfig::Meilisearch { .. } => "meilisearch",
+            SearchEngineConfig::Sqlite { .. } => "sqlite",
+            SearchEngineConfig::Postgres { .. } => "postgres",
+            SearchEngineConfig::Semantic { .. } => "semantic",
+            SearchEngineConfig::Qdrant { .. } => "qdrant",
+            SearchEngineConfig::Hybrid { .. } => "hybrid",
+            SearchEngineConfig::Typesense { .. } => "typesense",
+            SearchEngineConfig::Algolia { .. } => "algolia",
+            SearchEngineConfig::Sonic { .. } => "sonic",
+            SearchEngineConfig::Remote { .. } => "remote",
+            SearchEngineConfig::Redis { .. } => "redis",
+            SearchEngineConfig::Chunked { .. } => "chunked",
+            SearchEngineConfig::Deduplicated { .. } => "deduplicated",
+            SearchEngineConfig::Multi { .. } => "multi",
+        }
+    }
+
+    /// Files that must already exist for the engine to hold an index. Opening the engine on a
+    /// missing path would silently create a new empty index.
+    pub fn local_paths(&self) -> Vec<PathBuf> {
+        match self {
+            SearchEngineConfig::Tantivy { path, .. } => vec![path.join("meta.json")],
+            SearchEngineConfig::Sqlite { path } | SearchEngineConfig::Semantic { path, .. } => vec![path.clone()],
+            SearchEngineConfig::Sonic { store, .. } => vec![store.clone()],
+            SearchEngineConfig::Hybrid { keyword, vector, .. } => {
+                keyword.local_paths().into_iter().chain(vector.local_paths()).collect()
+            }
+            SearchEngineConfig::Chunked { engine, .. } | SearchEngineConfig::Deduplicated { engine } => engine.local_paths(),
+            SearchEngineConfig::Multi { engines, .. } => engines.iter().flat_map(|e| e.local_paths()).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The same engine storing its documents apart: `<parent>/<namespace>/<name>` for local
+    /// engines, and a suffixed index (or collection, or table) for the others.
+    pub fn namespaced(self, namespace: &str) -> Self {
+        let path = |path: PathBuf| match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => parent.join(namespace).join(name),
+            _ => path.join(namespace),
+        };
+        let name = |name: Option<String>| Some(format!("{}-{}", name.as_deref().unwrap_or("doks"), namespace));
+
+        match self {
+            SearchEngineConfig::Tantivy { path: p, analyzer, boosts, limits, fields } => {
+                SearchEngineConfig::Tantivy { path: path(p), analyzer, boosts, limits, fields }
+            }
+            SearchEngineConfig::Sqlite { path: p } => SearchEngineConfig::Sqlite { path: path(p) },
+            SearchEngineConfig::Semantic { path: p, embeddings, chunk_size } => {
+                SearchEngineConfig::Semantic { path: path(p), embeddings, chunk_size }
+            }
+            SearchEngineConfig::Elasticsearch { endpoint, index, username, password_file } => {
+                SearchEngineConfig::Elasticsearch { endpoint, index: name(index), username, password_file }
+            }
+            SearchEngineConfig::OpenSearch { endpoint, index, auth } => {
+                SearchEngineConfig::OpenSearch { endpoint, index: name(index), auth }
+            }
+            SearchEngineConfig::Meilisearch { endpoint, index, api_key_file } => {
+                SearchEngineConfig::Meilisearch { endpoint, index: name(index), api_key_file }
+            }
+            SearchEngineConfig::Postgres { url, table, language } => {
+                let table = format!("{}_{}", table.as_deref().unwrap_or("doks_documents"), namespace.replace('-', "_"));
+                SearchEngineConfig::Postgres { url, table: Some(table), language }
+            }
+            SearchEngineConfig::Qdrant { endpoint, collection, api_key_file, embeddings, chunk_size } => {
+                SearchEngineConfig::Qdrant { endpoint, collection: name(collection), api_key_file, embeddings, chunk_size }
+            }
+            SearchEngineConfig::Typesense { endpoint, collection, api_key_file } => {
+                SearchEngineConfig::Typesense { endpoint, collection: name(collection), api_key_file }
+            }
+            SearchEngineConfig::Algolia { application_id, api_key_file, index } => {
+                SearchEngineConfig::Algolia { application_id, api_key_file, index: name(index) }
+            }
+            SearchEngineConfig::Sonic { address, password_file, collection, store } => {
+                SearchEngineConfig::Sonic { address, password_file, collection: name(collection), store: path(store) }
+            }
+            SearchEngineConfig::Redis { url, index } => SearchEngineConfig::Redis { url, index: name(index) },
+            SearchEngineConfig::Hybrid { keyword, vector, k } => SearchEngineConfig::Hybrid {
+                keyword: Box::new(keyword.namespaced(namespace)),
+                vector: Box::new(vector.namespaced(namespace)),
+                k,
+            },
+            SearchEngineConfig::Chunked { engine, size, overlap } => SearchEngineConfig::Chunked {
+                engine: Box::new(engine.namespaced(namespace)),
+                size,
+                overlap,
+            },
+            SearchEngineConfig::Deduplicated { engine } => SearchEngineConfig::Deduplicated { engine: Box::new(engine.namespaced(namespace)) },
+            SearchEngineConfig::Multi { engines, primary } => SearchEngineConfig::Multi {
+                engines: engines.into_iter().map(|engine| engine.namespaced(namespace)).collect(),
+                primary,
+            },
+            // Nothing is persisted, or the server decides where the documents go
+            SearchEngineConfig::InMemory | SearchEngineConfig::Remote { .. } => self,
+        }
+    }
+
+    /// Files or directories where local engines store their data.
+    pub fn storage_paths(&self) -> Vec<PathBuf> {
+        match self {
+            SearchEngineConfig::Tantivy { path, .. } | SearchEngineConfig::Sqlite { path } => vec![path.clone()],
+            SearchEngineConfig::Semantic { path, .. } => vec![path.clone()],
+            SearchEngineConfig::Sonic { store, .. } => vec![store.clone()],
+            SearchEngineConfig::Hybrid { keyword, vector, .. } => {
+                keyword.storage_paths().into_iter().chain(vector.storage_paths()).collect()
+            }
+            SearchEngineConfig::Chunked { engine, .. } | SearchEngineConfig::Deduplicated { engine } => engine.storage_paths(),
+            SearchEngineConfig::Multi { engines, .. } => engines.iter().flat_map(|e| e.storage_paths()).collect(),
+            _ => vec![],
+        }
+    }
 }
 
 impl Default for SearchEngineConfig {
     fn default() -> Self {
-        SearchEngineConfig::Tantivy { path: PathBuf::from("/tmp/doks_index") }
+        SearchEngineConfig::Tantivy { path: PathBuf::from("/tmp/doks_index"), analyzer: None, boosts: None, limits: None, fields: vec![] }
     }
 }
 
+pub(crate) fn read_token_file(path: &str) -> anyhow::Result<String> {
+    let token = std::fs::read_to_string(path)
+        .with_context(|| format!("Couldn't read token file: {}", path))?;
+
+    Ok(token.trim().to_string())
+}
+
 impl TryInto<Box<dyn SearchEngine>> for &SearchEngineConfig {
     type Error = anyhow::Error;
 
     fn try_into(self) -> Result<Box<dyn SearchEngine>, Self::Error> {
         match self {
-            SearchEngineConfig::Tantivy { path } => {
-                Ok(Box::new(TantivySearchEngine::new(path)?))
+            SearchEngineConfig::Tantivy { path, analyzer, boosts, limits, fields } => {
+                let analyzer = analyzer.as_ref().map(TryInto::try_into).transpose()?.unwrap_or_default();
+                let mut engine = TantivySearchEngine::open(path, &fields.iter().map(CustomField::from).collect::<Vec<_>>(), &analyzer)?;
+
+                if let Some(boosts) = boosts {
+                    engine = engine.with_boosts(boosts.try_into()?);
+                }
+
+                if let Some(limits) = limits {
+                    limits.check()?;
+
+                    if let Some(max) = limits.max {
+                        engine = engine.with_max_results(max);
+                    }
+                }
+
+                Ok(Box::new(engine))
+            }
+            SearchEngineConfig::InMemory => {
+                Ok(Box::new(TantivySearchEngine::in_memory()?))
+            }
+            SearchEngineConfig::Elasticsearch { endpoint, index, username, password_file } => {
+                let credentials = match (username, password_file) {
+                    (Some(username), Some(password_file)) => Some((username.to_string(), read_token_file(password_file)?)),
+                    (None, None) => None,
+                    _ => bail!("Both 'username' and 'password_file' must be set to use basic authentication"),
+                };
+
+                Ok(
+                    Box::new(
+                        ElasticSearchEngine::new(
+                            endpoint,
+                            index.as_deref().unwrap_or("doks"),
+                            credentials,
+                        )
+                    )
+                )
+            }
+            SearchEngineConfig::OpenSearch { endpoint, index, auth } => {
+                let auth = match auth {
+                    None => OpenSearchAuth::None,
+                    Some(OpenSearchAuthConfig::Basic { username, password_file }) => OpenSearchAuth::Basic {
+                        username: username.to_string(),
+                        password: read_token_file(password_file)?,
+                    },
+                    Some(OpenSearchAuthConfig::SigV4 { region, service }) => OpenSearchAuth::SigV4(AwsCredentials {
+                        region: region.to_string(),
+                        service: service.as_deref().unwrap_or("es").to_string(),
+                        access_key: std::env::var("AWS_ACCESS_KEY_ID").context("AWS_ACCESS_KEY_ID is not set")?,
+                        secret_key: std::env::var("AWS_SECRET_ACCESS_KEY").context("AWS_SECRET_ACCESS_KEY is not set")?,
+                        session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+                    }),
+                };
+
+                Ok(Box::new(OpenSearchEngine::new(endpoint, index.as_deref().unwrap_or("doks"), auth)))
+            }
+            SearchEngineConfig::Meilisearch { endpoint, index, api_key_file } => {
+                Ok(
+                    Box::new(
+                        MeiliSearchEngine::new(
+                            endpoint,
+                            index.as_deref().unwrap_or("doks"),
+                            api_key_file.as_ref().map(|f| read_token_file(f)).transpose()?,
+                        )
+                    )
+                )
+            }
+            SearchEngineConfig::Sqlite { path } => {
+                Ok(Box::new(SqliteSearchEngine::new(path)?))
+            }
+            SearchEngineConfig::Postgres { url, table, language } => {
+                Ok(
+                    Box::new(
+                        PostgresSearchEngine::new(
+                            url,
+                            table.as_deref().unwrap_or("doks_documents"),
+                            language.as_deref().unwrap_or("english"),
+                        )?
+                    )
+                )
+            }
+            SearchEngineConfig::Semantic { path, embeddings, chunk_size } => {
+                Ok(Box::new(SemanticSearchEngine::new(path, embeddings.try_into()?, chunk_size.unwrap_or(200))?))
+            }
+            SearchEngineConfig::Qdrant { endpoint, collection, api_key_file, embeddings, chunk_size } => {
+                Ok(
+                    Box::new(
+                        QdrantSearchEngine::new(
+                            endpoint,
+                            collection.as_deref().unwrap_or("doks"),
+                            api_key_file.as_ref().map(|f| read_token_file(f)).transpose()?,
+                            embeddings.try_into()?,
+                            chunk_size.unwrap_or(200),
+                        )
+                    )
+                )
+            }
+            SearchEngineConfig::Hybrid { keyword, vector, k } => {
+                Ok(
+                    Box::new(
+                        HybridSearchEngine {
+                            keyword: keyword.as_ref().try_into()?,
+                            vector: vector.as_ref().try_into()?,
+                            k: k.unwrap_or(60),
+                        }
+                    )
+                )
+            }
+            SearchEngineConfig::Typesense { endpoint, collection, api_key_file } => {
+                Ok(
+                    Box::new(
+                        TypesenseSearchEngine::new(
+                            endpoint,
+                            collection.as_deref().unwrap_or("doks"),
+                            read_token_file(api_key_file)?,
+                        )
+                    )
+                )
+            }
+            SearchEngineConfig::Algolia { application_id, api_key_file, index } => {
+                Ok(
+                    Box::new(
+                        AlgoliaSearchEngine::new(
+                            application_id,
+                            read_token_file(api_key_file)?,
+                            index.as_deref().unwrap_or("doks"),
+                        )
+                    )
+                )
+            }
+            SearchEngineConfig::Sonic { address, password_file, collection, store } => {
+                Ok(
+                    Box::new(
+                        SonicSearchEngine::new(
+                            address,
+                            read_token_file(password_file)?,
+                            collection.as_deref().unwrap_or("doks"),
+                            store,
+                        )?
+                    )
+                )
+            }
+            SearchEngineConfig::Remote { endpoint, token_file } => {
+                Ok(
+                    Box::new(
+                        RemoteSearchEngine::new(
+                            endpoint,
+                            token_file.as_ref().map(|f| read_token_file(f)).transpose()?,
+                        )
+                    )
+                )
+            }
+            SearchEngineConfig::Redis { url, index } => {
+                Ok(Box::new(RedisSearchEngine::new(url, index.as_deref().unwrap_or("doks"))?))
+            }
+            SearchEngineConfig::Chunked { engine, size, overlap } => {
+                Ok(
+                    Box::new(
+                        ChunkedSearchEngine::new(
+                            engine.as_ref().try_into()?,
+                            size.unwrap_or(4000),
+                            overlap.unwrap_or(400),
+                        )?
+                    )
+                )
+            }
+            SearchEngineConfig::Deduplicated { engine } => Ok(Box::new(DedupedSearchEngine::new(engine.as_ref().try_into()?))),
+            SearchEngineConfig::Multi { engines, primary } => {
+                Ok(
+                    Box::new(
+                        MultiSearchEngine::new(
+                            engines.iter().map(|e| e.try_into()).collect::<anyhow::Result<Vec<_>>>()?,
+                            primary.unwrap_or(0),
+                        )?
+                    )
+                )
+            }
+        }
+    }
+}
+
+impl TryInto<Arc<dyn EmbeddingProvider>> for &EmbeddingsConfig {
+    type Error = anyhow::Error;
+
+    fn try_into(self) -> Result<Arc<dyn EmbeddingProvider>, Self::Error> {
+        match self {
+            EmbeddingsConfig::Local { model_dir } => Ok(Arc::new(LocalEmbedder::load(model_dir)?)),
+            EmbeddingsConfig::Onnx { model_dir } => Ok(Arc::new(OnnxEmbedder::load(model_dir)?)),
+            EmbeddingsConfig::OpenAi { endpoint, model, api_key_file, dimensions } => {
+                Ok(
+                    Arc::new(
+                        OpenAiEmbedder::new(
+                            endpoint.as_deref().unwrap_or("https://api.openai.com/v1"),
+                            model,
+                            api_key_file.as_ref().map(|f| read_token_file(f)).transpose()?,
+                            *dimensions,
+                        )
+                    )
+                )
             }
         }
     }
@@ -123,11 +1208,17 @@ impl TryInto<Box<dyn GitRepositoryLister>> for &GithubRepositoriesConfig {
 
     fn try_into(self) -> Result<Box<dyn GitRepositoryLister>, Self::Error> {
         match self {
-            GithubRepositoriesConfig::FromList { server, transport, list } => {
+            GithubRepositoriesConfig::FromList { server, transport, list, endpoint, token_file } => {
                 let server = server.as_ref()
                     .map(|s| s.to_string())
                     .unwrap_or_else(|| "github.com".to_string());
 
+                // GitHub Enterprise serves its API under the `/api/v3` path of the server
+                let endpoint = endpoint.clone().unwrap_or_else(|| match server.as_str() {
+                    "github.com" => "https://api.github.com".to_string(),
+                    server => format!("https://{}/api/v3", server),
+                });
+
                 Ok(
                     Box::new(GithubRepoStaticList {
                         list: list
@@ -138,8 +1229,11 @@ impl TryInto<Box<dyn GitRepositoryLister>> for &GithubRepositoriesConfig {
                                     GitCloneTransport::Ssh => format!("git@{}:{}.git", server, repo.name),
                                     GitCloneTransport::Https => format!("https://{}/{}.git", server, repo.name),
                                 },
+                                topics: vec![],
                             })
-                            .collect()
+                            .collect(),
+                        endpoint,
+                        token: token_file.as_deref().map(read_token_file).transpose()?,
                     })
                 )
             }
@@ -150,12 +1244,58 @@ impl TryInto<Box<dyn GitRepositoryLister>> for &GithubRepositoriesConfig {
     }
 }
 
+impl TryInto<Box<dyn DiscordChannelLoader>> for &DiscordChannelsConfig {
+    type Error = anyhow::Error;
+
+    fn try_into(self) -> Result<Box<dyn DiscordChannelLoader>, Self::Error> {
+        match self {
+            DiscordChannelsConfig::FromExport { paths } => {
+                Ok(Box::new(DiscordExportFiles { paths: paths.to_vec() }))
+            }
+            DiscordChannelsConfig::FromApi { channels, token_file, endpoint } => {
+                Ok(
+                    Box::new(DiscordApiChannels {
+                        endpoint: endpoint.as_ref()
+                            .map(|s| s.to_string())
+                            .unwrap_or_else(|| "https://discord.com/api/v10".to_string()),
+                        token: read_token_file(token_file)?,
+                        channels: channels.to_vec(),
+                    })
+                )
+            }
+        }
+    }
+}
+
+/// The source reading the files of a `FileSystem` source config.
+pub(crate) fn file_system_source(config: &SourceConfig) -> anyhow::Result<FileSystemDocumentSource> {
+    match config {
+        SourceConfig::FileSystem { id, paths, include, exclude, patterns, strip_code_blocks, strip_notebook_outputs, sections, max_file_size, ignore_files, .. } => {
+            Ok(
+                FileSystemDocumentSource {
+                    source_id: id.to_string(),
+                    include: patterns.compile(include)?,
+                    exclude: patterns.compile(exclude)?,
+                    relative_patterns: *patterns == PatternSyntax::Glob,
+                    paths: paths.to_vec(),
+                    strip_code_blocks: *strip_code_blocks,
+                    strip_notebook_outputs: *strip_notebook_outputs,
+                    sections: *sections,
+                    max_file_size: *max_file_size,
+                    ignore_files: *ignore_files,
+                }
+            )
+        }
+        other => bail!("Source '{}' doesn't read files (it's a {} source)", other.id(), other.kind()),
+    }
+}
+
 impl TryInto<Box<dyn DocumentSource>> for &SourceConfig {
     type Error = anyhow::Error;
 
     fn try_into(self) -> Result<Box<dyn DocumentSource>, Self::Error> {
-        match self {
-            SourceConfig::Github { id, repositories, include, exclude } => {
+        let source: anyhow::Result<Box<dyn DocumentSource>> = match self {
+            SourceConfig::Github { id, repositories, include, exclude, patterns, strip_code_blocks, strip_notebook_outputs, sections, max_file_size, ignore_files, .. } => {
                 let lister: Box<dyn GitRepositoryLister> = repositories.try_into()?;
 
                 Ok(
@@ -163,40 +1303,109 @@ impl TryInto<Box<dyn DocumentSource>> for &SourceConfig {
                         GithubSource {
                             source_id: id.to_string(),
                             lister,
-                            include: include.iter()
-                                .map(|e| Regex::new(e.as_str()))
-                                .collect::<Result<_, _>>()?,
-                            exclude: exclude.iter()
-                                .map(|e| Regex::new(e.as_str()))
-                                .collect::<Result<_, _>>()?,
+                            include: patterns.compile(include)?,
+                            exclude: patterns.compile(exclude)?,
+                            relative_patterns: *patterns == PatternSyntax::Glob,
+                            strip_code_blocks: *strip_code_blocks,
+                            strip_notebook_outputs: *strip_notebook_outputs,
+                            sections: *sections,
+                            max_file_size: *max_file_size,
+                            ignore_files: *ignore_files,
+                            clone_dir: cache_dir()?.join("clones"),
                         }
                     )
                 )
             }
-            SourceConfig::FileSystem { id, include, exclude, paths } => {
+            SourceConfig::FileSystem { .. } => {
+                Ok(Box::new(file_system_source(self)?))
+            }
+            SourceConfig::Asana { id, projects, token_file, .. } => {
                 Ok(
                     Box::new(
-                        FileSystemDocumentSource {
+                        AsanaSource {
                             source_id: id.to_string(),
-                            include: include.iter().map(|e| Regex::new(e.as_str())).collect::<Result<_, _>>()?,
-                            exclude: exclude.iter().map(|e| Regex::new(e.as_str())).collect::<Result<_, _>>()?,
-                            paths: paths.to_vec(),
+                            endpoint: self.endpoint().unwrap_or_default(),
+                            token: read_token_file(token_file)?,
+                            projects: projects.to_vec(),
                         }
                     )
                 )
             }
+            SourceConfig::Discord { id, channels, .. } => {
+                let loader: Box<dyn DiscordChannelLoader> = channels.try_into()?;
+
+                Ok(Box::new(DiscordSource { source_id: id.to_string(), loader }))
+            }
+            SourceConfig::GoogleDocs { id, documents, token_file, .. } => {
+                Ok(
+                    Box::new(
+                        GoogleDocsSource {
+                            source_id: id.to_string(),
+                            endpoint: self.endpoint().unwrap_or_default(),
+                            token: read_token_file(token_file)?,
+                            documents: documents.to_vec(),
+                        }
+                    )
+                )
+            }
+            SourceConfig::Airtable { id, tables, token_file, .. } => {
+                Ok(
+                    Box::new(
+                        AirtableSource {
+                            source_id: id.to_string(),
+                            endpoint: self.endpoint().unwrap_or_default(),
+                            token: read_token_file(token_file)?,
+                            tables: tables
+                                .iter()
+                                .map(|table| AirtableTable {
+                                    base: table.base.clone(),
+                                    table: table.table.clone(),
+                                    view: table.view.clone(),
+                                    title_field: table.title_field.clone(),
+                                    content_fields: table.content_fields.clone(),
+                                    metadata_fields: table.metadata_fields.clone(),
+                                })
+                                .collect(),
+                        }
+                    )
+                )
+            }
+            SourceConfig::Backstage { id, endpoint, frontend, kinds, token_file, .. } => {
+                Ok(
+                    Box::new(
+                        BackstageSource {
+                            source_id: id.to_string(),
+                            endpoint: endpoint.to_string(),
+                            frontend: frontend.as_ref().unwrap_or(endpoint).to_string(),
+                            token: token_file.as_ref().map(|f| read_token_file(f)).transpose()?,
+                            kinds: kinds.to_vec(),
+                        }
+                    )
+                )
+            }
+        };
+
+        match self.tags() {
+            [] => source,
+            tags => Ok(Box::new(TaggedSource { source: source?, tags: tags.to_vec() })),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+    use std::convert::TryInto;
     use std::path::PathBuf;
 
-    use crate::cli::config::{DoksConfig, GitCloneTransport, GithubRepo};
+    use tantivy::tokenizer::Language;
+    use tempdir::TempDir;
+
     use crate::cli::config::GithubRepositoriesConfig::FromList;
-    use crate::cli::config::SearchEngineConfig::Tantivy;
+    use crate::cli::config::SearchEngineConfig::{InMemory, Semantic, Tantivy};
     use crate::cli::config::SourceConfig::Github;
+    use crate::cli::config::{DaemonConfig, DoksConfig, EmbeddingsConfig, file_system_source, GitCloneTransport, GithubRepo, load_config, PatternSyntax, SearchEngineConfig, SourceConfig};
+    use crate::search::tantivy_impl::{Analyzer, Boosts, CustomField, FieldKind, StopWords};
 
     #[test]
     fn test_config_parse() -> anyhow::Result<()> {
@@ -226,6 +1435,8 @@ mod tests {
                     repositories: FromList {
                         server: None,
                         transport: GitCloneTransport::Ssh,
+                        endpoint: None,
+                        token_file: None,
                         list: vec![
                             GithubRepo {
                                 name: "wlezzar/jtab".to_string(),
@@ -251,12 +1462,192 @@ mod tests {
                     },
                     include: Vec::default(),
                     exclude: Vec::default(),
+                    patterns: PatternSyntax::Regex,
+                    strip_code_blocks: false,
+                    strip_notebook_outputs: false,
+                    sections: false,
+                    max_file_size: None,
+                    ignore_files: true,
+                    boost: None,
+                    tags: vec![],
                 }],
-            engine: Tantivy { path: PathBuf::from("/tmp/doks_index") },
+            engine: Tantivy { path: PathBuf::from("/tmp/doks_index"), analyzer: None, boosts: None, limits: None, fields: vec![] },
+            daemon: DaemonConfig::default(),
+            namespaces: BTreeMap::new(),
         };
 
         assert_eq!(parsed, expected);
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_glob_patterns() -> anyhow::Result<()> {
+        let config = r#"{ "source": "fs", "id": "docs", "paths": ["/docs"], "patterns": "glob", "include": ["**/*.md"], "exclude": ["vendor/**"] }"#;
+
+        let source = file_system_source(&serde_json::from_str::<SourceConfig>(config)?)?;
+
+        assert!(source.accepts("/docs/ops/runbook.md"));
+        assert!(!source.accepts("/docs/vendor/lib/README.md"));
+        assert!(!source.accepts("/docs/setup.txt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_in_memory_engine_config_parse() -> anyhow::Result<()> {
+        let config = r#"{ "sources": [], "engine": { "use": "in-memory" } }"#;
+
+        let parsed = serde_json::from_str::<DoksConfig>(config)?;
+
+        assert_eq!(parsed.engine, InMemory);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_analyzer_config_parse() -> anyhow::Result<()> {
+        let analyzer = |config: &str| -> anyhow::Result<Analyzer> {
+            match serde_json::from_str::<SearchEngineConfig>(config)? {
+                Tantivy { analyzer: Some(analyzer), .. } => (&analyzer).try_into(),
+                other => panic!("Unexpected engine: {:?}", other),
+            }
+        };
+
+        let parsed = analyzer(r#"{ "use": "tantivy", "path": "/index", "analyzer": { "stemmer": "french", "stop_words": "english" } }"#)?;
+        assert_eq!(parsed.stemmer, Some(Language::French));
+        assert!(parsed.lowercase);
+        assert!(matches!(parsed.stop_words, Some(StopWords::English)));
+
+        let parsed = analyzer(r#"{ "use": "tantivy", "path": "/index", "analyzer": { "stop_words": ["via", "per"] } }"#)?;
+        assert!(matches!(parsed.stop_words, Some(StopWords::Custom(words)) if words == vec!["via", "per"]));
+
+        let parsed = analyzer(r#"{ "use": "tantivy", "path": "/index", "analyzer": { "synonyms": { "k8s": ["kubernetes"] } } }"#)?;
+        assert_eq!(parsed.synonyms["k8s"], vec!["kubernetes"]);
+
+        assert!(analyzer(r#"{ "use": "tantivy", "path": "/index", "analyzer": { "stop_words": "klingon" } }"#).is_err());
+
+        let boosts = |config: &str| -> anyhow::Result<Boosts> {
+            match serde_json::from_str::<SearchEngineConfig>(config)? {
+                Tantivy { boosts: Some(boosts), .. } => (&boosts).try_into(),
+                other => panic!("Unexpected engine: {:?}", other),
+            }
+        };
+
+        assert_eq!(boosts(r#"{ "use": "tantivy", "path": "/index", "boosts": { "title": 3 } }"#)?, Boosts { title: 3.0, content: 1.0 });
+        assert!(boosts(r#"{ "use": "tantivy", "path": "/index", "boosts": { "content": -1 } }"#).is_err());
+
+        let engine = |config: &str| serde_json::from_str::<SearchEngineConfig>(config);
+        let limited = engine(r#"{ "use": "tantivy", "path": "/index", "limits": { "default": 50, "max": 200 } }"#)?;
+        assert_eq!(limited.default_limit(), 50);
+        assert_eq!(engine(r#"{ "use": "tantivy", "path": "/index", "limits": { "max": 200 } }"#)?.default_limit(), 10);
+        assert_eq!(engine(r#"{ "use": "in-memory" }"#)?.default_limit(), 10);
+
+        let limits = |config: &str| match engine(config) {
+            Ok(Tantivy { limits: Some(limits), .. }) => limits.check(),
+            other => panic!("Unexpected engine: {:?}", other),
+        };
+        assert!(limits(r#"{ "use": "tantivy", "path": "/index", "limits": { "default": 50, "max": 20 } }"#).is_err());
+        assert!(limits(r#"{ "use": "tantivy", "path": "/index", "limits": { "default": 0 } }"#).is_err());
+
+        let fields = match engine(r#"{ "use": "tantivy", "path": "/index", "fields": [{ "name": "team", "type": "string" }, { "name": "priority", "type": "u64", "fast": true }] }"#)? {
+            Tantivy { fields, .. } => fields.iter().map(CustomField::from).collect::<Vec<_>>(),
+            other => panic!("Unexpected engine: {:?}", other),
+        };
+        assert_eq!(fields[0], CustomField { name: "team".to_string(), kind: FieldKind::String, stored: true, indexed: true, fast: false });
+        assert_eq!((fields[1].kind, fields[1].fast), (FieldKind::U64, true));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_embeddings_config_parse() -> anyhow::Result<()> {
+        let config = r#"
+            {
+              "sources": [],
+              "engine": {
+                "use": "semantic",
+                "path": "/tmp/doks_vectors",
+                "embeddings": { "provider": "openai", "model": "text-embedding-3-small" }
+              }
+            }
+        "#;
+
+        let parsed = serde_json::from_str::<DoksConfig>(config)?;
+        let expected = Semantic {
+            path: PathBuf::from("/tmp/doks_vectors"),
+            embeddings: EmbeddingsConfig::OpenAi {
+                endpoint: None,
+                model: "text-embedding-3-small".to_string(),
+                api_key_file: None,
+                dimensions: None,
+            },
+            chunk_size: None,
+        };
+
+        assert_eq!(parsed.engine, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_namespaces() -> anyhow::Result<()> {
+        let config = r#"
+            {
+              "sources": [{ "source": "fs", "id": "docs", "paths": ["/docs"] }],
+              "engine": { "use": "tantivy", "path": "/data/doks/index" },
+              "namespaces": {
+                "personal": { "sources": [{ "source": "fs", "id": "notes", "paths": ["/notes"] }] },
+                "work": { "engine": { "use": "elasticsearch", "endpoint": "http://localhost:9200" } }
+              }
+            }
+        "#;
+
+        let parse = || serde_json::from_str::<DoksConfig>(config);
+
+        let default = parse()?.for_namespace("default")?;
+        assert_eq!(default.engine, Tantivy { path: PathBuf::from("/data/doks/index"), analyzer: None, boosts: None, limits: None, fields: vec![] });
+        assert_eq!(default.sources[0].id(), "docs");
+
+        let personal = parse()?.for_namespace("personal")?;
+        assert_eq!(personal.engine, Tantivy { path: PathBuf::from("/data/doks/personal/index"), analyzer: None, boosts: None, limits: None, fields: vec![] });
+        assert_eq!(personal.sources[0].id(), "notes");
+
+        let work = parse()?.for_namespace("work")?;
+        assert_eq!(work.engine.kind(), "elasticsearch");
+        assert_eq!(work.sources[0].id(), "docs");
+
+        let other = parse()?.for_namespace("other")?;
+        assert_eq!(other.engine, Tantivy { path: PathBuf::from("/data/doks/other/index"), analyzer: None, boosts: None, limits: None, fields: vec![] });
+
+        assert!(parse()?.for_namespace("../other").is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_load_config() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let base = root.path().join("base.json");
+        let personal = root.path().join("personal");
+
+        std::fs::create_dir(&personal)?;
+        std::fs::write(&base, r#"{ "sources": [{ "source": "fs", "id": "docs", "paths": ["/docs"] }], "engine": { "use": "in-memory" } }"#)?;
+        std::fs::write(personal.join("1-notes.json"), r#"{ "sources": [{ "source": "fs", "id": "notes", "paths": ["/notes"] }] }"#)?;
+        std::fs::write(personal.join("2-engine.json"), r#"{ "engine": { "use": "tantivy", "path": "/index" } }"#)?;
+        std::fs::write(personal.join("ignored.txt"), "not a config")?;
+
+        let config = load_config(&[base.clone(), personal.clone()]).await?;
+
+        assert_eq!(config.sources.iter().map(|source| source.id()).collect::<Vec<_>>(), vec!["docs", "notes"]);
+        assert_eq!(config.engine, Tantivy { path: PathBuf::from("/index"), analyzer: None, boosts: None, limits: None, fields: vec![] });
+
+        // A single file is read as before
+        assert_eq!(load_config(std::slice::from_ref(&base)).await?.engine, InMemory);
+
+        let duplicate = load_config(&[base.clone(), base.clone()]).await;
+        assert!(duplicate.unwrap_err().to_string().contains("Source 'docs' is defined in both"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::bail;
+use rustyline::DefaultEditor;
+use structopt::StructOpt;
+
+use crate::cli::config::{
+    DaemonConfig,
+    DoksConfig,
+    GitCloneTransport,
+    GithubRepo,
+    GithubRepositoriesConfig,
+    PatternSyntax,
+    SearchEngineConfig,
+    SourceConfig,
+};
+use crate::cli::state::data_dir;
+use crate::cli::validate::config_problems;
+
+/// Files indexed by the generated sources. Can be changed in the config afterwards.
+const DOCUMENTATION_FILES: &str = ".*\\.(md|txt|rst|adoc)$";
+
+#[derive(Debug, StructOpt)]
+pub enum ConfigCommand {
+    /// Writes a new config file. Asks for the sources and the engine unless given as flags.
+    Init {
+        /// Local directory to index
+        #[structopt(long = "fs")]
+        paths: Vec<String>,
+        /// GitHub repository to index (owner/name)
+        #[structopt(long = "github")]
+        repositories: Vec<String>,
+        #[structopt(long, possible_values = &["tantivy", "in-memory"])]
+        engine: Option<String>,
+        /// Where the tantivy engine stores the index
+        #[structopt(long, parse(from_os_str))]
+        index_path: Option<PathBuf>,
+        /// Overwrite the config file if it already exists
+        #[structopt(long)]
+        force: bool,
+    },
+}
+
+pub async fn config_main(config_file: &Path, command: &ConfigCommand) -> anyhow::Result<()> {
+    match command {
+        ConfigCommand::Init { paths, repositories, engine, index_path, force } => {
+            if config_file.exists() && !force {
+                bail!("Config file already exists: {:?} (use --force to overwrite it)", config_file)
+            }
+
+            let default_index = data_dir()?.join("index");
+            let config = if paths.is_empty() && repositories.is_empty() && engine.is_none() {
+                ask_config(&default_index)?
+            } else {
+                let engine = engine_config(engine.as_deref().unwrap_or("tantivy"), index_path.as_deref().unwrap_or(&default_index))?;
+                build_config(paths, repositories, engine)
+            };
+
+            let problems = config_problems(&config);
+            if !problems.is_empty() {
+                for problem in &problems {
+                    println!("✗ {}", problem);
+                }
+
+                bail!("The config wasn't written: {} problem(s) found", problems.len())
+            }
+
+            if let Some(parent) = config_file.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+
+            tokio::fs::write(config_file, serde_json::to_string_pretty(&config)?).await?;
+
+            println!("Config written to {:?}, run `doks index` to index the sources", config_file);
+        }
+    }
+
+    Ok(())
+}
+
+fn ask_config(default_index: &Path) -> anyhow::Result<DoksConfig> {
+    let mut editor = DefaultEditor::new()?;
+    let mut ask_list = |question: &str| -> anyhow::Result<Vec<String>> {
+        let answer = editor.readline(&format!("{} (comma separated, empty to skip): ", question))?;
+
+        Ok(answer.split(',').map(str::trim).filter(|item| !item.is_empty()).map(String::from).collect())
+    };
+
+    let paths = ask_list("Local directories to index")?;
+    let repositories = ask_list("GitHub repositories to index, as owner/name")?;
+
+    let engine = editor.readline("Search engine, tantivy or in-memory (tantivy): ")?;
+    let engine = match engine.trim() {
+        "" => "tantivy",
+        other => other,
+    };
+
+    let index_path = match engine {
+        "tantivy" => editor.readline(&format!("Index directory ({}): ", default_index.to_string_lossy()))?,
+        _ => String::new(),
+    };
+
+    let index_path = match index_path.trim() {
+        "" => default_index.to_path_buf(),
+        other => PathBuf::from(other),
+    };
+
+    Ok(build_config(&paths, &repositories, engine_config(engine, &index_path)?))
+}
+
+fn engine_config(engine: &str, index_path: &Path) -> anyhow::Result<SearchEngineConfig> {
+    match engine {
+        "tantivy" => Ok(SearchEngineConfig::Tantivy { path: index_path.to_path_buf(), analyzer: None, boosts: None, limits: None, fields: vec![] }),
+        "in-memory" => Ok(SearchEngineConfig::InMemory),
+        other => bail!("Unsupported engine: {} (other engines can be configured by editing the config)", other),
+    }
+}
+
+fn build_config(paths: &[String], repositories: &[String], engine: SearchEngineConfig) -> DoksConfig {
+    let mut sources = vec![];
+
+    if !paths.is_empty() {
+        sources.push(SourceConfig::FileSystem {
+            id: "docs".to_string(),
+            paths: paths.to_vec(),
+            include: vec![DOCUMENTATION_FILES.to_string()],
+            exclude: vec![],
+            patterns: PatternSyntax::Regex,
+            strip_code_blocks: false,
+            strip_notebook_outputs: false,
+            sections: false,
+            max_file_size: None,
+            ignore_files: true,
+            boost: None,
+            tags: vec![],
+        });
+    }
+
+    if !repositories.is_empty() {
+        sources.push(SourceConfig::Github {
+            id: "github".to_string(),
+            repositories: GithubRepositoriesConfig::FromList {
+                server: None,
+                transport: GitCloneTransport::Https,
+                list: repositories.iter().map(|name| GithubRepo::new(name)).collect(),
+                endpoint: None,
+                token_file: None,
+            },
+            include: vec![DOCUMENTATION_FILES.to_string()],
+            exclude: vec![],
+            patterns: PatternSyntax::Regex,
+            strip_code_blocks: false,
+            strip_notebook_outputs: false,
+            sections: false,
+            max_file_size: None,
+            ignore_files: true,
+            boost: None,
+            tags: vec![],
+        });
+    }
+
+    DoksConfig { sources, engine, daemon: DaemonConfig::default(), namespaces: BTreeMap::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::cli::config::{DoksConfig, SearchEngineConfig};
+    use crate::cli::config_init::build_config;
+
+    #[test]
+    fn test_build_config() -> anyhow::Result<()> {
+        let engine = SearchEngineConfig::Tantivy { path: PathBuf::from("/tmp/doks_index"), analyzer: None, boosts: None, limits: None, fields: vec![] };
+        let config = build_config(&["/docs".to_string()], &["wlezzar/doks".to_string()], engine);
+
+        let ids = config.sources.iter().map(|source| source.id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["docs", "github"]);
+
+        // What is written must be readable by the other commands
+        let written = serde_json::to_string_pretty(&config)?;
+        assert_eq!(serde_json::from_str::<DoksConfig>(&written)?, config);
+
+        assert!(build_config(&[], &[], SearchEngineConfig::InMemory).sources.is_empty());
+
+        Ok(())
+    }
+}
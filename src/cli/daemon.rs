@@ -0,0 +1,297 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context};
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::OwnedMutexGuard;
+
+use crate::cli::config::{DoksConfig, read_token_file, SourceConfig};
+use crate::cli::index_source;
+use crate::cli::state::StateStore;
+use crate::cli::webhooks::webhooks;
+use crate::search::SearchEngine;
+use crate::server::metrics::{metrics, serve_metrics};
+
+#[derive(Debug)]
+enum Schedule {
+    Every(Duration),
+    Cron(Box<cron::Schedule>),
+}
+
+impl FromStr for Schedule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(interval) = parse_interval(s) {
+            return Ok(Schedule::Every(interval));
+        }
+
+        // The cron crate expects seconds, usual crontab expressions don't have them
+        let expression = match s.split_whitespace().count() {
+            5 => format!("0 {}", s),
+            _ => s.to_string(),
+        };
+
+        let schedule = cron::Schedule::from_str(&expression)
+            .with_context(|| format!("Invalid schedule: '{}' (expected an interval like 6h or a cron expression)", s))?;
+
+        Ok(Schedule::Cron(Box::new(schedule)))
+    }
+}
+
+impl Schedule {
+    fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Schedule::Every(interval) => Some(time + chrono::Duration::from_std(*interval).ok()?),
+            Schedule::Cron(schedule) => schedule.after(&time).next(),
+        }
+    }
+}
+
+/// Parses intervals like `90s`, `15m`, `6h` or `1d`.
+pub(super) fn parse_interval(s: &str) -> Option<Duration> {
+    let (number, unit) = s.trim().split_at(s.trim().find(|c: char| !c.is_ascii_digit())?);
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None,
+    };
+
+    Some(Duration::from_secs(number.parse::<u64>().ok()? * seconds))
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct SourceStatus {
+    schedule: String,
+    running: bool,
+    next_run: Option<DateTime<Utc>>,
+    last_run: Option<RunStatus>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct RunStatus {
+    started: DateTime<Utc>,
+    finished: DateTime<Utc>,
+    documents: Option<u64>,
+    error: Option<String>,
+}
+
+type Statuses = Arc<Mutex<BTreeMap<String, SourceStatus>>>;
+
+/// One lock per source, held while it is indexed so that the scheduled runs and the reindexes
+/// requested by the webhooks don't index the same source concurrently.
+#[derive(Clone, Default)]
+pub(super) struct SourceLocks(Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>);
+
+impl SourceLocks {
+    /// Waits for the current indexing of the source, if any, to be over.
+    pub(super) async fn lock(&self, source: &str) -> OwnedMutexGuard<()> {
+        let lock = self.0.lock().unwrap().entry(source.to_string()).or_default().clone();
+        lock.lock_owned().await
+    }
+}
+
+/// Indexes each source on its schedule and serves the status of the runs on `GET /status`.
+/// Runs until interrupted.
+pub async fn daemon(
+    config: &DoksConfig,
+    search: &dyn SearchEngine,
+    state: &StateStore,
+    address: &SocketAddr,
+) -> anyhow::Result<()> {
+    let mut scheduled = vec![];
+
+    for source in &config.sources {
+        match config.daemon.schedules.get(source.id()).or(config.daemon.default_schedule.as_ref()) {
+            Some(schedule) => scheduled.push((source, schedule.as_str(), Schedule::from_str(schedule)?)),
+            None => log::info!("Source {} has no schedule, it won't be indexed", source.id()),
+        }
+    }
+
+    for id in config.daemon.schedules.keys() {
+        if !config.sources.iter().any(|source| source.id() == id) {
+            bail!("A schedule is defined for an unknown source: {}", id)
+        }
+    }
+
+    let secret = config.daemon.webhook_secret_file.as_deref().map(read_token_file).transpose()?;
+
+    if scheduled.is_empty() && secret.is_none() {
+        bail!("No source is scheduled, add schedules or a webhook secret in the `daemon` section of the config")
+    }
+
+    let statuses: Statuses = Arc::new(Mutex::new(
+        scheduled
+            .iter()
+            .map(|(source, schedule, _)| {
+                let status = SourceStatus { schedule: schedule.to_string(), running: false, next_run: None, last_run: None };
+                (source.id().to_string(), status)
+            })
+            .collect()
+    ));
+
+    let locks = SourceLocks::default();
+    let last_indexed = state.load().await?;
+    let runs = scheduled.iter().map(|(source, _, schedule)| {
+        let last = last_indexed.sources.get(source.id()).map(|s| s.last_indexed);
+        run_schedule(source, schedule, last, search, state, &statuses, &locks)
+    });
+
+    let mut router = Router::new()
+        .route("/status", get(|State(statuses): State<Statuses>| async move { Json(statuses.lock().unwrap().clone()) }))
+        .route("/healthz", get(|| async { "ok" }))
+        .route("/metrics", get(serve_metrics))
+        .with_state(statuses.clone());
+
+    let reindexes = match secret {
+        Some(secret) => {
+            let (hooks, queue) = webhooks(config, secret);
+            router = router.merge(hooks);
+            Some(queue.run(config, search, state, &locks))
+        }
+        None => None,
+    };
+
+    let server = axum::Server::try_bind(address)?.serve(router.into_make_service());
+    log::info!("Status available on: http://{}/status", server.local_addr());
+
+    // The webhooks keep the daemon running even without schedules
+    let runs = async {
+        futures::future::join_all(runs).await;
+
+        if let Some(reindexes) = reindexes {
+            reindexes.await;
+        }
+    };
+
+    tokio::select! {
+        _ = runs => {}
+        served = server => served?,
+        _ = tokio::signal::ctrl_c() => {}
+    }
+
+    Ok(())
+}
+
+/// Runs of a source never overlap: the next run is only planned once the current one is over, and
+/// runs missed meanwhile are skipped. A source never indexed (or whose run was missed while the
+/// daemon was down) is indexed right away. A run waits for the reindex of the source requested by a
+/// webhook, if any, to be over.
+async fn run_schedule(
+    source: &SourceConfig,
+    schedule: &Schedule,
+    last_indexed: Option<DateTime<Utc>>,
+    search: &dyn SearchEngine,
+    state: &StateStore,
+    statuses: &Mutex<BTreeMap<String, SourceStatus>>,
+    locks: &SourceLocks,
+) {
+    let update = |update: &dyn Fn(&mut SourceStatus)| {
+        if let Some(status) = statuses.lock().unwrap().get_mut(source.id()) {
+            update(status)
+        }
+    };
+
+    let mut next = last_indexed.and_then(|last| schedule.next_after(last)).unwrap_or_else(Utc::now);
+
+    loop {
+        update(&|status| status.next_run = Some(next));
+        tokio::time::sleep((next - Utc::now()).to_std().unwrap_or_default()).await;
+
+        let lock = locks.lock(source.id()).await;
+        update(&|status| status.running = true);
+        log::info!("Indexing source: {}", source.id());
+
+        let started = Utc::now();
+        let timer = Instant::now();
+        let indexed = match index_source(source, search, None).await {
+            Ok(summary) => state.record_indexed(source.id(), summary.documents).await.map(|_| summary.documents),
+            Err(err) => Err(err),
+        };
+
+        metrics().index_run(source.id(), timer.elapsed(), indexed.is_ok());
+
+        if let Err(err) = &indexed {
+            log::error!("Couldn't index source {}: {:#}", source.id(), err);
+        }
+
+        let run = RunStatus {
+            started,
+            finished: Utc::now(),
+            documents: indexed.as_ref().ok().copied(),
+            error: indexed.as_ref().err().map(|err| format!("{:#}", err)),
+        };
+
+        drop(lock);
+        let next_run = schedule.next_after(run.finished);
+
+        update(&|status| {
+            status.running = false;
+            status.next_run = next_run;
+            status.last_run = Some(run.clone());
+        });
+
+        match next_run {
+            Some(time) => next = time,
+            None => {
+                log::warn!("No next run for source {} in its schedule", source.id());
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::cli::daemon::{parse_interval, Schedule, SourceLocks};
+
+    #[test]
+    fn test_schedules() -> anyhow::Result<()> {
+        assert_eq!(parse_interval("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_interval("6h"), Some(Duration::from_secs(6 * 3600)));
+        assert_eq!(parse_interval("1w"), None);
+        assert_eq!(parse_interval("h"), None);
+
+        let time = Utc.ymd(2022, 1, 1).and_hms(10, 30, 0);
+
+        assert_eq!(Schedule::from_str("15m")?.next_after(time), Some(Utc.ymd(2022, 1, 1).and_hms(10, 45, 0)));
+        assert_eq!(Schedule::from_str("0 3 * * *")?.next_after(time), Some(Utc.ymd(2022, 1, 2).and_hms(3, 0, 0)));
+        assert!(Schedule::from_str("every day").is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_source_locks() {
+        let locks = SourceLocks::default();
+        let docs = locks.lock("docs").await;
+
+        // Other sources are indexed meanwhile
+        let _runbooks = locks.lock("runbooks").await;
+
+        let waiting = tokio::spawn({
+            let locks = locks.clone();
+            async move { locks.lock("docs").await; }
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+
+        drop(docs);
+        tokio::time::timeout(Duration::from_secs(1), waiting).await.unwrap().unwrap();
+    }
+}
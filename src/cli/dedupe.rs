@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+
+use tokio_stream::StreamExt;
+
+use crate::cli::config::DoksConfig;
+use crate::model::Document;
+use crate::search::SearchEngine;
+use crate::search::deduped_impl::content_hash;
+
+/// Documents sharing the same content, the one kept first.
+#[derive(Debug, Eq, PartialEq)]
+struct Duplicates {
+    hash: String,
+    documents: Vec<Document>,
+}
+
+/// Finds the documents with the same content (e.g. a README vendored in several repositories) and
+/// removes all the copies but one from the index.
+pub async fn dedupe(config: &DoksConfig, search: &dyn SearchEngine, dry_run: bool) -> anyhow::Result<()> {
+    let documents = search.documents().await?.collect::<anyhow::Result<Vec<_>>>().await?;
+    let groups = duplicates(config, documents);
+
+    for group in &groups {
+        println!("{} ({} copies)", &group.hash[..12], group.documents.len());
+
+        for (i, document) in group.documents.iter().enumerate() {
+            let action = if i == 0 { "keep" } else { "remove" };
+            println!("  {:<6}  {}  {}", action, document.source, document.link);
+        }
+    }
+
+    let removed = groups.iter().flat_map(|group| group.documents[1..].iter().map(|d| d.id.clone())).collect::<Vec<_>>();
+
+    if dry_run {
+        println!("Dry run: {} duplicates would be removed", removed.len());
+    } else if !removed.is_empty() {
+        search.delete(&removed).await?;
+        println!("Removed {} duplicates", removed.len());
+    } else {
+        println!("No duplicates found");
+    }
+
+    Ok(())
+}
+
+/// Groups the documents by content hash. The copy kept is the one from the source configured first
+/// (sources missing from the config come last), then the one with the shortest link.
+fn duplicates(config: &DoksConfig, documents: Vec<Document>) -> Vec<Duplicates> {
+    let mut by_hash = BTreeMap::<String, Vec<Document>>::new();
+
+    for document in documents.into_iter().filter(|document| !document.content.trim().is_empty()) {
+        let hash = content_hash(&document.content);
+        by_hash.entry(hash).or_default().push(document);
+    }
+
+    let source_rank = |source: &str| config.sources.iter().position(|s| s.id() == source).unwrap_or(usize::MAX);
+
+    by_hash
+        .into_iter()
+        .filter(|(_, documents)| documents.len() > 1)
+        .map(|(hash, mut documents)| {
+            documents.sort_by(|a, b| {
+                (source_rank(&a.source), a.link.len(), &a.id).cmp(&(source_rank(&b.source), b.link.len(), &b.id))
+            });
+
+            Duplicates { hash, documents }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use crate::cli::config::{DaemonConfig, DoksConfig, PatternSyntax, SearchEngineConfig, SourceConfig};
+    use crate::cli::dedupe::duplicates;
+    use crate::model::Document;
+
+    #[test]
+    fn test_duplicates() {
+        let source = |id: &str| SourceConfig::FileSystem {
+            id: id.to_string(),
+            paths: vec![],
+            include: vec![],
+            exclude: vec![],
+            patterns: PatternSyntax::Regex,
+            strip_code_blocks: false,
+            strip_notebook_outputs: false,
+            sections: false,
+            max_file_size: None,
+            ignore_files: true,
+            boost: None,
+            tags: vec![],
+        };
+        let config = DoksConfig {
+            sources: vec![source("docs"), source("github")],
+            engine: SearchEngineConfig::InMemory,
+            daemon: DaemonConfig::default(),
+            namespaces: BTreeMap::new(),
+        };
+
+        let document = |id: &str, source: &str, content: &str| Document {
+            id: id.to_string(),
+            source: source.to_string(),
+            title: "README.md".to_string(),
+            link: id.to_string(),
+            content: content.to_string(),
+            metadata: HashMap::new(),
+            tags: vec![],
+            modified: None,
+        };
+
+        let groups = duplicates(&config, vec![
+            document("github/vendored/README.md", "github", "How to build"),
+            document("removed/README.md", "removed", "How to build"),
+            document("docs/README.md", "docs", "How to build"),
+            document("github/README.md", "github", "How to build"),
+            document("docs/runbook.md", "docs", "Restart the database"),
+            document("empty1", "docs", ""),
+            document("empty2", "docs", " "),
+        ]);
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(
+            groups[0].documents.iter().map(|d| d.id.as_str()).collect::<Vec<_>>(),
+            vec!["docs/README.md", "github/README.md", "github/vendored/README.md", "removed/README.md"],
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::bail;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use tantivy::{Index, TantivyError};
+
+use crate::cli::config::{
+    DiscordChannelsConfig,
+    DoksConfig,
+    GitCloneTransport,
+    GithubRepositoriesConfig,
+    read_token_file,
+    SearchEngineConfig,
+    SourceConfig,
+};
+use crate::cli::validate::{config_problems, CONNECTIVITY_TIMEOUT, is_remote, reach_engine};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Status {
+    Ok,
+    Warning,
+    Failure,
+}
+
+#[derive(Debug)]
+struct Check {
+    subject: String,
+    status: Status,
+    message: String,
+}
+
+impl Check {
+    fn new(subject: &str, status: Status, message: String) -> Self {
+        Self { subject: subject.to_string(), status, message }
+    }
+}
+
+/// Diagnoses the environment doks runs in: the config, the index storage, the credentials of the
+/// sources and the reachability of the engine. Fails if any check fails.
+pub async fn doctor(config: &DoksConfig) -> anyhow::Result<()> {
+    let mut checks = vec![];
+
+    let problems = config_problems(config);
+    if problems.is_empty() {
+        let message = format!("{} source(s), {} engine", config.sources.len(), config.engine.kind());
+        checks.push(Check::new("config", Status::Ok, message));
+    }
+    for problem in problems {
+        checks.push(Check::new("config", Status::Failure, problem));
+    }
+
+    for path in config.engine.storage_paths() {
+        checks.push(storage_check(&path));
+    }
+
+    for path in tantivy_paths(&config.engine) {
+        if path.join("meta.json").exists() {
+            checks.push(lock_check(&path));
+        }
+    }
+
+    let client = Client::builder().timeout(CONNECTIVITY_TIMEOUT).build()?;
+
+    for source in &config.sources {
+        checks.extend(source_checks(&client, source).await);
+    }
+
+    if is_remote(&config.engine) {
+        let subject = format!("engine '{}'", config.engine.kind());
+
+        checks.push(match reach_engine(&config.engine).await {
+            Ok(()) => Check::new(&subject, Status::Ok, "reachable".to_string()),
+            Err(err) => Check::new(&subject, Status::Failure, format!("couldn't reach: {:#} (is it running? is the endpoint right?)", err)),
+        });
+    }
+
+    for check in &checks {
+        let symbol = match check.status {
+            Status::Ok => "✓",
+            Status::Warning => "!",
+            Status::Failure => "✗",
+        };
+
+        println!("{} {}: {}", symbol, check.subject, check.message);
+    }
+
+    let failures = checks.iter().filter(|check| check.status == Status::Failure).count();
+    if failures > 0 {
+        bail!("{} check(s) failed", failures)
+    }
+
+    Ok(())
+}
+
+fn tantivy_paths(engine: &SearchEngineConfig) -> Vec<PathBuf> {
+    match engine {
+        SearchEngineConfig::Tantivy { path, .. } => vec![path.clone()],
+        SearchEngineConfig::Hybrid { keyword, vector, .. } => {
+            tantivy_paths(keyword).into_iter().chain(tantivy_paths(vector)).collect()
+        }
+        SearchEngineConfig::Chunked { engine, .. } | SearchEngineConfig::Deduplicated { engine } => tantivy_paths(engine),
+        SearchEngineConfig::Multi { engines, .. } => engines.iter().flat_map(tantivy_paths).collect(),
+        _ => vec![],
+    }
+}
+
+/// The engines create their storage on first use: the closest existing parent must be writable.
+fn storage_check(path: &Path) -> Check {
+    let subject = format!("storage {:?}", path);
+    let existing = path.ancestors().find(|p| p.exists()).unwrap_or_else(|| Path::new("."));
+
+    let directory = match existing.is_dir() {
+        true => existing,
+        false => existing.parent().unwrap_or_else(|| Path::new(".")),
+    };
+
+    let probe = directory.join(format!(".doks-doctor-{}", std::process::id()));
+
+    match std::fs::write(&probe, b"").and_then(|_| std::fs::remove_file(&probe)) {
+        Ok(()) if existing == path => Check::new(&subject, Status::Ok, "writable".to_string()),
+        Ok(()) => Check::new(&subject, Status::Ok, "doesn't exist yet, will be created on the next index".to_string()),
+        Err(err) => Check::new(
+            &subject,
+            Status::Failure,
+            format!("{:?} isn't writable ({}): fix its permissions or move the index in the config", directory, err),
+        ),
+    }
+}
+
+/// Only one process can write to a tantivy index, the others fail to open it.
+fn lock_check(path: &Path) -> Check {
+    let subject = format!("index {:?}", path);
+
+    let writer = Index::open_in_dir(path).and_then(|index| index.writer_with_num_threads(1, 10_000_000));
+
+    match writer {
+        Ok(_) => Check::new(&subject, Status::Ok, "not locked".to_string()),
+        Err(TantivyError::LockFailure(..)) => Check::new(
+            &subject,
+            Status::Failure,
+            "locked by another process: stop the running `doks watch`, `doks daemon` or `doks serve` using it".to_string(),
+        ),
+        Err(TantivyError::SchemaError(_)) => Check::new(
+            &subject,
+            Status::Failure,
+            "built by an older version of doks: delete it and index again".to_string(),
+        ),
+        Err(err) => Check::new(&subject, Status::Failure, format!("couldn't be opened: {}", err)),
+    }
+}
+
+async fn source_checks(client: &Client, source: &SourceConfig) -> Vec<Check> {
+    let subject = format!("source '{}'", source.id());
+    let mut checks = vec![];
+
+    if let SourceConfig::Github { repositories: GithubRepositoriesConfig::FromList { transport: GitCloneTransport::Ssh, .. }, .. } = source {
+        checks.push(ssh_agent_check(&subject));
+    }
+
+    match auth_request(client, source) {
+        Ok(Some((request, token_file))) => checks.push(token_check(&subject, request, &token_file).await),
+        Ok(None) => {}
+        Err(err) => checks.push(Check::new(&subject, Status::Failure, format!("{:#}", err))),
+    }
+
+    checks
+}
+
+/// Repositories are cloned over ssh with the keys of the ssh agent.
+fn ssh_agent_check(subject: &str) -> Check {
+    if std::env::var_os("SSH_AUTH_SOCK").is_none() {
+        return Check::new(subject, Status::Failure, "SSH_AUTH_SOCK isn't set: start an ssh agent to clone over ssh".to_string());
+    }
+
+    match Command::new("ssh-add").arg("-l").output() {
+        Ok(output) if output.status.success() => Check::new(subject, Status::Ok, "ssh agent has keys".to_string()),
+        Ok(_) => Check::new(subject, Status::Failure, "the ssh agent has no keys: add one with `ssh-add`".to_string()),
+        Err(err) => Check::new(subject, Status::Warning, format!("couldn't run ssh-add to list the agent keys: {}", err)),
+    }
+}
+
+/// A cheap authenticated request telling whether the token of the source is accepted, along with
+/// the file the token was read from.
+fn auth_request(client: &Client, source: &SourceConfig) -> anyhow::Result<Option<(RequestBuilder, String)>> {
+    let endpoint = source.endpoint().unwrap_or_default();
+
+    let request = match source {
+        SourceConfig::Asana { token_file, .. } => {
+            Some((client.get(format!("{}/users/me", endpoint)).bearer_auth(read_token_file(token_file)?), token_file))
+        }
+        SourceConfig::Airtable { token_file, .. } => {
+            Some((client.get(format!("{}/meta/whoami", endpoint)).bearer_auth(read_token_file(token_file)?), token_file))
+        }
+        SourceConfig::GoogleDocs { documents, token_file, .. } if !documents.is_empty() => {
+            let request = client.get(format!("{}/documents/{}", endpoint, documents[0]));
+            Some((request.bearer_auth(read_token_file(token_file)?), token_file))
+        }
+        SourceConfig::Discord { channels: DiscordChannelsConfig::FromApi { token_file, .. }, .. } => {
+            let token = read_token_file(token_file)?;
+            Some((client.get(format!("{}/users/@me", endpoint)).header("Authorization", format!("Bot {}", token)), token_file))
+        }
+        SourceConfig::Backstage { token_file: Some(token_file), .. } => {
+            let request = client.get(format!("{}/api/catalog/entities", endpoint)).query(&[("limit", 1)]);
+            Some((request.bearer_auth(read_token_file(token_file)?), token_file))
+        }
+        SourceConfig::Github { repositories: GithubRepositoriesConfig::FromApi { endpoint, token_file: Some(token_file), .. }, .. } => {
+            let endpoint = endpoint.as_deref().unwrap_or("https://api.github.com");
+            let request = client
+                .get(format!("{}/user", endpoint))
+                .header("Authorization", format!("token {}", read_token_file(token_file)?))
+                .header("User-Agent", "doks");
+
+            Some((request, token_file))
+        }
+        _ => None,
+    };
+
+    Ok(request.map(|(request, token_file)| (request, token_file.to_string())))
+}
+
+async fn token_check(subject: &str, request: RequestBuilder, token_file: &str) -> Check {
+    match request.send().await {
+        Ok(response) if response.status().is_success() => Check::new(subject, Status::Ok, "token accepted".to_string()),
+        Ok(response) if matches!(response.status(), StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => Check::new(
+            subject,
+            Status::Failure,
+            format!("token rejected ({}): generate a new one and write it to {}", response.status(), token_file),
+        ),
+        Ok(response) => Check::new(subject, Status::Warning, format!("couldn't check the token: unexpected status {}", response.status())),
+        Err(err) => Check::new(subject, Status::Failure, format!("couldn't reach the API: {} (check the endpoint and the network)", err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use crate::cli::doctor::{lock_check, Status, storage_check};
+    use crate::search::tantivy_impl::{Analyzer, TantivySearchEngine};
+
+    #[test]
+    fn test_index_checks() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let index = root.path().join("index");
+
+        assert_eq!(storage_check(&index).status, Status::Ok);
+
+        let engine = TantivySearchEngine::open(&index, &[], &Analyzer::default())?;
+        assert_eq!(lock_check(&index).status, Status::Failure);
+
+        drop(engine);
+        assert_eq!(lock_check(&index).status, Status::Ok);
+        assert_eq!(storage_check(&index).status, Status::Ok);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::BTreeSet;
+
+use tantivy::tokenizer::TextAnalyzer;
+use tokio_stream::StreamExt;
+
+use crate::cli::open::{is_local_file, link_path};
+use crate::search::language;
+use crate::search::query::ParsedQuery;
+use crate::search::{SearchEngine, SearchRequest};
+
+/// Lines printed around each matching line, like the `-B` / `-A` options of grep.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct LineContext {
+    pub before: usize,
+    pub after: usize,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum GrepLine<'a> {
+    Match { number: usize, text: &'a str },
+    Context { number: usize, text: &'a str },
+    /// Between two groups of lines that aren't contiguous.
+    Separator,
+}
+
+/// Prints the lines of the matching local files containing the searched words of the query (not
+/// the excluded ones nor the filters), prefixed with `file:line:` like ripgrep. Results that aren't
+/// local files (web pages, remote documents) are skipped as their content can't be read back.
+pub async fn grep(search: &dyn SearchEngine, request: &SearchRequest, context: LineContext) -> anyhow::Result<()> {
+    let results = search.search(request).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+    let words = ParsedQuery::parse(&request.query)?.words();
+
+    // The sections of a file are grepped once, in the whole file
+    let mut files = vec![];
+    for result in results.iter().filter(|result| is_local_file(&result.link)) {
+        let file = link_path(&result.link);
+
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+
+    for file in files {
+        let content = match std::fs::read_to_string(file) {
+            Ok(content) => content,
+            Err(err) => {
+                log::warn!("Couldn't read {:?}: {}", file, err);
+                continue;
+            }
+        };
+
+        // Stemmed like the content indexed in its language, so that `restarting` finds `restart`
+        let analyzer = language::detect(&content).and_then(language::analyzer);
+        let query_terms = terms(&words, analyzer.as_ref());
+
+        for line in grep_lines(&content, &query_terms, analyzer.as_ref(), context) {
+            match line {
+                GrepLine::Match { number, text } => println!("{}:{}:{}", file.display(), number, text),
+                GrepLine::Context { number, text } => println!("{}-{}-{}", file.display(), number, text),
+                GrepLine::Separator => println!("--"),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The content is split into lowercase alphanumeric words, the way the indexes tokenize it, then
+/// stemmed by the analyzer of its language if any.
+fn terms(text: &str, analyzer: Option<&TextAnalyzer>) -> BTreeSet<String> {
+    match analyzer {
+        Some(analyzer) => {
+            let mut terms = BTreeSet::new();
+            analyzer.token_stream(text).process(&mut |token| {
+                terms.insert(token.text.clone());
+            });
+            terms
+        }
+        None => text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect(),
+    }
+}
+
+fn grep_lines<'a>(
+    content: &'a str,
+    query_terms: &BTreeSet<String>,
+    analyzer: Option<&TextAnalyzer>,
+    context: LineContext,
+) -> Vec<GrepLine<'a>> {
+    let lines = content.lines().collect::<Vec<_>>();
+    let matches = lines
+        .iter()
+        .map(|line| !query_terms.is_disjoint(&terms(line, analyzer)))
+        .collect::<Vec<_>>();
+
+    let mut printed = vec![];
+    let mut last: Option<usize> = None;
+
+    for (i, _) in matches.iter().enumerate().filter(|(_, matched)| **matched) {
+        let start = i.saturating_sub(context.before);
+        let end = (i + context.after).min(lines.len() - 1);
+
+        if last.is_some_and(|last| start > last + 1) {
+            printed.push(GrepLine::Separator);
+        }
+
+        // Lines already printed as the context of a previous match aren't printed again
+        for j in start.max(last.map_or(0, |last| last + 1))..=end {
+            printed.push(match matches[j] {
+                true => GrepLine::Match { number: j + 1, text: lines[j] },
+                false => GrepLine::Context { number: j + 1, text: lines[j] },
+            });
+            last = Some(j);
+        }
+    }
+
+    printed
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::grep::{grep_lines, GrepLine, LineContext, terms};
+    use crate::search::language;
+    use crate::search::query::ParsedQuery;
+
+    #[test]
+    fn test_grep_lines() {
+        let content = "# Runbook\n\nRestart the Database:\n  systemctl restart postgres\n\nThen check the logs.\nCall the on-call.\nDone";
+        let query_terms = terms("database logs", None);
+
+        assert_eq!(
+            grep_lines(content, &query_terms, None, LineContext::default()),
+            vec![
+                GrepLine::Match { number: 3, text: "Restart the Database:" },
+                GrepLine::Separator,
+                GrepLine::Match { number: 6, text: "Then check the logs." },
+            ],
+        );
+
+        assert_eq!(
+            grep_lines(content, &query_terms, None, LineContext { before: 1, after: 2 }),
+            vec![
+                GrepLine::Context { number: 2, text: "" },
+                GrepLine::Match { number: 3, text: "Restart the Database:" },
+                GrepLine::Context { number: 4, text: "  systemctl restart postgres" },
+                GrepLine::Context { number: 5, text: "" },
+                GrepLine::Match { number: 6, text: "Then check the logs." },
+                GrepLine::Context { number: 7, text: "Call the on-call." },
+                GrepLine::Context { number: 8, text: "Done" },
+            ],
+        );
+
+        assert!(grep_lines(content, &terms("missing", None), None, LineContext { before: 2, after: 2 }).is_empty());
+    }
+
+    #[test]
+    fn test_query_terms() -> anyhow::Result<()> {
+        let content = "Source of the incident\nRestarting the database\nThe github mirror is down";
+
+        // Neither the excluded words nor the filters are searched
+        let words = ParsedQuery::parse("title:incident database -mirror source:github")?.words();
+        assert_eq!(
+            grep_lines(content, &terms(&words, None), None, LineContext::default()),
+            vec![GrepLine::Match { number: 1, text: "Source of the incident" }, GrepLine::Match { number: 2, text: "Restarting the database" }],
+        );
+
+        let analyzer = language::analyzer("en");
+        assert_eq!(
+            grep_lines(content, &terms("restart", analyzer.as_ref()), analyzer.as_ref(), LineContext::default()),
+            vec![GrepLine::Match { number: 2, text: "Restarting the database" }],
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::cli::state::data_dir;
+use crate::utils::table::format_table;
+
+/// Past searches older than the last `MAX_HISTORY` ones are forgotten.
+const MAX_HISTORY: usize = 1000;
+
+/// The past searches and the saved queries, kept in `<data dir>/history.json`.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct History {
+    #[serde(default)]
+    pub searches: Vec<PastSearch>,
+    #[serde(default)]
+    pub saved: BTreeMap<String, SavedQuery>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct PastSearch {
+    pub query: String,
+    pub at: DateTime<Utc>,
+}
+
+/// A query along with the filters scoping it.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct SavedQuery {
+    pub query: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl History {
+    fn record(&mut self, query: &str, at: DateTime<Utc>) {
+        self.searches.push(PastSearch { query: query.to_string(), at });
+
+        if self.searches.len() > MAX_HISTORY {
+            self.searches.drain(..self.searches.len() - MAX_HISTORY);
+        }
+    }
+}
+
+pub struct HistoryStore {
+    path: PathBuf,
+}
+
+impl HistoryStore {
+    pub fn new<T: AsRef<Path>>(path: T) -> Self {
+        Self { path: path.as_ref().to_path_buf() }
+    }
+
+    pub fn default_location() -> anyhow::Result<Self> {
+        Ok(Self::new(data_dir()?.join("history.json")))
+    }
+
+    pub async fn load(&self) -> anyhow::Result<History> {
+        if !self.path.exists() {
+            return Ok(History::default());
+        }
+
+        let content = tokio::fs::read_to_string(&self.path).await?;
+
+        serde_json::from_str(&content).with_context(|| format!("Invalid history file: {:?}", self.path))
+    }
+
+    async fn store(&self, history: &History) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        tokio::fs::write(&self.path, serde_json::to_vec_pretty(history)?)
+            .await
+            .with_context(|| format!("Couldn't write history file: {:?}", self.path))
+    }
+
+    pub async fn record_search(&self, query: &str) -> anyhow::Result<()> {
+        let mut history = self.load().await?;
+        history.record(query, Utc::now());
+
+        self.store(&history).await
+    }
+
+    pub async fn save_query(&self, name: &str, query: SavedQuery) -> anyhow::Result<()> {
+        let mut history = self.load().await?;
+        history.saved.insert(name.to_string(), query);
+
+        self.store(&history).await
+    }
+
+    pub async fn saved_query(&self, name: &str) -> anyhow::Result<SavedQuery> {
+        match self.load().await?.saved.remove(name) {
+            Some(query) => Ok(query),
+            None => bail!("No saved query named '{}' (save one with `doks save-query`)", name),
+        }
+    }
+}
+
+/// The last `limit` searches, the most recent last.
+pub fn print_searches(history: &History, limit: usize) {
+    for search in history.searches.iter().skip(history.searches.len().saturating_sub(limit)) {
+        println!("{}  {}", search.at.format("%Y-%m-%d %H:%M"), search.query);
+    }
+}
+
+pub fn print_saved(history: &History) {
+    let header = ["NAME", "QUERY", "SOURCES", "FILTERS"].map(String::from);
+    let rows = history.saved.iter().map(|(name, saved)| {
+        let filters = saved.metadata
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .chain(saved.path.iter().map(|path| format!("path={}", path)))
+            .chain(saved.tags.iter().map(|tag| format!("tag={}", tag)))
+            .collect::<Vec<_>>();
+
+        [name.clone(), saved.query.clone(), saved.sources.join(","), filters.join(" ")]
+    });
+
+    for line in format_table(&std::iter::once(header).chain(rows).collect::<Vec<_>>()) {
+        println!("{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use tempdir::TempDir;
+
+    use crate::cli::history::{History, HistoryStore, MAX_HISTORY, SavedQuery};
+
+    #[tokio::test]
+    async fn test_history_store() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let store = HistoryStore::new(root.path().join("doks/history.json"));
+
+        store.record_search("database").await?;
+        store.record_search("on-call").await?;
+
+        let saved = SavedQuery { query: "runbook".to_string(), sources: vec!["wiki".to_string()], ..SavedQuery::default() };
+        store.save_query("runbooks", saved.clone()).await?;
+
+        let history = store.load().await?;
+        assert_eq!(history.searches.iter().map(|s| s.query.as_str()).collect::<Vec<_>>(), vec!["database", "on-call"]);
+        assert_eq!(store.saved_query("runbooks").await?, saved);
+        assert!(store.saved_query("unknown").await.is_err());
+
+        let mut history = History::default();
+        for i in 0..MAX_HISTORY + 5 {
+            history.record(&i.to_string(), Utc.ymd(2022, 1, 1).and_hms(0, 0, 0));
+        }
+        assert_eq!(history.searches.len(), MAX_HISTORY);
+        assert_eq!(history.searches[0].query, "5");
+
+        Ok(())
+    }
+}
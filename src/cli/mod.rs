@@ -1,25 +1,105 @@
+use std::collections::{BTreeMap, HashSet};
 use std::convert::TryInto;
+use std::fmt;
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-use anyhow::Context;
+use anyhow::{bail, Context};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use indicatif::MultiProgress;
+use structopt::clap::{self, ErrorKind};
 use structopt::StructOpt;
+use tokio::io::AsyncReadExt;
 use tokio_stream::StreamExt;
 
-use crate::cli::config::DoksConfig;
-use crate::search::SearchEngine;
+use crate::cli::bookmarks::{BookmarkCommand, BookmarkStore};
+use crate::cli::config::{default_config_file, DEFAULT_NAMESPACE, DoksConfig, load_config, read_token_file, SearchEngineConfig, SourceConfig};
+use crate::cli::config_init::ConfigCommand;
+use crate::cli::history::{HistoryStore, SavedQuery};
+use crate::cli::output::OutputFormat;
+use crate::cli::progress::{IndexSummary, SourceProgress};
+use crate::cli::sources::SourcesCommand;
+use crate::cli::state::StateStore;
+use crate::mcp;
+use crate::search::boosted_impl::SourceBoostedSearchEngine;
+use crate::search::{SearchEngine, SearchRequest, SortOrder, MAX_FUZZY_DISTANCE};
+use crate::server;
+use crate::server::metrics::metrics;
+use crate::slack;
 use crate::sources::DocumentSource;
+use crate::tui;
 use crate::utils::StreamUtils;
 
+mod bench;
+mod bookmarks;
 pub mod config;
+mod config_init;
+mod daemon;
+mod dedupe;
+mod doctor;
+mod grep;
+mod history;
+mod open;
+mod purge;
+mod reindex;
+pub(crate) mod output;
+mod progress;
+mod prune;
+mod repl;
+mod snapshot;
+mod sources;
+mod state;
+mod stats;
+mod tail;
+mod validate;
+mod watch;
+mod webhooks;
+
+/// Returned when a search finds nothing, so that doks exits with `EXIT_NO_RESULTS`.
+#[derive(Debug)]
+pub struct NoResults;
+
+impl fmt::Display for NoResults {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "No results found")
+    }
+}
+
+impl std::error::Error for NoResults {}
+
+pub const EXIT_NO_RESULTS: u8 = 1;
+pub const EXIT_ERROR: u8 = 2;
+
+/// The code doks exits with when a command fails, or its arguments can't be parsed: clap exits
+/// with 1 on usage errors, which is already the code of the searches without results.
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    match err.downcast_ref::<clap::Error>() {
+        Some(err) if matches!(err.kind, ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed) => 0,
+        _ if err.is::<NoResults>() => EXIT_NO_RESULTS,
+        _ => EXIT_ERROR,
+    }
+}
+
+/// Below this number of results, a spelling suggestion is looked for.
+const FEW_RESULTS: usize = 3;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "doks")]
 pub struct DoksOpts {
-    #[structopt(short = "-n", default_value = "default")]
+    /// Keeps the index apart from the other namespaces, and uses the config section of the namespace if any
+    #[structopt(short = "-n", long, default_value = DEFAULT_NAMESPACE)]
     pub namespace: String,
 
-    #[structopt(parse(from_os_str), short = "-c", long = "--config")]
-    pub config_file: PathBuf,
+    /// Config file or directory of config files, can be repeated to merge several configs.
+    /// Defaults to `~/.config/doks/config.json`
+    #[structopt(parse(from_os_str), short = "-c", long = "--config", number_of_values = 1)]
+    pub config_files: Vec<PathBuf>,
+
+    /// Don't print any log, only the results (and the errors)
+    #[structopt(short, long)]
+    pub quiet: bool,
 
     #[structopt(subcommand)]
     pub cmd: DoksCommand,
@@ -27,49 +107,895 @@ pub struct DoksOpts {
 
 #[derive(Debug, StructOpt)]
 pub enum DoksCommand {
-    Index,
+    Index {
+        /// Only index these sources (all of them by default)
+        #[structopt(long = "source")]
+        sources: Vec<String>,
+        /// Fetch and print the documents that would be indexed, without touching the engine
+        #[structopt(long)]
+        dry_run: bool,
+    },
+    /// Indexes all the sources into a fresh index then swaps it with the current one, which stays
+    /// untouched if the indexing fails (tantivy, sqlite and semantic engines).
+    Reindex,
+    /// Indexes a corpus and reports the indexing throughput and the query latencies of the engine,
+    /// or of the engines whose configs are given. The actual index isn't touched.
+    Bench {
+        /// Engine config file (the `engine` section of a config) to benchmark, repeatable
+        #[structopt(long = "engine", number_of_values = 1)]
+        engines: Vec<PathBuf>,
+        /// Index the documents of this source rather than a synthetic corpus
+        #[structopt(long)]
+        source: Option<String>,
+        /// Number of documents of the synthetic corpus
+        #[structopt(long, default_value = "1000")]
+        documents: usize,
+        /// Query to measure, repeatable (random queries by default)
+        #[structopt(long = "query", number_of_values = 1)]
+        queries: Vec<String>,
+        /// Times each query is run
+        #[structopt(long, default_value = "10")]
+        iterations: usize,
+    },
+    /// Removes from the index the documents having the same content as another one (e.g. the same
+    /// README in several repositories), keeping the copy of the source configured first. The copies
+    /// come back when their sources are indexed again.
+    Dedupe {
+        /// Print the duplicates without removing them
+        #[structopt(long)]
+        dry_run: bool,
+    },
+    /// Removes from the index the documents whose local file was deleted or whose web page is gone
+    /// (404 or 410). Links that couldn't be checked are reported but kept.
+    Prune {
+        /// Print the dead links without removing their documents
+        #[structopt(long)]
+        dry_run: bool,
+        /// Only check the local files, not the HTTP links
+        #[structopt(long)]
+        local_only: bool,
+        /// Maximum number of links checked at the same time
+        #[structopt(long, default_value = "8")]
+        concurrency: usize,
+    },
+    /// Creates the config file.
+    Config(ConfigCommand),
+    /// Lists and inspects the configured sources.
+    Sources(SourcesCommand),
+    /// Checks the config and reports all the problems found.
+    Validate {
+        /// Also check that the engine and the source APIs are reachable
+        #[structopt(long)]
+        check_connectivity: bool,
+    },
+    /// Shows the engine, its size on disk and the number of documents per source.
+    Stats,
+    /// Checks the config, the index storage, the credentials of the sources and the engine.
+    Doctor,
+    /// Searches the index. Exits with 1 when nothing is found, and 2 on errors.
     Search {
-        query: String
+        /// Words and "phrases", restricted to a field with `title:` or `content:`, required with `+`
+        /// or excluded with `-`. `source:`, `path:`, `tag:` and `meta:key=value` filter the documents
+        #[structopt(required_unless = "saved")]
+        query: Option<String>,
+        /// Run the query saved under this name (see `save-query`), the filters given add to its own
+        #[structopt(long, value_name = "NAME", conflicts_with = "query")]
+        saved: Option<String>,
+        #[structopt(long, alias = "format", default_value = "json", possible_values = OutputFormat::VARIANTS)]
+        output: OutputFormat,
+        /// End the results with a null character rather than a new line (json, plain and fzf outputs)
+        #[structopt(long)]
+        print0: bool,
+        /// Print the parsed query and the score breakdown of each result instead of the results
+        #[structopt(long, conflicts_with = "open-nth")]
+        explain: bool,
+        /// Open the Nth result once printed
+        #[structopt(long = "open", value_name = "N")]
+        open_nth: Option<usize>,
+        /// Maximum number of results (10 unless the engine config sets another default)
+        #[structopt(long)]
+        limit: Option<usize>,
+        /// Number of results to skip
+        #[structopt(long, default_value = "0")]
+        offset: usize,
+        /// Page of `--limit` results to show, starting at 1
+        #[structopt(long, conflicts_with = "offset")]
+        page: Option<usize>,
+        /// Only search the documents of this source
+        #[structopt(long = "source")]
+        sources: Vec<String>,
+        /// Only search the documents having this metadata value (key=value)
+        #[structopt(long = "meta", value_name = "key=value", parse(try_from_str = parse_metadata))]
+        metadata: Vec<(String, String)>,
+        /// Only search the documents whose path matches this glob (e.g. 'docs/**')
+        #[structopt(long)]
+        path: Option<String>,
+        /// Only search the documents having this tag
+        #[structopt(long = "tag")]
+        tags: Vec<String>,
+        /// Only search the documents modified in this period (e.g. `30d`, `12h`) or since this date
+        /// (e.g. `2024-01-31`)
+        #[structopt(long, value_name = "period|date", parse(try_from_str = parse_since))]
+        since: Option<DateTime<Utc>>,
+        /// Order of the results: the most relevant or the most recently modified first, or the
+        /// alphabetical order of the titles or sources
+        #[structopt(long, default_value = "relevance", possible_values = &["relevance", "date", "title", "source"], parse(try_from_str = parse_sort))]
+        sort: SortOrder,
+        /// Only search the bookmarked documents
+        #[structopt(long)]
+        bookmarked: bool,
+        /// Also find the words with typos: at most this number of edits (2 when not given)
+        #[structopt(long, value_name = "distance")]
+        fuzzy: Option<Option<u8>>,
+        /// Also count the matching documents per source, and per value of these metadata keys if
+        /// any (e.g. `--facets=lang,team`)
+        #[structopt(long, value_name = "keys", use_delimiter = true, require_equals = true)]
+        facets: Option<Vec<String>>,
+        /// Search the spelling suggestion instead when the query finds (almost) nothing
+        #[structopt(long)]
+        auto_correct: bool,
+    },
+    /// Saves a query along with its filters, to be run with `doks search --saved <name>`. A query
+    /// already saved under this name is replaced.
+    SaveQuery {
+        name: String,
+        query: String,
+        /// Only search the documents of this source
+        #[structopt(long = "source")]
+        sources: Vec<String>,
+        /// Only search the documents having this metadata value (key=value)
+        #[structopt(long = "meta", value_name = "key=value", parse(try_from_str = parse_metadata))]
+        metadata: Vec<(String, String)>,
+        /// Only search the documents whose path matches this glob (e.g. 'docs/**')
+        #[structopt(long)]
+        path: Option<String>,
+        /// Only search the documents having this tag
+        #[structopt(long = "tag")]
+        tags: Vec<String>,
+    },
+    /// Prints the past searches, the most recent last.
+    History {
+        /// Number of searches to print
+        #[structopt(long, default_value = "20")]
+        limit: usize,
+        /// Print the saved queries instead
+        #[structopt(long)]
+        saved: bool,
+    },
+    /// Bookmarks documents, to search them with `doks search --bookmarked`.
+    Bookmark(BookmarkCommand),
+    /// Finds the documents similar to an indexed one, given its id (the `id` of the search results).
+    Similar {
+        id: String,
+        #[structopt(long, alias = "format", default_value = "json", possible_values = OutputFormat::VARIANTS)]
+        output: OutputFormat,
+        /// Maximum number of results (10 unless the engine config sets another default)
+        #[structopt(long)]
+        limit: Option<usize>,
+    },
+    /// Keeps running the query and prints the documents that newly match it as the index is updated
+    /// (by `doks index`, `doks watch` or `doks daemon`).
+    Tail {
+        query: String,
+        #[structopt(long, alias = "format", default_value = "json", possible_values = OutputFormat::VARIANTS)]
+        output: OutputFormat,
+        /// Seconds between two runs of the query
+        #[structopt(long, default_value = "10")]
+        interval: u64,
+        /// Maximum number of results of each run
+        #[structopt(long, default_value = "100")]
+        limit: usize,
+        /// Only search the documents of this source
+        #[structopt(long = "source")]
+        sources: Vec<String>,
+    },
+    /// Prints the lines of the matching local files containing the query terms, like grep.
+    Grep {
+        query: String,
+        /// Lines to print after each matching line
+        #[structopt(short = "A", long, value_name = "NUM")]
+        after_context: Option<usize>,
+        /// Lines to print before each matching line
+        #[structopt(short = "B", long, value_name = "NUM")]
+        before_context: Option<usize>,
+        /// Lines to print before and after each matching line
+        #[structopt(short = "C", long, value_name = "NUM", default_value = "0")]
+        context: usize,
+        /// Maximum number of files to search
+        #[structopt(long, default_value = "10")]
+        limit: usize,
+        /// Only search the documents of this source
+        #[structopt(long = "source")]
+        sources: Vec<String>,
+    },
+    /// Opens a link or file, or the Nth result of a query: urls in the browser and files in $EDITOR.
+    Open {
+        #[structopt(required_unless = "from-stdin")]
+        query: Option<String>,
+        /// Open the line selected from `doks search --format fzf`, read from stdin
+        #[structopt(long, conflicts_with = "query")]
+        from_stdin: bool,
+        #[structopt(long, default_value = "1")]
+        nth: usize,
+    },
+    /// Indexes the sources then keeps reindexing them as they change.
+    Watch {
+        /// Seconds between two reindexing of the sources that can't be watched (all but filesystem ones)
+        #[structopt(long, default_value = "3600")]
+        interval: u64,
+    },
+    /// Indexes the sources on the schedules of the `daemon` section of the config, and the pushed
+    /// repositories on the GitHub and GitLab webhooks (`POST /hooks/<source id>`) when a webhook
+    /// secret is configured.
+    Daemon {
+        /// Address of the status and webhooks endpoints
+        #[structopt(long, default_value = "127.0.0.1:8081")]
+        address: SocketAddr,
+    },
+    /// Runs queries typed interactively, loading the engine only once.
+    Repl {
+        #[structopt(long, default_value = "table", possible_values = OutputFormat::VARIANTS)]
+        output: OutputFormat,
+    },
+    /// Interactive search in the terminal.
+    Tui {
+        #[structopt(default_value = "")]
+        query: String,
+    },
+    /// Exposes the index over HTTP, e.g. for the `remote` engine of other doks instances, and
+    /// optionally over gRPC (see `proto/doks.proto`).
+    Serve {
+        #[structopt(long, default_value = "127.0.0.1:8080")]
+        address: SocketAddr,
+        /// Also serve the gRPC API on this address
+        #[structopt(long)]
+        grpc_address: Option<SocketAddr>,
+        /// Only serve the gRPC API
+        #[structopt(long, requires = "grpc-address")]
+        no_http: bool,
+        /// File containing the bearer token clients must send
+        #[structopt(long)]
+        token_file: Option<String>,
+    },
+    /// Serves the index to LLM assistants over the Model Context Protocol (MCP) on stdin/stdout,
+    /// with `search` and `get_document` tools.
+    Mcp,
+    /// Answers the `/doks <query>` slash commands and the mentions of the bot in Slack with the top
+    /// results, connecting with Socket Mode (no public endpoint needed).
+    Slackbot {
+        /// File containing the app level token (xapp-...), with the connections:write scope
+        #[structopt(long)]
+        app_token_file: String,
+        /// File containing the bot token (xoxb-...), with the chat:write scope
+        #[structopt(long)]
+        bot_token_file: String,
+        /// Number of results of each answer
+        #[structopt(long, default_value = "5")]
+        limit: usize,
+    },
+    /// Packages the index in a snapshot that can be shared and imported elsewhere.
+    Export {
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+    },
+    /// Replaces the index with the content of a snapshot.
+    Import {
+        #[structopt(parse(from_os_str))]
+        snapshot: PathBuf,
+        /// Replace the index if it already exists
+        #[structopt(long)]
+        force: bool,
+    },
+    /// Removes the indexed documents (only the ones of the given sources with `--source`).
+    Purge {
+        #[structopt(long = "source")]
+        sources: Vec<String>,
+        /// Only show how many documents would be deleted
+        #[structopt(long)]
+        dry_run: bool,
+        /// Don't ask for a confirmation
+        #[structopt(long, short)]
+        yes: bool,
     },
-    Purge,
 }
 
 pub async fn cli_main(opts: DoksOpts) -> anyhow::Result<()> {
-    let config = tokio::fs::read_to_string(&opts.config_file).await?;
-    let config: DoksConfig = serde_json::from_str(config.as_str())?;
+    let config_files = match opts.config_files.is_empty() {
+        true => vec![default_config_file()?],
+        false => opts.config_files.clone(),
+    };
+
+    // These commands don't need a valid config
+    match &opts.cmd {
+        DoksCommand::Validate { check_connectivity } => return validate::validate(&config_files, *check_connectivity).await,
+        DoksCommand::Config(command) => match config_files.as_slice() {
+            [config_file] => return config_init::config_main(config_file, command).await,
+            _ => bail!("A single config file must be given to write the config"),
+        },
+        DoksCommand::SaveQuery { name, query, sources, metadata, path, tags } => {
+            let saved = SavedQuery {
+                query: query.clone(),
+                sources: sources.clone(),
+                metadata: metadata.iter().cloned().collect(),
+                path: path.clone(),
+                tags: tags.clone(),
+            };
+
+            return HistoryStore::default_location()?.save_query(name, saved).await;
+        }
+        DoksCommand::History { limit, saved } => {
+            let history = HistoryStore::default_location()?.load().await?;
+
+            match saved {
+                true => history::print_saved(&history),
+                false => history::print_searches(&history, *limit),
+            }
+
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let config = load_config(&config_files).await?.for_namespace(&opts.namespace)?;
+
+    // Before anything opens the index, which fails if it was built by another version of doks
+    if !matches!(opts.cmd, DoksCommand::Reindex | DoksCommand::Bench { .. } | DoksCommand::Index { dry_run: true, .. }) {
+        reindex::migrate(&config.engine).await?;
+    }
 
     match &opts.cmd {
-        DoksCommand::Index => {
+        DoksCommand::Index { sources, dry_run: true } => {
+            let (mut documents, mut bytes) = (0, 0);
+
+            for source in select_sources(&config, sources)? {
+                println!("Source: {}", source.id());
+
+                let (source_documents, source_bytes) = sources::dry_run_source(source).await?;
+                println!("{} documents ({} bytes)\n", source_documents, source_bytes);
+
+                documents += source_documents;
+                bytes += source_bytes;
+            }
+
+            println!("Dry run: {} documents ({} bytes) would be indexed, the index wasn't modified", documents, bytes);
+        }
+        DoksCommand::Index { sources, dry_run: false } => {
+            let selected = select_sources(&config, sources)?;
             let search: Box<dyn SearchEngine> = (&config.engine).try_into()?;
-            for source_config in &config.sources {
-                let source: Box<dyn DocumentSource> = source_config.try_into()?;
-                let mut stream = source.fetch().batched(10);
 
-                while let Some(documents) = stream.next().await {
-                    let collected = documents
-                        .into_iter()
-                        .collect::<anyhow::Result<Vec<_>>>()
-                        .context(format!("Error occurred while fetching documents from source: {}", source_config.id()))?;
+            let state = StateStore::for_namespace(&opts.namespace)?;
+            let progress = (!opts.quiet).then(MultiProgress::new);
+            let summaries = index_sources(selected, search.as_ref(), Some(&state), progress.as_ref()).await?;
 
-                    search.index(collected).await?;
+            progress::print_summary(&summaries);
+        }
+        DoksCommand::Bench { engines, source, documents, queries, iterations } => {
+            let options = bench::BenchOptions {
+                engines: engines.clone(),
+                source: source.clone(),
+                documents: *documents,
+                queries: queries.clone(),
+                iterations: *iterations,
+            };
+
+            bench::bench(config, &options).await?;
+        }
+        DoksCommand::Prune { dry_run, local_only, concurrency } => {
+            let search = queryable_engine(&config).await?;
+            let options = prune::PruneOptions { dry_run: *dry_run, local_only: *local_only, concurrency: *concurrency };
+
+            prune::prune(search.as_ref(), &options).await?;
+        }
+        DoksCommand::Dedupe { dry_run } => {
+            let search = queryable_engine(&config).await?;
+
+            dedupe::dedupe(&config, search.as_ref(), *dry_run).await?;
+        }
+        DoksCommand::Reindex => {
+            let state = StateStore::for_namespace(&opts.namespace)?;
+
+            progress::print_summary(&reindex::reindex(config, &state).await?);
+        }
+        DoksCommand::Validate { .. } | DoksCommand::Config(_) | DoksCommand::SaveQuery { .. } | DoksCommand::History { .. } => {
+            unreachable!("Handled before loading the config")
+        }
+        DoksCommand::Sources(command) => sources::sources_main(&config, command).await?,
+        DoksCommand::Doctor => doctor::doctor(&config).await?,
+        DoksCommand::Stats => {
+            let search = queryable_engine(&config).await?;
+            let state = StateStore::for_namespace(&opts.namespace)?.load().await?;
+
+            stats::print_stats(&config, search.as_ref(), &state).await?;
+        }
+        DoksCommand::Search { query, saved, output, print0, explain, open_nth, limit, offset, page, sources, metadata, path, tags, since, sort, bookmarked, fuzzy, facets, auto_correct } => {
+            let limit = limit.unwrap_or_else(|| config.engine.default_limit());
+            let offset = match page {
+                Some(0) => bail!("Pages start at 1"),
+                Some(page) => (page - 1) * limit,
+                None => *offset,
+            };
+
+            let fuzzy = fuzzy.map(|distance| distance.unwrap_or(MAX_FUZZY_DISTANCE));
+
+            if fuzzy.is_some_and(|distance| distance > MAX_FUZZY_DISTANCE) {
+                bail!("The fuzzy distance is at most {}", MAX_FUZZY_DISTANCE)
+            }
+
+            let history = HistoryStore::default_location()?;
+            let saved = match saved {
+                Some(name) => history.saved_query(name).await?,
+                None => SavedQuery { query: query.clone().context("A query is required")?, ..SavedQuery::default() },
+            };
+
+            // A search doesn't fail because its history couldn't be written
+            if let Err(err) = history.record_search(&saved.query).await {
+                log::warn!("Couldn't record the search in the history: {:#}", err);
+            }
+
+            let search = queryable_engine(&config).await?;
+            let mut request = SearchRequest {
+                limit,
+                offset,
+                source_filter: saved.sources.into_iter().chain(sources.iter().cloned()).collect(),
+                metadata_filter: saved.metadata.into_iter().chain(metadata.iter().cloned()).collect(),
+                path_filter: path.clone().or(saved.path),
+                tag_filter: saved.tags.into_iter().chain(tags.iter().cloned()).collect(),
+                modified_since: *since,
+                sort: *sort,
+                id_filter: match bookmarked {
+                    true => BookmarkStore::for_namespace(&opts.namespace)?.ids().await?,
+                    false => vec![],
+                },
+                fuzzy,
+                ..SearchRequest::new(&saved.query)
+            };
+
+            if *explain {
+                output::print_explanation(&search.explain(&request).await?);
+                return Ok(());
+            }
+
+            let mut results = search.search(&request).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+
+            let suggestion = match results.len() < FEW_RESULTS {
+                // Most engines can't suggest anything, which isn't worth failing the search
+                true => search.suggest(&request.query).await.unwrap_or_else(|err| {
+                    log::debug!("No spelling suggestion: {:#}", err);
+                    None
+                }),
+                false => None,
+            };
+
+            let suggestion = match (suggestion, auto_correct) {
+                (Some(suggestion), true) => {
+                    eprintln!("Showing the results of: {}", suggestion);
+                    request.query = suggestion;
+                    results = search.search(&request).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+                    None
                 }
+                (suggestion, _) => suggestion,
+            };
+
+            let printed = output::print_results(*output, *print0, tokio_stream::iter(results.iter().cloned().map(Ok))).await?;
+
+            if let (Some(nth), true) = (open_nth, printed > 0) {
+                open::open_link(&open::nth_result(results, *nth)?.link)?;
+            }
+
+            if let Some(keys) = facets {
+                output::print_facets(*output, &search.facets(&request, keys).await?)?;
+            }
+
+            if let Some(suggestion) = suggestion {
+                eprintln!("Did you mean: {}", suggestion);
+            }
+
+            if printed == 0 {
+                return Err(NoResults.into());
+            }
+        }
+        DoksCommand::Bookmark(command) => bookmarks::bookmark_main(&config, &opts.namespace, command).await?,
+        DoksCommand::Similar { id, output, limit } => {
+            let search = queryable_engine(&config).await?;
+
+            let limit = limit.unwrap_or_else(|| config.engine.default_limit());
+
+            output::print_results(*output, false, search.similar(id, limit).await?).await?;
+        }
+        DoksCommand::Tail { query, output, interval, limit, sources } => {
+            let request = SearchRequest { limit: *limit, source_filter: sources.clone(), ..SearchRequest::new(query) };
+
+            tail::tail(&config, &request, *output, Duration::from_secs(*interval)).await?;
+        }
+        DoksCommand::Grep { query, after_context, before_context, context, limit, sources } => {
+            let search = queryable_engine(&config).await?;
+            let request = SearchRequest { limit: *limit, source_filter: sources.clone(), ..SearchRequest::new(query) };
+            let context = grep::LineContext {
+                before: before_context.unwrap_or(*context),
+                after: after_context.unwrap_or(*context),
+            };
+
+            grep::grep(search.as_ref(), &request, context).await?;
+        }
+        DoksCommand::Open { from_stdin: true, .. } => {
+            let mut selection = String::new();
+            tokio::io::stdin().read_to_string(&mut selection).await?;
+
+            open::open_link(open::selected_link(&selection).context("Nothing selected")?)?;
+        }
+        DoksCommand::Open { query, nth, .. } => {
+            let query = query.as_deref().context("A query or --from-stdin is required")?;
+
+            if open::is_link(query) {
+                open::open_link(query)?;
+            } else {
+                let search = queryable_engine(&config).await?;
+                let request = SearchRequest { limit: *nth, ..SearchRequest::new(query) };
+                let results = search.search(&request).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+
+                open::open_link(&open::nth_result(results, *nth)?.link)?;
             }
         }
-        DoksCommand::Search { query } => {
+        DoksCommand::Watch { interval } => {
             let search: Box<dyn SearchEngine> = (&config.engine).try_into()?;
-            let mut results = search.search(query)?;
+            let state = StateStore::for_namespace(&opts.namespace)?;
 
-            while let Some(result) = results.next().await {
-                let document = result?;
-                let json = serde_json::to_string(&document)?;
+            watch::watch(&config, search.as_ref(), &state, Duration::from_secs(*interval)).await?;
+        }
+        DoksCommand::Daemon { address } => {
+            let search: Box<dyn SearchEngine> = (&config.engine).try_into()?;
+            let state = StateStore::for_namespace(&opts.namespace)?;
 
-                println!("{}", json)
+            daemon::daemon(&config, search.as_ref(), &state, address).await?;
+        }
+        DoksCommand::Repl { output } => {
+            let search = queryable_engine(&config).await?;
+            repl::repl(search.as_ref(), *output).await?;
+        }
+        DoksCommand::Tui { query } => {
+            let search = queryable_engine(&config).await?;
+
+            tui::run(search.as_ref(), query).await?;
+        }
+        DoksCommand::Serve { address, grpc_address, no_http, token_file } => {
+            let search: Arc<dyn SearchEngine> = Arc::from(queryable_engine(&config).await?);
+
+            let token = token_file.as_deref().map(read_token_file).transpose()?;
+
+            match (grpc_address, no_http) {
+                (Some(grpc_address), true) => server::grpc::serve(search, grpc_address, token).await?,
+                (Some(grpc_address), false) => {
+                    tokio::try_join!(
+                        server::serve(search.clone(), address, token.clone()),
+                        server::grpc::serve(search, grpc_address, token),
+                    )?;
+                }
+                (None, _) => server::serve(search, address, token).await?,
             }
         }
-        DoksCommand::Purge => {
+        DoksCommand::Mcp => {
+            let search = queryable_engine(&config).await?;
 
+            mcp::serve_stdio(search.as_ref()).await?;
         }
+        DoksCommand::Slackbot { app_token_file, bot_token_file, limit } => {
+            let search = queryable_engine(&config).await?;
+
+            slack::run(search.as_ref(), &read_token_file(app_token_file)?, &read_token_file(bot_token_file)?, *limit).await?;
+        }
+        DoksCommand::Export { out } => {
+            snapshot::export(&config.engine, &StateStore::for_namespace(&opts.namespace)?, out).await?;
+            println!("Index exported to {:?}", out);
+        }
+        DoksCommand::Import { snapshot, force } => {
+            snapshot::import(&config.engine, &StateStore::for_namespace(&opts.namespace)?, snapshot, *force).await?;
+            println!("Index imported from {:?}", snapshot);
+        }
+        DoksCommand::Purge { sources, dry_run, yes } => purge::purge(&config, sources, *dry_run, *yes).await?,
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+fn parse_metadata(s: &str) -> anyhow::Result<(String, String)> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => bail!("Expected key=value, got: {}", s),
+    }
+}
+
+fn parse_since(s: &str) -> anyhow::Result<DateTime<Utc>> {
+    if let Some(period) = daemon::parse_interval(s) {
+        return Ok(Utc::now() - chrono::Duration::from_std(period)?);
+    }
+
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .with_context(|| format!("Expected a period like 30d or a date like 2024-01-31, got: {}", s))?;
+
+    Ok(Utc.from_utc_datetime(&date.and_hms(0, 0, 0)))
+}
+
+fn parse_sort(s: &str) -> anyhow::Result<SortOrder> {
+    match s {
+        "relevance" => Ok(SortOrder::Relevance),
+        "date" => Ok(SortOrder::Date),
+        "title" => Ok(SortOrder::Title),
+        "source" => Ok(SortOrder::Source),
+        other => bail!("Unknown sort order: {}", other),
+    }
+}
+
+/// Opens the configured engine for querying.
+async fn queryable_engine(config: &DoksConfig) -> anyhow::Result<Box<dyn SearchEngine>> {
+    let search: Box<dyn SearchEngine> = (&config.engine).try_into()?;
+
+    // Nothing survives between invocations with an in-memory engine
+    if config.engine == SearchEngineConfig::InMemory {
+        index_sources(&config.sources, search.as_ref(), None, None).await?;
+    }
+
+    let boosts = config.sources
+        .iter()
+        .filter_map(|source| source.boost().map(|boost| (source.id().to_string(), boost)))
+        .collect::<BTreeMap<_, _>>();
+
+    match boosts.is_empty() {
+        true => Ok(search),
+        false => Ok(Box::new(SourceBoostedSearchEngine::new(search, boosts))),
+    }
+}
+
+/// Returns the sources with these ids, or all of them when no id is given.
+fn select_sources<'a>(config: &'a DoksConfig, ids: &[String]) -> anyhow::Result<Vec<&'a SourceConfig>> {
+    if ids.is_empty() {
+        return Ok(config.sources.iter().collect());
+    }
+
+    ids.iter()
+        .map(|id| {
+            config.sources
+                .iter()
+                .find(|source| source.id() == id)
+                .with_context(|| format!("Source not found: {}", id))
+        })
+        .collect()
+}
+
+/// Indexes the sources, recording each run in the state store if any.
+async fn index_sources<'a>(
+    sources: impl IntoIterator<Item=&'a SourceConfig>,
+    search: &dyn SearchEngine,
+    state: Option<&StateStore>,
+    progress: Option<&MultiProgress>,
+) -> anyhow::Result<Vec<IndexSummary>> {
+    let mut summaries = vec![];
+
+    for source_config in sources {
+        let summary = index_source(source_config, search, progress).await?;
+
+        if let Some(state) = state {
+            state.record_indexed(source_config.id(), summary.documents).await?;
+        }
+
+        summaries.push(summary);
+    }
+
+    Ok(summaries)
+}
+
+/// Reports the progress on `progress` if given. The documents of the source that it no longer
+/// produces (e.g. deleted files) are removed from the index at the end of the run.
+async fn index_source(
+    source_config: &SourceConfig,
+    search: &dyn SearchEngine,
+    progress: Option<&MultiProgress>,
+) -> anyhow::Result<IndexSummary> {
+    index_source_documents(source_config, search, progress, None).await
+}
+
+/// Indexes a source restricted to a single repository (see `SourceConfig::for_repository`): only
+/// the stale documents of that repository are removed.
+async fn index_repository(
+    source_config: &SourceConfig,
+    repository: &str,
+    search: &dyn SearchEngine,
+) -> anyhow::Result<IndexSummary> {
+    index_source_documents(source_config, search, None, Some(repository)).await
+}
+
+async fn index_source_documents(
+    source_config: &SourceConfig,
+    search: &dyn SearchEngine,
+    progress: Option<&MultiProgress>,
+    repository: Option<&str>,
+) -> anyhow::Result<IndexSummary> {
+    let source: Box<dyn DocumentSource> = source_config.try_into()?;
+    let mut stream = source.fetch().batched(10);
+    let mut progress = SourceProgress::new(source_config.id(), progress);
+    let mut seen = HashSet::new();
+
+    while let Some(documents) = stream.next().await {
+        let collected = documents
+            .into_iter()
+            .collect::<anyhow::Result<Vec<_>>>()
+            .context(format!("Error occurred while fetching documents from source: {}", source_config.id()))?;
+
+        seen.extend(collected.iter().map(|document| document.id.clone()));
+        search.index(collected.clone()).await?;
+        progress.indexed(&collected);
+        metrics().documents_indexed(source_config.id(), collected.len() as u64);
+    }
+
+    let removed = remove_stale(source_config.id(), repository, &seen, search)
+        .await
+        .context(format!("Couldn't remove the stale documents of source: {}", source_config.id()))?;
+    progress.removed(removed);
+
+    Ok(progress.finish())
+}
+
+/// Deletes the indexed documents of the source (and of `repository` if given) that weren't `seen`
+/// during its last run. Returns how many were deleted.
+async fn remove_stale(
+    source: &str,
+    repository: Option<&str>,
+    seen: &HashSet<String>,
+    search: &dyn SearchEngine,
+) -> anyhow::Result<u64> {
+    let mut documents = search.documents().await?;
+
+    let mut stale = vec![];
+
+    while let Some(document) = documents.next().await {
+        let document = document?;
+        let in_scope = document.source == source
+            && repository.is_none_or(|repository| document.metadata.get("repository").map(String::as_str) == Some(repository));
+
+        if in_scope && !seen.contains(&document.id) {
+            stale.push(document.id);
+        }
+    }
+
+    if !stale.is_empty() {
+        log::info!("Removing {} stale documents of source: {}", stale.len(), source);
+        search.delete(&stale).await?;
+    }
+
+    Ok(stale.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use structopt::StructOpt;
+    use tempdir::TempDir;
+
+    use crate::cli::config::{DoksConfig, PatternSyntax, SourceConfig};
+    use crate::cli::sources::dry_run_source;
+    use crate::cli::{cli_main, exit_code, index_source, index_sources, select_sources, DoksOpts, EXIT_ERROR, EXIT_NO_RESULTS};
+    use crate::search::SearchEngine;
+    use crate::search::tantivy_impl::TantivySearchEngine;
+
+    #[tokio::test]
+    async fn test_index_source_removes_stale_documents() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        std::fs::write(root.path().join("runbook.md"), "Restart the database")?;
+        std::fs::write(root.path().join("setup.md"), "Install the database")?;
+
+        let source = SourceConfig::FileSystem {
+            id: "docs".to_string(),
+            paths: vec![root.path().to_string_lossy().to_string()],
+            include: vec![r".*\.md".to_string()],
+            exclude: vec![],
+            patterns: PatternSyntax::Regex,
+            strip_code_blocks: false,
+            strip_notebook_outputs: false,
+            sections: false,
+            max_file_size: None,
+            ignore_files: true,
+            boost: None,
+            tags: vec![],
+        };
+        let search = TantivySearchEngine::in_memory()?;
+
+        let summary = index_source(&source, &search, None).await?;
+        assert_eq!((summary.documents, summary.removed), (2, 0));
+
+        std::fs::remove_file(root.path().join("setup.md"))?;
+
+        let summary = index_source(&source, &search, None).await?;
+        assert_eq!((summary.documents, summary.removed), (1, 1));
+        assert_eq!(search.stats().await?.documents, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_index_selected_sources() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        for dir in ["runbooks", "guides"] {
+            std::fs::create_dir(root.path().join(dir))?;
+            std::fs::write(root.path().join(dir).join("database.md"), "Restart the database")?;
+        }
+
+        let config = serde_json::from_value::<DoksConfig>(json!({
+            "sources": [
+                { "source": "fs", "id": "runbooks", "paths": [root.path().join("runbooks")], "include": [r".*\.md"] },
+                { "source": "fs", "id": "guides", "paths": [root.path().join("guides")], "include": [r".*\.md"] }
+            ]
+        }))?;
+
+        let search = TantivySearchEngine::in_memory()?;
+        index_sources(select_sources(&config, &["runbooks".to_string()])?, &search, None, None).await?;
+
+        let stats = search.stats().await?;
+        assert_eq!(stats.sources.keys().collect::<Vec<_>>(), vec!["runbooks"]);
+
+        assert_eq!(select_sources(&config, &[])?.len(), 2);
+        assert!(select_sources(&config, &["wiki".to_string()]).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_index_dry_run() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        std::fs::write(root.path().join("runbook.md"), "Restart the database")?;
+        std::fs::write(root.path().join("setup.md"), "Install")?;
+
+        let config = serde_json::from_value::<DoksConfig>(json!({
+            "sources": [{ "source": "fs", "id": "docs", "paths": [root.path()], "include": [r".*\.md"] }]
+        }))?;
+        assert_eq!(dry_run_source(&config.sources[0]).await?, (2, 27));
+
+        let index = root.path().join("index");
+        let config_file = root.path().join("config.json");
+        std::fs::write(&config_file, json!({
+            "sources": [{ "source": "fs", "id": "docs", "paths": [root.path()], "include": [r".*\.md"] }],
+            "engine": { "use": "tantivy", "path": index }
+        }).to_string())?;
+
+        let config_file = config_file.to_string_lossy().to_string();
+        cli_main(DoksOpts::from_iter(["doks", "--quiet", "-c", &config_file, "index", "--dry-run"])).await?;
+        assert!(!index.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_usage_exit_codes() {
+        let parse = |args: &[&str]| DoksOpts::from_iter_safe(args).map(|_| ()).map_err(|err| exit_code(&err.into()));
+
+        assert_eq!(parse(&["doks", "search", "database"]), Ok(()));
+        assert_eq!(parse(&["doks", "search", "--limit", "abc", "database"]), Err(EXIT_ERROR));
+        assert_eq!(parse(&["doks", "unknown"]), Err(EXIT_ERROR));
+        assert_eq!(parse(&["doks", "--help"]), Err(0));
+    }
+
+    #[tokio::test]
+    async fn test_search_exit_codes() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        std::fs::write(root.path().join("runbook.md"), "Restart the database")?;
+
+        let config = root.path().join("config.json");
+        std::fs::write(&config, json!({
+            "sources": [{ "source": "fs", "id": "docs", "paths": [root.path()], "include": [r".*\.md"] }],
+            "engine": { "use": "in-memory" }
+        }).to_string())?;
+
+        // Keeps the history of the searches out of the home directory
+        std::env::set_var("XDG_DATA_HOME", root.path().join("data"));
+
+        let search = |args: &[&str]| {
+            let config = config.to_string_lossy().to_string();
+            let args = ["doks", "--quiet", "-c", &config, "search"].iter().chain(args).map(|arg| arg.to_string()).collect::<Vec<_>>();
+            async move { cli_main(DoksOpts::from_iter(args)).await.map_err(|err| exit_code(&err)) }
+        };
+
+        assert_eq!(search(&["database"]).await, Ok(()));
+        assert_eq!(search(&["kubernetes"]).await, Err(EXIT_NO_RESULTS));
+        assert_eq!(search(&["database", "--page", "0"]).await, Err(EXIT_ERROR));
+
+        Ok(())
+    }
+}
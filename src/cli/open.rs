@@ -0,0 +1,118 @@
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Context};
+
+use crate::search::FoundItem;
+
+/// Opens urls in the browser and local files in `$VISUAL` / `$EDITOR` (or the default application
+/// when none is set).
+pub fn open_link(link: &str) -> anyhow::Result<()> {
+    let editor = std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")).ok();
+
+    match editor {
+        Some(editor) if is_local_file(link) => {
+            // The editor may be set with arguments, e.g. `code --wait`
+            let mut args = editor.split_whitespace();
+            let program = args.next().context("$EDITOR is empty")?;
+
+            let status = Command::new(program)
+                .args(args)
+                .arg(link_path(link))
+                .status()
+                .with_context(|| format!("Couldn't run editor: {}", editor))?;
+
+            if !status.success() {
+                bail!("Editor exited with: {}", status)
+            }
+        }
+        _ if is_local_file(link) => open::that_detached(link_path(link)).with_context(|| format!("Couldn't open: {}", link))?,
+        _ => open::that_detached(link).with_context(|| format!("Couldn't open: {}", link))?,
+    }
+
+    Ok(())
+}
+
+/// Whether the argument of `doks open` is a link to open as is rather than a query.
+pub fn is_link(argument: &str) -> bool {
+    argument.starts_with("http://") || argument.starts_with("https://") || is_local_file(argument)
+}
+
+pub fn is_local_file(link: &str) -> bool {
+    !link.contains("://") && link_path(link).is_file()
+}
+
+/// The file of a local link: the sections of a file link to it followed by `#<anchor>`, which
+/// is only kept when it's part of the name of the file.
+pub fn link_path(link: &str) -> &Path {
+    let path = Path::new(link);
+
+    match link.rsplit_once('#') {
+        Some((file, _)) if !path.exists() => Path::new(file),
+        _ => path,
+    }
+}
+
+/// The link of the line selected in fzf or rofi from the `fzf` output of `doks search`: the first
+/// field of the first line (lines may be null terminated).
+pub fn selected_link(selection: &str) -> Option<&str> {
+    selection
+        .split(['\0', '\n'])
+        .find(|line| !line.trim().is_empty())
+        .and_then(|line| line.split('\t').next())
+        .map(str::trim)
+}
+
+/// Returns the `nth` result, counting from 1 like the ranks of the table output.
+pub fn nth_result(results: Vec<FoundItem>, nth: usize) -> anyhow::Result<FoundItem> {
+    let count = results.len();
+
+    match nth.checked_sub(1).and_then(|i| results.into_iter().nth(i)) {
+        Some(item) => Ok(item),
+        None => bail!("No result #{} ({} results found)", nth, count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use crate::cli::open::{is_link, link_path, nth_result, selected_link};
+    use crate::search::FoundItem;
+
+    #[test]
+    fn test_open_helpers() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let file = root.path().join("runbook.md");
+        std::fs::write(&file, "content")?;
+
+        assert!(is_link("https://github.com/wlezzar/doks"));
+        assert!(is_link(&file.to_string_lossy()));
+        assert!(!is_link("restart database"));
+        assert!(!is_link(&root.path().to_string_lossy()));
+        assert!(is_link(&format!("{}#deployment", file.to_string_lossy())));
+
+        std::fs::write(root.path().join("C#.md"), "content")?;
+        assert_eq!(link_path(&root.path().join("C#.md").to_string_lossy()), root.path().join("C#.md"));
+        assert_eq!(link_path(&format!("{}#usage", root.path().join("C#.md").to_string_lossy())), root.path().join("C#.md"));
+
+        let item = |id: &str| FoundItem {
+            id: id.to_string(),
+            score: 1.0,
+            source: "docs".to_string(),
+            title: id.to_string(),
+            link: id.to_string(),
+            snippet: "".to_string(),
+        };
+
+        assert_eq!(nth_result(vec![item("1"), item("2")], 2)?.id, "2");
+        assert!(nth_result(vec![item("1")], 2).is_err());
+        assert!(nth_result(vec![item("1")], 0).is_err());
+
+        assert_eq!(selected_link("https://docs/runbook\tRunbook\tRestart the database\n"), Some("https://docs/runbook"));
+        assert_eq!(selected_link("\0/docs/setup.md\tSetup\t\0"), Some("/docs/setup.md"));
+        assert_eq!(selected_link("\n"), None);
+
+        Ok(())
+    }
+}
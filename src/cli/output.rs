@@ -0,0 +1,406 @@
+use std::io::IsTerminal;
+use std::str::FromStr;
+
+use anyhow::bail;
+use crossterm::style::Stylize;
+use serde_json::json;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::search::{Facets, FoundItem, ScoreExplanation, SearchExplanation};
+use crate::tui::{snippet_parts, unescape_html};
+use crate::utils::table::format_table;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// One JSON object per line.
+    Json,
+    /// Aligned columns, printed once all the results are received.
+    Table,
+    /// `title — link` per line, for piping.
+    Plain,
+    /// The title, link and snippet of each result, the matched terms highlighted in a terminal.
+    Text,
+    /// `link<TAB>title<TAB>snippet` per line, for fzf or rofi. Pipe the selected line to
+    /// `doks open --from-stdin` to open it, e.g.
+    /// `doks search "query" --format fzf | fzf --delimiter '\t' --with-nth 2,3 | doks open --from-stdin`
+    Fzf,
+    /// The script filter JSON of Alfred (and Raycast), printed once all the results are received.
+    Alfred,
+}
+
+impl OutputFormat {
+    pub const VARIANTS: &'static [&'static str] = &["json", "table", "plain", "text", "fzf", "alfred"];
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "table" => Ok(OutputFormat::Table),
+            "plain" => Ok(OutputFormat::Plain),
+            "text" => Ok(OutputFormat::Text),
+            "fzf" => Ok(OutputFormat::Fzf),
+            "alfred" => Ok(OutputFormat::Alfred),
+            other => bail!("Unknown output format: {} (expected one of: {})", other, Self::VARIANTS.join(", ")),
+        }
+    }
+}
+
+/// With `print0`, the results of the line based formats end with a null character instead of a new
+/// line (`fzf --read0`, `xargs -0`...). Returns the number of printed results.
+pub async fn print_results<S>(format: OutputFormat, print0: bool, mut results: S) -> anyhow::Result<usize>
+    where S: Stream<Item=anyhow::Result<FoundItem>> + Unpin
+{
+    let terminator = if print0 { '\0' } else { '\n' };
+    let mut printed = 0;
+
+    match format {
+        OutputFormat::Json => {
+            while let Some(result) = results.next().await {
+                print!("{}{}", serde_json::to_string(&result?)?, terminator);
+                printed += 1;
+            }
+        }
+        OutputFormat::Plain => {
+            while let Some(result) = results.next().await {
+                print!("{}{}", plain_line(&result?), terminator);
+                printed += 1;
+            }
+        }
+        OutputFormat::Text => {
+            let colored = std::io::stdout().is_terminal();
+
+            while let Some(result) = results.next().await {
+                print!("{}{}", text_block(&result?, colored), terminator);
+                printed += 1;
+            }
+        }
+        OutputFormat::Fzf => {
+            while let Some(result) = results.next().await {
+                print!("{}{}", fzf_line(&result?), terminator);
+                printed += 1;
+            }
+        }
+        OutputFormat::Table => {
+            let items = results.collect::<anyhow::Result<Vec<_>>>().await?;
+
+            for line in table_lines(&items) {
+                println!("{}", line);
+            }
+            printed = items.len();
+        }
+        OutputFormat::Alfred => {
+            let items = results.collect::<anyhow::Result<Vec<_>>>().await?;
+
+            println!("{}", serde_json::to_string(&alfred_items(&items))?);
+            printed = items.len();
+        }
+    }
+
+    Ok(printed)
+}
+
+/// The facets follow the results: as a JSON object with the json output, otherwise as aligned
+/// columns on stderr so that the piped outputs stay parsable.
+pub fn print_facets(format: OutputFormat, facets: &Facets) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string(&json!({ "facets": facets }))?),
+        _ => {
+            for line in facet_lines(facets) {
+                eprintln!("{}", line);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The most frequent values first.
+fn facet_lines(facets: &Facets) -> Vec<String> {
+    let header = ["FACET", "VALUE", "DOCUMENTS"].map(String::from);
+    let mut rows = vec![header];
+
+    let counts = std::iter::once(("source".to_string(), &facets.sources))
+        .chain(facets.metadata.iter().map(|(key, values)| (format!("meta:{}", key), values)));
+
+    for (facet, values) in counts {
+        let mut values = values.iter().collect::<Vec<_>>();
+        values.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+
+        rows.extend(values.into_iter().map(|(value, count)| [facet.clone(), value.clone(), count.to_string()]));
+    }
+
+    format_table(&rows)
+}
+
+/// The parsed query, then the score breakdown of each result as an indented tree.
+pub fn print_explanation(explanation: &SearchExplanation) {
+    for line in explanation_lines(explanation) {
+        println!("{}", line);
+    }
+}
+
+fn explanation_lines(explanation: &SearchExplanation) -> Vec<String> {
+    fn score_lines(score: &ScoreExplanation, depth: usize, lines: &mut Vec<String>) {
+        lines.push(format!("{}{:.3}  {}", "  ".repeat(depth), score.value, score.description));
+
+        for detail in &score.details {
+            score_lines(detail, depth + 1, lines);
+        }
+    }
+
+    let mut lines = vec![format!("Query: {}", explanation.query)];
+
+    for (rank, (item, score)) in explanation.results.iter().enumerate() {
+        lines.push(String::new());
+        lines.push(format!("#{} {} — {}", rank + 1, item.title, item.link));
+        score_lines(score, 1, &mut lines);
+    }
+
+    lines
+}
+
+fn plain_line(item: &FoundItem) -> String {
+    format!("{} — {}", item.title, item.link)
+}
+
+/// The snippet is on a single line, so that the blocks are separated by a blank line.
+fn text_block(item: &FoundItem, colored: bool) -> String {
+    let mut parts = snippet_parts(&item.snippet)
+        .into_iter()
+        .map(|(text, highlighted)| (collapse_whitespaces(&text), highlighted))
+        .collect::<Vec<_>>();
+
+    if let Some((text, _)) = parts.first_mut() {
+        *text = text.trim_start().to_string();
+    }
+    if let Some((text, _)) = parts.last_mut() {
+        *text = text.trim_end().to_string();
+    }
+
+    let snippet = parts
+        .into_iter()
+        .map(|(text, highlighted)| match colored && highlighted {
+            true => text.yellow().bold().to_string(),
+            false => text,
+        })
+        .collect::<String>();
+
+    let (title, link) = match colored {
+        true => (item.title.as_str().bold().to_string(), item.link.as_str().dark_grey().to_string()),
+        false => (item.title.clone(), item.link.clone()),
+    };
+
+    match snippet.is_empty() {
+        true => format!("{}\n{}\n", title, link),
+        false => format!("{}\n{}\n{}\n", title, link, snippet),
+    }
+}
+
+/// Tabs and new lines would break the fields apart: all the whitespaces are collapsed.
+fn fzf_line(item: &FoundItem) -> String {
+    format!("{}\t{}\t{}", item.link, single_line(&item.title), plain_snippet(item))
+}
+
+/// See https://www.alfredapp.com/help/workflows/inputs/script-filter/json/
+fn alfred_items(items: &[FoundItem]) -> serde_json::Value {
+    let items = items
+        .iter()
+        .map(|item| {
+            let snippet = plain_snippet(item);
+
+            json!({
+                "uid": item.id,
+                "title": item.title,
+                "subtitle": if snippet.is_empty() { item.source.clone() } else { snippet },
+                "arg": item.link,
+                "quicklookurl": item.link,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({ "items": items })
+}
+
+/// The snippet on a single line, without the highlighting tags.
+pub(crate) fn plain_snippet(item: &FoundItem) -> String {
+    let snippet = ["<b>", "</b>", "<em>", "</em>", "<mark>", "</mark>"]
+        .iter()
+        .fold(item.snippet.clone(), |snippet, tag| snippet.replace(tag, ""));
+
+    single_line(&unescape_html(&snippet))
+}
+
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Replaces each run of whitespaces by a single space, keeping the leading and trailing ones.
+fn collapse_whitespaces(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c.is_whitespace() {
+            true if collapsed.ends_with(' ') => {}
+            true => collapsed.push(' '),
+            false => collapsed.push(c),
+        }
+    }
+
+    collapsed
+}
+
+fn table_lines(items: &[FoundItem]) -> Vec<String> {
+    let header = ["#", "SCORE", "TITLE", "SOURCE", "LINK"].map(String::from);
+    let rows = items.iter().enumerate().map(|(rank, item)| {
+        [
+            (rank + 1).to_string(),
+            format!("{:.3}", item.score),
+            item.title.clone(),
+            item.source.clone(),
+            item.link.clone(),
+        ]
+    });
+
+    format_table(&std::iter::once(header).chain(rows).collect::<Vec<_>>())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde_json::json;
+
+    use crate::cli::open::selected_link;
+    use crate::cli::output::{alfred_items, explanation_lines, facet_lines, fzf_line, OutputFormat, plain_line, table_lines, text_block};
+    use crate::search::{Facets, FoundItem, ScoreExplanation, SearchExplanation};
+
+    #[test]
+    fn test_fzf_selection() {
+        let item = |link: &str, title: &str| FoundItem {
+            id: link.to_string(),
+            score: 1.0,
+            source: "docs".to_string(),
+            title: title.to_string(),
+            link: link.to_string(),
+            snippet: "Restart\tthe <b>database</b>".to_string(),
+        };
+
+        // The line picked in fzf, as is or null terminated, opens the link of its result
+        for item in [item("https://docs/runbook", "Runbook"), item("/docs/release notes.md", "Release\tnotes")] {
+            let line = fzf_line(&item);
+
+            assert_eq!(selected_link(&format!("{}\n", line)), Some(item.link.as_str()));
+            assert_eq!(selected_link(&format!("{}\0", line)), Some(item.link.as_str()));
+        }
+    }
+
+    #[test]
+    fn test_alfred_items() {
+        let item = FoundItem {
+            id: "1".to_string(),
+            score: 1.0,
+            source: "docs".to_string(),
+            title: "Runbook".to_string(),
+            link: "https://docs/runbook".to_string(),
+            snippet: "Restart the <b>database</b>\nand &lt;check&gt;".to_string(),
+        };
+
+        // The snippet, rather than the source, describes the results having one
+        let items = alfred_items(&[item]);
+        assert_eq!(items["items"][0]["subtitle"], "Restart the database and <check>");
+        assert_eq!(items["items"][0]["arg"], "https://docs/runbook");
+
+        // Alfred expects a script filter even without results
+        assert_eq!(alfred_items(&[]), json!({ "items": [] }));
+    }
+
+    #[test]
+    fn test_output_formats() -> anyhow::Result<()> {
+        let items = vec![
+            FoundItem {
+                id: "1".to_string(),
+                score: 1.5,
+                source: "docs".to_string(),
+                title: "Runbook".to_string(),
+                link: "https://docs/runbook".to_string(),
+                snippet: "".to_string(),
+            },
+            FoundItem {
+                id: "2".to_string(),
+                score: 0.25,
+                source: "github".to_string(),
+                title: "README.md".to_string(),
+                link: "https://github.com/readme".to_string(),
+                snippet: "Run <b>cargo</b>\tbuild &amp;\n test".to_string(),
+            },
+        ];
+
+        assert_eq!(plain_line(&items[0]), "Runbook — https://docs/runbook");
+        assert_eq!(fzf_line(&items[1]), "https://github.com/readme\tREADME.md\tRun cargo build & test");
+        assert_eq!(text_block(&items[0], false), "Runbook\nhttps://docs/runbook\n");
+        assert_eq!(text_block(&items[1], false), "README.md\nhttps://github.com/readme\nRun cargo build & test\n");
+        assert!(text_block(&items[1], true).contains("\u{1b}[1mcargo"), "{:?}", text_block(&items[1], true));
+        assert_eq!(
+            alfred_items(&items[..1]),
+            json!({
+                "items": [{
+                    "uid": "1",
+                    "title": "Runbook",
+                    "subtitle": "docs",
+                    "arg": "https://docs/runbook",
+                    "quicklookurl": "https://docs/runbook",
+                }]
+            }),
+        );
+        assert_eq!(
+            table_lines(&items),
+            vec![
+                "#  SCORE  TITLE      SOURCE  LINK",
+                "1  1.500  Runbook    docs    https://docs/runbook",
+                "2  0.250  README.md  github  https://github.com/readme",
+            ]
+        );
+
+        let score = |value: f32, description: &str, details: Vec<ScoreExplanation>| ScoreExplanation {
+            value,
+            description: description.to_string(),
+            details,
+        };
+        let explanation = SearchExplanation {
+            query: "TermQuery(content:database)".to_string(),
+            results: vec![(items[0].clone(), score(1.5, "Sum of", vec![score(1.5, "TermQuery, product of", vec![])]))],
+        };
+        assert_eq!(
+            explanation_lines(&explanation),
+            vec![
+                "Query: TermQuery(content:database)",
+                "",
+                "#1 Runbook — https://docs/runbook",
+                "  1.500  Sum of",
+                "    1.500  TermQuery, product of",
+            ],
+        );
+
+        let facets = Facets {
+            sources: BTreeMap::from([("docs".to_string(), 1), ("github".to_string(), 3)]),
+            metadata: BTreeMap::from([("lang".to_string(), BTreeMap::from([("rust".to_string(), 2)]))]),
+        };
+        assert_eq!(
+            facet_lines(&facets),
+            vec![
+                "FACET      VALUE   DOCUMENTS",
+                "source     github  3",
+                "source     docs    1",
+                "meta:lang  rust    2",
+            ],
+        );
+
+        assert_eq!("table".parse::<OutputFormat>()?, OutputFormat::Table);
+        assert!("yaml".parse::<OutputFormat>().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+use crate::cli::stats::human_size;
+use crate::model::Document;
+use crate::utils::table::format_table;
+
+/// Tracks what was indexed from a source, shown as a spinner on stderr when given a `MultiProgress`
+/// (nothing is drawn when stderr isn't a terminal).
+pub struct SourceProgress {
+    bar: ProgressBar,
+    summary: IndexSummary,
+    repositories: BTreeSet<String>,
+    started: Instant,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct IndexSummary {
+    pub source: String,
+    pub documents: u64,
+    pub bytes: u64,
+    /// Repositories cloned by git based sources.
+    pub repositories: usize,
+    /// Stale documents removed from the index, as the source no longer produces them.
+    pub removed: u64,
+    pub elapsed: Duration,
+}
+
+impl SourceProgress {
+    pub fn new(source: &str, display: Option<&MultiProgress>) -> Self {
+        let bar = match display {
+            Some(display) => {
+                let bar = display.add(ProgressBar::new_spinner());
+                bar.set_style(ProgressStyle::with_template("{spinner} {prefix}: {msg} [{elapsed}]").unwrap());
+                bar.enable_steady_tick(Duration::from_millis(100));
+                bar
+            }
+            None => ProgressBar::hidden(),
+        };
+
+        bar.set_prefix(source.to_string());
+        bar.set_message("fetching documents");
+
+        Self {
+            bar,
+            summary: IndexSummary {
+                source: source.to_string(),
+                documents: 0,
+                bytes: 0,
+                repositories: 0,
+                removed: 0,
+                elapsed: Duration::default(),
+            },
+            repositories: BTreeSet::new(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Called once the documents are indexed.
+    pub fn indexed(&mut self, documents: &[Document]) {
+        for document in documents {
+            self.summary.documents += 1;
+            self.summary.bytes += document.content.len() as u64;
+
+            if let Some(repository) = document.metadata.get("repository") {
+                self.repositories.insert(repository.clone());
+            }
+        }
+
+        self.summary.repositories = self.repositories.len();
+        self.bar.set_message(progress_message(&self.summary));
+    }
+
+    /// Called once the stale documents are removed.
+    pub fn removed(&mut self, removed: u64) {
+        self.summary.removed = removed;
+        self.bar.set_message(progress_message(&self.summary));
+    }
+
+    pub fn finish(mut self) -> IndexSummary {
+        self.summary.elapsed = self.started.elapsed();
+        self.bar.finish_with_message(progress_message(&self.summary));
+
+        self.summary
+    }
+}
+
+fn progress_message(summary: &IndexSummary) -> String {
+    let mut message = format!("{} documents indexed, {}", summary.documents, human_size(summary.bytes));
+
+    if summary.repositories > 0 {
+        message.push_str(&format!(" from {} repositories", summary.repositories));
+    }
+
+    if summary.removed > 0 {
+        message.push_str(&format!(", {} stale removed", summary.removed));
+    }
+
+    message
+}
+
+pub fn print_summary(summaries: &[IndexSummary]) {
+    let header = ["SOURCE", "DOCUMENTS", "SIZE", "REPOSITORIES", "REMOVED", "DURATION"].map(String::from);
+    let rows = summaries.iter().map(|summary| {
+        [
+            summary.source.clone(),
+            summary.documents.to_string(),
+            human_size(summary.bytes),
+            summary.repositories.to_string(),
+            summary.removed.to_string(),
+            format!("{:.1}s", summary.elapsed.as_secs_f64()),
+        ]
+    });
+
+    for line in format_table(&std::iter::once(header).chain(rows).collect::<Vec<_>>()) {
+        println!("{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::cli::progress::SourceProgress;
+    use crate::model::Document;
+
+    #[test]
+    fn test_source_progress() {
+        let document = |id: &str, repository: Option<&str>| Document {
+            id: id.to_string(),
+            source: "github".to_string(),
+            title: id.to_string(),
+            link: id.to_string(),
+            content: "0123456789".to_string(),
+            metadata: repository.map(|r| HashMap::from([("repository".to_string(), r.to_string())])).unwrap_or_default(),
+            tags: vec![],
+            modified: None,
+        };
+
+        let mut progress = SourceProgress::new("github", None);
+        progress.indexed(&[document("1", Some("wlezzar/doks")), document("2", Some("wlezzar/doks"))]);
+        progress.indexed(&[document("3", Some("wlezzar/jtab")), document("4", None)]);
+
+        let summary = progress.finish();
+
+        assert_eq!(summary.source, "github");
+        assert_eq!(summary.documents, 4);
+        assert_eq!(summary.bytes, 40);
+        assert_eq!(summary.repositories, 2);
+    }
+}
@@ -0,0 +1,180 @@
+use std::time::Duration;
+
+use futures::StreamExt;
+use reqwest::{Client, Method, StatusCode};
+
+use crate::cli::open::link_path;
+use crate::model::Document;
+use crate::search::SearchEngine;
+
+const LINK_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct PruneOptions {
+    /// Print the dead links without removing their documents.
+    pub dry_run: bool,
+    /// Only check the local files.
+    pub local_only: bool,
+    /// Maximum number of links checked at the same time.
+    pub concurrency: usize,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum LinkStatus {
+    Alive,
+    /// The target doesn't exist anymore, its document is removed.
+    Gone(String),
+    /// The target couldn't be checked (e.g. the server is down), its document is kept.
+    Unreachable(String),
+    /// Neither a local file nor an HTTP link.
+    Unchecked,
+}
+
+/// Removes from the index the documents whose local file was deleted or whose HTTP link answers
+/// 404 or 410. Links that can't be checked are reported but their documents are kept.
+pub async fn prune(search: &dyn SearchEngine, options: &PruneOptions) -> anyhow::Result<()> {
+    let documents = tokio_stream::StreamExt::collect::<anyhow::Result<Vec<Document>>>(search.documents().await?).await?;
+    let client = Client::builder().timeout(LINK_TIMEOUT).build()?;
+
+    let checked = futures::stream::iter(documents)
+        .map(|document| {
+            let client = &client;
+            async move {
+                let status = check(client, &document.link, options.local_only).await;
+                (document, status)
+            }
+        })
+        .buffer_unordered(options.concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut gone = vec![];
+
+    for (document, status) in checked {
+        match status {
+            LinkStatus::Gone(reason) => {
+                println!("gone         {}  {}  ({})", document.source, document.link, reason);
+                gone.push(document.id);
+            }
+            LinkStatus::Unreachable(reason) => println!("unreachable  {}  {}  ({})", document.source, document.link, reason),
+            LinkStatus::Alive | LinkStatus::Unchecked => {}
+        }
+    }
+
+    if options.dry_run {
+        println!("Dry run: {} documents would be removed", gone.len());
+    } else if !gone.is_empty() {
+        search.delete(&gone).await?;
+        println!("Removed {} documents", gone.len());
+    } else {
+        println!("No dead links found");
+    }
+
+    Ok(())
+}
+
+async fn check(client: &Client, link: &str, local_only: bool) -> LinkStatus {
+    if link.starts_with("http://") || link.starts_with("https://") {
+        return match local_only {
+            true => LinkStatus::Unchecked,
+            false => check_http(client, link).await,
+        };
+    }
+
+    let path = match link.strip_prefix("file://") {
+        Some(path) => path,
+        None if !link.contains("://") => link,
+        None => return LinkStatus::Unchecked,
+    };
+
+    if link_path(path).exists() {
+        LinkStatus::Alive
+    } else {
+        LinkStatus::Gone("file not found".to_string())
+    }
+}
+
+/// Some servers don't implement HEAD requests, they are retried with a GET.
+async fn check_http(client: &Client, link: &str) -> LinkStatus {
+    let mut status = http_status(client, Method::HEAD, link).await;
+
+    if matches!(status, Ok(StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED)) {
+        status = http_status(client, Method::GET, link).await;
+    }
+
+    match status {
+        Ok(status) if status == StatusCode::NOT_FOUND || status == StatusCode::GONE => LinkStatus::Gone(status.to_string()),
+        Ok(status) if status.is_server_error() => LinkStatus::Unreachable(status.to_string()),
+        Ok(_) => LinkStatus::Alive,
+        Err(err) => LinkStatus::Unreachable(err.to_string()),
+    }
+}
+
+async fn http_status(client: &Client, method: Method, link: &str) -> reqwest::Result<StatusCode> {
+    Ok(client.request(method, link).send().await?.status())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use reqwest::Client;
+    use tempdir::TempDir;
+    use tokio_stream::StreamExt;
+
+    use crate::cli::prune::{check, prune, LinkStatus, PruneOptions};
+    use crate::model::Document;
+    use crate::search::SearchEngine;
+    use crate::search::tantivy_impl::TantivySearchEngine;
+
+    #[tokio::test]
+    async fn test_check() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let file = root.path().join("runbook.md");
+        std::fs::write(&file, "Restart the database")?;
+
+        let client = Client::new();
+        let link = file.to_string_lossy().to_string();
+
+        assert_eq!(check(&client, &link, true).await, LinkStatus::Alive);
+        assert_eq!(check(&client, &format!("file://{}", link), true).await, LinkStatus::Alive);
+
+        std::fs::remove_file(&file)?;
+        assert_eq!(check(&client, &link, true).await, LinkStatus::Gone("file not found".to_string()));
+
+        assert_eq!(check(&client, "https://example.com/runbook", true).await, LinkStatus::Unchecked);
+        assert_eq!(check(&client, "slack://channel?id=C1", false).await, LinkStatus::Unchecked);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prune_sections() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let runbook = root.path().join("runbook.md").to_string_lossy().to_string();
+        let setup = root.path().join("setup.md").to_string_lossy().to_string();
+        std::fs::write(&runbook, "Restart")?;
+
+        let search = TantivySearchEngine::in_memory()?;
+        for link in [runbook.clone(), format!("{}#deployment", runbook), format!("{}#install", setup)] {
+            search.index(vec![Document {
+                id: link.clone(),
+                source: "docs".to_string(),
+                title: "Runbook".to_string(),
+                link,
+                content: "Restart".to_string(),
+                metadata: HashMap::new(),
+                tags: vec![],
+                modified: None,
+            }]).await?;
+        }
+
+        prune(&search, &PruneOptions { dry_run: false, local_only: true, concurrency: 2 }).await?;
+
+        // The sections of the existing files are kept, the ones of the deleted files removed
+        let mut ids = search.documents().await?.map(|document| document.map(|document| document.id)).collect::<anyhow::Result<Vec<_>>>().await?;
+        ids.sort();
+        assert_eq!(ids, vec![runbook.clone(), format!("{}#deployment", runbook)]);
+
+        Ok(())
+    }
+}
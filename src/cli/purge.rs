@@ -0,0 +1,168 @@
+use std::convert::TryInto;
+use std::io::IsTerminal;
+
+use anyhow::bail;
+use rustyline::DefaultEditor;
+
+use crate::cli::config::{DoksConfig, SearchEngineConfig};
+use crate::search::{IndexStats, SearchEngine};
+use crate::utils::table::format_table;
+
+/// Removes the documents of the given sources (all of them when empty) after showing how many
+/// documents each source has. Asks for a confirmation unless `yes` is set.
+pub async fn purge(config: &DoksConfig, sources: &[String], dry_run: bool, yes: bool) -> anyhow::Result<()> {
+    if config.engine == SearchEngineConfig::InMemory {
+        bail!("Nothing to purge: the in-memory engine doesn't persist any document")
+    }
+
+    for path in config.engine.local_paths() {
+        if !path.exists() {
+            bail!("Nothing to purge: no index found at {:?}", path)
+        }
+    }
+
+    let search: Box<dyn SearchEngine> = (&config.engine).try_into()?;
+
+    // Not all the engines can count documents, the purge is still possible without the counts
+    let stats = match search.stats().await {
+        Ok(stats) => Some(stats),
+        Err(err) => {
+            log::debug!("Couldn't count the documents before purging: {}", err);
+            None
+        }
+    };
+
+    for source in sources {
+        let configured = config.sources.iter().any(|s| s.id() == source);
+        let indexed = stats.as_ref().is_some_and(|stats| stats.sources.contains_key(source));
+
+        if !configured && !indexed {
+            bail!("Unknown source: {}", source)
+        }
+    }
+
+    let plan = purge_plan(config, stats.as_ref(), sources);
+    print_plan(&plan);
+
+    if dry_run {
+        println!("Dry run, nothing was deleted");
+        return Ok(());
+    }
+
+    if !yes && !confirm(&plan)? {
+        println!("Aborted, nothing was deleted");
+        return Ok(());
+    }
+
+    if sources.is_empty() {
+        search.purge().await?;
+    } else {
+        for source in sources {
+            search.purge_source(source).await?;
+        }
+    }
+
+    match plan.iter().map(|(_, documents)| *documents).sum::<Option<u64>>() {
+        Some(removed) => println!("Purged {} documents from the {} index", removed, config.engine.kind()),
+        None => println!("Purged {} source(s) from the {} index", plan.len(), config.engine.kind()),
+    }
+
+    Ok(())
+}
+
+/// The sources to purge with their number of documents, when the engine can count them. Purging
+/// everything targets the configured sources and the ones only found in the index.
+fn purge_plan(config: &DoksConfig, stats: Option<&IndexStats>, sources: &[String]) -> Vec<(String, Option<u64>)> {
+    let mut targets = sources.to_vec();
+
+    if targets.is_empty() {
+        targets.extend(config.sources.iter().map(|source| source.id().to_string()));
+
+        for source in stats.iter().flat_map(|stats| stats.sources.keys()) {
+            if !targets.contains(source) {
+                targets.push(source.clone());
+            }
+        }
+    }
+
+    targets
+        .into_iter()
+        .map(|source| {
+            let documents = stats.map(|stats| stats.sources.get(&source).copied().unwrap_or(0));
+            (source, documents)
+        })
+        .collect()
+}
+
+fn print_plan(plan: &[(String, Option<u64>)]) {
+    let header = ["SOURCE".to_string(), "DOCUMENTS".to_string()];
+    let rows = plan.iter().map(|(source, documents)| {
+        [source.clone(), documents.map_or("unknown".to_string(), |documents| documents.to_string())]
+    });
+
+    for line in format_table(&std::iter::once(header).chain(rows).collect::<Vec<_>>()) {
+        println!("{}", line);
+    }
+
+    println!();
+}
+
+fn confirm(plan: &[(String, Option<u64>)]) -> anyhow::Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        bail!("Refusing to purge without confirmation, use --yes to purge non-interactively")
+    }
+
+    let answer = DefaultEditor::new()?.readline(&format!("Delete the documents of {} source(s)? [y/N] ", plan.len()))?;
+
+    Ok(is_confirmed(&answer))
+}
+
+fn is_confirmed(answer: &str) -> bool {
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::cli::config::{DaemonConfig, DoksConfig, PatternSyntax, SearchEngineConfig, SourceConfig};
+    use crate::cli::purge::{is_confirmed, purge_plan};
+    use crate::search::IndexStats;
+
+    #[test]
+    fn test_purge_plan() {
+        let source = |id: &str| SourceConfig::FileSystem {
+            id: id.to_string(),
+            paths: vec![],
+            include: vec![],
+            exclude: vec![],
+            patterns: PatternSyntax::Regex,
+            strip_code_blocks: false,
+            strip_notebook_outputs: false,
+            sections: false,
+            max_file_size: None,
+            ignore_files: true,
+            boost: None,
+            tags: vec![],
+        };
+        let config = DoksConfig {
+            sources: vec![source("docs"), source("wiki")],
+            engine: SearchEngineConfig::InMemory,
+            daemon: DaemonConfig::default(),
+            namespaces: BTreeMap::new(),
+        };
+
+        let stats = IndexStats::from_sources(BTreeMap::from([("docs".to_string(), 3), ("removed".to_string(), 2)]));
+
+        assert_eq!(
+            purge_plan(&config, Some(&stats), &[]),
+            vec![("docs".to_string(), Some(3)), ("wiki".to_string(), Some(0)), ("removed".to_string(), Some(2))],
+        );
+        assert_eq!(purge_plan(&config, Some(&stats), &["removed".to_string()]), vec![("removed".to_string(), Some(2))]);
+        assert_eq!(purge_plan(&config, None, &["docs".to_string()]), vec![("docs".to_string(), None)]);
+
+        assert!(is_confirmed(" Yes\n"));
+        assert!(!is_confirmed(""));
+        assert!(!is_confirmed("no"));
+    }
+}
@@ -0,0 +1,458 @@
+use std::convert::TryInto;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use futures::future::{BoxFuture, FutureExt};
+use indicatif::MultiProgress;
+
+use crate::cli::config::{DoksConfig, SearchEngineConfig, SourceConfig};
+use crate::cli::index_sources;
+use crate::cli::progress::IndexSummary;
+use crate::cli::state::StateStore;
+use crate::search::SearchEngine;
+use crate::search::tantivy_impl::{self, CustomField, IncompatibleSchema};
+
+/// Indexes all the sources into fresh indexes next to the current ones, then swaps them in. The
+/// current indexes are left untouched if anything fails (or the run is interrupted) before the swap.
+pub async fn reindex(config: DoksConfig, state: &StateStore) -> anyhow::Result<Vec<IndexSummary>> {
+    let targets = index_paths(&config.engine)?.into_iter().map(Path::to_path_buf).collect::<Vec<_>>();
+    let stagings = targets.iter().map(|target| sibling(target, "reindex")).collect::<anyhow::Result<Vec<_>>>()?;
+
+    for staging in &stagings {
+        // Leftover of an interrupted run
+        remove(staging)?;
+
+        if let Some(parent) = staging.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+
+    let staging_engine = with_sibling_paths(config.engine, "reindex")?;
+
+    let summaries = match build_index(&config.sources, &staging_engine).await {
+        Ok(summaries) => summaries,
+        Err(err) => {
+            for staging in &stagings {
+                remove(staging)?;
+            }
+
+            return Err(err.context(format!("Reindexing failed, the indexes at {:?} weren't modified", targets)));
+        }
+    };
+
+    for (staging, target) in stagings.iter().zip(&targets) {
+        swap(staging, target)?;
+    }
+
+    for summary in &summaries {
+        state.record_indexed(&summary.source, summary.documents).await?;
+    }
+
+    Ok(summaries)
+}
+
+/// Moves the documents stored in the tantivy indexes that this version of doks can't open (built
+/// by another version, or with other fields) to new indexes, swapped in like a reindex. The
+/// tantivy engines wrapped by the others (chunked, hybrid, multi...) are migrated too. Returns
+/// whether an index was migrated.
+pub fn migrate(engine: &SearchEngineConfig) -> BoxFuture<'_, anyhow::Result<bool>> {
+    async move {
+        match engine {
+            SearchEngineConfig::Tantivy { .. } => migrate_tantivy(engine).await,
+            SearchEngineConfig::Chunked { engine, .. } | SearchEngineConfig::Deduplicated { engine } => migrate(engine).await,
+            SearchEngineConfig::Hybrid { keyword, vector, .. } => Ok(migrate(keyword).await? | migrate(vector).await?),
+            SearchEngineConfig::Multi { engines, .. } => {
+                let mut migrated = false;
+
+                for engine in engines {
+                    migrated |= migrate(engine).await?;
+                }
+
+                Ok(migrated)
+            }
+            _ => Ok(false),
+        }
+    }.boxed()
+}
+
+async fn migrate_tantivy(engine: &SearchEngineConfig) -> anyhow::Result<bool> {
+    let (path, analyzer, boosts, limits, fields) = match engine {
+        SearchEngineConfig::Tantivy { path, analyzer, boosts, limits, fields } => (path, analyzer, boosts, limits, fields),
+        _ => return Ok(false),
+    };
+
+    let configured_analyzer = analyzer.as_ref().map(TryInto::try_into).transpose()?.unwrap_or_default();
+
+    match tantivy_impl::check_schema(path, &fields.iter().map(CustomField::from).collect::<Vec<_>>(), &configured_analyzer) {
+        Err(err) if err.is::<IncompatibleSchema>() => log::warn!("{:#}, migrating its documents", err),
+        other => return other.map(|_| false),
+    }
+
+    let documents = tantivy_impl::stored_documents(path)
+        .with_context(|| format!("Couldn't migrate the index at {:?}: run `doks reindex` to build it again", path))?;
+    let count = documents.len();
+    let staging = sibling(path, "migrate")?;
+
+    // Leftover of an interrupted run
+    remove(&staging)?;
+
+    let migrated = async {
+        let staging_engine = SearchEngineConfig::Tantivy {
+            path: staging.clone(),
+            analyzer: analyzer.clone(),
+            boosts: *boosts,
+            limits: *limits,
+            fields: fields.clone(),
+        };
+        let search: Box<dyn SearchEngine> = (&staging_engine).try_into()?;
+        search.index(documents).await
+    };
+
+    if let Err(err) = migrated.await {
+        remove(&staging)?;
+        return Err(err.context(format!("Migrating the index at {:?} failed: run `doks reindex` to build it again", path)));
+    }
+
+    swap(&staging, path)?;
+    log::warn!("Migrated the {} documents of the index at {:?}", count, path);
+
+    Ok(true)
+}
+
+/// The paths of the indexes written by the engine, through the engines wrapping others. Only the
+/// engines storing their index in a single file or directory can swap it atomically.
+fn index_paths(engine: &SearchEngineConfig) -> anyhow::Result<Vec<&Path>> {
+    match engine {
+        SearchEngineConfig::Tantivy { path, .. } | SearchEngineConfig::Sqlite { path } | SearchEngineConfig::Semantic { path, .. } => {
+            Ok(vec![path])
+        }
+        SearchEngineConfig::Chunked { engine, .. } | SearchEngineConfig::Deduplicated { engine } => index_paths(engine),
+        SearchEngineConfig::Hybrid { keyword, vector, .. } => Ok([index_paths(keyword)?, index_paths(vector)?].concat()),
+        SearchEngineConfig::Multi { engines, .. } => Ok(engines.iter().map(index_paths).collect::<anyhow::Result<Vec<_>>>()?.concat()),
+        other => bail!(
+            "Reindexing is only supported by the tantivy, sqlite and semantic engines and the engines wrapping them (configured: {})",
+            other.kind(),
+        ),
+    }
+}
+
+/// The same engine writing its indexes to their `sibling`s with the suffix.
+fn with_sibling_paths(engine: SearchEngineConfig, suffix: &str) -> anyhow::Result<SearchEngineConfig> {
+    let wrapped = |engine: Box<SearchEngineConfig>| with_sibling_paths(*engine, suffix).map(Box::new);
+
+    Ok(match engine {
+        SearchEngineConfig::Tantivy { path, analyzer, boosts, limits, fields } => {
+            SearchEngineConfig::Tantivy { path: sibling(&path, suffix)?, analyzer, boosts, limits, fields }
+        }
+        SearchEngineConfig::Sqlite { path } => SearchEngineConfig::Sqlite { path: sibling(&path, suffix)? },
+        SearchEngineConfig::Semantic { path, embeddings, chunk_size } => {
+            SearchEngineConfig::Semantic { path: sibling(&path, suffix)?, embeddings, chunk_size }
+        }
+        SearchEngineConfig::Chunked { engine, size, overlap } => SearchEngineConfig::Chunked { engine: wrapped(engine)?, size, overlap },
+        SearchEngineConfig::Deduplicated { engine } => SearchEngineConfig::Deduplicated { engine: wrapped(engine)? },
+        SearchEngineConfig::Hybrid { keyword, vector, k } => SearchEngineConfig::Hybrid { keyword: wrapped(keyword)?, vector: wrapped(vector)?, k },
+        SearchEngineConfig::Multi { engines, primary } => SearchEngineConfig::Multi {
+            engines: engines.into_iter().map(|engine| with_sibling_paths(engine, suffix)).collect::<anyhow::Result<_>>()?,
+            primary,
+        },
+        other => other,
+    })
+}
+
+/// The engine is dropped once the sources are indexed so that the index is closed before the swap.
+async fn build_index(sources: &[SourceConfig], engine: &SearchEngineConfig) -> anyhow::Result<Vec<IndexSummary>> {
+    let search: Box<dyn SearchEngine> = engine.try_into()?;
+
+    index_sources(sources, search.as_ref(), None, Some(&MultiProgress::new())).await
+}
+
+/// `<parent>/.<name>.<suffix>`, on the same file system as the index so that renaming it is atomic.
+fn sibling(path: &Path, suffix: &str) -> anyhow::Result<PathBuf> {
+    let name = path.file_name().with_context(|| format!("Invalid index path: {:?}", path))?;
+    let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+
+    Ok(parent.join(format!(".{}.{}", name.to_string_lossy(), suffix)))
+}
+
+/// Replaces the index at the target with the staging one without the target path ever missing: a
+/// file is replaced by a single rename, and a directory exchanged with the staging one where the
+/// system supports it. Elsewhere, the current index is moved aside rather than deleted first so
+/// that the target path is only missing between two renames, and is moved back if the new index
+/// can't be moved in.
+pub(super) fn swap(staging: &Path, target: &Path) -> anyhow::Result<()> {
+    if staging.is_file() && !target.is_dir() {
+        return std::fs::rename(staging, target).with_context(|| format!("Couldn't move the new index to {:?}", target));
+    }
+
+    if staging.is_dir() && target.is_dir() && exchange(staging, target)? {
+        // The staging path now holds the previous index
+        return remove(staging);
+    }
+
+    let previous = sibling(target, "previous")?;
+    remove(&previous)?;
+
+    if target.exists() {
+        std::fs::rename(target, &previous).with_context(|| format!("Couldn't move the current index {:?} aside", target))?;
+    }
+
+    if let Err(err) = std::fs::rename(staging, target) {
+        if previous.exists() {
+            std::fs::rename(&previous, target).with_context(|| format!("Couldn't restore the index {:?} from {:?}", target, previous))?;
+        }
+
+        return Err(err).with_context(|| format!("Couldn't move the new index to {:?}", target));
+    }
+
+    remove(&previous)
+}
+
+/// Atomically exchanges the two paths, `false` when the file system doesn't support it.
+#[cfg(target_os = "linux")]
+fn exchange(first: &Path, second: &Path) -> anyhow::Result<bool> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let (first_c, second_c) = (CString::new(first.as_os_str().as_bytes())?, CString::new(second.as_os_str().as_bytes())?);
+
+    // Safety: both paths are valid NUL terminated strings, only read by the call
+    let exchanged = unsafe {
+        libc::renameat2(libc::AT_FDCWD, first_c.as_ptr(), libc::AT_FDCWD, second_c.as_ptr(), libc::RENAME_EXCHANGE)
+    };
+
+    if exchanged == 0 {
+        return Ok(true);
+    }
+
+    match std::io::Error::last_os_error() {
+        err if matches!(err.raw_os_error(), Some(libc::EINVAL | libc::ENOSYS | libc::ENOTSUP)) => Ok(false),
+        err => Err(err).with_context(|| format!("Couldn't exchange {:?} with {:?}", first, second)),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn exchange(_first: &Path, _second: &Path) -> anyhow::Result<bool> {
+    Ok(false)
+}
+
+fn remove(path: &Path) -> anyhow::Result<()> {
+    if path.is_dir() {
+        std::fs::remove_dir_all(path)?;
+    } else if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+    use std::convert::TryInto;
+    use std::path::Path;
+
+    use tempdir::TempDir;
+    use tokio_stream::StreamExt;
+
+    use crate::cli::config::{AnalyzerConfig, DaemonConfig, DoksConfig, FieldConfig, FieldTypeConfig, PatternSyntax, SearchEngineConfig, SourceConfig};
+    use crate::cli::reindex::{migrate, reindex, swap};
+    use crate::cli::state::StateStore;
+    use crate::search::{FoundItem, SearchEngine, SearchRequest};
+    use crate::model::Document;
+    use crate::search::tantivy_impl::{Analyzer, CustomField, TantivySearchEngine};
+
+    async fn search(index: &Path, query: &str) -> anyhow::Result<Vec<FoundItem>> {
+        let engine = TantivySearchEngine::open(index, &[], &Analyzer::default())?;
+        engine.search(&SearchRequest::new(query)).await?.collect::<anyhow::Result<Vec<_>>>().await
+    }
+
+    #[tokio::test]
+    async fn test_reindex() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let docs = root.path().join("docs");
+        let index = root.path().join("index");
+        let state = StateStore::new(root.path().join("state.json"));
+
+        std::fs::create_dir(&docs)?;
+        std::fs::write(docs.join("runbook.md"), "Restart the database")?;
+
+        let source = |path: &Path| SourceConfig::FileSystem {
+            id: "docs".to_string(),
+            paths: vec![path.to_string_lossy().to_string()],
+            include: vec![".*\\.md$".to_string()],
+            exclude: vec![],
+            patterns: PatternSyntax::Regex,
+            strip_code_blocks: false,
+            strip_notebook_outputs: false,
+            sections: false,
+            max_file_size: None,
+            ignore_files: true,
+            boost: None,
+            tags: vec![],
+        };
+        let config = |path: &Path| DoksConfig {
+            sources: vec![source(path)],
+            engine: SearchEngineConfig::Tantivy { path: index.clone(), analyzer: None, boosts: None, limits: None, fields: vec![] },
+            daemon: DaemonConfig::default(),
+            namespaces: BTreeMap::new(),
+        };
+
+        reindex(config(&docs), &state).await?;
+        assert_eq!(search(&index, "database").await?.len(), 1);
+
+        // Removed files don't survive a reindex
+        std::fs::remove_file(docs.join("runbook.md"))?;
+        std::fs::write(docs.join("setup.md"), "Install the toolchain")?;
+        reindex(config(&docs), &state).await?;
+
+        assert!(search(&index, "database").await?.is_empty());
+        assert_eq!(search(&index, "toolchain").await?.len(), 1);
+
+        // A failed run leaves the index as it was
+        assert!(reindex(config(&root.path().join("missing")), &state).await.is_err());
+        assert_eq!(search(&index, "toolchain").await?.len(), 1);
+        assert!(!root.path().join(".index.reindex").exists());
+
+        assert_eq!(state.load().await?.sources["docs"].documents, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reindex_wrapped_engines() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let docs = root.path().join("docs");
+        let (index, database) = (root.path().join("index"), root.path().join("index.db"));
+        let state = StateStore::new(root.path().join("state.json"));
+
+        std::fs::create_dir(&docs)?;
+        std::fs::write(docs.join("runbook.md"), "Restart the database")?;
+
+        let engine = || SearchEngineConfig::Multi {
+            engines: vec![
+                SearchEngineConfig::Chunked {
+                    engine: Box::new(SearchEngineConfig::Tantivy { path: index.clone(), analyzer: None, boosts: None, limits: None, fields: vec![] }),
+                    size: None,
+                    overlap: None,
+                },
+                SearchEngineConfig::Sqlite { path: database.clone() },
+            ],
+            primary: None,
+        };
+        let config = |engine: SearchEngineConfig| DoksConfig {
+            sources: vec![SourceConfig::FileSystem {
+                id: "docs".to_string(),
+                paths: vec![docs.to_string_lossy().to_string()],
+                include: vec![".*\\.md$".to_string()],
+                exclude: vec![],
+                patterns: PatternSyntax::Regex,
+                strip_code_blocks: false,
+                strip_notebook_outputs: false,
+                sections: false,
+                max_file_size: None,
+                ignore_files: true,
+                boost: None,
+                tags: vec![],
+            }],
+            engine,
+            daemon: DaemonConfig::default(),
+            namespaces: BTreeMap::new(),
+        };
+
+        reindex(config(engine()), &state).await?;
+        assert_eq!(search(&index, "database").await?.len(), 1);
+
+        let sqlite: Box<dyn SearchEngine> = (&SearchEngineConfig::Sqlite { path: database.clone() }).try_into()?;
+        assert_eq!(sqlite.search(&SearchRequest::new("database")).await?.collect::<Vec<_>>().await.len(), 1);
+        drop(sqlite);
+
+        let remote = SearchEngineConfig::Remote { endpoint: "http://localhost:8080".to_string(), token_file: None };
+        let error = reindex(config(SearchEngineConfig::Chunked { engine: Box::new(remote), size: None, overlap: None }), &state).await;
+        assert!(error.is_err_and(|err| err.to_string().contains("configured: remote")));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_migrate() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let index = root.path().join("index");
+        let engine = |fields: Vec<FieldConfig>| SearchEngineConfig::Tantivy { path: index.clone(), analyzer: None, boosts: None, limits: None, fields };
+
+        TantivySearchEngine::open(&index, &[], &Analyzer::default())?.index(vec![Document {
+            id: "runbook".to_string(),
+            source: "docs".to_string(),
+            title: "Runbook".to_string(),
+            link: "/docs/runbook.md".to_string(),
+            content: "Restart the database".to_string(),
+            metadata: HashMap::from([("team".to_string(), "storage".to_string())]),
+            tags: vec!["ops".to_string()],
+            modified: None,
+        }]).await?;
+
+        assert!(!migrate(&engine(vec![])).await?);
+
+        // Built by an older version
+        std::fs::write(index.join("doks_schema_version"), "0")?;
+        let error = TantivySearchEngine::open(&index, &[], &Analyzer::default()).err().map(|err| err.to_string()).unwrap_or_default();
+        assert!(error.contains("run `doks reindex`"), "{}", error);
+
+        assert!(migrate(&engine(vec![])).await?);
+        let results = search(&index, "database").await?;
+        assert_eq!(results.iter().map(|item| item.id.as_str()).collect::<Vec<_>>(), vec!["runbook"]);
+
+        // A field added to the config
+        let team = FieldConfig { name: "team".to_string(), kind: FieldTypeConfig::Text, stored: true, indexed: true, fast: false };
+        assert!(migrate(&engine(vec![team.clone()])).await?);
+        let migrated = TantivySearchEngine::open(&index, &[CustomField::from(&team)], &Analyzer::default())?;
+        assert_eq!(migrated.search(&SearchRequest::new("storage")).await?.collect::<Vec<_>>().await.len(), 1);
+        assert_eq!(migrated.document("runbook").await?.map(|document| document.tags), Some(vec!["ops".to_string()]));
+        drop(migrated);
+
+        // Wrapped in another engine
+        std::fs::write(index.join("doks_schema_version"), "0")?;
+        let chunked = SearchEngineConfig::Chunked { engine: Box::new(engine(vec![team.clone()])), size: None, overlap: None };
+        let multi = SearchEngineConfig::Multi { engines: vec![SearchEngineConfig::InMemory, chunked], primary: None };
+        assert!(migrate(&multi).await?);
+        assert!(!migrate(&multi).await?);
+
+        // A stemmer added to the config
+        let analyzer = AnalyzerConfig { stemmer: Some("english".to_string()), lowercase: true, ascii_folding: false, stop_words: None, synonyms: BTreeMap::new() };
+        let stemmed = SearchEngineConfig::Tantivy { path: index.clone(), analyzer: Some(analyzer), boosts: None, limits: None, fields: vec![team] };
+        assert!(migrate(&stemmed).await?);
+        assert!(!migrate(&stemmed).await?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_swap() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let (staging, target) = (root.path().join(".index.import"), root.path().join("index"));
+        std::fs::create_dir(&target)?;
+        std::fs::write(target.join("meta.json"), "current")?;
+
+        // The current index is kept when the new one can't be moved in
+        assert!(swap(&staging, &target).is_err());
+        assert_eq!(std::fs::read_to_string(target.join("meta.json"))?, "current");
+
+        std::fs::create_dir(&staging)?;
+        std::fs::write(staging.join("meta.json"), "new")?;
+        swap(&staging, &target)?;
+
+        assert_eq!(std::fs::read_to_string(target.join("meta.json"))?, "new");
+        assert!(!root.path().join(".index.previous").exists());
+        assert!(!staging.exists());
+
+        // A single file index is replaced in place
+        let (staging, target) = (root.path().join(".index.db.import"), root.path().join("index.db"));
+        std::fs::write(&target, "current")?;
+        std::fs::write(&staging, "new")?;
+        swap(&staging, &target)?;
+
+        assert_eq!(std::fs::read_to_string(&target)?, "new");
+        assert!(!staging.exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::bail;
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+use tokio_stream::StreamExt;
+
+use crate::cli::open::{nth_result, open_link};
+use crate::cli::output::{OutputFormat, print_results};
+use crate::cli::state::data_dir;
+use crate::search::{FoundItem, SearchEngine, SearchRequest};
+
+#[derive(Debug, Eq, PartialEq)]
+enum ReplCommand {
+    Query(String),
+    /// Opens the Nth result of the last query.
+    Open(usize),
+    Quit,
+}
+
+fn parse_command(line: &str) -> anyhow::Result<ReplCommand> {
+    let mut words = line.split_whitespace();
+
+    match words.next() {
+        Some(":q") | Some(":quit") => Ok(ReplCommand::Quit),
+        Some(":open") => Ok(ReplCommand::Open(words.next().map(|n| n.parse()).transpose()?.unwrap_or(1))),
+        Some(command) if command.starts_with(':') => bail!("Unknown command: {}", command),
+        _ => Ok(ReplCommand::Query(line.to_string())),
+    }
+}
+
+/// Runs queries typed by the user against an engine loaded once. The history is kept across
+/// sessions in the data dir.
+pub async fn repl(search: &dyn SearchEngine, output: OutputFormat) -> anyhow::Result<()> {
+    let mut editor = DefaultEditor::new()?;
+    let history = data_dir()?.join("repl_history");
+
+    if history.exists() {
+        editor.load_history(&history)?;
+    }
+
+    println!("Type a query, ':open N' to open the Nth result, ':quit' or Ctrl-D to exit");
+
+    let mut last_results: Vec<FoundItem> = vec![];
+
+    loop {
+        let line = match editor.readline("doks> ") {
+            Ok(line) => line,
+            // Ctrl-C only clears the current line
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err.into()),
+        };
+
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        editor.add_history_entry(line)?;
+
+        // Errors are reported without leaving the repl (e.g. a query with invalid syntax)
+        let result = match parse_command(line) {
+            Ok(ReplCommand::Quit) => break,
+            Ok(ReplCommand::Open(nth)) => nth_result(last_results.clone(), nth).and_then(|item| open_link(&item.link)),
+            Ok(ReplCommand::Query(query)) => match run_query(search, &query).await {
+                Ok(results) => {
+                    last_results = results;
+                    print_results(output, false, tokio_stream::iter(last_results.iter().cloned().map(Ok))).await.map(|_| ())
+                }
+                Err(err) => Err(err),
+            },
+            Err(err) => Err(err),
+        };
+
+        if let Err(err) = result {
+            eprintln!("Error: {:#}", err);
+        }
+    }
+
+    if let Some(parent) = history.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    editor.save_history(&history)?;
+
+    Ok(())
+}
+
+async fn run_query(search: &dyn SearchEngine, query: &str) -> anyhow::Result<Vec<FoundItem>> {
+    search.search(&SearchRequest::new(query)).await?.collect().await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::repl::{parse_command, ReplCommand};
+
+    #[test]
+    fn test_parse_command() -> anyhow::Result<()> {
+        assert_eq!(parse_command("restart database")?, ReplCommand::Query("restart database".to_string()));
+        assert_eq!(parse_command(":open 3")?, ReplCommand::Open(3));
+        assert_eq!(parse_command(":open")?, ReplCommand::Open(1));
+        assert_eq!(parse_command(":q")?, ReplCommand::Quit);
+        assert!(parse_command(":open x").is_err());
+        assert!(parse_command(":unknown").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tempdir::TempDir;
+
+use crate::cli::config::SearchEngineConfig;
+use crate::cli::reindex::swap;
+use crate::cli::state::{IndexState, StateStore};
+
+/// Bumped whenever the layout of the snapshot changes.
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Stored as `manifest.json` next to the `index` folder in the snapshot archive.
+#[derive(Serialize, Deserialize, Debug)]
+struct Manifest {
+    version: u32,
+    engine: String,
+    created: DateTime<Utc>,
+    /// When the sources were indexed, so that `doks stats` stays accurate after an import.
+    state: IndexState,
+}
+
+fn index_path(engine: &SearchEngineConfig) -> anyhow::Result<&Path> {
+    match engine {
+        SearchEngineConfig::Tantivy { path, .. } => Ok(path),
+        other => bail!("Snapshots are only supported by the tantivy engine (configured: {})", other.kind()),
+    }
+}
+
+/// Packages the index and the index state in a zstd compressed tar archive.
+pub async fn export(engine: &SearchEngineConfig, state: &StateStore, out: &Path) -> anyhow::Result<()> {
+    let index = index_path(engine)?.to_path_buf();
+
+    if !index.join("meta.json").exists() {
+        bail!("No index found at {:?}, nothing to export", index)
+    }
+
+    let manifest = Manifest {
+        version: SNAPSHOT_VERSION,
+        engine: engine.kind().to_string(),
+        created: Utc::now(),
+        state: state.load().await?,
+    };
+
+    let out = out.to_path_buf();
+    tokio::task::spawn_blocking(move || write_snapshot(&index, &manifest, &out)).await??;
+
+    Ok(())
+}
+
+/// Replaces the index (and the recorded state of its sources) with the ones of the snapshot.
+pub async fn import(engine: &SearchEngineConfig, state: &StateStore, snapshot: &Path, force: bool) -> anyhow::Result<()> {
+    let index = index_path(engine)?.to_path_buf();
+
+    if index.join("meta.json").exists() && !force {
+        bail!("An index already exists at {:?} (use --force to replace it)", index)
+    }
+
+    let snapshot = snapshot.to_path_buf();
+    let manifest = tokio::task::spawn_blocking(move || read_snapshot(&snapshot, &index)).await??;
+
+    for (source, source_state) in manifest.state.sources {
+        state.record(&source, source_state).await?;
+    }
+
+    Ok(())
+}
+
+fn write_snapshot(index: &Path, manifest: &Manifest, out: &Path) -> anyhow::Result<()> {
+    let file = File::create(out).with_context(|| format!("Couldn't create snapshot: {:?}", out))?;
+    let mut archive = tar::Builder::new(zstd::Encoder::new(file, 0)?);
+
+    let manifest = serde_json::to_vec_pretty(manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+
+    archive.append_data(&mut header, "manifest.json", manifest.as_slice())?;
+    archive.append_dir_all("index", index)?;
+    archive.into_inner()?.finish()?;
+
+    Ok(())
+}
+
+/// The snapshot is extracted next to the index and only moved in place once fully extracted.
+fn read_snapshot(snapshot: &Path, index: &Path) -> anyhow::Result<Manifest> {
+    let parent = index.parent().map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from("."));
+    std::fs::create_dir_all(&parent)?;
+
+    let staging = TempDir::new_in(&parent, ".doks-import")?;
+    let file = File::open(snapshot).with_context(|| format!("Couldn't open snapshot: {:?}", snapshot))?;
+
+    // Entries escaping the destination (absolute paths, `..`) are skipped by unpack
+    tar::Archive::new(zstd::Decoder::new(file)?)
+        .unpack(staging.path())
+        .with_context(|| format!("Invalid snapshot: {:?}", snapshot))?;
+
+    let manifest = std::fs::read_to_string(staging.path().join("manifest.json")).context("Manifest not found in snapshot")?;
+    let manifest: Manifest = serde_json::from_str(&manifest)?;
+
+    if manifest.version != SNAPSHOT_VERSION {
+        bail!("Unsupported snapshot version: {} (expected {})", manifest.version, SNAPSHOT_VERSION)
+    }
+
+    swap(&staging.path().join("index"), index)?;
+
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use tempdir::TempDir;
+    use tokio_stream::StreamExt;
+
+    use crate::cli::config::SearchEngineConfig;
+    use crate::cli::snapshot::{export, import};
+    use crate::cli::state::StateStore;
+    use crate::model::Document;
+    use crate::search::tantivy_impl::{Analyzer, TantivySearchEngine};
+    use crate::search::{SearchEngine, SearchRequest};
+
+    #[tokio::test]
+    async fn test_export_import() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let exported = SearchEngineConfig::Tantivy { path: root.path().join("index"), analyzer: None, boosts: None, limits: None, fields: vec![] };
+        let imported = SearchEngineConfig::Tantivy { path: root.path().join("imported/index"), analyzer: None, boosts: None, limits: None, fields: vec![] };
+        let snapshot = root.path().join("snapshot.tar.zst");
+
+        let state = StateStore::new(root.path().join("state.json"));
+        let imported_state = StateStore::new(root.path().join("imported_state.json"));
+
+        std::fs::create_dir(root.path().join("index"))?;
+        TantivySearchEngine::open(root.path().join("index"), &[], &Analyzer::default())?
+            .index(vec![Document {
+                id: "1".to_string(),
+                source: "docs".to_string(),
+                title: "Runbook".to_string(),
+                link: "link1".to_string(),
+                content: "Restart the database".to_string(),
+                metadata: HashMap::new(),
+                tags: vec![],
+                modified: None,
+            }])
+            .await?;
+        state.record_indexed("docs", 1).await?;
+
+        export(&exported, &state, &snapshot).await?;
+        import(&imported, &imported_state, &snapshot, false).await?;
+
+        // Not replaced without --force
+        assert!(import(&imported, &imported_state, &snapshot, false).await.is_err());
+        import(&imported, &imported_state, &snapshot, true).await?;
+
+        let engine = TantivySearchEngine::open(root.path().join("imported/index"), &[], &Analyzer::default())?;
+        let results = engine.search(&SearchRequest::new("database")).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(imported_state.load().await?.sources["docs"].documents, 1);
+
+        let in_memory = SearchEngineConfig::InMemory;
+        assert!(export(&in_memory, &state, &PathBuf::from("/tmp/unused.tar.zst")).await.is_err());
+
+        Ok(())
+    }
+}
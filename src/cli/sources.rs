@@ -0,0 +1,72 @@
+use std::convert::TryInto;
+
+use anyhow::Context;
+use structopt::StructOpt;
+use tokio_stream::StreamExt;
+
+use crate::cli::config::{DoksConfig, SourceConfig};
+use crate::sources::DocumentSource;
+use crate::utils::table::format_table;
+
+#[derive(Debug, StructOpt)]
+pub enum SourcesCommand {
+    /// Lists the configured sources.
+    List,
+    /// Shows the config of a source.
+    Inspect {
+        id: String,
+        /// Also fetch the documents the source would produce (without indexing them)
+        #[structopt(long)]
+        dry_run: bool,
+    },
+}
+
+pub async fn sources_main(config: &DoksConfig, command: &SourcesCommand) -> anyhow::Result<()> {
+    match command {
+        SourcesCommand::List => {
+            let rows = std::iter::once(["ID".to_string(), "TYPE".to_string()])
+                .chain(config.sources.iter().map(|source| [source.id().to_string(), source.kind().to_string()]))
+                .collect::<Vec<_>>();
+
+            for line in format_table(&rows) {
+                println!("{}", line);
+            }
+        }
+        SourcesCommand::Inspect { id, dry_run } => {
+            let source_config = config.sources
+                .iter()
+                .find(|source| source.id() == id)
+                .with_context(|| format!("Source not found: {}", id))?;
+
+            println!("{}", serde_json::to_string_pretty(source_config)?);
+
+            if *dry_run {
+                println!();
+
+                let (documents, bytes) = dry_run_source(source_config).await?;
+                println!("\n{} documents ({} bytes) would be indexed", documents, bytes);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Fetches the documents of a source and prints their size, id and link, without indexing them.
+/// Returns the number of documents and their total size in bytes.
+pub(super) async fn dry_run_source(source_config: &SourceConfig) -> anyhow::Result<(u64, u64)> {
+    let source: Box<dyn DocumentSource> = source_config.try_into()?;
+    let mut documents = source.fetch();
+    let (mut count, mut bytes) = (0, 0);
+
+    while let Some(document) = documents.next().await {
+        let document = document
+            .with_context(|| format!("Error occurred while fetching documents from source: {}", source_config.id()))?;
+
+        println!("{:>10}  {}  {}", document.content.len(), document.id, document.link);
+        count += 1;
+        bytes += document.content.len() as u64;
+    }
+
+    Ok((count, bytes))
+}
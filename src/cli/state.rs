@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Where doks keeps its own files: `$XDG_DATA_HOME/doks` (`~/.local/share/doks` by default).
+pub fn data_dir() -> anyhow::Result<PathBuf> {
+    let data_home = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME").context("HOME is not set")?).join(".local/share"),
+    };
+
+    Ok(data_home.join("doks"))
+}
+
+/// Where doks keeps the files it can download again (the clones of the GitHub repositories):
+/// `$XDG_CACHE_HOME/doks` (`~/.cache/doks` by default).
+pub fn cache_dir() -> anyhow::Result<PathBuf> {
+    let cache_home = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME").context("HOME is not set")?).join(".cache"),
+    };
+
+    Ok(cache_home.join("doks"))
+}
+
+/// What doks knows about past index runs, which the engines don't keep track of.
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq)]
+pub struct IndexState {
+    #[serde(default)]
+    pub sources: BTreeMap<String, SourceState>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct SourceState {
+    pub last_indexed: DateTime<Utc>,
+    /// Number of documents fetched by the last run.
+    pub documents: u64,
+}
+
+/// Persists the index state of a namespace in `<data dir>/<namespace>.json`.
+pub struct StateStore {
+    path: PathBuf,
+}
+
+impl StateStore {
+    pub fn new<T: AsRef<Path>>(path: T) -> Self {
+        Self { path: path.as_ref().to_path_buf() }
+    }
+
+    pub fn for_namespace(namespace: &str) -> anyhow::Result<Self> {
+        Ok(Self::new(data_dir()?.join(format!("{}.json", namespace))))
+    }
+
+    pub async fn load(&self) -> anyhow::Result<IndexState> {
+        if !self.path.exists() {
+            return Ok(IndexState::default());
+        }
+
+        let content = tokio::fs::read_to_string(&self.path).await?;
+
+        serde_json::from_str(&content).with_context(|| format!("Invalid index state file: {:?}", self.path))
+    }
+
+    pub async fn record_indexed(&self, source: &str, documents: u64) -> anyhow::Result<()> {
+        self.record(source, SourceState { last_indexed: Utc::now(), documents }).await
+    }
+
+    pub async fn record(&self, source: &str, source_state: SourceState) -> anyhow::Result<()> {
+        let mut state = self.load().await?;
+        state.sources.insert(source.to_string(), source_state);
+
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        tokio::fs::write(&self.path, serde_json::to_vec_pretty(&state)?)
+            .await
+            .with_context(|| format!("Couldn't write index state file: {:?}", self.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use crate::cli::state::StateStore;
+
+    #[tokio::test]
+    async fn test_record_indexed() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let store = StateStore::new(root.path().join("nested/default.json"));
+
+        assert!(store.load().await?.sources.is_empty());
+
+        store.record_indexed("github", 3).await?;
+        store.record_indexed("docs", 1).await?;
+        store.record_indexed("github", 5).await?;
+
+        let state = store.load().await?;
+
+        assert_eq!(state.sources.keys().collect::<Vec<_>>(), vec!["docs", "github"]);
+        assert_eq!(state.sources["github"].documents, 5);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use crate::cli::config::DoksConfig;
+use crate::cli::state::IndexState;
+use crate::search::SearchEngine;
+use crate::utils::table::format_table;
+
+/// Prints what's in the index: engine, size on disk (for local engines) and documents per source.
+pub async fn print_stats(config: &DoksConfig, search: &dyn SearchEngine, state: &IndexState) -> anyhow::Result<()> {
+    println!("Engine: {}", config.engine.kind());
+
+    let storage = config.engine.storage_paths();
+    if !storage.is_empty() {
+        let size = storage.iter().map(|path| disk_usage(path)).collect::<std::io::Result<Vec<_>>>()?;
+        println!("Size on disk: {}", human_size(size.iter().sum()));
+    }
+
+    let stats = match search.stats().await {
+        Ok(stats) => {
+            println!("Documents: {}", stats.documents);
+            Some(stats)
+        }
+        Err(err) => {
+            println!("Documents: unknown ({})", err);
+            None
+        }
+    };
+
+    // Configured sources first, then the ones only found in the index (e.g. removed from the config)
+    let mut sources = config.sources.iter().map(|source| source.id().to_string()).collect::<Vec<_>>();
+    for source in stats.iter().flat_map(|stats| stats.sources.keys()) {
+        if !sources.contains(source) {
+            sources.push(source.clone());
+        }
+    }
+
+    let rows = sources
+        .iter()
+        .map(|source| {
+            let documents = stats.as_ref().map_or("-".to_string(), |stats| stats.sources.get(source).copied().unwrap_or(0).to_string());
+            let last_indexed = state.sources
+                .get(source)
+                .map_or("never".to_string(), |s| s.last_indexed.format("%Y-%m-%d %H:%M:%S UTC").to_string());
+
+            [source.clone(), documents, last_indexed]
+        })
+        .collect::<Vec<_>>();
+
+    let header = ["SOURCE".to_string(), "DOCUMENTS".to_string(), "LAST INDEXED".to_string()];
+
+    println!();
+    for line in format_table(&std::iter::once(header).chain(rows).collect::<Vec<_>>()) {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+/// Size of a file or of all the files of a directory.
+fn disk_usage(path: &Path) -> std::io::Result<u64> {
+    if !path.exists() {
+        return Ok(0);
+    }
+
+    let metadata = std::fs::metadata(path)?;
+
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    std::fs::read_dir(path)?.map(|entry| disk_usage(&entry?.path())).sum()
+}
+
+pub(super) fn human_size(bytes: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, units[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use crate::cli::stats::{disk_usage, human_size};
+
+    #[test]
+    fn test_disk_usage() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        std::fs::create_dir(root.path().join("nested"))?;
+        std::fs::write(root.path().join("file1"), "12345")?;
+        std::fs::write(root.path().join("nested/file2"), "123")?;
+
+        assert_eq!(disk_usage(root.path())?, 8);
+        assert_eq!(disk_usage(&root.path().join("missing"))?, 0);
+
+        assert_eq!(human_size(512), "512 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+
+        Ok(())
+    }
+}
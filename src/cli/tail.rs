@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use tokio_stream::StreamExt;
+
+use crate::cli::config::DoksConfig;
+use crate::cli::output::{OutputFormat, print_results};
+use crate::cli::queryable_engine;
+use crate::search::{FoundItem, SearchRequest};
+
+/// Runs the search every `interval` and prints the matching documents that are new, or whose
+/// title or snippet changed, since the previous run. The documents matching when starting aren't
+/// printed. Runs until interrupted.
+pub async fn tail(config: &DoksConfig, request: &SearchRequest, output: OutputFormat, interval: Duration) -> anyhow::Result<()> {
+    let mut seen = HashMap::new();
+    new_items(&mut seen, search(config, request).await?);
+
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await; // The first tick is immediate
+
+    loop {
+        ticker.tick().await;
+
+        // The index may be briefly unavailable while another process writes to it
+        match search(config, request).await {
+            Ok(results) => {
+                let items = new_items(&mut seen, results);
+                print_results(output, false, tokio_stream::iter(items.into_iter().map(Ok))).await?;
+            }
+            Err(err) => log::warn!("Couldn't run the search, retrying in {:?}: {:#}", interval, err),
+        }
+    }
+}
+
+/// The engine is opened for every search rather than kept open: local engines like tantivy only
+/// allow one process at a time to open the index, which would block the `doks index` runs.
+async fn search(config: &DoksConfig, request: &SearchRequest) -> anyhow::Result<Vec<FoundItem>> {
+    let search = queryable_engine(config).await?;
+    let results = search.search(request).await?;
+
+    results.collect::<anyhow::Result<Vec<_>>>().await
+}
+
+/// Remembers the results and returns the ones that are new or changed.
+fn new_items(seen: &mut HashMap<String, (String, String)>, results: Vec<FoundItem>) -> Vec<FoundItem> {
+    results
+        .into_iter()
+        .filter(|item| {
+            let version = (item.title.clone(), item.snippet.clone());
+            seen.insert(item.id.clone(), version.clone()) != Some(version)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::cli::tail::new_items;
+    use crate::search::FoundItem;
+
+    #[test]
+    fn test_new_items() {
+        let item = |id: &str, snippet: &str| FoundItem {
+            id: id.to_string(),
+            score: 1.0,
+            source: "docs".to_string(),
+            title: "Incident runbook".to_string(),
+            link: id.to_string(),
+            snippet: snippet.to_string(),
+        };
+        let ids = |items: Vec<FoundItem>| items.into_iter().map(|item| item.id).collect::<Vec<_>>();
+
+        let mut seen = HashMap::new();
+
+        assert_eq!(ids(new_items(&mut seen, vec![item("1", "page the on-call")])), vec!["1"]);
+        assert!(new_items(&mut seen, vec![item("1", "page the on-call")]).is_empty());
+        assert_eq!(
+            ids(new_items(&mut seen, vec![item("1", "page the incident commander"), item("2", "")])),
+            vec!["1", "2"],
+        );
+    }
+}
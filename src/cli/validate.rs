@@ -0,0 +1,246 @@
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+
+use crate::cli::config::{
+    DoksConfig,
+    EmbeddingsConfig,
+    LimitsConfig,
+    load_config,
+    OpenSearchAuthConfig,
+    read_token_file,
+    SearchEngineConfig,
+    SourceConfig,
+};
+use crate::search::SearchEngine;
+use crate::search::tantivy_impl::{self, Analyzer, Boosts, CustomField};
+use crate::sources::DocumentSource;
+
+pub(super) const CONNECTIVITY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Checks the whole config and reports all the problems found instead of stopping at the first one.
+pub async fn validate(config_files: &[PathBuf], check_connectivity: bool) -> anyhow::Result<()> {
+    let config = load_config(config_files).await?;
+
+    let mut problems = config_problems(&config);
+
+    if check_connectivity {
+        problems.extend(connectivity_problems(&config).await);
+    }
+
+    for problem in &problems {
+        println!("✗ {}", problem);
+    }
+
+    if !problems.is_empty() {
+        bail!("{} problem(s) found in {:?}", problems.len(), config_files)
+    }
+
+    println!("✓ {:?} is valid", config_files);
+
+    Ok(())
+}
+
+/// Problems that can be found without reaching any remote service.
+pub(super) fn config_problems(config: &DoksConfig) -> Vec<String> {
+    let mut problems = vec![];
+    let mut ids = HashSet::new();
+
+    for source in &config.sources {
+        if !ids.insert(source.id()) {
+            problems.push(format!("source '{}': the id is used by several sources", source.id()));
+        }
+
+        // Compiles the patterns and reads the token files
+        let loaded: anyhow::Result<Box<dyn DocumentSource>> = source.try_into();
+        if let Err(err) = loaded {
+            problems.push(format!("source '{}': {:#}", source.id(), err));
+        }
+
+        if let SourceConfig::FileSystem { paths, .. } = source {
+            for path in paths.iter().filter(|path| !Path::new(path).exists()) {
+                problems.push(format!("source '{}': path not found: {}", source.id(), path));
+            }
+        }
+
+        if let Some(boost) = source.boost().filter(|boost| !boost.is_finite() || *boost <= 0.0) {
+            problems.push(format!("source '{}': the boost must be a positive number: {}", source.id(), boost));
+        }
+    }
+
+    for err in engine_problems(&config.engine) {
+        problems.push(format!("engine '{}': {:#}", config.engine.kind(), err));
+    }
+
+    if let Some(Err(err)) = config.daemon.webhook_secret_file.as_deref().map(read_token_file) {
+        problems.push(format!("daemon: {:#}", err));
+    }
+
+    problems
+}
+
+/// Opening the engine may create an index, so only its credentials and models are checked.
+fn engine_problems(engine: &SearchEngineConfig) -> Vec<anyhow::Error> {
+    let mut problems = vec![];
+    let mut check_file = |file: Option<&String>| {
+        if let Some(Err(err)) = file.map(|f| read_token_file(f)) {
+            problems.push(err);
+        }
+    };
+
+    match engine {
+        SearchEngineConfig::Elasticsearch { password_file, .. } => check_file(password_file.as_ref()),
+        SearchEngineConfig::OpenSearch { auth: Some(OpenSearchAuthConfig::Basic { password_file, .. }), .. } => {
+            check_file(Some(password_file))
+        }
+        SearchEngineConfig::OpenSearch { auth: Some(OpenSearchAuthConfig::SigV4 { .. }), .. } => {
+            for variable in ["AWS_ACCESS_KEY_ID", "AWS_SECRET_ACCESS_KEY"] {
+                if std::env::var_os(variable).is_none() {
+                    problems.push(anyhow::anyhow!("{} is not set", variable));
+                }
+            }
+        }
+        SearchEngineConfig::Tantivy { analyzer, boosts, limits, fields, .. } => {
+            let analyzer: Option<anyhow::Result<Analyzer>> = analyzer.as_ref().map(TryInto::try_into);
+            let boosts: Option<anyhow::Result<Boosts>> = boosts.as_ref().map(TryInto::try_into);
+            problems.extend(analyzer.and_then(Result::err));
+            problems.extend(boosts.and_then(Result::err));
+            problems.extend(limits.as_ref().map(LimitsConfig::check).and_then(Result::err));
+            problems.extend(tantivy_impl::check_fields(&fields.iter().map(CustomField::from).collect::<Vec<_>>()).err());
+        }
+        SearchEngineConfig::Meilisearch { api_key_file, .. } => check_file(api_key_file.as_ref()),
+        SearchEngineConfig::Qdrant { api_key_file, embeddings, .. } => {
+            check_file(api_key_file.as_ref());
+            problems.extend(embeddings_problems(embeddings));
+        }
+        SearchEngineConfig::Semantic { embeddings, .. } => problems.extend(embeddings_problems(embeddings)),
+        SearchEngineConfig::Typesense { api_key_file, .. } | SearchEngineConfig::Algolia { api_key_file, .. } => {
+            check_file(Some(api_key_file))
+        }
+        SearchEngineConfig::Sonic { password_file, .. } => check_file(Some(password_file)),
+        SearchEngineConfig::Remote { token_file, .. } => check_file(token_file.as_ref()),
+        SearchEngineConfig::Hybrid { keyword, vector, .. } => {
+            problems.extend(engine_problems(keyword));
+            problems.extend(engine_problems(vector));
+        }
+        SearchEngineConfig::Chunked { engine, size, overlap } => {
+            if overlap.unwrap_or(400) >= size.unwrap_or(4000) {
+                problems.push(anyhow::anyhow!("the chunks overlap must be smaller than their size"));
+            }
+
+            problems.extend(engine_problems(engine));
+        }
+        SearchEngineConfig::Deduplicated { engine } => problems.extend(engine_problems(engine)),
+        SearchEngineConfig::Multi { engines, primary } => {
+            if engines.is_empty() {
+                problems.push(anyhow::anyhow!("at least one engine must be configured"));
+            }
+
+            if primary.unwrap_or(0) >= engines.len().max(1) {
+                problems.push(anyhow::anyhow!("primary engine index {} is out of range", primary.unwrap_or(0)));
+            }
+
+            for engine in engines {
+                problems.extend(engine_problems(engine).into_iter().map(|err| err.context(engine.kind())));
+            }
+        }
+        _ => {}
+    }
+
+    problems
+}
+
+fn embeddings_problems(embeddings: &EmbeddingsConfig) -> Vec<anyhow::Error> {
+    let (model_dir, files) = match embeddings {
+        EmbeddingsConfig::Local { model_dir } => (model_dir, &["config.json", "tokenizer.json", "model.safetensors"][..]),
+        EmbeddingsConfig::Onnx { model_dir } => (model_dir, &["model.onnx", "tokenizer.json"][..]),
+        EmbeddingsConfig::OpenAi { api_key_file, .. } => {
+            return api_key_file.iter().filter_map(|f| read_token_file(f).err()).collect();
+        }
+    };
+
+    files
+        .iter()
+        .map(|file| model_dir.join(file))
+        .filter(|file| !file.exists())
+        .map(|file| anyhow::anyhow!("embeddings model file not found: {:?}", file))
+        .collect()
+}
+
+/// Reaches the source APIs and the engine (unless it stores its data locally).
+async fn connectivity_problems(config: &DoksConfig) -> Vec<String> {
+    let mut problems = vec![];
+    let client = reqwest::Client::builder().timeout(CONNECTIVITY_TIMEOUT).build().unwrap_or_default();
+
+    for source in &config.sources {
+        if let Some(endpoint) = source.endpoint() {
+            // Any response, even an error status, means the endpoint is reachable
+            if let Err(err) = client.get(&endpoint).send().await {
+                problems.push(format!("source '{}': couldn't reach {}: {}", source.id(), endpoint, err));
+            }
+        }
+    }
+
+    if is_remote(&config.engine) {
+        if let Err(err) = reach_engine(&config.engine).await {
+            problems.push(format!("engine '{}': couldn't reach: {:#}", config.engine.kind(), err));
+        }
+    }
+
+    problems
+}
+
+/// Engines that neither live in memory nor store their data locally.
+pub(super) fn is_remote(engine: &SearchEngineConfig) -> bool {
+    engine.storage_paths().is_empty() && *engine != SearchEngineConfig::InMemory
+}
+
+pub(super) async fn reach_engine(engine: &SearchEngineConfig) -> anyhow::Result<()> {
+    let search: Box<dyn SearchEngine> = engine.try_into()?;
+
+    tokio::time::timeout(CONNECTIVITY_TIMEOUT, search.stats()).await.context("timed out")??;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::config::DoksConfig;
+    use crate::cli::validate::config_problems;
+
+    #[test]
+    fn test_config_problems() -> anyhow::Result<()> {
+        let config = r#"
+            {
+              "sources": [
+                { "source": "fs", "id": "docs", "paths": ["/non/existing/path"], "include": ["(unclosed"] },
+                { "source": "asana", "id": "docs", "projects": [], "token_file": "/non/existing/token" }
+              ],
+              "engine": {
+                "use": "multi",
+                "engines": [
+                  { "use": "typesense", "endpoint": "http://localhost:8108", "api_key_file": "/non/existing/key" },
+                  { "use": "tantivy", "path": "/tmp/doks_index", "analyzer": { "stemmer": "klingon" } }
+                ],
+                "primary": 2
+              }
+            }
+        "#;
+
+        let problems = config_problems(&serde_json::from_str::<DoksConfig>(config)?);
+
+        assert_eq!(problems.len(), 7, "{:#?}", problems);
+        assert!(problems[0].contains("regex parse error"));
+        assert_eq!(problems[1], "source 'docs': path not found: /non/existing/path");
+        assert_eq!(problems[2], "source 'docs': the id is used by several sources");
+        assert!(problems[4].contains("primary engine index 2 is out of range"));
+        assert!(problems[6].contains("Unknown stemmer language: klingon"));
+
+        assert!(config_problems(&serde_json::from_str::<DoksConfig>(r#"{ "sources": [] }"#)?).is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc::unbounded_channel;
+
+use crate::cli::config::{DoksConfig, file_system_source, SourceConfig};
+use crate::cli::state::StateStore;
+use crate::cli::{index_source, index_sources};
+use crate::search::SearchEngine;
+use crate::sources::fs::FileSystemDocumentSource;
+
+/// Editors usually emit a burst of events for a single save.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Indexes all the sources, then keeps the index up to date: changed files of filesystem sources
+/// are reindexed as soon as they are saved while the other sources are fully reindexed every
+/// `interval`. Runs until interrupted.
+pub async fn watch(
+    config: &DoksConfig,
+    search: &dyn SearchEngine,
+    state: &StateStore,
+    interval: Duration,
+) -> anyhow::Result<()> {
+    index_sources(&config.sources, search, Some(state), None).await?;
+
+    let fs_sources = config.sources
+        .iter()
+        .filter(|source| matches!(source, SourceConfig::FileSystem { .. }))
+        .map(file_system_source)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let (tx, mut rx) = unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        // Only fails once the receiver is dropped, i.e. when exiting
+        let _ = tx.send(event);
+    })?;
+
+    for source in &fs_sources {
+        for path in &source.paths {
+            log::info!("Watching: {} (source: {})", path, source.source_id);
+            watcher.watch(Path::new(path), RecursiveMode::Recursive)?;
+        }
+    }
+
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await; // The first tick is immediate and everything was just indexed
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                for source in config.sources.iter().filter(|s| !matches!(s, SourceConfig::FileSystem { .. })) {
+                    log::info!("Reindexing source: {}", source.id());
+
+                    let indexed = match index_source(source, search, None).await {
+                        Ok(summary) => state.record_indexed(source.id(), summary.documents).await,
+                        Err(err) => Err(err),
+                    };
+
+                    if let Err(err) = indexed {
+                        log::error!("Couldn't reindex source {}: {:#}", source.id(), err);
+                    }
+                }
+            }
+            Some(event) = rx.recv() => {
+                let mut changed = BTreeSet::new();
+                changed_paths(event, &mut changed);
+
+                tokio::time::sleep(DEBOUNCE).await;
+
+                while let Ok(event) = rx.try_recv() {
+                    changed_paths(event, &mut changed);
+                }
+
+                if let Err(err) = index_files(&fs_sources, &changed, search).await {
+                    log::error!("Couldn't index changed files: {:#}", err);
+                }
+            }
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+/// Deleted files are not removed from the index, a full `doks index` is needed for that.
+fn changed_paths(event: notify::Result<notify::Event>, changed: &mut BTreeSet<PathBuf>) {
+    match event {
+        Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => changed.extend(event.paths),
+        Ok(_) => {}
+        Err(err) => log::warn!("File watcher error: {}", err),
+    }
+}
+
+async fn index_files(
+    sources: &[FileSystemDocumentSource],
+    paths: &BTreeSet<PathBuf>,
+    search: &dyn SearchEngine,
+) -> anyhow::Result<()> {
+    let mut documents = vec![];
+
+    for path in paths.iter().filter(|path| path.is_file()) {
+        for source in sources.iter().filter(|source| owns(source, path)) {
+            if source.ignores(path).await {
+                log::debug!("Ignoring file: {}", path.display());
+                continue;
+            }
+
+            documents.extend(source.load(path).await?);
+        }
+    }
+
+    if !documents.is_empty() {
+        search.index(documents).await?;
+    }
+
+    Ok(())
+}
+
+fn owns(source: &FileSystemDocumentSource, path: &Path) -> bool {
+    source.paths.iter().any(|root| path.starts_with(root)) && source.accepts(&path.to_string_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use regex::Regex;
+    use tempdir::TempDir;
+    use tokio_stream::StreamExt;
+
+    use crate::cli::watch::index_files;
+    use crate::search::tantivy_impl::TantivySearchEngine;
+    use crate::search::{SearchEngine, SearchRequest};
+    use crate::sources::fs::FileSystemDocumentSource;
+
+    #[tokio::test]
+    async fn test_index_files() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let source = FileSystemDocumentSource {
+            source_id: "docs".to_string(),
+            paths: vec![root.path().to_string_lossy().to_string()],
+            include: vec![Regex::new(".*\\.md")?],
+            exclude: vec![],
+            relative_patterns: false,
+            strip_code_blocks: false,
+            strip_notebook_outputs: false,
+            sections: false,
+            max_file_size: None,
+            ignore_files: false,
+        };
+
+        tokio::fs::write(root.path().join("runbook.md"), "restart the database").await?;
+        tokio::fs::write(root.path().join("notes.txt"), "restart the server").await?;
+
+        let outside = TempDir::new("doks-tests")?;
+        tokio::fs::write(outside.path().join("other.md"), "restart everything").await?;
+
+        let paths = vec![
+            root.path().join("runbook.md"),
+            root.path().join("notes.txt"),
+            root.path().join("deleted.md"),
+            outside.path().join("other.md"),
+        ].into_iter().collect::<BTreeSet<_>>();
+
+        let engine = TantivySearchEngine::in_memory()?;
+        index_files(&[source], &paths, &engine).await?;
+
+        let results = engine.search(&SearchRequest::new("restart")).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].title, "runbook.md");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::Router;
+use axum::routing::post;
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::cli::config::{DoksConfig, SourceConfig};
+use crate::cli::daemon::SourceLocks;
+use crate::cli::{index_repository, index_source};
+use crate::cli::state::StateStore;
+use crate::search::SearchEngine;
+use crate::server::metrics::metrics;
+
+/// A reindex requested by a push to a repository.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+struct ReindexJob {
+    source: String,
+    repository: String,
+}
+
+/// The jobs waiting to be run, so that a burst of pushes only reindexes a repository once.
+type Pending = Arc<Mutex<BTreeSet<ReindexJob>>>;
+
+#[derive(Clone)]
+struct WebhookState {
+    secret: Arc<String>,
+    sources: Arc<BTreeSet<String>>,
+    pending: Pending,
+    jobs: UnboundedSender<ReindexJob>,
+}
+
+/// The queue of the reindexes requested by the webhooks, run one at a time.
+pub struct ReindexQueue {
+    jobs: UnboundedReceiver<ReindexJob>,
+    pending: Pending,
+}
+
+/// Routes `POST /hooks/<source id>` receiving the GitHub and GitLab push webhooks. Pushes are
+/// queued for reindexing once their signature (GitHub) or token (GitLab) is checked against
+/// `secret`.
+pub fn webhooks(config: &DoksConfig, secret: String) -> (Router, ReindexQueue) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let pending = Pending::default();
+
+    let state = WebhookState {
+        secret: Arc::new(secret),
+        sources: Arc::new(config.sources.iter().map(|source| source.id().to_string()).collect()),
+        pending: pending.clone(),
+        jobs: sender,
+    };
+
+    let router = Router::new().route("/hooks/:source", post(hook)).with_state(state);
+
+    (router, ReindexQueue { jobs: receiver, pending })
+}
+
+async fn hook(State(state): State<WebhookState>, Path(source): Path<String>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    if !state.sources.contains(&source) {
+        return StatusCode::NOT_FOUND;
+    }
+
+    let repository = match verify_push(&headers, &body, &state.secret) {
+        Ok(Some(repository)) => repository,
+        Ok(None) => return StatusCode::NO_CONTENT,
+        Err(status) => return status,
+    };
+
+    let job = ReindexJob { source, repository };
+
+    if state.pending.lock().unwrap().insert(job.clone()) {
+        log::info!("Push webhook received, queuing reindex: {:?}", job);
+
+        if state.jobs.send(job).is_err() {
+            return StatusCode::SERVICE_UNAVAILABLE;
+        }
+    }
+
+    StatusCode::ACCEPTED
+}
+
+/// The repository (`owner/name`) pushed to, `None` for the other events (e.g. the ping sent when
+/// the webhook is created).
+fn verify_push(headers: &HeaderMap, body: &[u8], secret: &str) -> Result<Option<String>, StatusCode> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    let (event, repository) = if let Some(signature) = header("x-hub-signature-256") {
+        let signature = signature
+            .strip_prefix("sha256=")
+            .and_then(|signature| hex::decode(signature).ok())
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        mac.update(body);
+        mac.verify_slice(&signature).map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+        (header("x-github-event") == Some("push"), "/repository/full_name")
+    } else if let Some(token) = header("x-gitlab-token") {
+        // Comparing digests rather than the tokens doesn't leak the secret through timing
+        if Sha256::digest(token.as_bytes()) != Sha256::digest(secret.as_bytes()) {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+
+        (header("x-gitlab-event") == Some("Push Hook"), "/project/path_with_namespace")
+    } else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+
+    if !event {
+        return Ok(None);
+    }
+
+    let payload: Value = serde_json::from_slice(body).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    match payload.pointer(repository).and_then(Value::as_str) {
+        Some(repository) => Ok(Some(repository.to_string())),
+        None => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+impl ReindexQueue {
+    /// Runs the queued reindexes until the webhooks are gone, each once the scheduled run of its
+    /// source, if any, is over.
+    pub(super) async fn run(mut self, config: &DoksConfig, search: &dyn SearchEngine, state: &StateStore, locks: &SourceLocks) {
+        while let Some(job) = self.jobs.recv().await {
+            let _lock = locks.lock(&job.source).await;
+            self.pending.lock().unwrap().remove(&job);
+
+            if let Err(err) = reindex(&job, config, search, state).await {
+                log::error!("Couldn't reindex {:?}: {:#}", job, err);
+            }
+        }
+    }
+}
+
+/// Only the pushed repository is reindexed for the sources listing repositories, the whole source
+/// for the others (e.g. a file system source synced from the repository).
+async fn reindex(job: &ReindexJob, config: &DoksConfig, search: &dyn SearchEngine, state: &StateStore) -> anyhow::Result<()> {
+    let source = match config.sources.iter().find(|source| source.id() == job.source) {
+        Some(source) => source,
+        None => return Ok(()),
+    };
+
+    match (source, source.for_repository(&job.repository)) {
+        (_, Some(repository_source)) => {
+            let summary = index_repository(&repository_source, &job.repository, search).await?;
+            log::info!("Reindexed {:?}: {} documents, {} stale removed", job, summary.documents, summary.removed);
+        }
+        // Pushes to repositories the source doesn't list
+        (SourceConfig::Github { .. }, None) => log::warn!("Source {} doesn't list the repository {}", job.source, job.repository),
+        (_, None) => {
+            let started = Instant::now();
+            let indexed = index_source(source, search, None).await;
+            metrics().index_run(source.id(), started.elapsed(), indexed.is_ok());
+
+            let summary = indexed?;
+            state.record_indexed(source.id(), summary.documents).await?;
+            log::info!("Reindexed {:?}: {} documents, {} stale removed", job, summary.documents, summary.removed);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{HeaderMap, StatusCode};
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    use crate::cli::webhooks::verify_push;
+
+    #[test]
+    fn test_verify_push() {
+        let body = br#"{"repository": {"full_name": "wlezzar/doks"}, "project": {"path_with_namespace": "team/docs"}}"#;
+        let headers = |pairs: &[(&'static str, String)]| {
+            pairs.iter().map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap())).collect::<HeaderMap>()
+        };
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(body);
+        let signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+        let github = headers(&[("x-hub-signature-256", signature.clone()), ("x-github-event", "push".to_string())]);
+        assert_eq!(verify_push(&github, body, "secret"), Ok(Some("wlezzar/doks".to_string())));
+        assert_eq!(verify_push(&github, body, "other"), Err(StatusCode::UNAUTHORIZED));
+        assert_eq!(verify_push(&github, b"{}", "secret"), Err(StatusCode::UNAUTHORIZED));
+
+        let ping = headers(&[("x-hub-signature-256", signature), ("x-github-event", "ping".to_string())]);
+        assert_eq!(verify_push(&ping, body, "secret"), Ok(None));
+
+        let gitlab = headers(&[("x-gitlab-token", "secret".to_string()), ("x-gitlab-event", "Push Hook".to_string())]);
+        assert_eq!(verify_push(&gitlab, body, "secret"), Ok(Some("team/docs".to_string())));
+        assert_eq!(verify_push(&gitlab, body, "other"), Err(StatusCode::UNAUTHORIZED));
+
+        assert_eq!(verify_push(&HeaderMap::new(), body, "secret"), Err(StatusCode::UNAUTHORIZED));
+    }
+}
@@ -2,20 +2,55 @@
 
 extern crate core;
 
+use std::process::ExitCode;
+
 use structopt::StructOpt;
 
 use cli::DoksOpts;
 
-use crate::cli::cli_main;
+use crate::cli::{cli_main, exit_code, NoResults};
 
 mod model;
 mod sources;
 mod search;
 mod cli;
 mod utils;
+mod server;
+mod mcp;
+mod slack;
+mod tui;
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    env_logger::init();
-    cli_main(DoksOpts::from_args()).await
+async fn main() -> ExitCode {
+    let opts = match DoksOpts::from_args_safe() {
+        Ok(opts) => opts,
+        Err(err) => {
+            match err.use_stderr() {
+                true => eprintln!("{}", err.message),
+                false => println!("{}", err.message),
+            }
+
+            return ExitCode::from(exit_code(&err.into()));
+        }
+    };
+    let quiet = opts.quiet;
+
+    if !quiet {
+        env_logger::init();
+    }
+
+    match cli_main(opts).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) if err.is::<NoResults>() => {
+            if !quiet {
+                eprintln!("{}", err);
+            }
+
+            ExitCode::from(exit_code(&err))
+        }
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            ExitCode::from(exit_code(&err))
+        }
+    }
 }
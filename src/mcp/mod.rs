@@ -0,0 +1,244 @@
+use anyhow::Context;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio_stream::StreamExt;
+
+use crate::cli::output::plain_snippet;
+use crate::search::{SearchEngine, SearchRequest};
+
+/// The Model Context Protocol revision implemented.
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Serves the engine to LLM assistants over the Model Context Protocol until stdin is closed. The
+/// messages are JSON-RPC requests, one per line, and the answers are written to stdout (the logs
+/// go to stderr).
+pub async fn serve_stdio(engine: &dyn SearchEngine) -> anyhow::Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(response) = handle_message(engine, &line).await {
+            let mut response = serde_json::to_vec(&response)?;
+            response.push(b'\n');
+
+            stdout.write_all(&response).await?;
+            stdout.flush().await?;
+        }
+    }
+
+    Ok(())
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+/// The response to a message, none for the notifications (the messages without an id).
+async fn handle_message(engine: &dyn SearchEngine, message: &str) -> Option<Value> {
+    let message: Value = match serde_json::from_str(message) {
+        Ok(message) => message,
+        Err(err) => return Some(error_response(Value::Null, RpcError::new(PARSE_ERROR, err.to_string()))),
+    };
+
+    let method = message["method"].as_str().unwrap_or_default();
+    let result = handle_request(engine, method, &message["params"]).await;
+    let id = message.get("id")?.clone();
+
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => error_response(id, error),
+    })
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": error.code, "message": error.message } })
+}
+
+async fn handle_request(engine: &dyn SearchEngine, method: &str, params: &Value) -> Result<Value, RpcError> {
+    match method {
+        "initialize" => Ok(json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "doks", "version": env!("CARGO_PKG_VERSION") },
+        })),
+        "ping" | "notifications/initialized" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tools() })),
+        "tools/call" => {
+            let call = ToolCall::deserialize(params).map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))?;
+
+            // Tool failures are reported to the model rather than as protocol errors
+            Ok(match call_tool(engine, &call).await {
+                Ok(text) => json!({ "content": [{ "type": "text", "text": text }], "isError": false }),
+                Err(err) => json!({ "content": [{ "type": "text", "text": format!("{:#}", err) }], "isError": true }),
+            })
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method: {}", method))),
+    }
+}
+
+fn tools() -> Value {
+    json!([
+        {
+            "name": "search",
+            "description": "Searches the indexed documentation (full text). Returns the matching documents with their id, link and a snippet.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "The search query" },
+                    "limit": { "type": "integer", "description": "Maximum number of results (10 by default)" },
+                    "source": { "type": "string", "description": "Only search the documents of this source" },
+                },
+                "required": ["query"],
+            },
+        },
+        {
+            "name": "get_document",
+            "description": "Returns the full content of an indexed document, given its id (as returned by search).",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": { "type": "string", "description": "The id of the document" },
+                },
+                "required": ["id"],
+            },
+        },
+    ])
+}
+
+#[derive(Deserialize)]
+struct ToolCall {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Deserialize)]
+struct SearchArguments {
+    query: String,
+    limit: Option<usize>,
+    source: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GetDocumentArguments {
+    id: String,
+}
+
+async fn call_tool(engine: &dyn SearchEngine, call: &ToolCall) -> anyhow::Result<String> {
+    match call.name.as_str() {
+        "search" => {
+            let arguments = SearchArguments::deserialize(&call.arguments).context("Invalid arguments")?;
+            let mut request = SearchRequest::new(&arguments.query);
+            request.limit = arguments.limit.unwrap_or(request.limit);
+            request.source_filter = arguments.source.into_iter().collect();
+
+            let results = engine.search(&request).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+
+            if results.is_empty() {
+                return Ok("No results found".to_string());
+            }
+
+            let results = results
+                .iter()
+                .enumerate()
+                .map(|(rank, item)| {
+                    format!(
+                        "[{}] {}\nid: {}\nsource: {}\nlink: {}\n{}",
+                        rank + 1, item.title, item.id, item.source, item.link, plain_snippet(item),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            Ok(results.join("\n\n"))
+        }
+        "get_document" => {
+            let arguments = GetDocumentArguments::deserialize(&call.arguments).context("Invalid arguments")?;
+            let document = engine
+                .document(&arguments.id)
+                .await?
+                .with_context(|| format!("No indexed document with the id: {}", arguments.id))?;
+
+            Ok(format!("# {}\nsource: {}\nlink: {}\n\n{}", document.title, document.source, document.link, document.content))
+        }
+        other => anyhow::bail!("Unknown tool: {}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use crate::mcp::handle_message;
+    use crate::model::Document;
+    use crate::search::SearchEngine;
+    use crate::search::tantivy_impl::TantivySearchEngine;
+
+    #[tokio::test]
+    async fn test_handle_message() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+        engine.index(vec![Document {
+            id: "runbook".to_string(),
+            source: "docs".to_string(),
+            title: "Runbook".to_string(),
+            link: "/docs/runbook.md".to_string(),
+            content: "Restart the database".to_string(),
+            metadata: HashMap::new(),
+            tags: vec![],
+            modified: None,
+        }]).await?;
+
+        let request = |id: u64, method: &str, params: serde_json::Value| {
+            json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }).to_string()
+        };
+
+        let response = handle_message(&engine, &request(1, "initialize", json!({}))).await.unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["serverInfo"]["name"], "doks");
+
+        let notification = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }).to_string();
+        assert!(handle_message(&engine, &notification).await.is_none());
+
+        let response = handle_message(&engine, &request(2, "tools/list", json!({}))).await.unwrap();
+        assert_eq!(response["result"]["tools"].as_array().unwrap().len(), 2);
+
+        let search = json!({ "name": "search", "arguments": { "query": "database" } });
+        let response = handle_message(&engine, &request(3, "tools/call", search)).await.unwrap();
+        assert_eq!(response["result"]["isError"], false);
+        assert!(response["result"]["content"][0]["text"].as_str().unwrap().contains("id: runbook"));
+
+        let get = json!({ "name": "get_document", "arguments": { "id": "runbook" } });
+        let response = handle_message(&engine, &request(4, "tools/call", get)).await.unwrap();
+        assert!(response["result"]["content"][0]["text"].as_str().unwrap().ends_with("Restart the database"));
+
+        let get = json!({ "name": "get_document", "arguments": { "id": "unknown" } });
+        let response = handle_message(&engine, &request(5, "tools/call", get)).await.unwrap();
+        assert_eq!(response["result"]["isError"], true);
+
+        let response = handle_message(&engine, &request(6, "resources/list", json!({}))).await.unwrap();
+        assert_eq!(response["error"]["code"], -32601);
+
+        let response = handle_message(&engine, "{").await.unwrap();
+        assert_eq!(response["error"]["code"], -32700);
+
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
@@ -10,4 +11,10 @@ pub struct Document {
     pub link: String,
     pub content: String,
     pub metadata: HashMap<String, String>,
+    /// Labels shared across the sources (frontmatter tags, GitHub topics...), to filter on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// When the document was last modified, if the source knows it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<DateTime<Utc>>,
 }
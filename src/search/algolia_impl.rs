@@ -0,0 +1,349 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::{Client, Method, RequestBuilder, StatusCode};
+use serde_json::{json, Value};
+use tokio::sync::OnceCell;
+
+use crate::model::Document;
+use crate::search::{html_snippet, FoundItem, IndexStats, SearchEngine, SearchRequest, SearchResult, HIGHLIGHT_END, HIGHLIGHT_START};
+use crate::sources::DocStream;
+use crate::utils::json::{get_array, parse_json};
+use crate::utils::streams::channel_stream;
+
+/// Algolia rejects records above 10KB on most plans, so the indexed content is truncated.
+const MAX_CONTENT_BYTES: usize = 8_000;
+const BATCH_SIZE: usize = 1000;
+
+pub struct AlgoliaSearchEngine {
+    client: Client,
+    application_id: String,
+    api_key: String,
+    index: String,
+    settings_updated: OnceCell<()>,
+}
+
+impl AlgoliaSearchEngine {
+    pub fn new(application_id: &str, api_key: String, index: &str) -> Self {
+        Self {
+            client: Client::new(),
+            application_id: application_id.to_string(),
+            api_key,
+            index: index.to_string(),
+            settings_updated: OnceCell::new(),
+        }
+    }
+
+    /// Writes go to the main host while reads use the distributed search network (`-dsn`) host.
+    fn request(&self, method: Method, path: &str, read: bool) -> RequestBuilder {
+        let host = if read {
+            format!("{}-dsn.algolia.net", self.application_id)
+        } else {
+            format!("{}.algolia.net", self.application_id)
+        };
+
+        self.client
+            .request(method, format!("https://{}/1/indexes/{}{}", host, self.index, path))
+            .header("X-Algolia-Application-Id", &self.application_id)
+            .header("X-Algolia-API-Key", &self.api_key)
+    }
+
+    async fn update_settings(&self) -> anyhow::Result<()> {
+        self.request(Method::PUT, "/settings", false)
+            .json(&json!({
+                "searchableAttributes": ["title", "content"],
+                "attributesForFaceting": ["source"],
+                "attributesToSnippet": ["content:30"]
+            }))
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Couldn't update settings of algolia index: {}", self.index))?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SearchEngine for AlgoliaSearchEngine {
+    async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+        self.settings_updated.get_or_try_init(|| self.update_settings()).await?;
+
+        for batch in documents.chunks(BATCH_SIZE) {
+            self.request(Method::POST, "/batch", false)
+                .json(&batch_body(batch))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+
+        Ok(())
+    }
+
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        let (parsed, request) = request.parse_query()?;
+        request.ensure_no_document_filters()?;
+        request.ensure_no_dates()?;
+        request.ensure_relevance_sort()?;
+        request.ensure_no_source_boosts()?;
+
+        let mut body = json!({
+            "query": parsed.text(),
+            // The quoted phrases and the excluded words
+            "advancedSyntax": true,
+            "offset": request.offset,
+            "length": request.limit,
+            "attributesToSnippet": ["content:30"],
+            "highlightPreTag": HIGHLIGHT_START,
+            "highlightPostTag": HIGHLIGHT_END
+        });
+
+        if !request.source_filter.is_empty() {
+            // Facet filters in a nested array are combined with OR
+            let sources = request.source_filter.iter().map(|s| format!("source:{}", s)).collect::<Vec<_>>();
+            body["facetFilters"] = json!([sources]);
+        }
+
+        let http_request = self.request(Method::POST, "/query", true).json(&body);
+
+        let stream = channel_stream(|tx| async move {
+            let response: Value = http_request
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            for item in parse_hits(&response)? {
+                tx.send(Ok(item)).await?;
+            }
+
+            Ok(())
+        });
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        self.request(Method::POST, "/clear", false)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        self.request(Method::POST, "/deleteByQuery", false)
+            .json(&json!({ "facetFilters": [format!("source:{}", source)] }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        let mut documents = vec![];
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let body = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+
+            let response = self.request(Method::POST, "/browse", true).json(&body).send().await?;
+
+            // Nothing was indexed yet
+            if response.status() == StatusCode::NOT_FOUND {
+                break;
+            }
+
+            let response: Value = response.error_for_status()?.json().await?;
+            documents.extend(parse_documents(&response)?);
+
+            cursor = response.get("cursor").and_then(|c| c.as_str()).map(str::to_string);
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        Ok(Box::pin(tokio_stream::iter(documents.into_iter().map(Ok))))
+    }
+
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        for batch in ids.chunks(BATCH_SIZE) {
+            self.request(Method::POST, "/batch", false)
+                .json(&delete_body(batch))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+
+        Ok(())
+    }
+
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        let response: Value = self.request(Method::POST, "/query", true)
+            .json(&json!({ "query": "", "hitsPerPage": 0, "facets": ["source"], "maxValuesPerFacet": 1000 }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(IndexStats {
+            documents: response.get("nbHits").and_then(|n| n.as_u64()).unwrap_or_default(),
+            sources: parse_json(&response, &["facets", "source"]).unwrap_or_default(),
+        })
+    }
+}
+
+fn truncate(content: &str, max_bytes: usize) -> &str {
+    if content.len() <= max_bytes {
+        return content;
+    }
+
+    let mut end = max_bytes;
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &content[..end]
+}
+
+/// Builds a `batch` request saving the documents (`objectID` is the document id).
+fn batch_body(documents: &[Document]) -> Value {
+    let requests = documents
+        .iter()
+        .map(|document| {
+            log::info!("Indexing document: {} (source: {})", document.link, document.source);
+
+            json!({
+                "action": "updateObject",
+                "body": {
+                    "objectID": document.id,
+                    "source": document.source,
+                    "title": document.title,
+                    "link": document.link,
+                    "content": truncate(&document.content, MAX_CONTENT_BYTES),
+                    "metadata": document.metadata,
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({ "requests": requests })
+}
+
+fn delete_body(ids: &[String]) -> Value {
+    let requests = ids
+        .iter()
+        .map(|id| json!({ "action": "deleteObject", "body": { "objectID": id } }))
+        .collect::<Vec<_>>();
+
+    json!({ "requests": requests })
+}
+
+/// The records of a page of the browse endpoint, as indexed: their content is truncated.
+fn parse_documents(response: &Value) -> anyhow::Result<Vec<Document>> {
+    get_array(response, &["hits"])?
+        .iter()
+        .map(|hit| {
+            let field = |name: &str| hit.get(name).and_then(|f| f.as_str()).unwrap_or_default().to_string();
+            let id = hit.get("objectID").and_then(|f| f.as_str()).with_context(|| format!("Unexpected record: {}", hit))?;
+
+            Ok(Document {
+                id: id.to_string(),
+                source: field("source"),
+                title: field("title"),
+                link: field("link"),
+                content: field("content"),
+                metadata: hit.get("metadata").cloned().map(serde_json::from_value).transpose()?.unwrap_or_default(),
+                tags: vec![],
+                modified: None,
+            })
+        })
+        .collect()
+}
+
+fn parse_hits(response: &Value) -> anyhow::Result<Vec<FoundItem>> {
+    get_array(response, &["hits"])?
+        .iter()
+        .enumerate()
+        .map(|(rank, hit)| {
+            let field = |name: &str| -> anyhow::Result<String> {
+                hit.get(name)
+                    .and_then(|f| f.as_str())
+                    .map(|f| f.to_string())
+                    .with_context(|| format!("Field {} of type text not found in hit: {}", name, hit))
+            };
+
+            Ok(FoundItem {
+                id: field("objectID")?,
+                // Algolia doesn't expose relevance scores, the rank is used instead
+                score: 1.0 / (rank + 1) as f32,
+                source: field("source")?,
+                title: field("title")?,
+                link: field("link")?,
+                snippet: html_snippet(hit.pointer("/_snippetResult/content/value").and_then(|s| s.as_str()).unwrap_or_default()),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::search::algolia_impl::{delete_body, parse_documents, parse_hits, truncate};
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("hello", 10), "hello");
+        assert_eq!(truncate("hello", 3), "hel");
+        assert_eq!(truncate("héllo", 2), "h");
+    }
+
+    #[test]
+    fn test_parse_hits() -> anyhow::Result<()> {
+        let response = json!({
+            "hits": [{
+                "objectID": "1",
+                "source": "src",
+                "title": "Hello",
+                "link": "link1",
+                "_snippetResult": { "content": { "value": "\u{2}Hello\u{3} <content>", "matchLevel": "full" } }
+            }]
+        });
+
+        let hits = parse_hits(&response)?;
+
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, "1");
+        assert_eq!(hits[0].snippet, "<b>Hello</b> &lt;content&gt;");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_documents() -> anyhow::Result<()> {
+        let response = json!({
+            "hits": [{ "objectID": "1", "source": "src", "title": "Hello", "link": "link1", "content": "Hello", "metadata": { "repository": "doks" } }],
+            "cursor": "abc"
+        });
+
+        let documents = parse_documents(&response)?;
+
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].id, "1");
+        assert_eq!(documents[0].metadata.get("repository").map(String::as_str), Some("doks"));
+
+        assert_eq!(
+            delete_body(&["1".to_string()]),
+            json!({ "requests": [{ "action": "deleteObject", "body": { "objectID": "1" } }] }),
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+
+use crate::model::Document;
+use crate::search::{Facets, IndexStats, SearchEngine, SearchExplanation, SearchRequest, SearchResult};
+use crate::sources::DocStream;
+
+/// Applies the relevance weights of the sources to the searches of the wrapped engine. The
+/// requests that already have weights keep them.
+pub struct SourceBoostedSearchEngine {
+    engine: Box<dyn SearchEngine>,
+    boosts: BTreeMap<String, f32>,
+}
+
+impl SourceBoostedSearchEngine {
+    pub fn new(engine: Box<dyn SearchEngine>, boosts: BTreeMap<String, f32>) -> Self {
+        SourceBoostedSearchEngine { engine, boosts }
+    }
+
+    fn boosted(&self, request: &SearchRequest) -> SearchRequest {
+        match request.source_boosts.is_empty() {
+            true => SearchRequest { source_boosts: self.boosts.clone(), ..request.clone() },
+            false => request.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl SearchEngine for SourceBoostedSearchEngine {
+    async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+        self.engine.index(documents).await
+    }
+
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        self.engine.search(&self.boosted(request)).await
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        self.engine.purge().await
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        self.engine.purge_source(source).await
+    }
+
+    async fn similar(&self, id: &str, limit: usize) -> SearchResult {
+        self.engine.similar(id, limit).await
+    }
+
+    async fn explain(&self, request: &SearchRequest) -> anyhow::Result<SearchExplanation> {
+        self.engine.explain(&self.boosted(request)).await
+    }
+
+    async fn facets(&self, request: &SearchRequest, metadata_keys: &[String]) -> anyhow::Result<Facets> {
+        self.engine.facets(request, metadata_keys).await
+    }
+
+    async fn suggest(&self, query: &str) -> anyhow::Result<Option<String>> {
+        self.engine.suggest(query).await
+    }
+
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        self.engine.documents().await
+    }
+
+    async fn document(&self, id: &str) -> anyhow::Result<Option<Document>> {
+        self.engine.document(id).await
+    }
+
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        self.engine.delete(ids).await
+    }
+
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        self.engine.stats().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use tempdir::TempDir;
+    use tokio_stream::StreamExt;
+
+    use crate::model::Document;
+    use crate::search::boosted_impl::SourceBoostedSearchEngine;
+    use crate::search::sqlite_impl::SqliteSearchEngine;
+    use crate::search::tantivy_impl::TantivySearchEngine;
+    use crate::search::{SearchEngine, SearchRequest};
+
+    fn document(id: &str, source: &str, title: &str) -> Document {
+        Document {
+            title: title.to_string(),
+            content: "Promote the replica".to_string(),
+            source: source.to_string(),
+            link: format!("{}.md", id),
+            metadata: HashMap::new(),
+            id: id.to_string(),
+            tags: vec![],
+            modified: None,
+        }
+    }
+
+    async fn sources(engine: &dyn SearchEngine, request: SearchRequest) -> anyhow::Result<Vec<String>> {
+        let results = engine.search(&request).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+        Ok(results.into_iter().map(|item| item.source).collect())
+    }
+
+    #[tokio::test]
+    async fn test_source_boosts() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+        engine.index(vec![document("1", "blog", "Failover failover")]).await?;
+        engine.index(vec![document("2", "runbooks", "Failover")]).await?;
+
+        let boosts = BTreeMap::from([("runbooks".to_string(), 10.0)]);
+        let engine = SourceBoostedSearchEngine::new(Box::new(engine), boosts);
+        assert_eq!(sources(&engine, SearchRequest::new("failover")).await?, vec!["runbooks", "blog"]);
+
+        // The boosts of the request replace the configured ones
+        let request = SearchRequest { source_boosts: BTreeMap::from([("blog".to_string(), 1.0)]), ..SearchRequest::new("failover") };
+        assert_eq!(sources(&engine, request).await?, vec!["blog", "runbooks"]);
+
+        let explanation = engine.explain(&SearchRequest::new("failover")).await?;
+        assert_eq!(explanation.results[0].1.details[1].value, 10.0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_source_boosts() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let engine = SqliteSearchEngine::new(root.path().join("index.db"))?;
+        engine.index(vec![document("1", "runbooks", "Failover")]).await?;
+
+        let engine = SourceBoostedSearchEngine::new(Box::new(engine), BTreeMap::from([("runbooks".to_string(), 10.0)]));
+        let error = engine.search(&SearchRequest::new("failover")).await.err().map(|err| err.to_string());
+        assert!(error.is_some_and(|err| err.contains("Source boosts are not supported")));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::bail;
+use async_trait::async_trait;
+use tokio_stream::StreamExt;
+
+use crate::model::Document;
+use crate::search::{Facets, IndexStats, SearchEngine, SearchExplanation, SearchRequest, SearchResult};
+use crate::sources::DocStream;
+
+/// Separates the id of a document from the position of its chunks: `<id>#chunk-<n>`.
+const CHUNK_SEPARATOR: &str = "#chunk-";
+
+/// Chunks fetched per requested result, as several chunks of a document may match.
+const CANDIDATES_PER_RESULT: usize = 4;
+
+/// Splits the large documents in overlapping chunks indexed in the wrapped engine, and merges the
+/// hits of the chunks of a document back into a single result. Keeps a huge file from ranking
+/// on every query and its snippets to the matching part.
+///
+/// The first chunk keeps the id of the document, the next ones are `<id>#chunk-<n>`. All of them
+/// have the `parent_id` and `chunk_offset` (in characters) metadata. The listed documents are
+/// reassembled from their chunks, but the stats count the chunks.
+pub struct ChunkedSearchEngine {
+    engine: Box<dyn SearchEngine>,
+    /// Maximum size of a chunk, in characters.
+    size: usize,
+    /// Characters shared by consecutive chunks, so that the phrases cut at a chunk boundary are
+    /// still found in one of them.
+    overlap: usize,
+}
+
+impl ChunkedSearchEngine {
+    pub fn new(engine: Box<dyn SearchEngine>, size: usize, overlap: usize) -> anyhow::Result<Self> {
+        if overlap >= size {
+            bail!("The chunks overlap ({}) must be smaller than their size ({})", overlap, size)
+        }
+
+        Ok(Self { engine, size, overlap })
+    }
+
+    fn step(&self) -> usize {
+        self.size - self.overlap
+    }
+
+    /// The chunks of the document, or the document itself when it fits in a single chunk.
+    fn split(&self, document: Document) -> Vec<Document> {
+        let boundaries = document.content
+            .char_indices()
+            .map(|(index, _)| index)
+            .chain(std::iter::once(document.content.len()))
+            .collect::<Vec<_>>();
+        let length = boundaries.len() - 1;
+
+        if length <= self.size {
+            return vec![document];
+        }
+
+        let mut chunks = vec![];
+        let mut start = 0;
+
+        loop {
+            let end = (start + self.size).min(length);
+            let mut chunk = Document {
+                id: chunk_id(&document.id, chunks.len()),
+                content: document.content[boundaries[start]..boundaries[end]].to_string(),
+                metadata: document.metadata.clone(),
+                ..document.clone()
+            };
+            chunk.metadata.insert("parent_id".to_string(), document.id.clone());
+            chunk.metadata.insert("chunk_offset".to_string(), start.to_string());
+            chunks.push(chunk);
+
+            if end == length {
+                break;
+            }
+
+            start += self.step();
+        }
+
+        chunks
+    }
+
+    /// Reassembles the documents from their chunks. The chunks that don't follow a full chunk are
+    /// left over from a longer version of their document (or the document is gone), and are
+    /// returned as is so that they get removed as stale documents.
+    fn merge(&self, documents: Vec<Document>) -> Vec<Document> {
+        let mut by_parent = BTreeMap::<String, Vec<(usize, Document)>>::new();
+
+        for document in documents {
+            let (parent, position) = chunk_position(&document.id);
+            by_parent.entry(parent.to_string()).or_default().push((position, document));
+        }
+
+        let mut merged = vec![];
+
+        for (_, mut chunks) in by_parent {
+            chunks.sort_by_key(|(position, _)| *position);
+            let mut chunks = chunks.into_iter().peekable();
+
+            let mut document = match chunks.next_if(|(position, _)| *position == 0) {
+                Some((_, document)) => document,
+                None => {
+                    merged.extend(chunks.map(|(_, chunk)| chunk));
+                    continue;
+                }
+            };
+            let mut last_full = document.content.chars().count() == self.size;
+            let mut next = 1;
+
+            while let Some((_, chunk)) = chunks.next_if(|(position, _)| last_full && *position == next) {
+                let length = chunk.content.chars().count();
+                document.content.extend(chunk.content.chars().skip(self.overlap));
+                last_full = length == self.size;
+                next += 1;
+            }
+
+            document.metadata.remove("parent_id");
+            document.metadata.remove("chunk_offset");
+            merged.push(document);
+            merged.extend(chunks.map(|(_, chunk)| chunk));
+        }
+
+        merged
+    }
+}
+
+fn chunk_id(parent: &str, position: usize) -> String {
+    match position {
+        0 => parent.to_string(),
+        _ => format!("{}{}{}", parent, CHUNK_SEPARATOR, position),
+    }
+}
+
+/// The id of the document a chunk was cut from, and the position of the chunk in it.
+fn chunk_position(id: &str) -> (&str, usize) {
+    id.rsplit_once(CHUNK_SEPARATOR)
+        .and_then(|(parent, position)| Some((parent, position.parse().ok()?)))
+        .unwrap_or((id, 0))
+}
+
+#[async_trait]
+impl SearchEngine for ChunkedSearchEngine {
+    async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+        self.engine.index(documents.into_iter().flat_map(|document| self.split(document)).collect()).await
+    }
+
+    /// The best matching chunk of a document stands for it.
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        let candidates = SearchRequest {
+            limit: (request.offset + request.limit) * CANDIDATES_PER_RESULT,
+            offset: 0,
+            ..request.clone()
+        };
+        let mut found = HashSet::new();
+
+        let results = self.engine
+            .search(&candidates)
+            .await?
+            .filter_map(move |item| match item {
+                Ok(mut item) => {
+                    item.id = chunk_position(&item.id).0.to_string();
+                    found.insert(item.id.clone()).then_some(Ok(item))
+                }
+                Err(err) => Some(Err(err)),
+            })
+            .skip(request.offset)
+            .take(request.limit);
+
+        Ok(Box::pin(results))
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        self.engine.purge().await
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        self.engine.purge_source(source).await
+    }
+
+    async fn similar(&self, id: &str, limit: usize) -> SearchResult {
+        let parent = chunk_position(id).0.to_string();
+        let mut found = HashSet::from([parent.clone()]);
+
+        let results = self.engine
+            .similar(&parent, limit * CANDIDATES_PER_RESULT)
+            .await?
+            .filter_map(move |item| match item {
+                Ok(mut item) => {
+                    item.id = chunk_position(&item.id).0.to_string();
+                    found.insert(item.id.clone()).then_some(Ok(item))
+                }
+                Err(err) => Some(Err(err)),
+            })
+            .take(limit);
+
+        Ok(Box::pin(results))
+    }
+
+    async fn explain(&self, request: &SearchRequest) -> anyhow::Result<SearchExplanation> {
+        let mut explanation = self.engine.explain(request).await?;
+        let mut found = HashSet::new();
+
+        explanation.results.retain_mut(|(item, _)| {
+            item.id = chunk_position(&item.id).0.to_string();
+            found.insert(item.id.clone())
+        });
+
+        Ok(explanation)
+    }
+
+    /// The matching chunks are counted rather than the documents.
+    async fn facets(&self, request: &SearchRequest, metadata_keys: &[String]) -> anyhow::Result<Facets> {
+        self.engine.facets(request, metadata_keys).await
+    }
+
+    async fn suggest(&self, query: &str) -> anyhow::Result<Option<String>> {
+        self.engine.suggest(query).await
+    }
+
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        let documents = self.engine.documents().await?.collect::<anyhow::Result<Vec<_>>>().await?;
+
+        Ok(Box::pin(tokio_stream::iter(self.merge(documents).into_iter().map(Ok))))
+    }
+
+    /// The chunks of the documents are deleted along with them.
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        let parents = ids.iter().map(String::as_str).collect::<HashSet<_>>();
+        let mut chunks = ids.to_vec();
+        let mut documents = self.engine.documents().await?;
+
+        while let Some(document) = documents.next().await {
+            let document = document?;
+            let (parent, position) = chunk_position(&document.id);
+
+            if position > 0 && parents.contains(parent) {
+                chunks.push(document.id);
+            }
+        }
+
+        self.engine.delete(&chunks).await
+    }
+
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        self.engine.stats().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+    use tokio_stream::StreamExt;
+
+    use crate::model::Document;
+    use crate::search::chunked_impl::ChunkedSearchEngine;
+    use crate::search::{FoundItem, IndexStats, SearchEngine, SearchRequest, SearchResult};
+    use crate::sources::DocStream;
+
+    /// Keeps the documents in a list, matching the ones containing the query.
+    #[derive(Default)]
+    struct Memory(Mutex<Vec<Document>>);
+
+    #[async_trait]
+    impl SearchEngine for Memory {
+        async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+            let mut indexed = self.0.lock().unwrap();
+
+            for document in documents {
+                indexed.retain(|existing| existing.id != document.id);
+                indexed.push(document);
+            }
+
+            Ok(())
+        }
+
+        async fn search(&self, request: &SearchRequest) -> SearchResult {
+            let found = self.0.lock().unwrap()
+                .iter()
+                .filter(|document| document.content.contains(&request.query))
+                .map(|document| Ok(FoundItem {
+                    id: document.id.clone(),
+                    score: 1.0,
+                    source: document.source.clone(),
+                    title: document.title.clone(),
+                    link: document.link.clone(),
+                    snippet: document.content.clone(),
+                }))
+                .collect::<Vec<_>>();
+
+            Ok(Box::pin(tokio_stream::iter(found)))
+        }
+
+        async fn documents(&self) -> anyhow::Result<DocStream> {
+            let documents = self.0.lock().unwrap().clone();
+
+            Ok(Box::pin(tokio_stream::iter(documents.into_iter().map(Ok))))
+        }
+
+        async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+            self.0.lock().unwrap().retain(|document| !ids.contains(&document.id));
+
+            Ok(())
+        }
+
+        async fn stats(&self) -> anyhow::Result<IndexStats> {
+            Ok(IndexStats { documents: self.0.lock().unwrap().len() as u64, ..IndexStats::default() })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chunked_search_engine() -> anyhow::Result<()> {
+        let engine = ChunkedSearchEngine::new(Box::<Memory>::default(), 40, 10)?;
+        assert!(ChunkedSearchEngine::new(Box::<Memory>::default(), 10, 10).is_err());
+        let document = |id: &str, content: &str| Document {
+            id: id.to_string(),
+            source: "docs".to_string(),
+            title: id.to_string(),
+            link: format!("/docs/{}", id),
+            content: content.to_string(),
+            metadata: HashMap::new(),
+            tags: vec![],
+            modified: None,
+        };
+
+        let runbook = "Restart the database. Then check the replication lag. The database must catch up.";
+        let chunks = engine.split(document("runbook", runbook));
+        assert_eq!(chunks.iter().map(|chunk| chunk.id.as_str()).collect::<Vec<_>>(), vec!["runbook", "runbook#chunk-1", "runbook#chunk-2"]);
+        assert_eq!(chunks[1].metadata["chunk_offset"], "30");
+        assert_eq!(chunks[1].metadata["parent_id"], "runbook");
+
+        engine.index(vec![document("runbook", runbook), document("setup", "Install the database")]).await?;
+
+        let results = engine.search(&SearchRequest::new("database")).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+        let mut ids = results.iter().map(|item| item.id.as_str()).collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(ids, vec!["runbook", "setup"]);
+
+        let runbook_document = engine.document("runbook").await?.unwrap();
+        assert_eq!(runbook_document.content, runbook);
+        assert!(runbook_document.metadata.is_empty());
+
+        // A shorter version leaves a chunk behind, listed apart to be removed as stale
+        engine.index(vec![document("runbook", &runbook[..60])]).await?;
+        let mut ids = engine.documents().await?.map(|document| document.unwrap().id).collect::<Vec<_>>().await;
+        ids.sort();
+        assert_eq!(ids, vec!["runbook", "runbook#chunk-2", "setup"]);
+
+        engine.delete(&["runbook".to_string(), "runbook#chunk-2".to_string()]).await?;
+        assert_eq!(engine.stats().await?.documents, 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+use tantivy::tokenizer::{BoxTokenStream, PreTokenizedString, Token, TokenStream, Tokenizer};
+
+/// Extensions of the source code files.
+const CODE_EXTENSIONS: &[&str] = &[
+    "c", "cc", "cpp", "cs", "go", "h", "hpp", "java", "js", "jsx", "kt", "kts", "php", "py", "rb", "rs", "scala", "sh",
+    "swift", "ts", "tsx",
+];
+
+/// Same limit as the default tantivy analyzer: longer tokens are mostly encoded data.
+const MAX_TOKEN_LENGTH: usize = 40;
+
+pub fn is_code(link: &str) -> bool {
+    match link.rsplit_once('.') {
+        Some((_, extension)) => CODE_EXTENSIONS.contains(&extension.to_lowercase().as_str()),
+        None => false,
+    }
+}
+
+/// Splits the identifiers of source code in their words (`IndexWriter`, `index_writer` and
+/// `indexWriter` all give `index` and `writer` at consecutive positions), and adds the joined
+/// words (`indexwriter`) at the position of the first one. The queries tokenized by the default
+/// analyzer thus find an identifier whatever the case it is written in.
+///
+/// Digits stay attached to the letters before them (`utf8`, `base64`) and the tokens are
+/// lowercased.
+#[derive(Clone)]
+pub struct CodeTokenizer;
+
+pub struct CodeTokenStream {
+    tokens: Vec<Token>,
+    current: Option<usize>,
+}
+
+impl Tokenizer for CodeTokenizer {
+    fn token_stream<'a>(&self, text: &'a str) -> BoxTokenStream<'a> {
+        BoxTokenStream::from(CodeTokenStream { tokens: tokenize(text), current: None })
+    }
+}
+
+impl TokenStream for CodeTokenStream {
+    fn advance(&mut self) -> bool {
+        let next = self.current.map_or(0, |current| current + 1);
+        self.current = Some(next);
+
+        next < self.tokens.len()
+    }
+
+    fn token(&self) -> &Token {
+        &self.tokens[self.current.unwrap_or_default()]
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.tokens[self.current.unwrap_or_default()]
+    }
+}
+
+/// The text with its tokens, to index it with the code tokenizer in a field using the default
+/// analyzer.
+pub fn pre_tokenize(text: String) -> PreTokenizedString {
+    let mut tokens = vec![];
+    CodeTokenizer.token_stream(&text).process(&mut |token| tokens.push(token.clone()));
+
+    PreTokenizedString { text, tokens }
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut position = 0;
+    let token = |text: &str, offset_from: usize, offset_to: usize, position: usize| Token {
+        offset_from,
+        offset_to,
+        position,
+        text: text.to_lowercase(),
+        position_length: 1,
+    };
+
+    for (start, identifier) in identifiers(text) {
+        let words = split_identifier(identifier);
+
+        if words.len() > 1 {
+            let joined = words.iter().map(|(from, to)| &identifier[*from..*to]).collect::<String>();
+            tokens.push(token(&joined, start, start + identifier.len(), position));
+        }
+
+        for (from, to) in &words {
+            tokens.push(token(&identifier[*from..*to], start + from, start + to, position));
+            position += 1;
+        }
+    }
+
+    tokens.retain(|token| token.text.len() <= MAX_TOKEN_LENGTH);
+    tokens
+}
+
+/// The runs of alphanumeric characters and underscores, with their offset.
+fn identifiers(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let is_identifier = |c: char| c.is_alphanumeric() || c == '_';
+
+    text.char_indices()
+        .filter(move |(offset, c)| is_identifier(*c) && !text[..*offset].ends_with(is_identifier))
+        .map(move |(start, _)| {
+            let end = text[start..].find(|c: char| !is_identifier(c)).map_or(text.len(), |end| start + end);
+            (start, &text[start..end])
+        })
+}
+
+/// The offsets of the words of the identifier: separated by underscores, or starting with an
+/// uppercase letter following a lowercase letter or a digit (`indexWriter`), or followed by a
+/// lowercase letter at the end of an uppercase run (`HTTPServer`).
+fn split_identifier(identifier: &str) -> Vec<(usize, usize)> {
+    let chars = identifier.char_indices().collect::<Vec<_>>();
+    let mut words = vec![];
+    let mut start: Option<usize> = None;
+
+    for (index, (offset, c)) in chars.iter().copied().enumerate() {
+        if c == '_' {
+            words.extend(start.take().map(|start| (start, offset)));
+            continue;
+        }
+
+        match start {
+            Some(word) => {
+                let previous = chars[index - 1].1;
+                let next_lowercase = chars.get(index + 1).is_some_and(|(_, next)| next.is_lowercase());
+                let boundary = c.is_uppercase()
+                    && (previous.is_lowercase() || previous.is_numeric() || (previous.is_uppercase() && next_lowercase));
+
+                if boundary {
+                    words.push((word, offset));
+                    start = Some(offset);
+                }
+            }
+            None => start = Some(offset),
+        }
+    }
+
+    words.extend(start.map(|start| (start, identifier.len())));
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::search::code_tokenizer::{is_code, pre_tokenize};
+
+    #[test]
+    fn test_code_tokenizer() {
+        let tokens = |text: &str| {
+            pre_tokenize(text.to_string()).tokens.into_iter().map(|token| (token.text, token.position)).collect::<Vec<_>>()
+        };
+        let owned = |tokens: &[(&str, usize)]| tokens.iter().map(|(text, position)| (text.to_string(), *position)).collect::<Vec<_>>();
+
+        assert_eq!(tokens("let w = IndexWriter::new();"), owned(&[("let", 0), ("w", 1), ("indexwriter", 2), ("index", 2), ("writer", 3), ("new", 4)]));
+        assert_eq!(tokens("index_writer"), owned(&[("indexwriter", 0), ("index", 0), ("writer", 1)]));
+        assert_eq!(tokens("HTTPServer utf8 base64Encode __init__"), owned(&[
+            ("httpserver", 0), ("http", 0), ("server", 1),
+            ("utf8", 2),
+            ("base64encode", 3), ("base64", 3), ("encode", 4),
+            ("init", 5),
+        ]));
+
+        assert!(is_code("src/search/tantivy_impl.rs"));
+        assert!(!is_code("docs/README.md"));
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use tokio_stream::StreamExt;
+
+use crate::model::Document;
+use crate::search::{Facets, IndexStats, SearchEngine, SearchExplanation, SearchRequest, SearchResult};
+use crate::sources::DocStream;
+
+/// Metadata holding the SHA-256 of the content of the documents.
+pub const CONTENT_HASH: &str = "content_hash";
+
+/// Metadata holding the links of the copies of a document, one per line.
+pub const DUPLICATE_LINKS: &str = "duplicate_links";
+
+/// Collapses the documents with the same content (vendored files, forked READMEs...) into the
+/// first one indexed, whatever their sources: the copies aren't indexed, their links are added to
+/// the `duplicate_links` metadata of the original. All the documents get their `content_hash`
+/// metadata, which finds the original of a copy in the wrapped engine.
+pub struct DedupedSearchEngine {
+    engine: Box<dyn SearchEngine>,
+}
+
+impl DedupedSearchEngine {
+    pub fn new(engine: Box<dyn SearchEngine>) -> Self {
+        DedupedSearchEngine { engine }
+    }
+
+    /// The indexed document with this content hash, if any.
+    async fn indexed_original(&self, hash: &str) -> anyhow::Result<Option<Document>> {
+        let request = SearchRequest {
+            limit: 1,
+            metadata_filter: BTreeMap::from([(CONTENT_HASH.to_string(), hash.to_string())]),
+            ..SearchRequest::new("")
+        };
+
+        match self.engine.search(&request).await?.next().await.transpose()? {
+            Some(item) => self.engine.document(&item.id).await,
+            None => Ok(None),
+        }
+    }
+}
+
+pub fn content_hash(content: &str) -> String {
+    hex::encode(Sha256::digest(content.as_bytes()))
+}
+
+fn add_link(document: &mut Document, link: &str) {
+    let links = document.metadata.entry(DUPLICATE_LINKS.to_string()).or_default();
+
+    if link != document.link && !links.lines().any(|existing| existing == link) {
+        if !links.is_empty() {
+            links.push('\n');
+        }
+
+        links.push_str(link);
+    }
+}
+
+#[async_trait]
+impl SearchEngine for DedupedSearchEngine {
+    async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+        let mut kept: Vec<Document> = vec![];
+        let mut copies = vec![];
+
+        for mut document in documents {
+            let hash = content_hash(&document.content);
+            document.metadata.insert(CONTENT_HASH.to_string(), hash.clone());
+
+            if document.content.trim().is_empty() {
+                kept.push(document);
+                continue;
+            }
+
+            let original = match kept.iter().position(|kept| kept.metadata.get(CONTENT_HASH) == Some(&hash)) {
+                Some(position) => Some(kept.remove(position)),
+                None => self.indexed_original(&hash).await?,
+            };
+
+            let document = match original {
+                Some(mut original) if original.id != document.id => {
+                    add_link(&mut original, &document.link);
+                    copies.push(document.id);
+                    original
+                }
+                // A new version of the original keeps the links of its copies
+                Some(previous) => {
+                    if let Some(links) = previous.metadata.get(DUPLICATE_LINKS) {
+                        document.metadata.insert(DUPLICATE_LINKS.to_string(), links.clone());
+                    }
+
+                    document
+                }
+                None => document,
+            };
+
+            kept.push(document);
+        }
+
+        self.engine.index(kept).await?;
+
+        // The copies indexed before the deduplication was enabled, or before their original
+        if !copies.is_empty() {
+            if let Err(err) = self.engine.delete(&copies).await {
+                log::debug!("The indexed copies are kept: {:#}", err);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        self.engine.search(request).await
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        self.engine.purge().await
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        self.engine.purge_source(source).await
+    }
+
+    async fn similar(&self, id: &str, limit: usize) -> SearchResult {
+        self.engine.similar(id, limit).await
+    }
+
+    async fn explain(&self, request: &SearchRequest) -> anyhow::Result<SearchExplanation> {
+        self.engine.explain(request).await
+    }
+
+    async fn facets(&self, request: &SearchRequest, metadata_keys: &[String]) -> anyhow::Result<Facets> {
+        self.engine.facets(request, metadata_keys).await
+    }
+
+    async fn suggest(&self, query: &str) -> anyhow::Result<Option<String>> {
+        self.engine.suggest(query).await
+    }
+
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        self.engine.documents().await
+    }
+
+    async fn document(&self, id: &str) -> anyhow::Result<Option<Document>> {
+        self.engine.document(id).await
+    }
+
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        self.engine.delete(ids).await
+    }
+
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        self.engine.stats().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tokio_stream::StreamExt;
+
+    use crate::model::Document;
+    use crate::search::deduped_impl::{DedupedSearchEngine, DUPLICATE_LINKS};
+    use crate::search::tantivy_impl::TantivySearchEngine;
+    use crate::search::{SearchEngine, SearchRequest};
+
+    #[tokio::test]
+    async fn test_deduped_search_engine() -> anyhow::Result<()> {
+        let engine = DedupedSearchEngine::new(Box::new(TantivySearchEngine::in_memory()?));
+        let document = |id: &str, source: &str, content: &str| Document {
+            id: id.to_string(),
+            source: source.to_string(),
+            title: "README.md".to_string(),
+            link: format!("/{}/README.md", id),
+            content: content.to_string(),
+            metadata: HashMap::new(),
+            tags: vec![],
+            modified: None,
+        };
+
+        engine.index(vec![document("api", "github", "Restart the service"), document("fork", "github", "Restart the service")]).await?;
+        engine.index(vec![document("vendored", "docs", "Restart the service"), document("cli", "docs", "Install the cli")]).await?;
+        // Indexing the original again keeps the links of its copies
+        engine.index(vec![document("api", "github", "Restart the service")]).await?;
+
+        let results = engine.search(&SearchRequest::new("restart")).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+        assert_eq!(results.iter().map(|item| item.id.as_str()).collect::<Vec<_>>(), vec!["api"]);
+
+        let original = engine.document("api").await?.expect("The original is indexed");
+        assert_eq!(original.metadata[DUPLICATE_LINKS], "/fork/README.md\n/vendored/README.md");
+        assert_eq!(engine.stats().await?.documents, 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use candle_core::{Device, DType, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config};
+use tokenizers::Tokenizer;
+
+use crate::search::embeddings::{EmbeddingProvider, load_tokenizer};
+
+/// Computes sentence embeddings using a local BERT-like model (e.g. `all-MiniLM-L6-v2`).
+///
+/// The model directory must contain the `config.json`, `tokenizer.json` and `model.safetensors`
+/// files as published on the huggingface hub.
+pub struct LocalEmbedder {
+    model: BertModel,
+    tokenizer: Tokenizer,
+    device: Device,
+}
+
+impl LocalEmbedder {
+    pub fn load<T: AsRef<Path>>(model_dir: T) -> anyhow::Result<Self> {
+        let model_dir = model_dir.as_ref();
+        let device = Device::Cpu;
+
+        let config: Config = serde_json::from_str(
+            &std::fs::read_to_string(model_dir.join("config.json"))
+                .with_context(|| format!("Couldn't read model config in: {:?}", model_dir))?
+        )?;
+
+        let tokenizer = load_tokenizer(model_dir)?;
+
+        // Safety: the weights file is expected not to be modified while the model is loaded
+        let vb = unsafe {
+            VarBuilder::from_mmaped_safetensors(&[model_dir.join("model.safetensors")], DType::F32, &device)?
+        };
+        let model = BertModel::load(vb, &config)?;
+
+        Ok(Self { model, tokenizer, device })
+    }
+}
+
+impl EmbeddingProvider for LocalEmbedder {
+    /// Returns one L2-normalized vector per text (mean pooling over the tokens).
+    fn embed(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let encodings = self.tokenizer
+            .encode_batch(texts.to_vec(), true)
+            .map_err(|err| anyhow!("Couldn't tokenize texts: {}", err))?;
+
+        let tensor = |values: Vec<Vec<u32>>| -> anyhow::Result<Tensor> {
+            let rows = values
+                .into_iter()
+                .map(|row| Tensor::new(row.as_slice(), &self.device))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Tensor::stack(&rows, 0)?)
+        };
+
+        let input_ids = tensor(encodings.iter().map(|e| e.get_ids().to_vec()).collect())?;
+        let type_ids = tensor(encodings.iter().map(|e| e.get_type_ids().to_vec()).collect())?;
+        let attention_mask = tensor(encodings.iter().map(|e| e.get_attention_mask().to_vec()).collect())?;
+
+        let output = self.model.forward(&input_ids, &type_ids, Some(&attention_mask))?;
+
+        // Mean pooling ignoring padding tokens
+        let mask = attention_mask.to_dtype(DType::F32)?.unsqueeze(2)?;
+        let summed = output.broadcast_mul(&mask)?.sum(1)?;
+        let counts = mask.sum(1)?;
+        let pooled = summed.broadcast_div(&counts)?;
+
+        let norms = pooled.sqr()?.sum_keepdim(1)?.sqrt()?;
+        let normalized = pooled.broadcast_div(&norms)?;
+
+        Ok(normalized.to_vec2::<f32>()?)
+    }
+}
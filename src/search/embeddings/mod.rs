@@ -0,0 +1,78 @@
+use std::path::Path;
+
+use anyhow::anyhow;
+use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
+
+/// Computes the embeddings of texts, used by the vector search engines.
+///
+/// Implementations may block (model inference, HTTP calls) so they must be called from a
+/// blocking task (e.g. `tokio::task::spawn_blocking`).
+pub trait EmbeddingProvider: Send + Sync {
+    /// Returns one vector per text, in the same order.
+    fn embed(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>>;
+}
+
+/// Loads the `tokenizer.json` of a model directory, padding the batches and truncating texts to
+/// the maximum length supported by BERT-like models.
+fn load_tokenizer(model_dir: &Path) -> anyhow::Result<Tokenizer> {
+    let mut tokenizer = Tokenizer::from_file(model_dir.join("tokenizer.json"))
+        .map_err(|err| anyhow!("Couldn't load tokenizer: {}", err))?;
+
+    tokenizer
+        .with_padding(Some(PaddingParams::default()))
+        .with_truncation(Some(TruncationParams { max_length: 512, ..Default::default() }))
+        .map_err(|err| anyhow!("Couldn't configure tokenizer: {}", err))?;
+
+    Ok(tokenizer)
+}
+
+/// Averages the token vectors of each text ignoring padding, then L2-normalizes the result.
+///
+/// `hidden` is a `[texts, tokens, dimension]` row-major tensor and `mask` the `[texts, tokens]`
+/// attention mask.
+fn mean_pooling(hidden: &[f32], mask: &[Vec<u32>], dimension: usize) -> Vec<Vec<f32>> {
+    let tokens = mask.first().map(|m| m.len()).unwrap_or_default();
+
+    mask.iter()
+        .enumerate()
+        .map(|(text, mask)| {
+            let mut pooled = vec![0.0; dimension];
+            let mut count = 0.0;
+
+            for (token, _) in mask.iter().enumerate().filter(|(_, m)| **m > 0) {
+                let offset = (text * tokens + token) * dimension;
+
+                for (value, hidden) in pooled.iter_mut().zip(&hidden[offset..offset + dimension]) {
+                    *value += hidden;
+                }
+                count += 1.0;
+            }
+
+            let norm = pooled.iter().map(|v| (v / count) * (v / count)).sum::<f32>().sqrt();
+
+            pooled
+                .into_iter()
+                .map(|v| if norm > 0.0 { v / count / norm } else { 0.0 })
+                .collect()
+        })
+        .collect()
+}
+
+pub mod local;
+pub mod onnx;
+pub mod openai;
+
+#[cfg(test)]
+mod tests {
+    use crate::search::embeddings::mean_pooling;
+
+    #[test]
+    fn test_mean_pooling() {
+        // 1 text, 3 tokens (the last one is padding), dimension 2
+        let hidden = vec![1.0, 0.0, 3.0, 0.0, 100.0, 100.0];
+
+        let pooled = mean_pooling(&hidden, &[vec![1, 1, 0]], 2);
+
+        assert_eq!(pooled, vec![vec![1.0, 0.0]]);
+    }
+}
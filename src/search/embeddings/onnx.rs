@@ -0,0 +1,79 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Context};
+use ort::session::Session;
+use ort::value::{DynValue, Tensor};
+use tokenizers::Tokenizer;
+
+use crate::search::embeddings::{EmbeddingProvider, load_tokenizer, mean_pooling};
+
+/// Computes sentence embeddings using a local ONNX export of a BERT-like model (e.g. the `onnx`
+/// folder of `all-MiniLM-L6-v2` on the huggingface hub).
+///
+/// The model directory must contain the `model.onnx` and `tokenizer.json` files. The ONNX runtime
+/// library is loaded at runtime from `ORT_DYLIB_PATH` (or `libonnxruntime` in the library path).
+pub struct OnnxEmbedder {
+    session: Mutex<Session>,
+    tokenizer: Tokenizer,
+    with_type_ids: bool,
+}
+
+impl OnnxEmbedder {
+    pub fn load<T: AsRef<Path>>(model_dir: T) -> anyhow::Result<Self> {
+        let model_dir = model_dir.as_ref();
+
+        let session = Session::builder()?
+            .commit_from_file(model_dir.join("model.onnx"))
+            .with_context(|| format!("Couldn't load onnx model in: {:?}", model_dir))?;
+
+        // Some exports (e.g. distilbert based models) don't take token type ids
+        let with_type_ids = session.inputs.iter().any(|input| input.name == "token_type_ids");
+
+        Ok(Self {
+            session: Mutex::new(session),
+            tokenizer: load_tokenizer(model_dir)?,
+            with_type_ids,
+        })
+    }
+}
+
+impl EmbeddingProvider for OnnxEmbedder {
+    /// Returns one L2-normalized vector per text (mean pooling over the tokens).
+    fn embed(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let encodings = self.tokenizer
+            .encode_batch(texts.to_vec(), true)
+            .map_err(|err| anyhow!("Couldn't tokenize texts: {}", err))?;
+
+        let shape = [encodings.len(), encodings[0].get_ids().len()];
+        let tensor = |values: Vec<i64>| -> anyhow::Result<DynValue> {
+            Ok(Tensor::from_array((shape, values))?.into_dyn())
+        };
+
+        let mask = encodings.iter().map(|e| e.get_attention_mask().to_vec()).collect::<Vec<_>>();
+
+        let mut inputs = vec![
+            ("input_ids", tensor(encodings.iter().flat_map(|e| e.get_ids()).map(|v| *v as i64).collect())?),
+            ("attention_mask", tensor(mask.iter().flatten().map(|v| *v as i64).collect())?),
+        ];
+
+        if self.with_type_ids {
+            inputs.push(("token_type_ids", tensor(encodings.iter().flat_map(|e| e.get_type_ids()).map(|v| *v as i64).collect())?));
+        }
+
+        let mut session = self.session.lock().unwrap();
+        let outputs = session.run(inputs)?;
+
+        // The first output is the last hidden state: [texts, tokens, dimension]
+        let (output_shape, hidden) = outputs[0].try_extract_tensor::<f32>()?;
+        let dimension = *output_shape
+            .last()
+            .with_context(|| format!("Unexpected onnx model output shape: {:?}", output_shape))? as usize;
+
+        Ok(mean_pooling(hidden, &mask, dimension))
+    }
+}
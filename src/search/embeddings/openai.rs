@@ -0,0 +1,112 @@
+use anyhow::Context;
+use reqwest::Client;
+use serde_json::{json, Value};
+use tokio::runtime::Handle;
+
+use crate::search::embeddings::EmbeddingProvider;
+use crate::utils::json::get_array;
+
+/// Computes embeddings using an OpenAI compatible `/embeddings` endpoint (OpenAI, Azure, Ollama,
+/// vLLM, text-embeddings-inference...).
+pub struct OpenAiEmbedder {
+    client: Client,
+    runtime: Handle,
+    endpoint: String,
+    model: String,
+    api_key: Option<String>,
+    dimensions: Option<usize>,
+}
+
+impl OpenAiEmbedder {
+    /// Must be created from within the tokio runtime that will run the requests.
+    pub fn new(endpoint: &str, model: &str, api_key: Option<String>, dimensions: Option<usize>) -> Self {
+        Self {
+            client: Client::new(),
+            runtime: Handle::current(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            api_key,
+            dimensions,
+        }
+    }
+
+    async fn request(&self, texts: &[String]) -> anyhow::Result<Value> {
+        let mut body = json!({ "model": self.model, "input": texts });
+
+        if let Some(dimensions) = self.dimensions {
+            body["dimensions"] = json!(dimensions);
+        }
+
+        let request = self.client.post(format!("{}/embeddings", self.endpoint)).json(&body);
+        let request = match &self.api_key {
+            Some(api_key) => request.bearer_auth(api_key),
+            None => request,
+        };
+
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Couldn't reach embeddings endpoint: {}", self.endpoint))?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(response)
+    }
+}
+
+impl EmbeddingProvider for OpenAiEmbedder {
+    fn embed(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+
+        parse_embeddings(&self.runtime.block_on(self.request(texts))?)
+    }
+}
+
+/// Returns the embeddings ordered by the index of their input.
+fn parse_embeddings(response: &Value) -> anyhow::Result<Vec<Vec<f32>>> {
+    let mut embeddings = get_array(response, &["data"])?
+        .iter()
+        .map(|item| {
+            let index = item.get("index").and_then(|i| i.as_u64()).unwrap_or_default();
+            let embedding = item.get("embedding")
+                .and_then(|e| e.as_array())
+                .with_context(|| format!("Embedding not found in: {}", item))?
+                .iter()
+                .map(|v| v.as_f64().map(|v| v as f32).with_context(|| format!("Invalid embedding value: {}", v)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            Ok((index, embedding))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    embeddings.sort_by_key(|(index, _)| *index);
+
+    Ok(embeddings.into_iter().map(|(_, embedding)| embedding).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::search::embeddings::openai::parse_embeddings;
+
+    #[test]
+    fn test_parse_embeddings() -> anyhow::Result<()> {
+        let response = json!({
+            "object": "list",
+            "data": [
+                { "object": "embedding", "index": 1, "embedding": [0.5, 0.5] },
+                { "object": "embedding", "index": 0, "embedding": [1.0, 0.0] }
+            ],
+            "model": "text-embedding-3-small"
+        });
+
+        assert_eq!(parse_embeddings(&response)?, vec![vec![1.0, 0.0], vec![0.5, 0.5]]);
+        assert!(parse_embeddings(&json!({ "error": "bad request" })).is_err());
+
+        Ok(())
+    }
+}
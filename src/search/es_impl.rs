@@ -0,0 +1,560 @@
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder};
+use serde_json::{json, Value};
+use tokio::sync::OnceCell;
+
+use crate::model::Document;
+use crate::search::{FoundItem, IndexStats, SearchEngine, SearchRequest, SearchResult, SortOrder};
+use crate::sources::DocStream;
+use crate::utils::glob::glob_to_regex;
+use crate::utils::json::get_array;
+use crate::utils::streams::channel_stream;
+
+/// Number of documents fetched per request when listing them.
+pub(crate) const DOCUMENTS_PAGE_SIZE: usize = 1000;
+
+pub struct ElasticSearchEngine {
+    client: Client,
+    endpoint: String,
+    index: String,
+    credentials: Option<(String, String)>,
+    index_created: OnceCell<()>,
+}
+
+impl ElasticSearchEngine {
+    pub fn new(endpoint: &str, index: &str, credentials: Option<(String, String)>) -> Self {
+        Self {
+            client: Client::new(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            index: index.to_string(),
+            credentials,
+            index_created: OnceCell::new(),
+        }
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> RequestBuilder {
+        let request = self.client.request(method, format!("{}/{}", self.endpoint, path));
+
+        match &self.credentials {
+            Some((username, password)) => request.basic_auth(username, Some(password)),
+            None => request,
+        }
+    }
+
+    async fn ensure_index(&self) -> anyhow::Result<()> {
+        let exists = self.request(reqwest::Method::HEAD, &self.index).send().await?;
+
+        // A denied request (401, 403...) doesn't mean that the index is missing
+        if exists.status() != reqwest::StatusCode::NOT_FOUND {
+            exists
+                .error_for_status()
+                .with_context(|| format!("Couldn't check whether elasticsearch index {} exists", self.index))?;
+
+            return Ok(());
+        }
+
+        log::info!("Creating elasticsearch index: {}", &self.index);
+
+        self.request(reqwest::Method::PUT, &self.index)
+            .json(&index_mapping())
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Couldn't create elasticsearch index: {}", self.index))?;
+
+        Ok(())
+    }
+
+    async fn delete_by_query(&self, body: &Value) -> anyhow::Result<()> {
+        let response = self.request(reqwest::Method::POST, &format!("{}/_delete_by_query?refresh=true", self.index))
+            .json(body)
+            .send()
+            .await?;
+
+        if response.status() != reqwest::StatusCode::NOT_FOUND {
+            response.error_for_status()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SearchEngine for ElasticSearchEngine {
+    async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+        self.index_created.get_or_try_init(|| self.ensure_index()).await?;
+
+        let response: Value = self.request(reqwest::Method::POST, "_bulk")
+            .header("Content-Type", "application/x-ndjson")
+            .body(bulk_body(&self.index, &documents)?)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        check_bulk_response(&response)
+    }
+
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        let http_request = self.request(reqwest::Method::POST, &format!("{}/_search", self.index))
+            .json(&search_body(request, "")?);
+
+        let stream = channel_stream(|tx| async move {
+            let response: Value = http_request
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            for item in parse_hits(&response)? {
+                tx.send(Ok(item)).await?;
+            }
+
+            Ok(())
+        });
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        self.delete_by_query(&delete_by_query_body(None)).await
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        self.delete_by_query(&delete_by_query_body(Some(source))).await
+    }
+
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        let mut documents = vec![];
+        let mut after = None;
+
+        loop {
+            let response = self.request(reqwest::Method::POST, &format!("{}/_search", self.index))
+                .json(&documents_body(after.as_deref()))
+                .send()
+                .await?;
+
+            // Nothing was indexed yet
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                break;
+            }
+
+            let page = parse_documents(&response.error_for_status()?.json().await?)?;
+            let last_page = page.len() < DOCUMENTS_PAGE_SIZE;
+            after = page.last().map(|document| document.id.clone());
+            documents.extend(page.into_iter().map(Ok));
+
+            if last_page {
+                break;
+            }
+        }
+
+        Ok(Box::pin(tokio_stream::iter(documents)))
+    }
+
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        self.delete_by_query(&delete_ids_body(ids)).await
+    }
+
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        let response: Value = self.request(reqwest::Method::POST, &format!("{}/_search", self.index))
+            .json(&stats_body())
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        parse_stats(&response)
+    }
+}
+
+pub(crate) fn index_mapping() -> Value {
+    json!({
+        "mappings": {
+            "properties": {
+                "id": { "type": "keyword" },
+                "source": { "type": "keyword" },
+                "title": { "type": "text", "fields": { "keyword": { "type": "keyword", "ignore_above": 256 } } },
+                "link": { "type": "keyword" },
+                "content": { "type": "text" },
+                "metadata": { "type": "flattened" },
+                "modified": { "type": "date" },
+                "tags": { "type": "keyword" }
+            }
+        }
+    })
+}
+
+/// Builds the NDJSON payload of the `_bulk` API. Documents are indexed under their id.
+pub(crate) fn bulk_body(index: &str, documents: &[Document]) -> anyhow::Result<String> {
+    let mut body = String::new();
+
+    for document in documents {
+        log::info!("Indexing document: {} (source: {})", document.link, document.source);
+
+        body.push_str(&serde_json::to_string(&json!({ "index": { "_index": index, "_id": document.id } }))?);
+        body.push('\n');
+        body.push_str(&serde_json::to_string(document)?);
+        body.push('\n');
+    }
+
+    Ok(body)
+}
+
+pub(crate) fn check_bulk_response(response: &Value) -> anyhow::Result<()> {
+    if response.get("errors").and_then(|e| e.as_bool()).unwrap_or(false) {
+        let first_error = get_array(response, &["items"])?
+            .iter()
+            .filter_map(|item| item.pointer("/index/error"))
+            .next()
+            .cloned()
+            .unwrap_or(Value::Null);
+
+        bail!("Bulk indexing failed for some documents. First error: {}", first_error);
+    }
+
+    Ok(())
+}
+
+/// `keyword_suffix` is appended to the metadata fields to reach their exact (keyword) values.
+pub(crate) fn search_body(request: &SearchRequest, keyword_suffix: &str) -> anyhow::Result<Value> {
+    let (parsed, request) = request.parse_query()?;
+    let mut query = json!({
+        "bool": {
+            "must": {
+                "query_string": {
+                    "query": parsed.query_string(),
+                    "fields": ["title", "content"]
+                }
+            }
+        }
+    });
+
+    // The query string syntax only makes the terms suffixed by `~` fuzzy: the words of the query
+    // are also matched with typos in a separate clause
+    if let Some(distance) = request.fuzzy {
+        let exact = query["bool"]["must"].take();
+        let fuzzy = json!({ "multi_match": { "query": parsed.words(), "fields": ["title", "content"], "fuzziness": distance } });
+        query["bool"]["must"] = json!({ "bool": { "should": [exact, fuzzy] } });
+    }
+
+    let mut filters = vec![];
+
+    if !request.source_filter.is_empty() {
+        filters.push(json!({ "terms": { "source": request.source_filter } }));
+    }
+
+    for (key, value) in &request.metadata_filter {
+        filters.push(json!({ "term": { format!("metadata.{}{}", key, keyword_suffix): value } }));
+    }
+
+    if let Some(path) = &request.path_filter {
+        filters.push(json!({ "regexp": { format!("metadata.path{}", keyword_suffix): glob_to_regex(path) } }));
+    }
+
+    for tag in &request.tag_filter {
+        filters.push(json!({ "term": { "tags": tag } }));
+    }
+
+    if !request.id_filter.is_empty() {
+        filters.push(json!({ "terms": { "id": request.id_filter } }));
+    }
+
+    if let Some(since) = request.modified_since {
+        filters.push(json!({ "range": { "modified": { "gte": since.to_rfc3339() } } }));
+    }
+
+    if !filters.is_empty() {
+        query["bool"]["filter"] = json!(filters);
+    }
+
+    if !request.source_boosts.is_empty() {
+        let functions = request.source_boosts
+            .iter()
+            .map(|(source, boost)| json!({ "filter": { "term": { "source": source } }, "weight": boost }))
+            .collect::<Vec<_>>();
+
+        query = json!({
+            "function_score": { "query": query, "functions": functions, "score_mode": "first", "boost_mode": "multiply" }
+        });
+    }
+
+    let mut body = json!({
+        "from": request.offset,
+        "size": request.limit,
+        "query": query,
+        "highlight": {
+            "encoder": "html",
+            "fields": { "content": {} }
+        }
+    });
+
+    // The indexes created before the dates and the title keywords were indexed don't map the fields
+    match request.sort {
+        SortOrder::Relevance => {}
+        SortOrder::Date => {
+            body["sort"] = json!([{ "modified": { "order": "desc", "missing": "_last", "unmapped_type": "date" } }, "_score"]);
+        }
+        SortOrder::Title => body["sort"] = json!([{ "title.keyword": { "order": "asc", "unmapped_type": "keyword" } }, "_score"]),
+        SortOrder::Source => body["sort"] = json!([{ "source": "asc" }, "_score"]),
+    }
+
+    Ok(body)
+}
+
+/// Matches all the documents, or only the ones of a source.
+pub(crate) fn delete_by_query_body(source: Option<&str>) -> Value {
+    match source {
+        Some(source) => json!({ "query": { "term": { "source": source } } }),
+        None => json!({ "query": { "match_all": {} } }),
+    }
+}
+
+/// Matches the documents with the given ids.
+pub(crate) fn delete_ids_body(ids: &[String]) -> Value {
+    json!({ "query": { "terms": { "id": ids } } })
+}
+
+/// A page of all the documents sorted by id, starting after the id of the last document of the
+/// previous page (`search_after`, not limited to the first 10000 documents like `from`).
+pub(crate) fn documents_body(after: Option<&str>) -> Value {
+    let mut body = json!({ "size": DOCUMENTS_PAGE_SIZE, "query": { "match_all": {} }, "sort": [{ "id": "asc" }] });
+
+    if let Some(after) = after {
+        body["search_after"] = json!([after]);
+    }
+
+    body
+}
+
+/// The documents of the hits, as indexed by `bulk_body`.
+pub(crate) fn parse_documents(response: &Value) -> anyhow::Result<Vec<Document>> {
+    get_array(response, &["hits", "hits"])?
+        .iter()
+        .map(|hit| {
+            let source = hit.get("_source").with_context(|| format!("No source in hit: {}", hit))?;
+            serde_json::from_value(source.clone()).with_context(|| format!("Unexpected document in hit: {}", hit))
+        })
+        .collect()
+}
+
+/// Counts the documents per source with a terms aggregation (no hits are returned).
+pub(crate) fn stats_body() -> Value {
+    json!({
+        "size": 0,
+        "track_total_hits": true,
+        "aggs": {
+            "sources": { "terms": { "field": "source", "size": 1000 } }
+        }
+    })
+}
+
+pub(crate) fn parse_stats(response: &Value) -> anyhow::Result<IndexStats> {
+    let sources = get_array(response, &["aggregations", "sources", "buckets"])?
+        .iter()
+        .map(|bucket| {
+            let source = bucket.get("key").and_then(|k| k.as_str());
+            let count = bucket.get("doc_count").and_then(|c| c.as_u64());
+
+            match (source, count) {
+                (Some(source), Some(count)) => Ok((source.to_string(), count)),
+                _ => bail!("Unexpected aggregation bucket: {}", bucket),
+            }
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok(IndexStats {
+        documents: response.pointer("/hits/total/value").and_then(|t| t.as_u64()).unwrap_or_default(),
+        sources,
+    })
+}
+
+pub(crate) fn parse_hits(response: &Value) -> anyhow::Result<Vec<FoundItem>> {
+    get_array(response, &["hits", "hits"])?
+        .iter()
+        .map(|hit| {
+            let field = |name: &str| -> anyhow::Result<String> {
+                hit.pointer(&format!("/_source/{}", name))
+                    .and_then(|f| f.as_str())
+                    .map(|f| f.to_string())
+                    .with_context(|| format!("Field {} of type text not found in hit: {}", name, hit))
+            };
+
+            let snippet = match hit.pointer("/highlight/content").and_then(|h| h.as_array()) {
+                Some(fragments) => fragments
+                    .iter()
+                    .filter_map(|f| f.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ... "),
+                None => String::new(),
+            };
+
+            Ok(FoundItem {
+                id: field("id")?,
+                score: hit.get("_score").and_then(|s| s.as_f64()).unwrap_or_default() as f32,
+                source: field("source")?,
+                title: field("title")?,
+                link: field("link")?,
+                snippet,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+
+    use crate::model::Document;
+    use crate::search::es_impl::{bulk_body, documents_body, parse_documents, parse_hits, parse_stats, search_body};
+    use crate::search::{SearchRequest, SortOrder};
+
+    #[test]
+    fn test_bulk_body() -> anyhow::Result<()> {
+        let document = Document {
+            id: "1".to_string(),
+            source: "src".to_string(),
+            title: "Hello".to_string(),
+            link: "link1".to_string(),
+            content: "Hello content".to_string(),
+            metadata: HashMap::new(),
+            tags: vec![],
+            modified: None,
+        };
+
+        let body = bulk_body("doks", &[document])?;
+        let lines = body.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(lines[0])?, json!({ "index": { "_index": "doks", "_id": "1" } }));
+        assert!(body.ends_with('\n'));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_hits() -> anyhow::Result<()> {
+        let response = json!({
+            "hits": {
+                "hits": [{
+                    "_id": "1",
+                    "_score": 1.5,
+                    "_source": { "id": "1", "source": "src", "title": "Hello", "link": "link1", "content": "Hello content" },
+                    "highlight": { "content": ["<em>Hello</em> content"] }
+                }]
+            }
+        });
+
+        let hits = parse_hits(&response)?;
+
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].title, "Hello");
+        assert_eq!(hits[0].score, 1.5);
+        assert_eq!(hits[0].snippet, "<em>Hello</em> content");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_documents() -> anyhow::Result<()> {
+        assert!(documents_body(None).get("search_after").is_none());
+        assert_eq!(documents_body(Some("runbook"))["search_after"], json!(["runbook"]));
+
+        let response = json!({
+            "hits": {
+                "hits": [{
+                    "_id": "1",
+                    "_source": { "id": "1", "source": "src", "title": "Hello", "link": "link1", "content": "Hello content", "metadata": { "repository": "doks" } }
+                }]
+            }
+        });
+
+        let documents = parse_documents(&response)?;
+        assert_eq!(documents.iter().map(|document| document.id.as_str()).collect::<Vec<_>>(), vec!["1"]);
+        assert_eq!(documents[0].metadata.get("repository").map(String::as_str), Some("doks"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_stats() -> anyhow::Result<()> {
+        let response = json!({
+            "hits": { "total": { "value": 3, "relation": "eq" }, "hits": [] },
+            "aggregations": {
+                "sources": {
+                    "buckets": [{ "key": "github", "doc_count": 2 }, { "key": "fs", "doc_count": 1 }]
+                }
+            }
+        });
+
+        let stats = parse_stats(&response)?;
+
+        assert_eq!(stats.documents, 3);
+        assert_eq!(stats.sources.get("github"), Some(&2));
+        assert_eq!(stats.sources.get("fs"), Some(&1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_body() -> anyhow::Result<()> {
+        let request = SearchRequest {
+            limit: 5,
+            offset: 10,
+            source_filter: vec!["github".to_string()],
+            metadata_filter: BTreeMap::from([("lang".to_string(), "rust".to_string())]),
+            path_filter: Some("docs/*.md".to_string()),
+            tag_filter: vec!["ops".to_string()],
+            id_filter: vec!["1".to_string()],
+            ..SearchRequest::new("hello")
+        };
+
+        let body = search_body(&request, "")?;
+
+        assert_eq!(body["from"], 10);
+        assert_eq!(body["size"], 5);
+        assert_eq!(body["highlight"]["encoder"], "html");
+        assert_eq!(
+            body["query"]["bool"]["filter"],
+            json!([
+                { "terms": { "source": ["github"] } },
+                { "term": { "metadata.lang": "rust" } },
+                { "regexp": { "metadata.path": "docs/[^/]*\\.md" } },
+                { "term": { "tags": "ops" } },
+                { "terms": { "id": ["1"] } },
+            ])
+        );
+        assert!(search_body(&SearchRequest::new("hello"), "")?["query"]["bool"].get("filter").is_none());
+
+        let body = search_body(&SearchRequest { fuzzy: Some(2), ..SearchRequest::new("kuberentes") }, "")?;
+        assert_eq!(body["query"]["bool"]["must"]["bool"]["should"][1]["multi_match"]["fuzziness"], 2);
+
+        // The filters of the query join the ones of the request
+        let body = search_body(&SearchRequest::new("source:confluence title:\"release process\" -archived"), "")?;
+        assert_eq!(body["query"]["bool"]["must"]["query_string"]["query"], "title:\"release process\" -archived");
+        assert_eq!(body["query"]["bool"]["filter"], json!([{ "terms": { "source": ["confluence"] } }]));
+
+        let request = SearchRequest { source_boosts: BTreeMap::from([("docs".to_string(), 2.0)]), ..SearchRequest::new("hello") };
+        let body = search_body(&request, "")?;
+        assert_eq!(body["query"]["function_score"]["functions"], json!([{ "filter": { "term": { "source": "docs" } }, "weight": 2.0 }]));
+        assert!(body["query"]["function_score"]["query"]["bool"]["must"].is_object());
+
+        let since = Utc.ymd(2024, 1, 1).and_hms(0, 0, 0);
+        let body = search_body(&SearchRequest { modified_since: Some(since), sort: SortOrder::Date, ..SearchRequest::new("hello") }, "")?;
+        assert_eq!(body["query"]["bool"]["filter"], json!([{ "range": { "modified": { "gte": "2024-01-01T00:00:00+00:00" } } }]));
+        assert_eq!(body["sort"][1], "_score");
+        assert!(search_body(&SearchRequest::new("hello"), "")?.get("sort").is_none());
+
+        let body = search_body(&SearchRequest { sort: SortOrder::Source, ..SearchRequest::new("hello") }, "")?;
+        assert_eq!(body["sort"], json!([{ "source": "asc" }, "_score"]));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::{BTreeMap, HashMap};
+
+use async_trait::async_trait;
+use tokio_stream::StreamExt;
+
+use crate::model::Document;
+use crate::search::{FoundItem, IndexStats, SearchEngine, SearchExplanation, SearchRequest, SearchResult};
+use crate::sources::DocStream;
+use crate::utils::streams::channel_stream;
+
+/// Indexes documents in both a keyword and a vector engine and fuses their results at query time
+/// using reciprocal rank fusion.
+pub struct HybridSearchEngine {
+    pub keyword: Box<dyn SearchEngine>,
+    pub vector: Box<dyn SearchEngine>,
+    /// Ranking constant of the fusion. Higher values reduce the weight of the top ranks.
+    pub k: u32,
+}
+
+#[async_trait]
+impl SearchEngine for HybridSearchEngine {
+    async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+        self.keyword.index(documents.clone()).await?;
+        self.vector.index(documents).await
+    }
+
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        // Both engines return their top results which are fused, boosted and then paginated
+        let candidates = SearchRequest {
+            limit: request.offset + request.limit,
+            offset: 0,
+            source_boosts: BTreeMap::new(),
+            ..request.clone()
+        };
+        let keyword = self.keyword.search(&candidates).await?;
+        let vector = self.vector.search(&candidates).await?;
+        let (offset, limit) = (request.offset, request.limit);
+        let k = self.k as f32;
+        let boosts = request.source_boosts.clone();
+
+        let stream = channel_stream(move |tx| async move {
+            let (keyword, vector) = tokio::join!(
+                keyword.collect::<anyhow::Result<Vec<_>>>(),
+                vector.collect::<anyhow::Result<Vec<_>>>(),
+            );
+
+            for item in reciprocal_rank_fusion(vec![keyword?, vector?], k, &boosts, offset + limit).into_iter().skip(offset) {
+                tx.send(Ok(item)).await?;
+            }
+
+            Ok(())
+        });
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        self.keyword.purge().await?;
+        self.vector.purge().await
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        self.keyword.purge_source(source).await?;
+        self.vector.purge_source(source).await
+    }
+
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        self.keyword.documents().await
+    }
+
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        self.keyword.delete(ids).await?;
+        self.vector.delete(ids).await
+    }
+
+    async fn explain(&self, request: &SearchRequest) -> anyhow::Result<SearchExplanation> {
+        self.keyword.explain(request).await
+    }
+
+    async fn similar(&self, id: &str, limit: usize) -> SearchResult {
+        self.keyword.similar(id, limit).await
+    }
+
+    /// Both engines hold the same documents: the keyword one is usually the cheapest to count.
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        self.keyword.stats().await
+    }
+}
+
+/// Scores every document with the sum of `1 / (k + rank)` over the result lists it appears in,
+/// multiplied by the boost of its source. The item of the first list containing a document is kept
+/// (so its snippet is used).
+fn reciprocal_rank_fusion(lists: Vec<Vec<FoundItem>>, k: f32, boosts: &BTreeMap<String, f32>, limit: usize) -> Vec<FoundItem> {
+    let mut fused = HashMap::<String, (f32, FoundItem)>::new();
+
+    for list in lists {
+        for (rank, item) in list.into_iter().enumerate() {
+            let score = 1.0 / (k + (rank + 1) as f32);
+
+            fused
+                .entry(item.id.clone())
+                .and_modify(|(total, _)| *total += score)
+                .or_insert((score, item));
+        }
+    }
+
+    let mut fused = fused
+        .into_values()
+        .map(|(score, item)| (score * boosts.get(&item.source).copied().unwrap_or(1.0), item))
+        .collect::<Vec<_>>();
+    fused.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    fused
+        .into_iter()
+        .take(limit)
+        .map(|(score, item)| FoundItem { score, ..item })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::search::FoundItem;
+    use crate::search::hybrid_impl::reciprocal_rank_fusion;
+
+    fn item(id: &str, snippet: &str) -> FoundItem {
+        FoundItem {
+            id: id.to_string(),
+            score: 0.0,
+            source: id.to_string(),
+            title: id.to_string(),
+            link: id.to_string(),
+            snippet: snippet.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_reciprocal_rank_fusion() {
+        let keyword = vec![item("a", "keyword a"), item("b", "keyword b")];
+        let vector = vec![item("c", "vector c"), item("b", "vector b")];
+
+        let fused = reciprocal_rank_fusion(vec![keyword.clone(), vector.clone()], 60.0, &BTreeMap::new(), 10);
+        let ids = fused.iter().map(|i| i.id.as_str()).collect::<Vec<_>>();
+
+        assert_eq!(ids[0], "b");
+        assert_eq!(fused[0].snippet, "keyword b");
+        assert_eq!(fused.len(), 3);
+
+        let boosts = BTreeMap::from([("c".to_string(), 3.0)]);
+        let fused = reciprocal_rank_fusion(vec![keyword, vector], 60.0, &boosts, 10);
+        assert_eq!(fused.iter().map(|i| i.id.as_str()).collect::<Vec<_>>(), vec!["c", "b", "a"]);
+    }
+}
@@ -0,0 +1,84 @@
+use tantivy::tokenizer::{Language, LowerCaser, RemoveLongFilter, SimpleTokenizer, Stemmer, TextAnalyzer};
+use whatlang::Lang;
+
+/// The languages tantivy has a stemmer for, with their ISO 639-1 code.
+const LANGUAGES: &[(Lang, Language, &str)] = &[
+    (Lang::Ara, Language::Arabic, "ar"),
+    (Lang::Dan, Language::Danish, "da"),
+    (Lang::Deu, Language::German, "de"),
+    (Lang::Ell, Language::Greek, "el"),
+    (Lang::Eng, Language::English, "en"),
+    (Lang::Spa, Language::Spanish, "es"),
+    (Lang::Fin, Language::Finnish, "fi"),
+    (Lang::Fra, Language::French, "fr"),
+    (Lang::Hun, Language::Hungarian, "hu"),
+    (Lang::Ita, Language::Italian, "it"),
+    (Lang::Nld, Language::Dutch, "nl"),
+    (Lang::Nob, Language::Norwegian, "no"),
+    (Lang::Por, Language::Portuguese, "pt"),
+    (Lang::Ron, Language::Romanian, "ro"),
+    (Lang::Rus, Language::Russian, "ru"),
+    (Lang::Swe, Language::Swedish, "sv"),
+    (Lang::Tam, Language::Tamil, "ta"),
+    (Lang::Tur, Language::Turkish, "tr"),
+];
+
+/// The stemmer language by name (`french`) or code (`fr`).
+pub fn stemmer(name: &str) -> Option<Language> {
+    LANGUAGES
+        .iter()
+        .find(|(_, language, code)| *code == name || format!("{:?}", language).eq_ignore_ascii_case(name))
+        .map(|(_, language, _)| *language)
+}
+
+/// The codes of the languages with a stemmer.
+pub fn stemmed_languages() -> impl Iterator<Item = &'static str> {
+    LANGUAGES.iter().map(|(_, _, code)| *code)
+}
+
+/// The code of the language of the text, when it is reliably detected and has a stemmer.
+pub fn detect(text: &str) -> Option<&'static str> {
+    let info = whatlang::detect(text).filter(|info| info.is_reliable())?;
+
+    LANGUAGES.iter().find(|(lang, _, _)| *lang == info.lang()).map(|(_, _, code)| *code)
+}
+
+/// The name the stemming analyzer of the language is registered under.
+pub fn analyzer_name(code: &str) -> String {
+    format!("{}_stem", code)
+}
+
+/// The default analyzer followed by the stemmer of the language.
+pub fn analyzer(code: &str) -> Option<TextAnalyzer> {
+    let (_, language, _) = LANGUAGES.iter().find(|(_, _, other)| *other == code)?;
+
+    Some(
+        TextAnalyzer::from(SimpleTokenizer)
+            .filter(RemoveLongFilter::limit(40))
+            .filter(LowerCaser)
+            .filter(Stemmer::new(*language)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::tokenizer::Language;
+
+    use crate::search::language::{analyzer, detect, stemmer};
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect("Redémarrez la base de données puis vérifiez le retard de réplication des répliques."), Some("fr"));
+        assert_eq!(detect("Starten Sie die Datenbank neu und prüfen Sie anschließend die Verzögerung der Replikation."), Some("de"));
+        assert_eq!(detect("Restart the database, then check the replication lag of the replicas."), Some("en"));
+        assert_eq!(detect("42"), None);
+
+        let mut tokens = vec![];
+        analyzer("fr").unwrap().token_stream("répliques").process(&mut |token| tokens.push(token.text.clone()));
+        assert_eq!(tokens, vec!["répliqu"]);
+
+        assert_eq!(stemmer("French"), Some(Language::French));
+        assert_eq!(stemmer("de"), Some(Language::German));
+        assert_eq!(stemmer("klingon"), None);
+    }
+}
@@ -0,0 +1,324 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::{Client, Method, RequestBuilder, StatusCode};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+
+use crate::model::Document;
+use crate::search::{html_snippet, FoundItem, IndexStats, SearchEngine, SearchRequest, SearchResult, HIGHLIGHT_END, HIGHLIGHT_START};
+use crate::sources::DocStream;
+use crate::utils::json::{get_array, parse_json};
+use crate::utils::streams::channel_stream;
+
+/// Number of documents fetched per request when listing them.
+const DOCUMENTS_PAGE_SIZE: usize = 1000;
+
+pub struct MeiliSearchEngine {
+    client: Client,
+    endpoint: String,
+    index: String,
+    api_key: Option<String>,
+    index_created: OnceCell<()>,
+}
+
+impl MeiliSearchEngine {
+    pub fn new(endpoint: &str, index: &str, api_key: Option<String>) -> Self {
+        Self {
+            client: Client::new(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            index: index.to_string(),
+            api_key,
+            index_created: OnceCell::new(),
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.client.request(method, format!("{}/{}", self.endpoint, path));
+
+        match &self.api_key {
+            Some(api_key) => request.bearer_auth(api_key),
+            None => request,
+        }
+    }
+
+    async fn ensure_index(&self) -> anyhow::Result<()> {
+        let response = self.request(Method::POST, "indexes")
+            .json(&json!({ "uid": self.index, "primaryKey": "uid" }))
+            .send()
+            .await?;
+
+        // Index creation is asynchronous in recent versions and fails with a conflict in older ones
+        if response.status() != StatusCode::CONFLICT {
+            response
+                .error_for_status()
+                .with_context(|| format!("Couldn't create meilisearch index: {}", self.index))?;
+        }
+
+        self.request(Method::POST, &format!("indexes/{}/settings", self.index))
+            .json(&json!({
+                "searchableAttributes": ["title", "content"],
+                "filterableAttributes": ["source"],
+                "displayedAttributes": ["id", "source", "title", "link", "content", "metadata"]
+            }))
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Couldn't update settings of meilisearch index: {}", self.index))?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SearchEngine for MeiliSearchEngine {
+    async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+        self.index_created.get_or_try_init(|| self.ensure_index()).await?;
+
+        let documents = documents
+            .iter()
+            .map(to_meili_document)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        self.request(Method::POST, &format!("indexes/{}/documents", self.index))
+            .json(&documents)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        let (parsed, request) = request.parse_query()?;
+        request.ensure_no_document_filters()?;
+        request.ensure_no_dates()?;
+        request.ensure_relevance_sort()?;
+        request.ensure_no_source_boosts()?;
+
+        let mut body = json!({
+            "q": parsed.text(),
+            "limit": request.limit,
+            "offset": request.offset,
+            "attributesToCrop": ["content"],
+            "cropLength": 30,
+            "attributesToHighlight": ["content"],
+            "highlightPreTag": HIGHLIGHT_START,
+            "highlightPostTag": HIGHLIGHT_END,
+            "showRankingScore": true
+        });
+
+        if !request.source_filter.is_empty() {
+            body["filter"] = json!(format!("source IN {}", serde_json::to_string(&request.source_filter)?));
+        }
+
+        let http_request = self.request(Method::POST, &format!("indexes/{}/search", self.index)).json(&body);
+
+        let stream = channel_stream(|tx| async move {
+            let response: Value = http_request
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            for item in parse_hits(&response)? {
+                tx.send(Ok(item)).await?;
+            }
+
+            Ok(())
+        });
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        let response = self.request(Method::DELETE, &format!("indexes/{}/documents", self.index)).send().await?;
+
+        if response.status() != StatusCode::NOT_FOUND {
+            response.error_for_status()?;
+        }
+
+        Ok(())
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        self.request(Method::POST, &format!("indexes/{}/documents/delete", self.index))
+            .json(&json!({ "filter": format!("source = {}", serde_json::to_string(source)?) }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        let mut documents = vec![];
+
+        loop {
+            let response = self.request(Method::GET, &format!("indexes/{}/documents", self.index))
+                .query(&[("limit", DOCUMENTS_PAGE_SIZE), ("offset", documents.len())])
+                .send()
+                .await?;
+
+            // Nothing was indexed yet
+            if response.status() == StatusCode::NOT_FOUND {
+                break;
+            }
+
+            let page = parse_documents(&response.error_for_status()?.json().await?)?;
+            let last_page = page.len() < DOCUMENTS_PAGE_SIZE;
+            documents.extend(page);
+
+            if last_page {
+                break;
+            }
+        }
+
+        Ok(Box::pin(tokio_stream::iter(documents.into_iter().map(Ok))))
+    }
+
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        let uids = ids.iter().map(|id| meili_uid(id)).collect::<Vec<_>>();
+
+        self.request(Method::POST, &format!("indexes/{}/documents/delete-batch", self.index))
+            .json(&uids)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        let response: Value = self.request(Method::POST, &format!("indexes/{}/search", self.index))
+            .json(&json!({ "q": "", "limit": 0, "facets": ["source"] }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        parse_stats(&response)
+    }
+}
+
+/// Meilisearch primary keys only accept alphanumeric characters, dashes and underscores, so
+/// documents are keyed by a hash of their id.
+fn to_meili_document(document: &Document) -> anyhow::Result<Value> {
+    log::info!("Indexing document: {} (source: {})", document.link, document.source);
+
+    let mut value = serde_json::to_value(document)?;
+
+    if let Value::Object(ref mut fields) = value {
+        fields.insert("uid".to_string(), Value::String(meili_uid(&document.id)));
+    }
+
+    Ok(value)
+}
+
+fn meili_uid(id: &str) -> String {
+    hex::encode(Sha256::digest(id.as_bytes()))
+}
+
+/// The documents of a page of the documents API: a `results` object in recent versions, an array
+/// in older ones. The documents indexed before their metadata was displayed have none.
+fn parse_documents(response: &Value) -> anyhow::Result<Vec<Document>> {
+    let documents = match response {
+        Value::Array(documents) => documents,
+        _ => get_array(response, &["results"])?,
+    };
+
+    documents
+        .iter()
+        .map(|document| {
+            let mut document = document.clone();
+
+            if let Value::Object(ref mut fields) = document {
+                fields.entry("metadata").or_insert_with(|| json!({}));
+            }
+
+            serde_json::from_value(document.clone()).with_context(|| format!("Unexpected document: {}", document))
+        })
+        .collect()
+}
+
+fn parse_stats(response: &Value) -> anyhow::Result<IndexStats> {
+    let sources = parse_json(response, &["facetDistribution", "source"])?;
+
+    Ok(IndexStats {
+        documents: response.get("estimatedTotalHits").and_then(|t| t.as_u64()).unwrap_or_default(),
+        sources,
+    })
+}
+
+fn parse_hits(response: &Value) -> anyhow::Result<Vec<FoundItem>> {
+    get_array(response, &["hits"])?
+        .iter()
+        .enumerate()
+        .map(|(rank, hit)| {
+            let field = |name: &str| -> anyhow::Result<String> {
+                hit.get(name)
+                    .and_then(|f| f.as_str())
+                    .map(|f| f.to_string())
+                    .with_context(|| format!("Field {} of type text not found in hit: {}", name, hit))
+            };
+
+            Ok(FoundItem {
+                id: field("id")?,
+                // Older versions don't expose a ranking score, fallback to the rank
+                score: hit.get("_rankingScore")
+                    .and_then(|s| s.as_f64())
+                    .map(|s| s as f32)
+                    .unwrap_or(1.0 / (rank + 1) as f32),
+                source: field("source")?,
+                title: field("title")?,
+                link: field("link")?,
+                snippet: html_snippet(hit.pointer("/_formatted/content").and_then(|s| s.as_str()).unwrap_or_default()),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::search::meili_impl::{parse_documents, parse_hits};
+
+    #[test]
+    fn test_parse_hits() -> anyhow::Result<()> {
+        let response = json!({
+            "hits": [
+                {
+                    "id": "1", "source": "src", "title": "Hello", "link": "link1", "content": "Hello content",
+                    "_formatted": { "content": "\u{2}Hello\u{3} <content>" },
+                    "_rankingScore": 0.9
+                },
+                { "id": "2", "source": "src", "title": "World", "link": "link2", "content": "World content" }
+            ]
+        });
+
+        let hits = parse_hits(&response)?;
+
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].snippet, "<b>Hello</b> &lt;content&gt;");
+        assert_eq!(hits[0].score, 0.9);
+        assert_eq!(hits[1].score, 0.5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_documents() -> anyhow::Result<()> {
+        let document = json!({ "uid": "6b86", "id": "1", "source": "src", "title": "Hello", "link": "link1", "content": "Hello content" });
+
+        let documents = parse_documents(&json!({ "results": [document], "offset": 0, "limit": 1000, "total": 1 }))?;
+        assert_eq!(documents.iter().map(|document| document.id.as_str()).collect::<Vec<_>>(), vec!["1"]);
+        assert!(documents[0].metadata.is_empty());
+
+        assert_eq!(parse_documents(&json!([document]))?.len(), 1);
+
+        Ok(())
+    }
+}
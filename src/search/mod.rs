@@ -1,28 +1,339 @@
+use std::collections::BTreeMap;
 use std::pin::Pin;
 
+use anyhow::anyhow;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use tokio_stream::Stream;
+use tokio_stream::{Stream, StreamExt};
 
 use crate::model::Document;
+use crate::search::query::ParsedQuery;
 use crate::sources::DocStream;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FoundItem {
     pub id: String,
     pub score: f32,
     pub source: String,
     pub title: String,
     pub link: String,
+    /// An excerpt of the content in html: its text is escaped, the matched terms are highlighted
+    /// with `<b>`, `<em>` or `<mark>` which are its only markup.
     pub snippet: String,
 }
 
+/// Why the results of a search matched and how they were scored, to debug the relevance.
+#[derive(Serialize, Debug, Clone)]
+pub struct SearchExplanation {
+    /// The query as parsed by the engine.
+    pub query: String,
+    pub results: Vec<(FoundItem, ScoreExplanation)>,
+}
+
+/// A score as the combination of the scores of its details (the matching terms and fields).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScoreExplanation {
+    pub value: f32,
+    pub description: String,
+    #[serde(default)]
+    pub details: Vec<ScoreExplanation>,
+}
+
+/// The number of documents matching a search per source and per value of some metadata keys, to
+/// see at a glance where the answers live.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Facets {
+    pub sources: BTreeMap<String, u64>,
+    /// The counts per value of each requested metadata key.
+    #[serde(default)]
+    pub metadata: BTreeMap<String, BTreeMap<String, u64>>,
+}
+
+/// Options of a search. Use `SearchRequest::new` for the defaults (top 10 results of all sources).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SearchRequest {
+    pub query: String,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    #[serde(default)]
+    pub offset: usize,
+    /// Only return documents of these sources (all the sources when empty).
+    #[serde(default)]
+    pub source_filter: Vec<String>,
+    /// Only return documents having all these metadata values.
+    #[serde(default)]
+    pub metadata_filter: BTreeMap<String, String>,
+    /// Only return documents whose `path` metadata matches this glob.
+    #[serde(default)]
+    pub path_filter: Option<String>,
+    /// Only return the documents having all these tags.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tag_filter: Vec<String>,
+    /// Only return the documents with these ids (all the documents when empty).
+    #[serde(default)]
+    pub id_filter: Vec<String>,
+    /// Only return the documents modified since this date.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_since: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub sort: SortOrder,
+    /// Also match the words within this edit distance of the words of the query (typos), at most
+    /// `MAX_FUZZY_DISTANCE`. The engines tolerating typos anyway (meilisearch, algolia, sonic)
+    /// ignore it.
+    #[serde(default)]
+    pub fuzzy: Option<u8>,
+    /// Multiplies the scores of the documents of these sources. Only the tantivy, elasticsearch,
+    /// opensearch and hybrid engines support it, the others fail the searches with boosts.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub source_boosts: BTreeMap<String, f32>,
+}
+
+/// The largest edit distance of the fuzzy searches supported by the engines.
+pub const MAX_FUZZY_DISTANCE: u8 = 2;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Relevance,
+    /// The most recently modified documents first, the ones without a date last.
+    Date,
+    /// Alphabetical order of the titles, the most relevant first among the same titles.
+    Title,
+    /// Alphabetical order of the sources, the most relevant first within each source.
+    Source,
+}
+
+/// The number of results of the searches not giving one.
+pub fn default_limit() -> usize {
+    10
+}
+
+impl SearchRequest {
+    pub fn new(query: &str) -> Self {
+        Self {
+            query: query.to_string(),
+            limit: default_limit(),
+            offset: 0,
+            source_filter: vec![],
+            metadata_filter: BTreeMap::new(),
+            path_filter: None,
+            tag_filter: vec![],
+            id_filter: vec![],
+            modified_since: None,
+            sort: SortOrder::default(),
+            fuzzy: None,
+            source_boosts: BTreeMap::new(),
+        }
+    }
+
+    /// Whether a document of this source can be returned.
+    pub fn accepts_source(&self, source: &str) -> bool {
+        self.source_filter.is_empty() || self.source_filter.iter().any(|s| s == source)
+    }
+
+    /// The query parsed, and the request with the filters of the query (`source:`, `path:`,
+    /// `meta:`, `tag:`) added to its own.
+    pub fn parse_query(&self) -> anyhow::Result<(ParsedQuery, SearchRequest)> {
+        let parsed = ParsedQuery::parse(&self.query)?;
+        let mut request = self.clone();
+
+        request.source_filter.extend(parsed.sources.iter().cloned());
+        request.metadata_filter.extend(parsed.metadata.clone());
+        request.tag_filter.extend(parsed.tags.iter().cloned());
+
+        if let Some(path) = &parsed.path {
+            if request.path_filter.is_some() {
+                return Err(anyhow!("Only one path filter is supported: {}", path));
+            }
+
+            request.path_filter = Some(path.clone());
+        }
+
+        Ok((parsed, request))
+    }
+
+    /// Fails for the engines that can only filter by source.
+    pub fn ensure_no_document_filters(&self) -> anyhow::Result<()> {
+        if !self.metadata_filter.is_empty() || self.path_filter.is_some() || !self.id_filter.is_empty() {
+            return Err(anyhow!("Metadata, path and id filters are not supported by this search engine"));
+        }
+
+        self.ensure_no_tags()
+    }
+
+    /// Fails for the engines that don't index the tags.
+    pub fn ensure_no_tags(&self) -> anyhow::Result<()> {
+        if !self.tag_filter.is_empty() {
+            return Err(anyhow!("Tag filters are not supported by this search engine"));
+        }
+
+        Ok(())
+    }
+
+    /// Fails for the engines that don't index the modification dates: they can't filter nor sort
+    /// by them.
+    pub fn ensure_no_dates(&self) -> anyhow::Result<()> {
+        if self.modified_since.is_some() || self.sort == SortOrder::Date {
+            return Err(anyhow!("Date filters and sorting are not supported by this search engine"));
+        }
+
+        Ok(())
+    }
+
+    /// Fails for the engines that can only rank the results by relevance.
+    pub fn ensure_relevance_sort(&self) -> anyhow::Result<()> {
+        if self.sort != SortOrder::Relevance {
+            return Err(anyhow!("Sorting the results is not supported by this search engine"));
+        }
+
+        Ok(())
+    }
+
+    /// Fails for the engines that can't weigh the results by source.
+    pub fn ensure_no_source_boosts(&self) -> anyhow::Result<()> {
+        if !self.source_boosts.is_empty() {
+            return Err(anyhow!("Source boosts are not supported by this search engine: remove the boost of the sources"));
+        }
+
+        Ok(())
+    }
+
+    /// Fails for the engines that can't search with typos.
+    pub fn ensure_not_fuzzy(&self) -> anyhow::Result<()> {
+        if self.fuzzy.is_some() {
+            return Err(anyhow!("Fuzzy search is not supported by this search engine"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Number of documents in the index, overall and per source.
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq)]
+pub struct IndexStats {
+    pub documents: u64,
+    pub sources: BTreeMap<String, u64>,
+}
+
+impl IndexStats {
+    pub fn from_sources(sources: BTreeMap<String, u64>) -> Self {
+        Self { documents: sources.values().sum(), sources }
+    }
+}
+
+/// Escapes a text put in an html snippet, as the highlighting tags are the only markup of the
+/// snippets.
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#x27;")
+}
+
+/// Highlighting tags asked to the engines that don't escape the text of their snippets: control
+/// characters, which can't be mistaken for the content, turned into `<b>` by `html_snippet`.
+pub(crate) const HIGHLIGHT_START: &str = "\u{2}";
+pub(crate) const HIGHLIGHT_END: &str = "\u{3}";
+
+/// The html snippet of a text highlighted with `HIGHLIGHT_START` and `HIGHLIGHT_END`.
+pub(crate) fn html_snippet(text: &str) -> String {
+    escape_html(text).replace(HIGHLIGHT_START, "<b>").replace(HIGHLIGHT_END, "</b>")
+}
+
 type SearchResult = anyhow::Result<Pin<Box<dyn Stream<Item=anyhow::Result<FoundItem>> + Send>>>;
 
 #[async_trait]
-pub trait SearchEngine {
+pub trait SearchEngine: Send + Sync {
     async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()>;
-    fn search(&self, query: &str) -> SearchResult;
+    async fn search(&self, request: &SearchRequest) -> SearchResult;
+
+    /// Removes all the documents from the index.
+    async fn purge(&self) -> anyhow::Result<()> {
+        Err(anyhow!("Purge is not supported by this search engine"))
+    }
+
+    /// Removes the documents of a single source from the index.
+    async fn purge_source(&self, _source: &str) -> anyhow::Result<()> {
+        Err(anyhow!("Purging a single source is not supported by this search engine"))
+    }
+
+    /// The documents most similar to the indexed document with the given id (excluded from the results).
+    async fn similar(&self, _id: &str, _limit: usize) -> SearchResult {
+        Err(anyhow!("Finding similar documents is not supported by this search engine"))
+    }
+
+    /// Runs the search and explains the score of each result.
+    async fn explain(&self, _request: &SearchRequest) -> anyhow::Result<SearchExplanation> {
+        Err(anyhow!("Explaining searches is not supported by this search engine"))
+    }
+
+    /// Counts the documents matching the search, all of them rather than the requested page, per
+    /// source and per value of the given metadata keys.
+    async fn facets(&self, _request: &SearchRequest, _metadata_keys: &[String]) -> anyhow::Result<Facets> {
+        Err(anyhow!("Facets are not supported by this search engine"))
+    }
+
+    /// The query with its words missing from the index replaced by the closest indexed words, to
+    /// suggest a spelling correction. `None` when there is nothing to correct.
+    async fn suggest(&self, _query: &str) -> anyhow::Result<Option<String>> {
+        Err(anyhow!("Spelling suggestions are not supported by this search engine"))
+    }
+
+    /// Streams all the indexed documents, as stored by the engine: their tags and dates may be
+    /// missing. Indexing a source relies on it (and `delete`) to remove the documents that are no
+    /// longer in the source.
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        Err(anyhow!("Listing the documents is not supported by this search engine"))
+    }
+
+    /// The indexed document with the given id, looked up among all the documents by default.
+    async fn document(&self, id: &str) -> anyhow::Result<Option<Document>> {
+        let mut documents = self.documents().await?;
+
+        while let Some(document) = documents.next().await {
+            let document = document?;
+
+            if document.id == id {
+                return Ok(Some(document));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Removes the documents with the given ids from the index.
+    async fn delete(&self, _ids: &[String]) -> anyhow::Result<()> {
+        Err(anyhow!("Deleting documents is not supported by this search engine"))
+    }
+
+    /// Counts the indexed documents, overall and per source.
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        Err(anyhow!("Stats are not supported by this search engine"))
+    }
 }
 
-pub mod tantivy_impl;
\ No newline at end of file
+pub mod tantivy_impl;
+pub mod es_impl;
+pub mod opensearch_impl;
+pub mod meili_impl;
+pub mod sqlite_impl;
+pub mod postgres_impl;
+pub mod embeddings;
+pub mod code_tokenizer;
+pub mod language;
+pub mod query;
+pub mod semantic_impl;
+pub mod qdrant_impl;
+pub mod hybrid_impl;
+pub mod typesense_impl;
+pub mod algolia_impl;
+pub mod sonic_impl;
+pub mod remote_impl;
+pub mod multi_impl;
+pub mod chunked_impl;
+pub mod deduped_impl;
+pub mod boosted_impl;
+pub mod redis_impl;
\ No newline at end of file
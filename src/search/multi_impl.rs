@@ -0,0 +1,161 @@
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use futures::future::try_join_all;
+
+use crate::model::Document;
+use crate::search::{Facets, IndexStats, SearchEngine, SearchExplanation, SearchRequest, SearchResult};
+use crate::sources::DocStream;
+
+/// Mirrors every indexed batch to several engines while queries are only served by the primary
+/// one. Useful to migrate between backends or to keep a local and a central index in sync.
+pub struct MultiSearchEngine {
+    engines: Vec<Box<dyn SearchEngine>>,
+    primary: usize,
+}
+
+impl MultiSearchEngine {
+    pub fn new(engines: Vec<Box<dyn SearchEngine>>, primary: usize) -> anyhow::Result<Self> {
+        if engines.is_empty() {
+            bail!("At least one engine must be configured")
+        }
+
+        if primary >= engines.len() {
+            bail!("Primary engine index {} is out of range ({} engines configured)", primary, engines.len())
+        }
+
+        Ok(Self { engines, primary })
+    }
+}
+
+#[async_trait]
+impl SearchEngine for MultiSearchEngine {
+    async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+        try_join_all(
+            self.engines.iter().enumerate().map(|(i, engine)| {
+                let documents = documents.clone();
+                async move {
+                    engine.index(documents)
+                        .await
+                        .with_context(|| format!("Couldn't index documents in engine #{}", i))
+                }
+            })
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        self.engines[self.primary].search(request).await
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        for (i, engine) in self.engines.iter().enumerate() {
+            engine.purge().await.with_context(|| format!("Couldn't purge engine #{}", i))?;
+        }
+
+        Ok(())
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        for (i, engine) in self.engines.iter().enumerate() {
+            engine.purge_source(source).await.with_context(|| format!("Couldn't purge engine #{}", i))?;
+        }
+
+        Ok(())
+    }
+
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        self.engines[self.primary].documents().await
+    }
+
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        for (i, engine) in self.engines.iter().enumerate() {
+            engine.delete(ids).await.with_context(|| format!("Couldn't delete documents from engine #{}", i))?;
+        }
+
+        Ok(())
+    }
+
+    async fn explain(&self, request: &SearchRequest) -> anyhow::Result<SearchExplanation> {
+        self.engines[self.primary].explain(request).await
+    }
+
+    async fn similar(&self, id: &str, limit: usize) -> SearchResult {
+        self.engines[self.primary].similar(id, limit).await
+    }
+
+    async fn facets(&self, request: &SearchRequest, metadata_keys: &[String]) -> anyhow::Result<Facets> {
+        self.engines[self.primary].facets(request, metadata_keys).await
+    }
+
+    async fn suggest(&self, query: &str) -> anyhow::Result<Option<String>> {
+        self.engines[self.primary].suggest(query).await
+    }
+
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        self.engines[self.primary].stats().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use tokio_stream::StreamExt;
+
+    use crate::model::Document;
+    use crate::search::multi_impl::MultiSearchEngine;
+    use crate::search::tantivy_impl::TantivySearchEngine;
+    use crate::search::{SearchEngine, SearchRequest, SearchResult};
+
+    /// Allows keeping a handle on an engine after handing it to the multi engine.
+    struct Shared(Arc<TantivySearchEngine>);
+
+    #[async_trait]
+    impl SearchEngine for Shared {
+        async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+            self.0.index(documents).await
+        }
+
+        async fn search(&self, request: &SearchRequest) -> SearchResult {
+            self.0.search(request).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_multi_search_engine() -> anyhow::Result<()> {
+        let primary = Arc::new(TantivySearchEngine::in_memory()?);
+        let mirror = Arc::new(TantivySearchEngine::in_memory()?);
+
+        let engine = MultiSearchEngine::new(
+            vec![Box::new(Shared(primary.clone())), Box::new(Shared(mirror.clone()))],
+            0,
+        )?;
+
+        let document = Document {
+            title: "Runbook".to_string(),
+            content: "Restart the database".to_string(),
+            source: "My source".to_string(),
+            link: "link1".to_string(),
+            metadata: HashMap::new(),
+            id: "1".to_string(),
+            tags: vec![],
+            modified: None,
+        };
+
+        engine.index(vec![document]).await?;
+
+        let request = SearchRequest::new("database");
+
+        for results in [engine.search(&request).await?, mirror.search(&request).await?] {
+            let results = results.collect::<Result<Vec<_>, _>>().await?;
+            assert_eq!(results.len(), 1);
+        }
+
+        assert!(MultiSearchEngine::new(vec![], 0).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,346 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Method, Request};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+
+use crate::model::Document;
+use crate::search::{FoundItem, IndexStats, SearchEngine, SearchRequest, SearchResult};
+use crate::search::es_impl::{
+    bulk_body, check_bulk_response, delete_by_query_body, delete_ids_body, documents_body, parse_documents, parse_hits, parse_stats,
+    search_body, stats_body, DOCUMENTS_PAGE_SIZE,
+};
+use crate::sources::DocStream;
+use crate::utils::streams::channel_stream;
+
+pub enum OpenSearchAuth {
+    None,
+    Basic { username: String, password: String },
+    SigV4(AwsCredentials),
+}
+
+#[derive(Clone)]
+pub struct AwsCredentials {
+    pub region: String,
+    pub service: String,
+    pub access_key: String,
+    pub secret_key: String,
+    pub session_token: Option<String>,
+}
+
+/// OpenSearch engine. Shares the bulk/search payloads with the elasticsearch engine but uses a
+/// mapping compatible with OpenSearch and supports AWS SigV4 signed requests.
+pub struct OpenSearchEngine {
+    client: Client,
+    endpoint: String,
+    index: String,
+    auth: OpenSearchAuth,
+    index_created: OnceCell<()>,
+}
+
+impl OpenSearchEngine {
+    pub fn new(endpoint: &str, index: &str, auth: OpenSearchAuth) -> Self {
+        Self {
+            client: Client::new(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            index: index.to_string(),
+            auth,
+            index_created: OnceCell::new(),
+        }
+    }
+
+    fn request(&self, method: Method, path: &str, body: Option<(Vec<u8>, &str)>) -> anyhow::Result<Request> {
+        let mut request = self.client.request(method, format!("{}/{}", self.endpoint, path));
+
+        if let Some((body, content_type)) = body {
+            request = request.header("Content-Type", content_type).body(body);
+        }
+
+        if let OpenSearchAuth::Basic { username, password } = &self.auth {
+            request = request.basic_auth(username, Some(password));
+        }
+
+        let mut request = request.build()?;
+
+        if let OpenSearchAuth::SigV4(credentials) = &self.auth {
+            sign_v4(&mut request, credentials, Utc::now())?;
+        }
+
+        Ok(request)
+    }
+
+    async fn send(&self, request: Request) -> anyhow::Result<Value> {
+        let response = self.client
+            .execute(request)
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(response)
+    }
+
+    async fn ensure_index(&self) -> anyhow::Result<()> {
+        let exists = self.client.execute(self.request(Method::HEAD, &self.index, None)?).await?;
+
+        if exists.status().is_success() {
+            return Ok(());
+        }
+
+        log::info!("Creating opensearch index: {}", &self.index);
+
+        let mapping = serde_json::to_vec(&index_mapping())?;
+        self.send(self.request(Method::PUT, &self.index, Some((mapping, "application/json")))?)
+            .await
+            .with_context(|| format!("Couldn't create opensearch index: {}", self.index))?;
+
+        Ok(())
+    }
+
+    async fn delete_by_query(&self, body: &Value) -> anyhow::Result<()> {
+        let body = serde_json::to_vec(body)?;
+        let path = format!("{}/_delete_by_query?refresh=true", self.index);
+        let response = self.client.execute(self.request(Method::POST, &path, Some((body, "application/json")))?).await?;
+
+        if response.status() != reqwest::StatusCode::NOT_FOUND {
+            response.error_for_status()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SearchEngine for OpenSearchEngine {
+    async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+        self.index_created.get_or_try_init(|| self.ensure_index()).await?;
+
+        let body = bulk_body(&self.index, &documents)?.into_bytes();
+        let response = self.send(self.request(Method::POST, "_bulk", Some((body, "application/x-ndjson")))?).await?;
+
+        check_bulk_response(&response)
+    }
+
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        let http_request = self.request(
+            Method::POST,
+            &format!("{}/_search", self.index),
+            Some((serde_json::to_vec(&search_body(request, ".keyword")?)?, "application/json")),
+        )?;
+        let client = self.client.clone();
+
+        let stream = channel_stream(|tx| async move {
+            let response: Value = client
+                .execute(http_request)
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            let items: Vec<FoundItem> = parse_hits(&response)?;
+            for item in items {
+                tx.send(Ok(item)).await?;
+            }
+
+            Ok(())
+        });
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        self.delete_by_query(&delete_by_query_body(None)).await
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        self.delete_by_query(&delete_by_query_body(Some(source))).await
+    }
+
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        let mut documents = vec![];
+        let mut after = None;
+
+        loop {
+            let body = serde_json::to_vec(&documents_body(after.as_deref()))?;
+            let path = format!("{}/_search", self.index);
+            let response = self.client.execute(self.request(Method::POST, &path, Some((body, "application/json")))?).await?;
+
+            // Nothing was indexed yet
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                break;
+            }
+
+            let page = parse_documents(&response.error_for_status()?.json().await?)?;
+            let last_page = page.len() < DOCUMENTS_PAGE_SIZE;
+            after = page.last().map(|document| document.id.clone());
+            documents.extend(page.into_iter().map(Ok));
+
+            if last_page {
+                break;
+            }
+        }
+
+        Ok(Box::pin(tokio_stream::iter(documents)))
+    }
+
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        self.delete_by_query(&delete_ids_body(ids)).await
+    }
+
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        let request = self.request(
+            Method::POST,
+            &format!("{}/_search", self.index),
+            Some((serde_json::to_vec(&stats_body())?, "application/json")),
+        )?;
+
+        parse_stats(&self.send(request).await?)
+    }
+}
+
+/// OpenSearch has no `flattened` type (the metadata is mapped as a dynamic object instead).
+fn index_mapping() -> Value {
+    json!({
+        "mappings": {
+            "properties": {
+                "id": { "type": "keyword" },
+                "source": { "type": "keyword" },
+                "title": { "type": "text", "fields": { "keyword": { "type": "keyword", "ignore_above": 256 } } },
+                "link": { "type": "keyword" },
+                "content": { "type": "text" },
+                "metadata": { "type": "object", "dynamic": true },
+                "modified": { "type": "date" },
+                "tags": { "type": "keyword" }
+            }
+        }
+    })
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn hmac(key: &[u8], data: &str) -> anyhow::Result<Vec<u8>> {
+    let mut mac = HmacSha256::new_from_slice(key)?;
+    mac.update(data.as_bytes());
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+fn aws_uri_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            other => format!("%{:02X}", other),
+        })
+        .collect()
+}
+
+/// Signs the request using AWS signature version 4 (headers `host` and `x-amz-date` are signed).
+fn sign_v4(request: &mut Request, credentials: &AwsCredentials, now: DateTime<Utc>) -> anyhow::Result<()> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+
+    let url = request.url().clone();
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+
+    let mut query = url
+        .query_pairs()
+        .map(|(k, v)| format!("{}={}", aws_uri_encode(&k), aws_uri_encode(&v)))
+        .collect::<Vec<_>>();
+    query.sort();
+
+    let payload = request
+        .body()
+        .and_then(|b| b.as_bytes())
+        .unwrap_or_default();
+    let payload_hash = hex::encode(Sha256::digest(payload));
+
+    let mut headers = vec![("host", host), ("x-amz-date", amz_date.clone())];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token", token.clone()));
+    }
+
+    let canonical_headers = headers
+        .iter()
+        .map(|(k, v)| format!("{}:{}\n", k, v.trim()))
+        .collect::<String>();
+    let signed_headers = headers.iter().map(|(k, _)| *k).collect::<Vec<_>>().join(";");
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        request.method().as_str(),
+        url.path(),
+        query.join("&"),
+        canonical_headers,
+        signed_headers,
+        payload_hash,
+    );
+
+    let scope = format!("{}/{}/{}/aws4_request", date, credentials.region, credentials.service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes())),
+    );
+
+    let key = hmac(format!("AWS4{}", credentials.secret_key).as_bytes(), &date)?;
+    let key = hmac(&key, &credentials.region)?;
+    let key = hmac(&key, &credentials.service)?;
+    let key = hmac(&key, "aws4_request")?;
+    let signature = hex::encode(hmac(&key, &string_to_sign)?);
+
+    let request_headers = request.headers_mut();
+    for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+        request_headers.insert(*name, value.parse()?);
+    }
+    request_headers.insert(
+        "Authorization",
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            credentials.access_key, scope, signed_headers, signature,
+        ).parse()?,
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use reqwest::{Client, Method};
+
+    use crate::search::opensearch_impl::{AwsCredentials, sign_v4};
+
+    #[test]
+    fn test_sign_v4() -> anyhow::Result<()> {
+        // "get-vanilla" case of the AWS signature v4 test suite
+        let mut request = Client::new()
+            .request(Method::GET, "https://example.amazonaws.com/")
+            .build()?;
+
+        let credentials = AwsCredentials {
+            region: "us-east-1".to_string(),
+            service: "service".to_string(),
+            access_key: "AKIDEXAMPLE".to_string(),
+            secret_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+        };
+
+        sign_v4(&mut request, &credentials, Utc.ymd(2015, 8, 30).and_hms(12, 36, 0))?;
+
+        assert_eq!(
+            request.headers().get("Authorization").unwrap().to_str()?,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31",
+        );
+
+        Ok(())
+    }
+}
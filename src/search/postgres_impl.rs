@@ -0,0 +1,276 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use tokio::sync::OnceCell;
+use tokio_postgres::{Client, NoTls};
+
+use crate::model::Document;
+use crate::search::{html_snippet, FoundItem, IndexStats, SearchEngine, SearchRequest, SearchResult, SortOrder};
+use crate::sources::DocStream;
+use crate::utils::glob::glob_to_regex;
+use crate::utils::streams::channel_stream;
+
+/// Stores documents in a postgres table with a generated `tsvector` column and searches them using
+/// `websearch_to_tsquery`.
+pub struct PostgresSearchEngine {
+    connection: Arc<PostgresConnection>,
+}
+
+struct PostgresConnection {
+    url: String,
+    table: String,
+    language: String,
+    client: OnceCell<Client>,
+}
+
+impl PostgresConnection {
+    async fn client(&self) -> anyhow::Result<&Client> {
+        self.client.get_or_try_init(|| self.connect()).await
+    }
+
+    async fn connect(&self) -> anyhow::Result<Client> {
+        let (client, connection) = tokio_postgres::connect(&self.url, NoTls)
+            .await
+            .context("Couldn't connect to postgres")?;
+
+        tokio::spawn(async move {
+            if let Err(err) = connection.await {
+                log::error!("postgres connection error: {}", err);
+            }
+        });
+
+        client
+            .batch_execute(&format!(
+                "CREATE TABLE IF NOT EXISTS {table} (
+                    id TEXT PRIMARY KEY,
+                    source TEXT NOT NULL,
+                    title TEXT NOT NULL,
+                    link TEXT NOT NULL,
+                    content TEXT NOT NULL,
+                    metadata JSONB NOT NULL DEFAULT '{{}}',
+                    tsv TSVECTOR GENERATED ALWAYS AS (
+                        setweight(to_tsvector('{language}', title), 'A') ||
+                        setweight(to_tsvector('{language}', content), 'B')
+                    ) STORED
+                );
+                CREATE INDEX IF NOT EXISTS {table}_tsv_idx ON {table} USING GIN (tsv);",
+                table = self.table,
+                language = self.language,
+            ))
+            .await
+            .with_context(|| format!("Couldn't create postgres table: {}", self.table))?;
+
+        Ok(client)
+    }
+}
+
+impl PostgresSearchEngine {
+    pub fn new(url: &str, table: &str, language: &str) -> anyhow::Result<Self> {
+        // Both are interpolated in the SQL statements
+        check_identifier(table)?;
+        check_identifier(language)?;
+
+        Ok(Self {
+            connection: Arc::new(PostgresConnection {
+                url: url.to_string(),
+                table: table.to_string(),
+                language: language.to_string(),
+                client: OnceCell::new(),
+            })
+        })
+    }
+}
+
+fn check_identifier(identifier: &str) -> anyhow::Result<()> {
+    let valid = !identifier.is_empty()
+        && identifier.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !identifier.starts_with(|c: char| c.is_ascii_digit());
+
+    if !valid {
+        bail!("Invalid postgres identifier: '{}'", identifier);
+    }
+
+    Ok(())
+}
+
+#[async_trait]
+impl SearchEngine for PostgresSearchEngine {
+    async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+        let client = self.connection.client().await?;
+        let statement = client
+            .prepare(&format!(
+                "INSERT INTO {} (id, source, title, link, content, metadata)
+                 VALUES ($1, $2, $3, $4, $5, $6)
+                 ON CONFLICT (id) DO UPDATE SET
+                    source = EXCLUDED.source,
+                    title = EXCLUDED.title,
+                    link = EXCLUDED.link,
+                    content = EXCLUDED.content,
+                    metadata = EXCLUDED.metadata",
+                self.connection.table,
+            ))
+            .await?;
+
+        for document in documents {
+            log::info!("Indexing document: {} (source: {})", document.link, document.source);
+
+            client
+                .execute(
+                    &statement,
+                    &[
+                        &document.id,
+                        &document.source,
+                        &document.title,
+                        &document.link,
+                        &document.content,
+                        &serde_json::to_value(&document.metadata)?,
+                    ],
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        let (parsed, request) = request.parse_query()?;
+        request.ensure_no_dates()?;
+        request.ensure_no_tags()?;
+        request.ensure_not_fuzzy()?;
+        request.ensure_no_source_boosts()?;
+
+        // websearch_to_tsquery understands the quoted phrases and the excluded words
+        let query = parsed.text();
+        let connection = self.connection.clone();
+
+        let stream = channel_stream(|tx| async move {
+            let metadata_filter = serde_json::to_value(&request.metadata_filter)?;
+            let path_filter = request.path_filter.as_ref().map(|glob| format!("^{}$", glob_to_regex(glob)));
+            let order = match request.sort {
+                SortOrder::Title => "lower(title), ts_rank(tsv, query) DESC",
+                SortOrder::Source => "source, ts_rank(tsv, query) DESC",
+                _ => "ts_rank(tsv, query) DESC",
+            };
+
+            let client = connection.client().await?;
+            let rows = client
+                .query(
+                    format!(
+                        "SELECT id, source, title, link,
+                                ts_headline('{language}', content, query, 'StartSel=' || chr(2) || ', StopSel=' || chr(3) || ', MaxFragments=2'),
+                                ts_rank(tsv, query)
+                         FROM {table}, websearch_to_tsquery('{language}', $1) query
+                         WHERE tsv @@ query AND (cardinality($2::text[]) = 0 OR source = ANY($2))
+                           AND metadata @> $5 AND ($6::text IS NULL OR metadata->>'path' ~ $6)
+                           AND (cardinality($7::text[]) = 0 OR id = ANY($7))
+                         ORDER BY {order}
+                         LIMIT $3 OFFSET $4",
+                        table = connection.table,
+                        language = connection.language,
+                        order = order,
+                    ).as_str(),
+                    &[
+                        &query,
+                        &request.source_filter,
+                        &(request.limit as i64),
+                        &(request.offset as i64),
+                        &metadata_filter,
+                        &path_filter,
+                        &request.id_filter,
+                    ],
+                )
+                .await?;
+
+            for row in rows {
+                tx.send(Ok(FoundItem {
+                    id: row.try_get(0)?,
+                    source: row.try_get(1)?,
+                    title: row.try_get(2)?,
+                    link: row.try_get(3)?,
+                    snippet: html_snippet(row.try_get(4)?),
+                    score: row.try_get(5)?,
+                })).await?;
+            }
+
+            Ok(())
+        });
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        let client = self.connection.client().await?;
+        client.execute(format!("DELETE FROM {}", self.connection.table).as_str(), &[]).await?;
+        Ok(())
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        let client = self.connection.client().await?;
+        client.execute(format!("DELETE FROM {} WHERE source = $1", self.connection.table).as_str(), &[&source]).await?;
+        Ok(())
+    }
+
+    /// The tags and modification dates aren't stored.
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        let connection = self.connection.clone();
+
+        let stream = channel_stream(|tx| async move {
+            let client = connection.client().await?;
+            let rows = client
+                .query(format!("SELECT id, source, title, link, content, metadata FROM {}", connection.table).as_str(), &[])
+                .await?;
+
+            for row in rows {
+                tx.send(Ok(Document {
+                    id: row.try_get(0)?,
+                    source: row.try_get(1)?,
+                    title: row.try_get(2)?,
+                    link: row.try_get(3)?,
+                    content: row.try_get(4)?,
+                    metadata: serde_json::from_value(row.try_get(5)?)?,
+                    tags: vec![],
+                    modified: None,
+                })).await?;
+            }
+
+            Ok(())
+        });
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        let client = self.connection.client().await?;
+        client.execute(format!("DELETE FROM {} WHERE id = ANY($1)", self.connection.table).as_str(), &[&ids]).await?;
+        Ok(())
+    }
+
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        let client = self.connection.client().await?;
+        let rows = client
+            .query(format!("SELECT source, COUNT(*) FROM {} GROUP BY source", self.connection.table).as_str(), &[])
+            .await?;
+
+        let sources = rows
+            .iter()
+            .map(|row| (row.get::<_, String>(0), row.get::<_, i64>(1) as u64))
+            .collect();
+
+        Ok(IndexStats::from_sources(sources))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::search::postgres_impl::check_identifier;
+
+    #[test]
+    fn test_check_identifier() {
+        assert!(check_identifier("doks_documents").is_ok());
+        assert!(check_identifier("english").is_ok());
+        assert!(check_identifier("").is_err());
+        assert!(check_identifier("1table").is_err());
+        assert!(check_identifier("docs; DROP TABLE users").is_err());
+    }
+}
@@ -0,0 +1,423 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use reqwest::{Client, Method, RequestBuilder};
+use serde_json::{json, Value};
+use tokio::sync::OnceCell;
+
+use crate::model::Document;
+use crate::search::{escape_html, FoundItem, SearchEngine, SearchRequest, SearchResult};
+use crate::search::embeddings::EmbeddingProvider;
+use crate::search::semantic_impl::chunk_text;
+use crate::sources::DocStream;
+use crate::utils::json::get_array;
+use crate::utils::streams::channel_stream;
+
+const EMBEDDING_BATCH_SIZE: usize = 32;
+/// Number of points fetched per request when listing the documents.
+const SCROLL_PAGE_SIZE: usize = 1000;
+
+/// Vector search engine storing document chunks as points of a Qdrant collection.
+pub struct QdrantSearchEngine {
+    client: QdrantClient,
+    embedder: Arc<dyn EmbeddingProvider>,
+    chunk_size: usize,
+    collection_created: OnceCell<()>,
+}
+
+#[derive(Clone)]
+struct QdrantClient {
+    client: Client,
+    endpoint: String,
+    collection: String,
+    api_key: Option<String>,
+}
+
+impl QdrantClient {
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.client.request(
+            method,
+            format!("{}/collections/{}{}", self.endpoint, self.collection, path),
+        );
+
+        match &self.api_key {
+            Some(api_key) => request.header("api-key", api_key),
+            None => request,
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder) -> anyhow::Result<Value> {
+        let response = request
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(response)
+    }
+}
+
+impl QdrantSearchEngine {
+    pub fn new(
+        endpoint: &str,
+        collection: &str,
+        api_key: Option<String>,
+        embedder: Arc<dyn EmbeddingProvider>,
+        chunk_size: usize,
+    ) -> Self {
+        Self {
+            client: QdrantClient {
+                client: Client::new(),
+                endpoint: endpoint.trim_end_matches('/').to_string(),
+                collection: collection.to_string(),
+                api_key,
+            },
+            embedder,
+            chunk_size,
+            collection_created: OnceCell::new(),
+        }
+    }
+
+    async fn ensure_collection(&self, vector_size: usize) -> anyhow::Result<()> {
+        let exists = self.client.request(Method::GET, "").send().await?;
+
+        if exists.status().is_success() {
+            return Ok(());
+        }
+
+        log::info!("Creating qdrant collection: {}", &self.client.collection);
+
+        self.client
+            .send(
+                self.client
+                    .request(Method::PUT, "")
+                    .json(&json!({ "vectors": { "size": vector_size, "distance": "Cosine" } }))
+            )
+            .await
+            .with_context(|| format!("Couldn't create qdrant collection: {}", self.client.collection))?;
+
+        self.client
+            .send(
+                self.client
+                    .request(Method::PUT, "/index")
+                    .json(&json!({ "field_name": "document_id", "field_schema": "keyword" }))
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn embed(&self, texts: Vec<String>) -> anyhow::Result<Vec<Vec<f32>>> {
+        let embedder = self.embedder.clone();
+        tokio::task::spawn_blocking(move || embedder.embed(&texts)).await?
+    }
+}
+
+#[async_trait]
+impl SearchEngine for QdrantSearchEngine {
+    async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+        let mut points = vec![];
+
+        for document in &documents {
+            log::info!("Indexing document: {} (source: {})", document.link, document.source);
+
+            for (position, text) in chunk_text(&document.content, self.chunk_size).into_iter().enumerate() {
+                points.push((document, position, text));
+            }
+        }
+
+        let mut payloads = vec![];
+
+        for batch in points.chunks(EMBEDDING_BATCH_SIZE) {
+            let texts = batch
+                .iter()
+                .map(|(document, _, text)| format!("{}\n{}", document.title, text))
+                .collect();
+
+            for ((document, position, text), vector) in batch.iter().zip(self.embed(texts).await?) {
+                payloads.push(json!({
+                    "id": point_id(&document.id, *position),
+                    "vector": vector,
+                    "payload": {
+                        "document_id": document.id,
+                        "source": document.source,
+                        "title": document.title,
+                        "link": document.link,
+                        "text": text,
+                        "metadata": document.metadata,
+                        "tags": document.tags,
+                    }
+                }));
+            }
+        }
+
+        let vector_size = match payloads.first().and_then(|p| p["vector"].as_array()) {
+            Some(vector) => vector.len(),
+            None => return Ok(()),
+        };
+
+        self.collection_created.get_or_try_init(|| self.ensure_collection(vector_size)).await?;
+
+        // Drop the previous chunks of these documents
+        let ids = documents.iter().map(|d| d.id.as_str()).collect::<Vec<_>>();
+        self.client
+            .send(
+                self.client
+                    .request(Method::POST, "/points/delete?wait=true")
+                    .json(&json!({ "filter": { "must": [{ "key": "document_id", "match": { "any": ids } }] } }))
+            )
+            .await?;
+
+        self.client
+            .send(
+                self.client
+                    .request(Method::PUT, "/points?wait=true")
+                    .json(&json!({ "points": payloads }))
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        let (parsed, request) = request.parse_query()?;
+
+        if request.path_filter.is_some() || !request.id_filter.is_empty() {
+            bail!("Path and id filters are not supported by the qdrant engine")
+        }
+
+        request.ensure_no_dates()?;
+        request.ensure_relevance_sort()?;
+        request.ensure_no_source_boosts()?;
+
+        let client = self.client.clone();
+        let embedder = self.embedder.clone();
+
+        let stream = channel_stream(|tx| async move {
+            let query = parsed.words();
+            let vector = tokio::task::spawn_blocking(move || embedder.embed(&[query]))
+                .await??
+                .remove(0);
+
+            // Groups can't be paginated: the first pages are fetched and skipped
+            let mut body = json!({
+                "vector": vector,
+                "group_by": "document_id",
+                "limit": request.offset + request.limit,
+                "group_size": 1,
+                "with_payload": true
+            });
+
+            let mut conditions = vec![];
+
+            if !request.source_filter.is_empty() {
+                conditions.push(json!({ "key": "source", "match": { "any": request.source_filter } }));
+            }
+
+            for (key, value) in &request.metadata_filter {
+                conditions.push(json!({ "key": format!("metadata.{}", key), "match": { "value": value } }));
+            }
+
+            for tag in &request.tag_filter {
+                conditions.push(json!({ "key": "tags", "match": { "value": tag } }));
+            }
+
+            if !conditions.is_empty() {
+                body["filter"] = json!({ "must": conditions });
+            }
+
+            let response = client
+                .send(client.request(Method::POST, "/points/search/groups").json(&body))
+                .await?;
+
+            for item in parse_groups(&response)?.into_iter().skip(request.offset) {
+                tx.send(Ok(item)).await?;
+            }
+
+            Ok(())
+        });
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        let response = self.client.request(Method::DELETE, "").send().await?;
+
+        if response.status() != reqwest::StatusCode::NOT_FOUND {
+            response.error_for_status()?;
+        }
+
+        Ok(())
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        self.client
+            .send(
+                self.client
+                    .request(Method::POST, "/points/delete?wait=true")
+                    .json(&json!({ "filter": { "must": [{ "key": "source", "match": { "value": source } }] } }))
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        let mut points = vec![];
+        let mut offset: Option<Value> = None;
+
+        loop {
+            let mut body = json!({ "limit": SCROLL_PAGE_SIZE, "with_payload": true, "with_vector": false });
+            if let Some(offset) = offset {
+                body["offset"] = offset;
+            }
+
+            let response = self.client.request(Method::POST, "/points/scroll").json(&body).send().await?;
+
+            // Nothing was indexed yet
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                break;
+            }
+
+            let response: Value = response.error_for_status()?.json().await?;
+            points.extend(get_array(&response, &["result", "points"])?.iter().cloned());
+
+            offset = response.pointer("/result/next_page_offset").filter(|offset| !offset.is_null()).cloned();
+            if offset.is_none() {
+                break;
+            }
+        }
+
+        let documents = parse_points(&points)?;
+
+        Ok(Box::pin(tokio_stream::iter(documents.into_iter().map(Ok))))
+    }
+
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        self.client
+            .send(
+                self.client
+                    .request(Method::POST, "/points/delete?wait=true")
+                    .json(&json!({ "filter": { "must": [{ "key": "document_id", "match": { "any": ids } }] } }))
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Qdrant point ids must be integers or UUIDs: derive a stable UUID from the document id and the chunk position.
+fn point_id(document_id: &str, position: usize) -> String {
+    uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_URL, format!("{}#{}", document_id, position).as_bytes()).to_string()
+}
+
+/// Gathers the chunks of the scrolled points back into their documents. The content is made of the
+/// text of the chunks, in the order they were scrolled.
+fn parse_points(points: &[Value]) -> anyhow::Result<Vec<Document>> {
+    let mut documents: Vec<Document> = vec![];
+
+    for point in points {
+        let field = |name: &str| point.pointer(&format!("/payload/{}", name)).and_then(|f| f.as_str()).unwrap_or_default();
+        let id = point.pointer("/payload/document_id")
+            .and_then(|f| f.as_str())
+            .with_context(|| format!("Unexpected point: {}", point))?;
+
+        match documents.iter_mut().find(|document| document.id == id) {
+            Some(document) => {
+                document.content.push('\n');
+                document.content.push_str(field("text"));
+            }
+            None => documents.push(Document {
+                id: id.to_string(),
+                source: field("source").to_string(),
+                title: field("title").to_string(),
+                link: field("link").to_string(),
+                content: field("text").to_string(),
+                metadata: point.pointer("/payload/metadata").cloned().map(serde_json::from_value).transpose()?.unwrap_or_default(),
+                tags: point.pointer("/payload/tags").cloned().map(serde_json::from_value).transpose()?.unwrap_or_default(),
+                modified: None,
+            }),
+        }
+    }
+
+    Ok(documents)
+}
+
+fn parse_groups(response: &Value) -> anyhow::Result<Vec<FoundItem>> {
+    get_array(response, &["result", "groups"])?
+        .iter()
+        .filter_map(|group| group.pointer("/hits/0"))
+        .map(|hit| {
+            let field = |name: &str| -> anyhow::Result<String> {
+                hit.pointer(&format!("/payload/{}", name))
+                    .and_then(|f| f.as_str())
+                    .map(|f| f.to_string())
+                    .with_context(|| format!("Field {} of type text not found in hit: {}", name, hit))
+            };
+
+            Ok(FoundItem {
+                id: field("document_id")?,
+                score: hit.get("score").and_then(|s| s.as_f64()).unwrap_or_default() as f32,
+                source: field("source")?,
+                title: field("title")?,
+                link: field("link")?,
+                snippet: escape_html(&field("text")?),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::search::qdrant_impl::{parse_groups, parse_points, point_id};
+
+    #[test]
+    fn test_point_id_is_stable() {
+        assert_eq!(point_id("doc1", 0), point_id("doc1", 0));
+        assert_ne!(point_id("doc1", 0), point_id("doc1", 1));
+    }
+
+    #[test]
+    fn test_parse_groups() -> anyhow::Result<()> {
+        let response = json!({
+            "result": {
+                "groups": [{
+                    "id": "doc1",
+                    "hits": [{
+                        "id": "5c56c793-69f3-4fbf-87e6-c4bf54c28c26",
+                        "score": 0.87,
+                        "payload": { "document_id": "doc1", "source": "src", "title": "Doc", "link": "link1", "text": "chunk <text>" }
+                    }]
+                }]
+            }
+        });
+
+        let found = parse_groups(&response)?;
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, "doc1");
+        assert_eq!(found[0].snippet, "chunk &lt;text&gt;");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_points() -> anyhow::Result<()> {
+        let point = |document_id: &str, text: &str| json!({
+            "id": point_id(document_id, 0),
+            "payload": { "document_id": document_id, "source": "src", "title": "Doc", "link": "link1", "text": text, "metadata": { "repository": "doks" }, "tags": [] }
+        });
+
+        let documents = parse_points(&[point("doc1", "first"), point("doc2", "other"), point("doc1", "second")])?;
+
+        assert_eq!(documents.iter().map(|document| document.id.as_str()).collect::<Vec<_>>(), vec!["doc1", "doc2"]);
+        assert_eq!(documents[0].content, "first\nsecond");
+        assert_eq!(documents[0].metadata.get("repository").map(String::as_str), Some("doks"));
+
+        Ok(())
+    }
+}
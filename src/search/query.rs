@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context};
+
+/// The fields a query can be restricted to, and the filters (`name:value`).
+const FIELDS: &[&str] = &["title", "content", "source", "path", "meta", "tag"];
+
+/// A query in the doks syntax: words and `"phrases"`, searched in the title and content or only
+/// in one of them (`title:runbook`), optional unless required (`+oncall`) or excluded
+/// (`-archived`), along with filters on the documents searched (`source:github`, `path:docs/**`,
+/// `meta:lang=rust`, `tag:ops`).
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct ParsedQuery {
+    pub clauses: Vec<Clause>,
+    pub sources: Vec<String>,
+    pub path: Option<String>,
+    pub metadata: BTreeMap<String, String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Clause {
+    pub occur: Occur,
+    /// Both the title and the content are searched when not set.
+    pub field: Option<TextField>,
+    pub text: String,
+    pub phrase: bool,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Occur {
+    Should,
+    Must,
+    MustNot,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TextField {
+    Title,
+    Content,
+}
+
+impl TextField {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextField::Title => "title",
+            TextField::Content => "content",
+        }
+    }
+}
+
+struct Token<'a> {
+    occur: Occur,
+    field: Option<&'a str>,
+    text: &'a str,
+    phrase: bool,
+}
+
+impl ParsedQuery {
+    /// Parses the query leniently: unknown fields (`IndexWriter::new`, `https://...`) are part of
+    /// the words, and an unclosed quote ends with the query. Only the invalid filters fail.
+    pub fn parse(query: &str) -> anyhow::Result<Self> {
+        let mut parsed = ParsedQuery::default();
+        let mut rest = query.trim_start();
+
+        while !rest.is_empty() {
+            let (token, remaining) = next_token(rest);
+            parsed.add(token)?;
+            rest = remaining.trim_start();
+        }
+
+        Ok(parsed)
+    }
+
+    fn add(&mut self, token: Token) -> anyhow::Result<()> {
+        let filter = match token.field {
+            None | Some("title") | Some("content") => {
+                if !token.text.trim().is_empty() {
+                    self.clauses.push(Clause {
+                        occur: token.occur,
+                        field: token.field.map(|field| if field == "title" { TextField::Title } else { TextField::Content }),
+                        text: token.text.to_string(),
+                        phrase: token.phrase,
+                    });
+                }
+
+                return Ok(());
+            }
+            Some(filter) => filter,
+        };
+
+        if token.occur == Occur::MustNot {
+            bail!("Filters can't be excluded: -{}:{}", filter, token.text)
+        }
+
+        match filter {
+            "source" => self.sources.push(token.text.to_string()),
+            "path" if self.path.is_some() => bail!("Only one path filter is supported: {}", token.text),
+            "path" => self.path = Some(token.text.to_string()),
+            "tag" => self.tags.push(token.text.to_string()),
+            _ => {
+                let (key, value) = token.text
+                    .split_once('=')
+                    .with_context(|| format!("Invalid metadata filter (expected meta:key=value): {}", token.text))?;
+                self.metadata.insert(key.to_string(), value.to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The query in the syntax understood by most engines: the phrases quoted and the excluded
+    /// words prefixed by `-`. The fields are dropped.
+    pub fn text(&self) -> String {
+        self.render(|clause| {
+            let text = if clause.phrase { format!("\"{}\"", clause.text) } else { clause.text.clone() };
+
+            match clause.occur {
+                Occur::MustNot => format!("-{}", text),
+                _ => text,
+            }
+        })
+    }
+
+    /// The words and phrases searched, without the excluded ones: for the engines without any
+    /// query syntax (embeddings...).
+    pub fn words(&self) -> String {
+        self.render(|clause| match clause.occur {
+            Occur::MustNot => String::new(),
+            _ => clause.text.clone(),
+        })
+    }
+
+    /// The query in the Lucene query string syntax (elasticsearch and opensearch).
+    pub fn query_string(&self) -> String {
+        self.render(|clause| {
+            let occur = match clause.occur {
+                Occur::Should => "",
+                Occur::Must => "+",
+                Occur::MustNot => "-",
+            };
+            let field = clause.field.map(|field| format!("{}:", field.name())).unwrap_or_default();
+            let text = match clause.phrase {
+                true => format!("\"{}\"", clause.text.replace('\\', "\\\\")),
+                false => clause.text.chars().fold(String::new(), |mut escaped, c| {
+                    if "+-=&|><!(){}[]^\"~*?:\\/".contains(c) {
+                        escaped.push('\\');
+                    }
+                    escaped.push(c);
+                    escaped
+                }),
+            };
+
+            format!("{}{}{}", occur, field, text)
+        })
+    }
+
+    fn render(&self, clause: impl Fn(&Clause) -> String) -> String {
+        self.clauses.iter().map(clause).filter(|text| !text.is_empty()).collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// The next word or phrase of the query, with its occurrence and field, and the rest of the query.
+fn next_token(input: &str) -> (Token<'_>, &str) {
+    let (occur, unsigned) = match input.chars().next() {
+        Some('+') => (Occur::Must, &input[1..]),
+        Some('-') => (Occur::MustNot, &input[1..]),
+        _ => (Occur::Should, input),
+    };
+
+    // A lone sign is a word
+    let (occur, unsigned) = match unsigned.is_empty() || unsigned.starts_with(char::is_whitespace) {
+        true => (Occur::Should, input),
+        false => (occur, unsigned),
+    };
+
+    let (field, value) = match unsigned.split_once(':') {
+        Some((field, value)) if FIELDS.contains(&field) && !value.is_empty() && !value.starts_with(char::is_whitespace) => {
+            (Some(field), value)
+        }
+        _ => (None, unsigned),
+    };
+
+    match value.strip_prefix('"') {
+        Some(phrase) => {
+            let end = phrase.find('"').unwrap_or(phrase.len());
+            (Token { occur, field, text: &phrase[..end], phrase: true }, phrase.get(end + 1..).unwrap_or_default())
+        }
+        None => {
+            let end = value.find(char::is_whitespace).unwrap_or(value.len());
+            (Token { occur, field, text: &value[..end], phrase: false }, &value[end..])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::search::query::{Clause, Occur, ParsedQuery, TextField};
+
+    #[test]
+    fn test_parse() -> anyhow::Result<()> {
+        let parsed = ParsedQuery::parse(r#"source:github title:"release process" +oncall -archived meta:lang=rust tag:ops IndexWriter::new"#)?;
+        let clause = |occur, field, text: &str, phrase| Clause { occur, field, text: text.to_string(), phrase };
+
+        assert_eq!(
+            parsed,
+            ParsedQuery {
+                clauses: vec![
+                    clause(Occur::Should, Some(TextField::Title), "release process", true),
+                    clause(Occur::Must, None, "oncall", false),
+                    clause(Occur::MustNot, None, "archived", false),
+                    clause(Occur::Should, None, "IndexWriter::new", false),
+                ],
+                sources: vec!["github".to_string()],
+                path: None,
+                metadata: BTreeMap::from([("lang".to_string(), "rust".to_string())]),
+                tags: vec!["ops".to_string()],
+            }
+        );
+
+        assert_eq!(parsed.text(), r#""release process" oncall -archived IndexWriter::new"#);
+        assert_eq!(parsed.words(), "release process oncall IndexWriter::new");
+        assert_eq!(parsed.query_string(), r#"title:"release process" +oncall -archived IndexWriter\:\:new"#);
+
+        // Unclosed quote, lone sign
+        let parsed = ParsedQuery::parse(r#"- "failover steps"#)?;
+        assert_eq!(parsed.clauses, vec![clause(Occur::Should, None, "-", false), clause(Occur::Should, None, "failover steps", true)]);
+
+        assert!(ParsedQuery::parse("-source:github").is_err());
+        assert!(ParsedQuery::parse("meta:lang").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use redis::aio::MultiplexedConnection;
+use redis::{Client, Value};
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+
+use crate::model::Document;
+use crate::search::{html_snippet, FoundItem, IndexStats, SearchEngine, SearchRequest, SearchResult, HIGHLIGHT_END, HIGHLIGHT_START};
+use crate::sources::DocStream;
+use crate::utils::streams::channel_stream;
+
+/// Search engine storing documents as Redis hashes indexed by the RediSearch module.
+pub struct RedisSearchEngine {
+    client: Client,
+    index: String,
+    connection: OnceCell<MultiplexedConnection>,
+    index_created: OnceCell<()>,
+}
+
+impl RedisSearchEngine {
+    pub fn new(url: &str, index: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: Client::open(url).with_context(|| format!("Invalid redis url: {}", url))?,
+            index: index.to_string(),
+            connection: OnceCell::new(),
+            index_created: OnceCell::new(),
+        })
+    }
+
+    async fn connection(&self) -> anyhow::Result<MultiplexedConnection> {
+        let connection = self.connection
+            .get_or_try_init(|| async {
+                self.client
+                    .get_multiplexed_tokio_connection()
+                    .await
+                    .context("Couldn't connect to redis")
+            })
+            .await?;
+
+        Ok(connection.clone())
+    }
+
+    /// Documents are stored under `doks:<index>:<hash of the document id>`.
+    fn key_prefix(&self) -> String {
+        format!("doks:{}:", self.index)
+    }
+
+    fn key(&self, id: &str) -> String {
+        format!("{}{}", self.key_prefix(), hex::encode(Sha256::digest(id.as_bytes())))
+    }
+
+    async fn ensure_index(&self) -> anyhow::Result<()> {
+        let mut connection = self.connection().await?;
+
+        if redis::cmd("FT.INFO").arg(&self.index).query_async::<_, Value>(&mut connection).await.is_ok() {
+            return Ok(());
+        }
+
+        log::info!("Creating redisearch index: {}", &self.index);
+
+        redis::cmd("FT.CREATE")
+            .arg(&self.index)
+            .arg(&["ON", "HASH", "PREFIX", "1"])
+            .arg(self.key_prefix())
+            .arg(&["SCHEMA", "title", "TEXT", "WEIGHT", "2.0", "content", "TEXT", "source", "TAG"])
+            .query_async::<_, ()>(&mut connection)
+            .await
+            .with_context(|| format!("Couldn't create redisearch index: {}", self.index))?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SearchEngine for RedisSearchEngine {
+    async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+        self.index_created.get_or_try_init(|| self.ensure_index()).await?;
+
+        let mut pipeline = redis::pipe();
+
+        for document in &documents {
+            log::info!("Indexing document: {} (source: {})", document.link, document.source);
+
+            // The metadata isn't in the schema: it's stored as JSON but not indexed
+            let key = self.key(&document.id);
+            let metadata = serde_json::to_string(&document.metadata)?;
+
+            pipeline
+                .hset_multiple(
+                    key,
+                    &[
+                        ("doc_id", document.id.as_str()),
+                        ("source", document.source.as_str()),
+                        ("title", document.title.as_str()),
+                        ("link", document.link.as_str()),
+                        ("content", document.content.as_str()),
+                        ("metadata", metadata.as_str()),
+                    ],
+                )
+                .ignore();
+        }
+
+        pipeline.query_async::<_, ()>(&mut self.connection().await?).await?;
+
+        Ok(())
+    }
+
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        let (parsed, request) = request.parse_query()?;
+        request.ensure_no_document_filters()?;
+        request.ensure_no_dates()?;
+        request.ensure_relevance_sort()?;
+        request.ensure_not_fuzzy()?;
+        request.ensure_no_source_boosts()?;
+
+        let query = if request.source_filter.is_empty() {
+            parsed.text()
+        } else {
+            format!("({}) {}", parsed.text(), source_filter(&request.source_filter))
+        };
+
+        let mut command = redis::cmd("FT.SEARCH");
+        command
+            .arg(&self.index)
+            .arg(query)
+            .arg("WITHSCORES")
+            .arg(&["RETURN", "5", "doc_id", "source", "title", "link", "content"])
+            .arg(&["SUMMARIZE", "FIELDS", "1", "content", "FRAGS", "1", "LEN", "30"])
+            .arg(&["HIGHLIGHT", "FIELDS", "1", "content", "TAGS", HIGHLIGHT_START, HIGHLIGHT_END])
+            .arg("LIMIT")
+            .arg(request.offset)
+            .arg(request.limit);
+
+        let mut connection = self.connection().await?;
+
+        let stream = channel_stream(|tx| async move {
+            let response = command.query_async::<_, Value>(&mut connection).await?;
+
+            for item in parse_search_response(&response)? {
+                tx.send(Ok(item)).await?;
+            }
+
+            Ok(())
+        });
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        let mut connection = self.connection().await?;
+
+        // `DD` also deletes the documents. The index is created again so it stays usable.
+        let dropped = redis::cmd("FT.DROPINDEX")
+            .arg(&self.index)
+            .arg("DD")
+            .query_async::<_, ()>(&mut connection)
+            .await;
+
+        if let Err(err) = dropped {
+            if !err.to_string().to_lowercase().contains("unknown index") {
+                return Err(err.into());
+            }
+        }
+
+        self.ensure_index().await
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        let mut connection = self.connection().await?;
+
+        loop {
+            let response = redis::cmd("FT.SEARCH")
+                .arg(&self.index)
+                .arg(source_filter(&[source.to_string()]))
+                .arg(&["NOCONTENT", "LIMIT", "0", "1000"])
+                .query_async::<_, Vec<Value>>(&mut connection)
+                .await?;
+
+            // [total, key, key, ...]
+            let keys = response
+                .get(1..)
+                .unwrap_or_default()
+                .iter()
+                .map(redis::from_redis_value::<String>)
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if keys.is_empty() {
+                return Ok(());
+            }
+
+            redis::cmd("DEL").arg(keys).query_async::<_, ()>(&mut connection).await?;
+        }
+    }
+
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        let mut connection = self.connection().await?;
+        let mut documents = vec![];
+        let mut cursor = 0u64;
+
+        loop {
+            let (next, keys) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg(&["MATCH", &format!("{}*", self.key_prefix()), "COUNT", "1000"])
+                .query_async::<_, (u64, Vec<String>)>(&mut connection)
+                .await?;
+
+            let mut pipeline = redis::pipe();
+            for key in &keys {
+                pipeline.hgetall(key);
+            }
+
+            for hash in pipeline.query_async::<_, Vec<HashMap<String, String>>>(&mut connection).await? {
+                // Deleted since it was scanned
+                if !hash.is_empty() {
+                    documents.push(parse_hash(hash)?);
+                }
+            }
+
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+
+        Ok(Box::pin(tokio_stream::iter(documents.into_iter().map(Ok))))
+    }
+
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        let mut connection = self.connection().await?;
+
+        for batch in ids.chunks(1000) {
+            let keys = batch.iter().map(|id| self.key(id)).collect::<Vec<_>>();
+            redis::cmd("DEL").arg(keys).query_async::<_, ()>(&mut connection).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        let response = redis::cmd("FT.AGGREGATE")
+            .arg(&self.index)
+            .arg("*")
+            .arg(&["GROUPBY", "1", "@source", "REDUCE", "COUNT", "0", "AS", "count"])
+            .query_async::<_, Value>(&mut self.connection().await?)
+            .await?;
+
+        parse_aggregate_response(&response)
+    }
+}
+
+/// Builds a tag clause matching any of the sources. Punctuation and spaces must be escaped in tags.
+fn source_filter(sources: &[String]) -> String {
+    let escape = |source: &String| {
+        source
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '_' { c.to_string() } else { format!("\\{}", c) })
+            .collect::<String>()
+    };
+
+    format!("@source:{{{}}}", sources.iter().map(escape).collect::<Vec<_>>().join(" | "))
+}
+
+/// Parses a document hash. The documents indexed before their metadata was stored have none.
+fn parse_hash(mut fields: HashMap<String, String>) -> anyhow::Result<Document> {
+    let id = fields.remove("doc_id").with_context(|| format!("Document without id: {:?}", fields))?;
+    let metadata = match fields.remove("metadata") {
+        Some(metadata) => serde_json::from_str(&metadata).with_context(|| format!("Invalid metadata: {}", metadata))?,
+        None => HashMap::new(),
+    };
+    let mut field = |name: &str| fields.remove(name).unwrap_or_default();
+
+    Ok(Document {
+        id,
+        source: field("source"),
+        title: field("title"),
+        link: field("link"),
+        content: field("content"),
+        metadata,
+        tags: vec![],
+        modified: None,
+    })
+}
+
+/// Parses the reply of `FT.SEARCH ... WITHSCORES`: `[total, key, score, [field, value, ...], ...]`.
+fn parse_search_response(response: &Value) -> anyhow::Result<Vec<FoundItem>> {
+    let items = match response {
+        Value::Bulk(items) => items,
+        other => bail!("Unexpected redisearch response: {:?}", other),
+    };
+
+    items
+        .get(1..)
+        .unwrap_or_default()
+        .chunks(3)
+        .map(|hit| {
+            let (score, fields) = match hit {
+                [_, score, fields] => (score, fields),
+                _ => bail!("Unexpected redisearch hit: {:?}", hit),
+            };
+
+            let fields = redis::from_redis_value::<HashMap<String, String>>(fields)?;
+            let field = |name: &str| -> anyhow::Result<String> {
+                fields
+                    .get(name)
+                    .cloned()
+                    .with_context(|| format!("Field {} of type text not found in hit: {:?}", name, fields))
+            };
+
+            Ok(FoundItem {
+                id: field("doc_id")?,
+                score: redis::from_redis_value::<f32>(score)?,
+                source: field("source")?,
+                title: field("title")?,
+                link: field("link")?,
+                snippet: fields.get("content").map(|content| html_snippet(content)).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Parses the reply of the `FT.AGGREGATE` grouping by source: `[groups, [source, <source>, count, <count>], ...]`.
+fn parse_aggregate_response(response: &Value) -> anyhow::Result<IndexStats> {
+    let groups = match response {
+        Value::Bulk(groups) => groups,
+        other => bail!("Unexpected redisearch response: {:?}", other),
+    };
+
+    let sources = groups
+        .get(1..)
+        .unwrap_or_default()
+        .iter()
+        .map(|group| {
+            let fields = redis::from_redis_value::<HashMap<String, String>>(group)?;
+
+            match (fields.get("source"), fields.get("count").and_then(|c| c.parse().ok())) {
+                (Some(source), Some(count)) => Ok((source.to_string(), count)),
+                _ => bail!("Unexpected redisearch group: {:?}", fields),
+            }
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok(IndexStats::from_sources(sources))
+}
+
+#[cfg(test)]
+mod tests {
+    use redis::Value;
+
+    use crate::search::redis_impl::{parse_hash, parse_search_response, source_filter};
+
+    fn data(value: &str) -> Value {
+        Value::Data(value.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_parse_search_response() -> anyhow::Result<()> {
+        let response = Value::Bulk(vec![
+            Value::Int(1),
+            data("doks:doks:abc"),
+            data("1.5"),
+            Value::Bulk(vec![
+                data("doc_id"), data("1"),
+                data("source"), data("src"),
+                data("title"), data("Hello"),
+                data("link"), data("link1"),
+                data("content"), data("\u{2}Hello\u{3} <content>... "),
+            ]),
+        ]);
+
+        let hits = parse_search_response(&response)?;
+
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, "1");
+        assert_eq!(hits[0].score, 1.5);
+        assert_eq!(hits[0].snippet, "<b>Hello</b> &lt;content&gt;... ");
+        assert!(parse_search_response(&Value::Okay).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_source_filter() {
+        let sources = vec!["github".to_string(), "my-docs v2".to_string()];
+
+        assert_eq!(source_filter(&sources), "@source:{github | my\\-docs\\ v2}");
+    }
+    #[test]
+    fn test_parse_hash() -> anyhow::Result<()> {
+        let hash = |fields: &[(&str, &str)]| fields.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+
+        let document = parse_hash(hash(&[("doc_id", "1"), ("source", "src"), ("title", "Hello"), ("metadata", "{\"repository\":\"doks\"}")]))?;
+        assert_eq!(document.id, "1");
+        assert_eq!(document.metadata.get("repository").map(String::as_str), Some("doks"));
+
+        assert!(parse_hash(hash(&[("doc_id", "2"), ("source", "src")]))?.metadata.is_empty());
+        assert!(parse_hash(hash(&[("source", "src")])).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::{Client, Method, RequestBuilder};
+use serde::de::DeserializeOwned;
+
+use crate::model::Document;
+use crate::search::{IndexStats, SearchEngine, SearchRequest, SearchResult};
+use crate::sources::DocStream;
+use crate::utils::streams::channel_stream;
+
+/// Proxies index and search calls to another doks instance running `doks serve`.
+pub struct RemoteSearchEngine {
+    client: Client,
+    endpoint: String,
+    token: Option<String>,
+}
+
+impl RemoteSearchEngine {
+    pub fn new(endpoint: &str, token: Option<String>) -> Self {
+        Self {
+            client: Client::new(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.client.request(method, format!("{}/{}", self.endpoint, path));
+
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+}
+
+#[async_trait]
+impl SearchEngine for RemoteSearchEngine {
+    async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+        self.request(Method::POST, "index")
+            .json(&documents)
+            .send()
+            .await
+            .with_context(|| format!("Couldn't reach remote doks server: {}", self.endpoint))?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        let http_request = self.request(Method::POST, "search").json(request);
+        let endpoint = self.endpoint.clone();
+
+        let stream = channel_stream(|tx| async move {
+            let response = http_request
+                .send()
+                .await
+                .with_context(|| format!("Couldn't reach remote doks server: {}", endpoint))?
+                .error_for_status()?
+                .text()
+                .await?;
+
+            for item in parse_json_lines(&response)? {
+                tx.send(Ok(item)).await?;
+            }
+
+            Ok(())
+        });
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        self.request(Method::POST, "purge")
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        self.request(Method::POST, "purge")
+            .query(&[("source", source)])
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        let response = self.request(Method::GET, "documents")
+            .send()
+            .await
+            .with_context(|| format!("Couldn't reach remote doks server: {}", self.endpoint))?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        let documents = parse_json_lines::<Document>(&response)?;
+
+        Ok(Box::pin(tokio_stream::iter(documents.into_iter().map(Ok))))
+    }
+
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        self.request(Method::POST, "delete")
+            .json(ids)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        let stats = self.request(Method::GET, "stats")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(stats)
+    }
+}
+
+/// Search results and documents are served as newline delimited JSON.
+fn parse_json_lines<T: DeserializeOwned>(body: &str) -> anyhow::Result<Vec<T>> {
+    body.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str::<T>(line)
+                .with_context(|| format!("Couldn't parse line: {}", line))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::search::FoundItem;
+    use crate::search::remote_impl::parse_json_lines;
+
+    #[test]
+    fn test_parse_json_lines() -> anyhow::Result<()> {
+        let body = r#"{"id":"1","score":1.5,"source":"src","title":"Hello","link":"link1","snippet":"<b>Hello</b>"}
+{"id":"2","score":0.5,"source":"src","title":"World","link":"link2","snippet":""}
+"#;
+
+        let items = parse_json_lines::<FoundItem>(body)?;
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].id, "2");
+        assert!(parse_json_lines::<FoundItem>("not json").is_err());
+
+        Ok(())
+    }
+}
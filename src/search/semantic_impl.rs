@@ -0,0 +1,499 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::model::Document;
+use crate::search::{escape_html, FoundItem, IndexStats, SearchEngine, SearchRequest, SearchResult};
+use crate::search::embeddings::EmbeddingProvider;
+use crate::sources::DocStream;
+
+const EMBEDDING_BATCH_SIZE: usize = 32;
+/// Log of the `StoreEntry`s, one JSON object per line, compacted when the engine is opened.
+const STORE_FILE: &str = "vectors.jsonl";
+/// The whole list of chunks, rewritten on every change by the previous versions.
+const LEGACY_STORE_FILE: &str = "vectors.json";
+
+/// Semantic search engine: documents are split in chunks that are embedded using the configured
+/// provider and queries are answered with the nearest chunks (cosine similarity).
+pub struct SemanticSearchEngine {
+    path: PathBuf,
+    embedder: Arc<dyn EmbeddingProvider>,
+    chunks: Arc<RwLock<Vec<Chunk>>>,
+    chunk_size: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Chunk {
+    document_id: String,
+    source: String,
+    title: String,
+    link: String,
+    text: String,
+    vector: Vec<f32>,
+    /// Missing from the chunks stored by the previous versions.
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+}
+
+/// The changes appended to the store, so that indexing a batch only writes its own chunks.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum StoreEntry {
+    /// The chunks of the documents, replacing their previous ones.
+    Index { document_ids: Vec<String>, chunks: Vec<Chunk> },
+    /// Removes the chunks of the documents.
+    Delete { document_ids: Vec<String> },
+    /// Removes the chunks of the source, or all of them.
+    Purge { source: Option<String> },
+}
+
+impl StoreEntry {
+    fn apply(self, chunks: &mut Vec<Chunk>) {
+        match self {
+            StoreEntry::Index { document_ids, chunks: new_chunks } => {
+                let ids = document_ids.iter().map(String::as_str).collect::<HashSet<_>>();
+                chunks.retain(|chunk| !ids.contains(chunk.document_id.as_str()));
+                chunks.extend(new_chunks);
+            }
+            StoreEntry::Delete { document_ids } => {
+                let ids = document_ids.iter().map(String::as_str).collect::<HashSet<_>>();
+                chunks.retain(|chunk| !ids.contains(chunk.document_id.as_str()));
+            }
+            StoreEntry::Purge { source: Some(source) } => chunks.retain(|chunk| chunk.source != source),
+            StoreEntry::Purge { source: None } => chunks.clear(),
+        }
+    }
+}
+
+impl SemanticSearchEngine {
+    pub fn new<T: AsRef<Path>>(path: T, embedder: Arc<dyn EmbeddingProvider>, chunk_size: usize) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+
+        if !path.exists() {
+            std::fs::create_dir_all(path)?;
+        }
+
+        let chunks = load(path)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            embedder,
+            chunks: Arc::new(RwLock::new(chunks)),
+            chunk_size,
+        })
+    }
+
+    /// Appends the entry to the store then applies it to the chunks in memory. This blocks, so it
+    /// runs on a blocking task.
+    async fn update(&self, entry: StoreEntry) -> anyhow::Result<()> {
+        let (chunks, path) = (self.chunks.clone(), self.path.clone());
+
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            // Held while appending so that the entries are written in the order they're applied
+            let mut chunks = chunks.write().unwrap();
+            append(&path, &entry)?;
+            entry.apply(&mut chunks);
+            Ok(())
+        }).await?
+    }
+}
+
+/// Replays the store of the directory, then compacts it to a single entry.
+fn load(path: &Path) -> anyhow::Result<Vec<Chunk>> {
+    let (store, legacy) = (path.join(STORE_FILE), path.join(LEGACY_STORE_FILE));
+
+    if !store.exists() {
+        if !legacy.exists() {
+            return Ok(vec![]);
+        }
+
+        let chunks: Vec<Chunk> = serde_json::from_slice(&std::fs::read(&legacy)?)
+            .with_context(|| format!("Couldn't read vectors store: {:?}", legacy))?;
+        compact(path, &chunks)?;
+        std::fs::remove_file(legacy)?;
+
+        return Ok(chunks);
+    }
+
+    let content = std::fs::read_to_string(&store)?;
+    let lines = content.lines().filter(|line| !line.trim().is_empty()).collect::<Vec<_>>();
+    let (mut chunks, mut truncated) = (vec![], false);
+
+    for (i, line) in lines.iter().enumerate() {
+        match serde_json::from_str::<StoreEntry>(line) {
+            Ok(entry) => entry.apply(&mut chunks),
+            // Left by a write that was interrupted
+            Err(err) if i == lines.len() - 1 => {
+                log::warn!("Ignoring the truncated end of the vectors store {:?}: {}", store, err);
+                truncated = true;
+            }
+            Err(err) => return Err(err).with_context(|| format!("Couldn't read vectors store: {:?}", store)),
+        }
+    }
+
+    // The next entries mustn't be appended to a truncated line
+    if lines.len() > 1 || truncated {
+        compact(path, &chunks)?;
+    }
+
+    Ok(chunks)
+}
+
+/// Rewrites the store with the chunks as a single entry (written aside then renamed, so that an
+/// interrupted write keeps the previous store).
+fn compact(path: &Path, chunks: &[Chunk]) -> anyhow::Result<()> {
+    let entry = StoreEntry::Index { document_ids: vec![], chunks: chunks.to_vec() };
+    let tmp = path.join(format!("{}.tmp", STORE_FILE));
+    let mut content = serde_json::to_vec(&entry)?;
+    content.push(b'\n');
+
+    std::fs::write(&tmp, content)?;
+    std::fs::rename(&tmp, path.join(STORE_FILE))?;
+    Ok(())
+}
+
+fn append(path: &Path, entry: &StoreEntry) -> anyhow::Result<()> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+
+    let mut store = OpenOptions::new().create(true).append(true).open(path.join(STORE_FILE))?;
+    store.write_all(&line)?;
+    Ok(())
+}
+
+#[async_trait]
+impl SearchEngine for SemanticSearchEngine {
+    async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+        let embedder = self.embedder.clone();
+        let chunk_size = self.chunk_size;
+
+        let task = tokio::task::spawn_blocking(move || -> anyhow::Result<StoreEntry> {
+            let mut new_chunks = vec![];
+
+            for document in &documents {
+                log::info!("Indexing document: {} (source: {})", document.link, document.source);
+
+                for text in chunk_text(&document.content, chunk_size) {
+                    new_chunks.push(Chunk {
+                        document_id: document.id.clone(),
+                        source: document.source.clone(),
+                        title: document.title.clone(),
+                        link: document.link.clone(),
+                        text,
+                        vector: vec![],
+                        metadata: document.metadata.clone(),
+                    });
+                }
+            }
+
+            for batch in new_chunks.chunks_mut(EMBEDDING_BATCH_SIZE) {
+                let texts = batch
+                    .iter()
+                    .map(|chunk| format!("{}\n{}", chunk.title, chunk.text))
+                    .collect::<Vec<_>>();
+
+                for (chunk, vector) in batch.iter_mut().zip(embedder.embed(&texts)?) {
+                    chunk.vector = vector;
+                }
+            }
+
+            let document_ids = documents.into_iter().map(|document| document.id).collect();
+
+            Ok(StoreEntry::Index { document_ids, chunks: new_chunks })
+        });
+
+        self.update(task.await??).await
+    }
+
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        let (parsed, request) = request.parse_query()?;
+        request.ensure_no_document_filters()?;
+        request.ensure_no_dates()?;
+        request.ensure_relevance_sort()?;
+        request.ensure_no_source_boosts()?;
+
+        let embedder = self.embedder.clone();
+        let chunks = self.chunks.clone();
+        let query = parsed.words();
+        let (results_tx, results_rx) = tokio::sync::mpsc::channel(64);
+
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            let found = embedder
+                .embed(std::slice::from_ref(&query))
+                .map(|mut vectors| {
+                    let chunks = chunks.read().unwrap();
+                    let candidates = chunks.iter().filter(|chunk| request.accepts_source(&chunk.source));
+
+                    nearest(candidates, &vectors.remove(0), request.offset + request.limit)
+                        .into_iter()
+                        .skip(request.offset)
+                        .collect::<Vec<_>>()
+                });
+
+            match found {
+                Ok(items) => {
+                    for item in items {
+                        results_tx.blocking_send(Ok(item))?;
+                    }
+                }
+                Err(err) => results_tx.blocking_send(Err(err))?,
+            }
+
+            Ok(())
+        });
+
+        Ok(Box::pin(tokio_stream::wrappers::ReceiverStream::new(results_rx)))
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        self.update(StoreEntry::Purge { source: None }).await
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        self.update(StoreEntry::Purge { source: Some(source.to_string()) }).await
+    }
+
+    /// The documents are gathered from their chunks: their content is made of the overlapping texts
+    /// of the chunks.
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        let chunks = self.chunks.read().unwrap();
+        let mut documents: Vec<Document> = vec![];
+        let mut positions = HashMap::new();
+
+        for chunk in chunks.iter() {
+            match positions.get(&chunk.document_id) {
+                Some(&position) => {
+                    let document: &mut Document = &mut documents[position];
+                    document.content.push('\n');
+                    document.content.push_str(&chunk.text);
+                }
+                None => {
+                    positions.insert(chunk.document_id.clone(), documents.len());
+                    documents.push(Document {
+                        id: chunk.document_id.clone(),
+                        source: chunk.source.clone(),
+                        title: chunk.title.clone(),
+                        link: chunk.link.clone(),
+                        content: chunk.text.clone(),
+                        metadata: chunk.metadata.clone(),
+                        tags: vec![],
+                        modified: None,
+                    });
+                }
+            }
+        }
+
+        Ok(Box::pin(tokio_stream::iter(documents.into_iter().map(Ok))))
+    }
+
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        self.update(StoreEntry::Delete { document_ids: ids.to_vec() }).await
+    }
+
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        let chunks = self.chunks.read().unwrap();
+        let mut sources = BTreeMap::new();
+
+        for (source, _) in chunks.iter().map(|c| (c.source.as_str(), c.document_id.as_str())).collect::<HashSet<_>>() {
+            *sources.entry(source.to_string()).or_default() += 1;
+        }
+
+        Ok(IndexStats::from_sources(sources))
+    }
+}
+
+/// Splits the text in chunks of `size` words overlapping by a quarter of their size.
+pub(crate) fn chunk_text(text: &str, size: usize) -> Vec<String> {
+    let words = text.split_whitespace().collect::<Vec<_>>();
+    let size = size.max(1);
+    let step = (size - size / 4).max(1);
+
+    if words.is_empty() {
+        return vec![];
+    }
+
+    let mut chunks = vec![];
+    let mut start = 0;
+
+    loop {
+        let end = (start + size).min(words.len());
+        chunks.push(words[start..end].join(" "));
+
+        if end == words.len() {
+            break;
+        }
+
+        start += step;
+    }
+
+    chunks
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot = a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// Returns the documents owning the closest chunks. A document is scored by its best chunk.
+fn nearest<'a>(chunks: impl IntoIterator<Item=&'a Chunk>, query: &[f32], limit: usize) -> Vec<FoundItem> {
+    let mut best = HashMap::<&str, (f32, &Chunk)>::new();
+
+    for chunk in chunks {
+        let score = cosine_similarity(&chunk.vector, query);
+        let entry = best.entry(chunk.document_id.as_str()).or_insert((score, chunk));
+
+        if score > entry.0 {
+            *entry = (score, chunk);
+        }
+    }
+
+    let mut found = best.into_values().collect::<Vec<_>>();
+    found.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    found
+        .into_iter()
+        .take(limit)
+        .map(|(score, chunk)| FoundItem {
+            id: chunk.document_id.clone(),
+            score,
+            source: chunk.source.clone(),
+            title: chunk.title.clone(),
+            link: chunk.link.clone(),
+            snippet: escape_html(&chunk.text),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::Write;
+    use std::sync::Arc;
+
+    use tempdir::TempDir;
+    use tokio_stream::StreamExt;
+
+    use crate::model::Document;
+    use crate::search::embeddings::EmbeddingProvider;
+    use crate::search::semantic_impl::{Chunk, chunk_text, nearest, SemanticSearchEngine, STORE_FILE};
+    use crate::search::{SearchEngine, SearchRequest};
+
+    /// Counts the occurrences of a few words, enough to tell the test documents apart.
+    struct WordCounts;
+
+    impl EmbeddingProvider for WordCounts {
+        fn embed(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+            let words = ["database", "network", "deploy"];
+
+            Ok(texts
+                .iter()
+                .map(|text| words.iter().map(|word| text.to_lowercase().matches(word).count() as f32).collect())
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_chunk_text() {
+        let text = (1..=10).map(|i| i.to_string()).collect::<Vec<_>>().join(" ");
+
+        assert_eq!(chunk_text(&text, 4), vec!["1 2 3 4", "4 5 6 7", "7 8 9 10"]);
+        assert_eq!(chunk_text(&text, 20), vec![text.clone()]);
+        assert!(chunk_text("  ", 4).is_empty());
+    }
+
+    #[test]
+    fn test_nearest() {
+        let chunk = |id: &str, text: &str, vector: Vec<f32>| Chunk {
+            document_id: id.to_string(),
+            source: "src".to_string(),
+            title: id.to_string(),
+            link: id.to_string(),
+            text: text.to_string(),
+            vector,
+            metadata: HashMap::new(),
+        };
+
+        let chunks = vec![
+            chunk("doc1", "first", vec![1.0, 0.0]),
+            chunk("doc1", "second", vec![0.7, 0.7]),
+            chunk("doc2", "other", vec![0.0, 1.0]),
+        ];
+
+        let found = nearest(&chunks, &[1.0, 0.1], 10);
+
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].id, "doc1");
+        assert_eq!(found[0].snippet, "first");
+        assert_eq!(found[1].id, "doc2");
+    }
+
+    #[tokio::test]
+    async fn test_store() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let document = |id: &str, source: &str, content: &str| Document {
+            id: id.to_string(),
+            source: source.to_string(),
+            title: id.to_string(),
+            link: id.to_string(),
+            content: content.to_string(),
+            metadata: HashMap::new(),
+            tags: vec![],
+            modified: None,
+        };
+
+        let engine = SemanticSearchEngine::new(root.path(), Arc::new(WordCounts), 50)?;
+        engine.index(vec![document("runbook", "docs", "Restart the database")]).await?;
+        engine.index(vec![document("network", "wiki", "Check the network")]).await?;
+        engine.index(vec![document("runbook", "docs", "Deploy the service")]).await?;
+        engine.purge_source("wiki").await?;
+
+        // Each change is appended to the store
+        assert_eq!(std::fs::read_to_string(root.path().join(STORE_FILE))?.lines().count(), 4);
+        drop(engine);
+
+        let engine = SemanticSearchEngine::new(root.path(), Arc::new(WordCounts), 50)?;
+        let results = engine.search(&SearchRequest::new("deploy")).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+        assert_eq!(results.iter().map(|item| item.id.as_str()).collect::<Vec<_>>(), vec!["runbook"]);
+        assert_eq!(results[0].snippet, "Deploy the service");
+        assert_eq!(engine.stats().await?.documents, 1);
+        assert_eq!(std::fs::read_to_string(root.path().join(STORE_FILE))?.lines().count(), 1);
+
+        // A write interrupted midway is ignored
+        std::fs::OpenOptions::new().append(true).open(root.path().join(STORE_FILE))?.write_all(b"{\"op\":\"ind")?;
+        drop(engine);
+
+        let engine = SemanticSearchEngine::new(root.path(), Arc::new(WordCounts), 50)?;
+        engine.index(vec![document("network", "wiki", "Check the network")]).await?;
+        drop(engine);
+
+        let engine = SemanticSearchEngine::new(root.path(), Arc::new(WordCounts), 50)?;
+        assert_eq!(engine.stats().await?.documents, 2);
+
+        let mut setup = document("setup", "docs", "Install the database");
+        setup.metadata.insert("repository".to_string(), "doks".to_string());
+        engine.index(vec![setup]).await?;
+        engine.delete(&["network".to_string()]).await?;
+
+        let documents = engine.documents().await?.collect::<anyhow::Result<Vec<_>>>().await?;
+        assert_eq!(documents.iter().map(|document| document.id.as_str()).collect::<Vec<_>>(), vec!["runbook", "setup"]);
+        assert_eq!(documents[1].metadata.get("repository").map(String::as_str), Some("doks"));
+
+        engine.purge().await?;
+        assert_eq!(engine.stats().await?.documents, 0);
+
+        Ok(())
+    }
+}
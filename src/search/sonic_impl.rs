@@ -0,0 +1,485 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use rusqlite::{Connection, OptionalExtension, params};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::model::Document;
+use crate::search::{escape_html, FoundItem, IndexStats, SearchEngine, SearchRequest, SearchResult};
+use crate::sources::DocStream;
+use crate::utils::streams::channel_stream;
+
+const BUCKET: &str = "default";
+/// Default maximum of results returned by sonic for a query (`query_limit_maximum`).
+const MAX_FILTERED_CANDIDATES: usize = 100;
+
+/// Search engine using the Sonic search backend for the inverted index. Sonic only stores object
+/// identifiers so the documents are kept in a local SQLite store to hydrate the results.
+pub struct SonicSearchEngine {
+    address: String,
+    password: String,
+    collection: String,
+    store: Arc<Mutex<Connection>>,
+}
+
+impl SonicSearchEngine {
+    pub fn new<T: AsRef<Path>>(address: &str, password: String, collection: &str, store_path: T) -> anyhow::Result<Self> {
+        let store_path = store_path.as_ref();
+
+        if let Some(parent) = store_path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+
+        let store = Connection::open(store_path)?;
+        store.execute_batch(
+            "CREATE TABLE IF NOT EXISTS documents (
+                object TEXT PRIMARY KEY,
+                id TEXT NOT NULL,
+                source TEXT NOT NULL,
+                title TEXT NOT NULL,
+                link TEXT NOT NULL,
+                content TEXT NOT NULL,
+                metadata TEXT NOT NULL DEFAULT '{}'
+            );"
+        )?;
+
+        // The stores created before the metadata was kept don't have the column
+        let has_metadata = store.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('documents') WHERE name = 'metadata'",
+            [],
+            |row| row.get::<_, u64>(0),
+        )? > 0;
+        if !has_metadata {
+            store.execute("ALTER TABLE documents ADD COLUMN metadata TEXT NOT NULL DEFAULT '{}'", [])?;
+        }
+
+        Ok(Self {
+            address: address.to_string(),
+            password,
+            collection: collection.to_string(),
+            store: Arc::new(Mutex::new(store)),
+        })
+    }
+}
+
+#[async_trait]
+impl SearchEngine for SonicSearchEngine {
+    async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+        let mut channel = SonicChannel::start(&self.address, "ingest", &self.password).await?;
+
+        for document in &documents {
+            log::info!("Indexing document: {} (source: {})", document.link, document.source);
+
+            let object = object_id(&document.id);
+
+            channel.command(&format!("FLUSHO {} {} {}", self.collection, BUCKET, object)).await?;
+
+            let text = format!("{}\n{}", document.title, document.content);
+            for chunk in split_text(&text, channel.max_text_size()) {
+                channel
+                    .expect_ok(&format!("PUSH {} {} {} \"{}\"", self.collection, BUCKET, object, escape(chunk)))
+                    .await?;
+            }
+        }
+
+        channel.quit().await?;
+
+        let store = self.store.clone();
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            let mut store = store.lock().unwrap();
+            let transaction = store.transaction()?;
+
+            for document in documents {
+                transaction.execute(
+                    "INSERT OR REPLACE INTO documents (object, id, source, title, link, content, metadata) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        object_id(&document.id),
+                        document.id,
+                        document.source,
+                        document.title,
+                        document.link,
+                        document.content,
+                        serde_json::to_string(&document.metadata)?,
+                    ],
+                )?;
+            }
+
+            transaction.commit()?;
+            Ok(())
+        }).await?
+    }
+
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        let (parsed, request) = request.parse_query()?;
+        request.ensure_no_document_filters()?;
+        request.ensure_no_dates()?;
+        request.ensure_relevance_sort()?;
+        request.ensure_no_source_boosts()?;
+
+        let address = self.address.clone();
+        let password = self.password.clone();
+        let collection = self.collection.clone();
+        let store = self.store.clone();
+        // Sonic has no query syntax
+        let query = parsed.words();
+
+        let stream = channel_stream(|tx| async move {
+            let mut channel = SonicChannel::start(&address, "search", &password).await?;
+
+            // Sonic doesn't know about sources: filtered searches are done on the top results
+            let (limit, offset) = if request.source_filter.is_empty() {
+                (request.limit, request.offset)
+            } else {
+                (MAX_FILTERED_CANDIDATES, 0)
+            };
+
+            let pending = channel
+                .command(&format!(
+                    "QUERY {} {} \"{}\" LIMIT({}) OFFSET({})",
+                    collection, BUCKET, escape(&query), limit, offset,
+                ))
+                .await?;
+            if !pending.starts_with("PENDING") {
+                bail!("Unexpected sonic response: {}", pending);
+            }
+
+            let event = channel.read_line().await?;
+            channel.quit().await?;
+
+            // EVENT QUERY <marker> <object> <object> ...
+            let objects = event.split_whitespace().skip(3).map(|o| o.to_string()).collect::<Vec<_>>();
+            let terms = query.split_whitespace().map(|t| t.to_lowercase()).collect::<Vec<_>>();
+            let mut rank = offset;
+
+            for object in objects {
+                let store = store.clone();
+                let document = tokio::task::spawn_blocking(move || {
+                    store.lock().unwrap()
+                        .query_row(
+                            "SELECT id, source, title, link, content FROM documents WHERE object = ?1",
+                            params![object],
+                            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get(2)?, row.get(3)?, row.get::<_, String>(4)?)),
+                        )
+                        .optional()
+                }).await??;
+
+                let (id, source, title, link, content) = match document {
+                    Some(document) if request.accepts_source(&document.1) => document,
+                    _ => continue,
+                };
+
+                rank += 1;
+
+                if rank <= request.offset {
+                    continue;
+                }
+
+                if rank > request.offset + request.limit {
+                    break;
+                }
+
+                tx.send(Ok(FoundItem {
+                    id,
+                    score: 1.0 / rank as f32,
+                    source,
+                    title,
+                    link,
+                    snippet: snippet(&content, &terms, 200),
+                })).await?;
+            }
+
+            Ok(())
+        });
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        let mut channel = SonicChannel::start(&self.address, "ingest", &self.password).await?;
+        channel.command(&format!("FLUSHC {}", self.collection)).await?;
+        channel.quit().await?;
+
+        let store = self.store.clone();
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            store.lock().unwrap().execute("DELETE FROM documents", [])?;
+            Ok(())
+        }).await?
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        let store = self.store.clone();
+        let source = source.to_string();
+        let query_source = source.clone();
+
+        let objects = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<String>> {
+            let store = store.lock().unwrap();
+            let mut statement = store.prepare("SELECT object FROM documents WHERE source = ?1")?;
+            let objects = statement.query_map(params![query_source], |row| row.get(0))?.collect::<Result<_, _>>()?;
+            Ok(objects)
+        }).await??;
+
+        let mut channel = SonicChannel::start(&self.address, "ingest", &self.password).await?;
+        for object in objects {
+            channel.command(&format!("FLUSHO {} {} {}", self.collection, BUCKET, object)).await?;
+        }
+        channel.quit().await?;
+
+        let store = self.store.clone();
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            store.lock().unwrap().execute("DELETE FROM documents WHERE source = ?1", params![source])?;
+            Ok(())
+        }).await?
+    }
+
+    /// Read from the local store, which holds every document pushed to sonic.
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        let store = self.store.clone();
+
+        let documents = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<anyhow::Result<Document>>> {
+            let store = store.lock().unwrap();
+            let mut statement = store.prepare("SELECT id, source, title, link, content, metadata FROM documents")?;
+            let rows = statement.query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get::<_, String>(5)?))
+            })?;
+
+            Ok(
+                rows.map(|row| {
+                    let (id, source, title, link, content, metadata) = row?;
+
+                    Ok(Document { id, source, title, link, content, metadata: serde_json::from_str(&metadata)?, tags: vec![], modified: None })
+                })
+                .collect()
+            )
+        }).await??;
+
+        Ok(Box::pin(tokio_stream::iter(documents)))
+    }
+
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        let mut channel = SonicChannel::start(&self.address, "ingest", &self.password).await?;
+        for id in ids {
+            channel.command(&format!("FLUSHO {} {} {}", self.collection, BUCKET, object_id(id))).await?;
+        }
+        channel.quit().await?;
+
+        let store = self.store.clone();
+        let ids = serde_json::to_string(ids)?;
+
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            store.lock().unwrap().execute("DELETE FROM documents WHERE id IN (SELECT value FROM json_each(?1))", params![ids])?;
+            Ok(())
+        }).await?
+    }
+
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        let store = self.store.clone();
+
+        tokio::task::spawn_blocking(move || -> anyhow::Result<IndexStats> {
+            let store = store.lock().unwrap();
+            let mut statement = store.prepare("SELECT source, COUNT(*) FROM documents GROUP BY source")?;
+            let sources = statement
+                .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?)))?
+                .collect::<Result<_, _>>()?;
+
+            Ok(IndexStats::from_sources(sources))
+        }).await?
+    }
+}
+
+/// Minimal client of the Sonic channel protocol.
+struct SonicChannel {
+    reader: BufReader<TcpStream>,
+    buffer_size: usize,
+}
+
+impl SonicChannel {
+    async fn start(address: &str, mode: &str, password: &str) -> anyhow::Result<Self> {
+        let stream = TcpStream::connect(address)
+            .await
+            .with_context(|| format!("Couldn't connect to sonic at: {}", address))?;
+
+        let mut channel = Self { reader: BufReader::new(stream), buffer_size: 20_000 };
+
+        let connected = channel.read_line().await?;
+        if !connected.starts_with("CONNECTED") {
+            bail!("Unexpected sonic greeting: {}", connected);
+        }
+
+        let started = channel.command(&format!("START {} {}", mode, password)).await?;
+        if !started.starts_with("STARTED") {
+            bail!("Couldn't start sonic {} channel: {}", mode, started);
+        }
+
+        // STARTED <mode> protocol(1) buffer(20000)
+        if let Some(size) = started
+            .split_whitespace()
+            .find_map(|part| part.strip_prefix("buffer(").and_then(|s| s.strip_suffix(')')))
+            .and_then(|s| s.parse().ok()) {
+            channel.buffer_size = size;
+        }
+
+        Ok(channel)
+    }
+
+    /// Maximum size of the text of a command, leaving room for the command itself.
+    fn max_text_size(&self) -> usize {
+        (self.buffer_size / 2).max(128)
+    }
+
+    async fn read_line(&mut self) -> anyhow::Result<String> {
+        let mut line = String::new();
+        self.reader.read_line(&mut line).await?;
+
+        let line = line.trim_end().to_string();
+        if line.starts_with("ERR") {
+            bail!("Sonic error: {}", line);
+        }
+
+        Ok(line)
+    }
+
+    async fn command(&mut self, command: &str) -> anyhow::Result<String> {
+        self.reader.get_mut().write_all(format!("{}\r\n", command).as_bytes()).await?;
+        self.read_line().await
+    }
+
+    async fn expect_ok(&mut self, command: &str) -> anyhow::Result<()> {
+        let response = self.command(command).await?;
+
+        if response != "OK" {
+            bail!("Unexpected sonic response: {}", response);
+        }
+
+        Ok(())
+    }
+
+    async fn quit(mut self) -> anyhow::Result<()> {
+        self.command("QUIT").await?;
+        Ok(())
+    }
+}
+
+/// Sonic object identifiers can't contain spaces.
+fn object_id(document_id: &str) -> String {
+    hex::encode(Sha256::digest(document_id.as_bytes()))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace(['\n', '\r'], " ")
+}
+
+/// Splits the text in chunks of at most `max_bytes`, preferably on whitespaces.
+fn split_text(text: &str, max_bytes: usize) -> Vec<&str> {
+    let mut chunks = vec![];
+    let mut rest = text.trim();
+
+    while !rest.is_empty() {
+        if rest.len() <= max_bytes {
+            chunks.push(rest);
+            break;
+        }
+
+        let mut end = max_bytes;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        let end = rest[..end].rfind(char::is_whitespace).filter(|i| *i > 0).unwrap_or(end);
+
+        chunks.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+
+    chunks
+}
+
+/// Returns a window of the content around the first occurrence of one of the terms, the words
+/// containing a term highlighted.
+fn snippet(content: &str, terms: &[String], size: usize) -> String {
+    let lowercase = content.to_lowercase();
+    let position = terms
+        .iter()
+        .filter_map(|term| lowercase.find(term.as_str()))
+        .min()
+        .unwrap_or(0);
+
+    let start = content
+        .char_indices()
+        .map(|(i, _)| i)
+        .take_while(|i| *i + size / 2 <= position)
+        .last()
+        .unwrap_or(0);
+
+    let window = content[start..].chars().take(size).collect::<String>();
+
+    window
+        .trim()
+        .split_inclusive(char::is_whitespace)
+        .map(|word| {
+            let (text, space) = word.split_at(word.trim_end().len());
+
+            match terms.iter().any(|term| text.to_lowercase().contains(term.as_str())) {
+                true => format!("<b>{}</b>{}", escape_html(text), space),
+                false => escape_html(word),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+    use tempdir::TempDir;
+    use tokio_stream::StreamExt;
+
+    use crate::search::SearchEngine;
+    use crate::search::sonic_impl::{escape, snippet, SonicSearchEngine, split_text};
+
+    #[tokio::test]
+    async fn test_documents_of_legacy_store() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let store_path = root.path().join("sonic.db");
+
+        Connection::open(&store_path)?.execute_batch(
+            "CREATE TABLE documents (object TEXT PRIMARY KEY, id TEXT NOT NULL, source TEXT NOT NULL, title TEXT NOT NULL, link TEXT NOT NULL, content TEXT NOT NULL);
+            INSERT INTO documents VALUES ('abc', '1', 'docs', 'Runbook', 'link1', 'Restart the database');"
+        )?;
+
+        let engine = SonicSearchEngine::new("localhost:1491", "".to_string(), "doks", &store_path)?;
+        let documents = engine.documents().await?.collect::<anyhow::Result<Vec<_>>>().await?;
+
+        assert_eq!(documents.iter().map(|document| document.id.as_str()).collect::<Vec<_>>(), vec!["1"]);
+        assert!(documents[0].metadata.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_text() {
+        assert_eq!(split_text("hello world foo", 11), vec!["hello", "world foo"]);
+        assert_eq!(split_text("short", 100), vec!["short"]);
+        assert!(split_text("", 100).is_empty());
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("say \"hi\"\nnow"), "say \\\"hi\\\" now");
+    }
+
+    #[test]
+    fn test_snippet() {
+        let content = "aaaa bbbb cccc dddd eeee";
+
+        assert_eq!(snippet(content, &["dddd".to_string()], 10), "cccc <b>dddd</b>");
+        assert_eq!(snippet("a <b> & DDDD!", &["dddd".to_string()], 20), "a &lt;b&gt; &amp; <b>DDDD!</b>");
+        assert_eq!(snippet(content, &["zzzz".to_string()], 9), "aaaa bbbb");
+    }
+}
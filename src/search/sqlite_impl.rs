@@ -0,0 +1,305 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::bail;
+use async_trait::async_trait;
+use rusqlite::{Connection, params};
+
+use crate::model::Document;
+use crate::search::query::{Clause, Occur, ParsedQuery};
+use crate::search::{html_snippet, FoundItem, IndexStats, SearchEngine, SearchRequest, SearchResult, SortOrder};
+use crate::sources::DocStream;
+
+/// Search engine storing documents in a single SQLite file using an FTS5 virtual table.
+pub struct SqliteSearchEngine {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteSearchEngine {
+    pub fn new<T: AsRef<Path>>(path: T) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    fn from_connection(connection: Connection) -> anyhow::Result<Self> {
+        connection.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS documents USING fts5(
+                id UNINDEXED,
+                source UNINDEXED,
+                title,
+                link UNINDEXED,
+                content,
+                metadata UNINDEXED
+            );"
+        )?;
+
+        Ok(Self { connection: Arc::new(Mutex::new(connection)) })
+    }
+}
+
+#[async_trait]
+impl SearchEngine for SqliteSearchEngine {
+    async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+        let connection = self.connection.clone();
+
+        let task = tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            let mut connection = connection.lock().unwrap();
+            let transaction = connection.transaction()?;
+
+            for document in documents {
+                log::info!("Indexing document: {} (source: {})", document.link, document.source);
+
+                transaction.execute("DELETE FROM documents WHERE id = ?1", params![document.id])?;
+                transaction.execute(
+                    "INSERT INTO documents (id, source, title, link, content, metadata) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        document.id,
+                        document.source,
+                        document.title,
+                        document.link,
+                        document.content,
+                        serde_json::to_string(&document.metadata)?,
+                    ],
+                )?;
+            }
+
+            transaction.commit()?;
+
+            Ok(())
+        });
+
+        task.await?
+    }
+
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        let (parsed, request) = request.parse_query()?;
+        request.ensure_no_document_filters()?;
+        request.ensure_no_dates()?;
+        request.ensure_not_fuzzy()?;
+        request.ensure_no_source_boosts()?;
+
+        let query = fts5_query(&parsed)?;
+        let connection = self.connection.clone();
+        let source_filter = serde_json::to_string(&request.source_filter)?;
+        let order = match request.sort {
+            SortOrder::Title => "title COLLATE NOCASE, bm25(documents)",
+            SortOrder::Source => "source, bm25(documents)",
+            _ => "bm25(documents)",
+        };
+        let (results_tx, results_rx) = tokio::sync::mpsc::channel(64);
+
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            let found = (|| -> anyhow::Result<Vec<FoundItem>> {
+                let connection = connection.lock().unwrap();
+                let mut statement = connection.prepare(&format!(
+                    "SELECT id, source, title, link, snippet(documents, 4, char(2), char(3), '...', 16), bm25(documents)
+                     FROM documents
+                     WHERE documents MATCH ?1
+                       AND (json_array_length(?2) = 0 OR source IN (SELECT value FROM json_each(?2)))
+                     ORDER BY {}
+                     LIMIT ?3 OFFSET ?4",
+                    order,
+                ))?;
+
+                let parameters = params![query, source_filter, request.limit as i64, request.offset as i64];
+                let rows = statement.query_map(parameters, |row| {
+                    Ok(FoundItem {
+                        id: row.get(0)?,
+                        source: row.get(1)?,
+                        title: row.get(2)?,
+                        link: row.get(3)?,
+                        snippet: html_snippet(&row.get::<_, String>(4)?),
+                        // bm25 is negative, the lower the better
+                        score: -row.get::<_, f64>(5)? as f32,
+                    })
+                })?;
+
+                Ok(rows.collect::<Result<Vec<_>, _>>()?)
+            })();
+
+            match found {
+                Ok(items) => {
+                    for item in items {
+                        results_tx.blocking_send(Ok(item))?;
+                    }
+                }
+                Err(err) => results_tx.blocking_send(Err(err))?,
+            }
+
+            Ok(())
+        });
+
+        Ok(Box::pin(tokio_stream::wrappers::ReceiverStream::new(results_rx)))
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        let connection = self.connection.clone();
+
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            connection.lock().unwrap().execute("DELETE FROM documents", [])?;
+            Ok(())
+        }).await?
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        let connection = self.connection.clone();
+        let source = source.to_string();
+
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            connection.lock().unwrap().execute("DELETE FROM documents WHERE source = ?1", params![source])?;
+            Ok(())
+        }).await?
+    }
+
+    /// The tags and modification dates aren't stored.
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        let connection = self.connection.clone();
+
+        let documents = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<anyhow::Result<Document>>> {
+            let connection = connection.lock().unwrap();
+            let mut statement = connection.prepare("SELECT id, source, title, link, content, metadata FROM documents")?;
+            let rows = statement.query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get::<_, String>(5)?))
+            })?;
+
+            Ok(
+                rows.map(|row| {
+                    let (id, source, title, link, content, metadata) = row?;
+
+                    Ok(Document { id, source, title, link, content, metadata: serde_json::from_str(&metadata)?, tags: vec![], modified: None })
+                })
+                .collect()
+            )
+        }).await??;
+
+        Ok(Box::pin(tokio_stream::iter(documents)))
+    }
+
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        let connection = self.connection.clone();
+        let ids = serde_json::to_string(ids)?;
+
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            connection.lock().unwrap().execute("DELETE FROM documents WHERE id IN (SELECT value FROM json_each(?1))", params![ids])?;
+            Ok(())
+        }).await?
+    }
+
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        let connection = self.connection.clone();
+
+        tokio::task::spawn_blocking(move || -> anyhow::Result<IndexStats> {
+            let connection = connection.lock().unwrap();
+            let mut statement = connection.prepare("SELECT source, COUNT(*) FROM documents GROUP BY source")?;
+            let sources = statement
+                .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?)))?
+                .collect::<Result<_, _>>()?;
+
+            Ok(IndexStats::from_sources(sources))
+        }).await?
+    }
+}
+
+/// The query in the FTS5 syntax: all the words and phrases (quoted, so that punctuation isn't
+/// read as operators) must match in their column, and the excluded ones mustn't.
+fn fts5_query(query: &ParsedQuery) -> anyhow::Result<String> {
+    let quoted = |clause: &Clause| {
+        let text = format!("\"{}\"", clause.text.replace('"', "\"\""));
+
+        match clause.field {
+            Some(field) => format!("{} : {}", field.name(), text),
+            None => text,
+        }
+    };
+
+    let (excluded, searched): (Vec<&Clause>, Vec<&Clause>) = query.clauses.iter().partition(|clause| clause.occur == Occur::MustNot);
+
+    if searched.is_empty() {
+        bail!("The sqlite engine needs words to search")
+    }
+
+    Ok(searched.into_iter().map(quoted).chain(excluded.into_iter().map(|clause| format!("NOT {}", quoted(clause)))).collect::<Vec<_>>().join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rusqlite::Connection;
+    use tokio_stream::StreamExt;
+
+    use crate::model::Document;
+    use crate::search::{SearchEngine, SearchRequest, SortOrder};
+    use crate::search::sqlite_impl::SqliteSearchEngine;
+
+    #[tokio::test]
+    async fn test_sqlite_search_engine() -> anyhow::Result<()> {
+        let engine = SqliteSearchEngine::from_connection(Connection::open_in_memory()?)?;
+
+        let document1 = Document {
+            title: "Hello world".to_string(),
+            content: "Hello content".to_string(),
+            source: "My source".to_string(),
+            link: "link1".to_string(),
+            metadata: HashMap::new(),
+            id: "1".to_string(),
+            tags: vec![],
+            modified: None,
+        };
+
+        let document2 = Document {
+            title: "Computer science".to_string(),
+            content: "Computer science <content>".to_string(),
+            source: "My source".to_string(),
+            link: "link2".to_string(),
+            metadata: HashMap::new(),
+            id: "2".to_string(),
+            tags: vec![],
+            modified: None,
+        };
+
+        engine.index(vec![document1, document2.clone()]).await?;
+        engine.index(vec![document2.clone()]).await?;
+
+        let results = engine.search(&SearchRequest::new("computer")).await?.collect::<Result<Vec<_>, _>>().await?;
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, document2.id);
+        assert_eq!(results[0].snippet, "<b>Computer</b> science &lt;content&gt;");
+
+        let request = SearchRequest { source_filter: vec!["Other source".to_string()], ..SearchRequest::new("computer") };
+        let results = engine.search(&request).await?.collect::<Result<Vec<_>, _>>().await?;
+        assert!(results.is_empty());
+
+        let results = engine.search(&SearchRequest::new("title:hello -computer")).await?.collect::<Result<Vec<_>, _>>().await?;
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["1"]);
+        let results = engine.search(&SearchRequest::new("source:\"My source\" content -hello")).await?.collect::<Result<Vec<_>, _>>().await?;
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["2"]);
+
+        let request = SearchRequest { sort: SortOrder::Title, ..SearchRequest::new("content") };
+        let results = engine.search(&request).await?.collect::<Result<Vec<_>, _>>().await?;
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["2", "1"]);
+
+        let stats = engine.stats().await?;
+        assert_eq!(stats.documents, 2);
+        assert_eq!(stats.sources.get("My source"), Some(&2));
+
+        engine.delete(&["1".to_string()]).await?;
+        let documents = engine.documents().await?.collect::<anyhow::Result<Vec<_>>>().await?;
+        assert_eq!(documents, vec![document2]);
+
+        engine.purge().await?;
+
+        let results = engine.search(&SearchRequest::new("computer")).await?.collect::<Result<Vec<_>, _>>().await?;
+        assert!(results.is_empty());
+
+        Ok(())
+    }
+}
@@ -1,18 +1,28 @@
 use std::borrow::Borrow;
-use std::collections::HashMap;
-use std::path::Path;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 
+use anyhow::{bail, Context};
 use async_trait::async_trait;
-use tantivy::{doc, Index, IndexReader, IndexWriter, SnippetGenerator};
-use tantivy::collector::TopDocs;
+use chrono::{TimeZone, Utc};
+use regex::{Captures, NoExpand, Regex};
+use strsim::osa_distance;
+use tantivy::{doc, DocAddress, DocId, DocSet, Index, IndexReader, IndexWriter, LeasedItem, Score, Searcher, SegmentId, SegmentReader, SnippetGenerator, Term, TERMINATED};
+use tantivy::collector::{Collector, Count, FacetCollector, TopDocs};
 use tantivy::directory::MmapDirectory;
-use tantivy::query::QueryParser;
-use tantivy::schema::{Document as TantivyDoc, Field, SchemaBuilder, STORED, STRING, TEXT};
+use tantivy::fastfield::FastFieldReader;
+use tantivy::query::{AllQuery, BooleanQuery, BoostQuery, FuzzyTermQuery, MoreLikeThisQuery, Occur, PhraseQuery, Query, RangeQuery, RegexQuery, TermQuery};
+use tantivy::schema::{Cardinality, Document as TantivyDoc, Facet, Field, FieldValue, IndexRecordOption, IntOptions, Schema, SchemaBuilder, TextFieldIndexing, TextOptions, Value, FAST, INDEXED, STORED, STRING};
+use tantivy::tokenizer::{AsciiFoldingFilter, Language, LowerCaser, RemoveLongFilter, SimpleTokenizer, Stemmer, StopWordFilter, TextAnalyzer};
 
 use crate::model::Document;
-use crate::search::{FoundItem, SearchEngine, SearchResult};
+use crate::search::query::{self, TextField};
+use crate::search::{code_tokenizer, escape_html, language, Facets, FoundItem, IndexStats, ScoreExplanation, SearchEngine, SearchExplanation, SearchRequest, SearchResult, SortOrder};
 use crate::sources::DocStream;
+use crate::utils::glob::glob_to_regex;
 
 pub struct TantivySearchEngine {
     index: Index,
@@ -24,6 +34,212 @@ pub struct TantivySearchEngine {
 
 struct Options {
     default_fields: Vec<Field>,
+    synonyms: BTreeMap<String, Vec<String>>,
+    boosts: Boosts,
+    max_results: Option<usize>,
+}
+
+/// The weights of the matches in the title and in the content (in any language).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Boosts {
+    pub title: f32,
+    pub content: f32,
+}
+
+impl Default for Boosts {
+    fn default() -> Self {
+        Boosts { title: 1.0, content: 1.0 }
+    }
+}
+
+/// A field added to the schema by the config, filled with the metadata value of the same name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomField {
+    pub name: String,
+    pub kind: FieldKind,
+    pub stored: bool,
+    pub indexed: bool,
+    /// Only for the numeric fields.
+    pub fast: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    /// Analyzed like the content, and searched along with the title and content when indexed.
+    Text,
+    /// The whole value as a single term.
+    String,
+    I64,
+    U64,
+    F64,
+}
+
+impl CustomField {
+    fn add_to(&self, schema_builder: &mut SchemaBuilder) -> anyhow::Result<Field> {
+        let numeric = || {
+            let mut options = IntOptions::default();
+            if self.stored {
+                options = options.set_stored();
+            }
+            if self.indexed {
+                options = options.set_indexed();
+            }
+            if self.fast {
+                options = options.set_fast(Cardinality::SingleValue);
+            }
+            options
+        };
+        let text = |indexing: TextFieldIndexing| {
+            let options = match self.indexed {
+                true => TextOptions::default().set_indexing_options(indexing),
+                false => TextOptions::default(),
+            };
+            match self.stored {
+                true => options.set_stored(),
+                false => options,
+            }
+        };
+
+        if self.fast && matches!(self.kind, FieldKind::Text | FieldKind::String) {
+            bail!("Field '{}': only the numeric fields can be fast", self.name)
+        }
+
+        Ok(match self.kind {
+            FieldKind::Text => schema_builder.add_text_field(&self.name, text(
+                TextFieldIndexing::default().set_tokenizer(ANALYZER).set_index_option(IndexRecordOption::WithFreqsAndPositions),
+            )),
+            FieldKind::String => schema_builder.add_text_field(&self.name, text(TextFieldIndexing::default().set_tokenizer("raw"))),
+            FieldKind::I64 => schema_builder.add_i64_field(&self.name, numeric()),
+            FieldKind::U64 => schema_builder.add_u64_field(&self.name, numeric()),
+            FieldKind::F64 => schema_builder.add_f64_field(&self.name, numeric()),
+        })
+    }
+
+    /// Adds the metadata value to the document, or logs why it can't.
+    fn add(&self, field: Field, value: &str, doc: &mut TantivyDoc) {
+        let added = match self.kind {
+            FieldKind::Text | FieldKind::String => {
+                doc.add_text(field, value);
+                Ok(())
+            }
+            FieldKind::I64 => value.trim().parse().map(|value| doc.add_i64(field, value)).map_err(anyhow::Error::from),
+            FieldKind::U64 => value.trim().parse().map(|value| doc.add_u64(field, value)).map_err(anyhow::Error::from),
+            FieldKind::F64 => value.trim().parse().map(|value| doc.add_f64(field, value)).map_err(anyhow::Error::from),
+        };
+
+        if let Err(err) = added {
+            log::warn!("Field '{}': ignoring the value '{}': {}", self.name, value, err);
+        }
+    }
+}
+
+/// The name the analyzer of the title and content is registered under.
+const ANALYZER: &str = "doks";
+
+/// Version of the schema and of the way the documents are indexed, stamped in the index
+/// directory. Bumped on the changes that require indexing the documents again:
+/// - 2: the metadata is stored, with the `=` of its keys escaped.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// The file of the index directory holding its `SCHEMA_VERSION`, followed by the
+/// `Analyzer::settings` of its documents on a second line. The indexes built before it was
+/// introduced have none, and the first version.
+const SCHEMA_VERSION_FILE: &str = "doks_schema_version";
+
+/// The index was built by another version of doks, or with other custom fields or analyzer.
+#[derive(Debug)]
+pub struct IncompatibleSchema {
+    pub path: PathBuf,
+    /// `None` when the stamp of the index can't be read.
+    pub version: Option<u32>,
+}
+
+impl fmt::Display for IncompatibleSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = self.version.map_or_else(|| "unknown".to_string(), |version| version.to_string());
+
+        write!(
+            f,
+            "The index at {:?} was built by another version of doks (schema version {}, expected {}) or with other fields or analyzer: run `doks reindex` to build it again",
+            self.path, version, SCHEMA_VERSION,
+        )
+    }
+}
+
+impl std::error::Error for IncompatibleSchema {}
+
+/// Maximum size of the snippets, in characters.
+const SNIPPET_SIZE: usize = 200;
+
+/// How the title and content of the documents are tokenized: split on whitespaces and
+/// punctuation, then lowercased, folded to ASCII, filtered from the stop words and stemmed when
+/// enabled.
+pub struct Analyzer {
+    pub stemmer: Option<Language>,
+    pub lowercase: bool,
+    pub ascii_folding: bool,
+    pub stop_words: Option<StopWords>,
+    /// Words of the queries also searched as other words or phrases (`k8s` as `kubernetes`).
+    pub synonyms: BTreeMap<String, Vec<String>>,
+}
+
+pub enum StopWords {
+    /// The common English words built in tantivy.
+    English,
+    Custom(Vec<String>),
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Analyzer { stemmer: None, lowercase: true, ascii_folding: false, stop_words: None, synonyms: BTreeMap::new() }
+    }
+}
+
+impl Analyzer {
+    fn build(&self) -> TextAnalyzer {
+        let mut analyzer = TextAnalyzer::from(SimpleTokenizer).filter(RemoveLongFilter::limit(40));
+
+        if self.lowercase {
+            analyzer = analyzer.filter(LowerCaser);
+        }
+
+        if self.ascii_folding {
+            analyzer = analyzer.filter(AsciiFoldingFilter);
+        }
+
+        // Before the stemmer, as the stop words aren't stemmed
+        match &self.stop_words {
+            Some(StopWords::English) => analyzer = analyzer.filter(StopWordFilter::default()),
+            Some(StopWords::Custom(words)) => {
+                let words = words.iter().map(|word| if self.lowercase { word.to_lowercase() } else { word.clone() }).collect();
+                analyzer = analyzer.filter(StopWordFilter::remove(words));
+            }
+            None => {}
+        }
+
+        if let Some(language) = self.stemmer {
+            analyzer = analyzer.filter(Stemmer::new(language));
+        }
+
+        analyzer
+    }
+
+    /// The settings changing how the documents are indexed, stamped in the index directory. The
+    /// synonyms are left out as they only apply to the queries.
+    fn settings(&self) -> String {
+        let stemmer = self.stemmer.map_or_else(|| "none".to_string(), |language| format!("{:?}", language).to_lowercase());
+        let stop_words = match &self.stop_words {
+            Some(StopWords::English) => "english".to_string(),
+            Some(StopWords::Custom(words)) => {
+                let mut words = words.clone();
+                words.sort();
+                format!("[{}]", words.join(","))
+            }
+            None => "none".to_string(),
+        };
+
+        format!("stemmer={} lowercase={} ascii_folding={} stop_words={}", stemmer, self.lowercase, self.ascii_folding, stop_words)
+    }
 }
 
 #[derive(Clone)]
@@ -33,38 +249,319 @@ struct SchemaFields {
     link: Field,
     content: Field,
     source: Field,
+    /// `key=value` terms of the document metadata, used for filtering. Stored, so that the
+    /// metadata is returned along with the documents.
+    metadata: Field,
+    tags: Field,
+    /// `/source/<source>` and `/meta/<key>/<value>` facets, to count the matching documents.
+    facets: Field,
+    /// Unix timestamp (in seconds) of the last modification, 0 when unknown.
+    modified: Field,
+    /// The content of the documents in each language, stemmed: `content_<language code>`.
+    stemmed_content: BTreeMap<&'static str, Field>,
+    custom: Vec<(CustomField, Field)>,
 }
 
 impl TantivySearchEngine {
-    pub fn new<T: AsRef<Path>>(path: T) -> anyhow::Result<Self> {
+    /// Opens the index at the path (created if missing), with the custom fields added to its schema
+    /// and the documents tokenized by the analyzer.
+    pub fn open<T: AsRef<Path>>(path: T, custom: &[CustomField], analyzer: &Analyzer) -> anyhow::Result<Self> {
         let path = path.as_ref();
 
         if !path.exists() {
             std::fs::create_dir_all(path)?;
         }
 
-        let mut schema_builder = SchemaBuilder::new();
-        let id = schema_builder.add_text_field("id", STRING | STORED);
-        let title = schema_builder.add_text_field("title", TEXT | STORED);
-        let link = schema_builder.add_text_field("link", STRING | STORED);
-        let content = schema_builder.add_text_field("content", TEXT | STORED);
-        let source = schema_builder.add_text_field("source", STRING | STORED);
+        let (schema, fields) = build_schema(custom)?;
+        check_index_schema(path, &schema, analyzer)?;
 
-        let default_fields = vec![title, content];
-        let fields = SchemaFields { title, id, link, content, source };
+        let index = Index::open_or_create(MmapDirectory::open(path)?, schema)?;
+        let settings = analyzer.settings();
 
-        let schema = schema_builder.build();
-        let index = Index::open_or_create(
-            MmapDirectory::open(path)?,
-            schema,
-        )?;
+        if schema_version(path) != Some(SCHEMA_VERSION) || analyzer_settings(path).as_ref() != Some(&settings) {
+            std::fs::write(path.join(SCHEMA_VERSION_FILE), format!("{}\n{}", SCHEMA_VERSION, settings))?;
+        }
+
+        Ok(Self::from_index(index, fields)?.with_analyzer(analyzer))
+    }
+
+    /// Creates an engine whose index only lives in memory (nothing is written to disk).
+    pub fn in_memory() -> anyhow::Result<Self> {
+        let (schema, fields) = build_schema(&[])?;
+        Self::from_index(Index::create_in_ram(schema), fields)
+    }
+
+    /// Replaces the default analyzer of the title and content. The documents indexed with another
+    /// analyzer won't match the queries anymore.
+    pub fn with_analyzer(mut self, analyzer: &Analyzer) -> Self {
+        self.index.tokenizers().register(ANALYZER, analyzer.build());
+        self.options.synonyms = analyzer.synonyms.clone();
+        self
+    }
+
+    /// Weighs the matches in the title and in the content differently (the same by default).
+    pub fn with_boosts(mut self, boosts: Boosts) -> Self {
+        self.options.boosts = boosts;
+        self
+    }
+
+    /// Only returns the first `max` results of the searches (offset included), to bound the cost
+    /// of the queries.
+    pub fn with_max_results(mut self, max: usize) -> Self {
+        self.options.max_results = Some(max);
+        self
+    }
+
+    /// The request with its page cut at the maximum number of results.
+    fn capped(&self, request: SearchRequest) -> anyhow::Result<SearchRequest> {
+        match self.options.max_results {
+            Some(max) if request.offset >= max => bail!("Only the first {} results of a search can be returned", max),
+            Some(max) => Ok(SearchRequest { limit: request.limit.min(max - request.offset), ..request }),
+            None => Ok(request),
+        }
+    }
+
+    /// The query of the request, and the request with the filters of the query.
+    fn parse_query(&self, request: &SearchRequest) -> anyhow::Result<(Box<dyn Query>, SearchRequest)> {
+        let (parsed, request) = request.parse_query()?;
+        let mut clauses = vec![];
+
+        for clause in &parsed.clauses {
+            let fields = match clause.field {
+                Some(TextField::Title) => vec![self.fields.title],
+                Some(TextField::Content) => std::iter::once(self.fields.content).chain(self.fields.stemmed_content.values().copied()).collect(),
+                None => self.options.default_fields.clone(),
+            };
+
+            let mut alternatives = vec![];
+
+            for field in fields {
+                let boost = if field == self.fields.title { self.options.boosts.title } else { self.options.boosts.content };
+
+                if let Some(query) = self.text_query(field, &clause.text)? {
+                    let query = self.with_alternatives(query, request.fuzzy)?;
+
+                    alternatives.push((Occur::Should, match boost == 1.0 {
+                        true => query,
+                        false => Box::new(BoostQuery::new(query, boost)),
+                    }));
+                }
+            }
+
+            let occur = match clause.occur {
+                query::Occur::Should => Occur::Should,
+                query::Occur::Must => Occur::Must,
+                query::Occur::MustNot => Occur::MustNot,
+            };
+
+            if !alternatives.is_empty() {
+                clauses.push((occur, Box::new(BooleanQuery::new(alternatives)) as Box<dyn Query>));
+            }
+        }
+
+        // Only filters or excluded words: all the other documents match
+        if clauses.iter().all(|(occur, _)| *occur == Occur::MustNot) {
+            clauses.push((Occur::Must, Box::new(AllQuery)));
+        }
+
+        Ok((Box::new(BooleanQuery::new(clauses)), request))
+    }
+
+    /// The text analyzed like the field: a term, or a phrase when it has several words.
+    fn text_query(&self, field: Field, text: &str) -> anyhow::Result<Option<Box<dyn Query>>> {
+        let mut terms = self.terms(field, text)?;
+
+        match terms.len() {
+            0 => Ok(None),
+            1 => Ok(Some(Box::new(TermQuery::new(terms.remove(0), IndexRecordOption::WithFreqs)))),
+            _ => Ok(Some(Box::new(PhraseQuery::new(terms)))),
+        }
+    }
+
+    fn terms(&self, field: Field, text: &str) -> anyhow::Result<Vec<Term>> {
+        let mut terms = vec![];
+        self.index.tokenizer_for_field(field)?.token_stream(text).process(&mut |token| terms.push(Term::from_field_text(field, &token.text)));
+
+        Ok(terms)
+    }
+
+    /// The query of a single term, or of the term or its alternatives: the words within the
+    /// `fuzzy` edit distance of the term, and its synonyms (once analyzed like the field they
+    /// search), the synonyms of several words being phrases.
+    fn with_alternatives(&self, query: Box<dyn Query>, fuzzy: Option<u8>) -> anyhow::Result<Box<dyn Query>> {
+        let term = match query.downcast_ref::<TermQuery>() {
+            Some(term_query) => term_query.term().clone(),
+            None => return Ok(query),
+        };
+
+        let mut alternatives = vec![];
+
+        for (word, synonyms) in &self.options.synonyms {
+            if self.terms(term.field(), word)? == vec![term.clone()] {
+                for synonym in synonyms {
+                    alternatives.extend(self.text_query(term.field(), synonym)?);
+                }
+            }
+        }
+
+        // The fuzzy matches all score the same: the exact term is kept to rank its matches first
+        if let Some(distance) = fuzzy.map(|distance| distance.min(max_typos(term.text()))).filter(|distance| *distance > 0) {
+            alternatives.push(Box::new(FuzzyTermQuery::new(term, distance, true)));
+        }
+
+        match alternatives.is_empty() {
+            true => Ok(query),
+            false => Ok(Box::new(BooleanQuery::new(
+                std::iter::once(query).chain(alternatives).map(|query| (Occur::Should, query)).collect(),
+            ))),
+        }
+    }
+
+    fn from_index(index: Index, fields: SchemaFields) -> anyhow::Result<Self> {
+        let mut default_fields = vec![fields.title, fields.content];
+        default_fields.extend(fields.stemmed_content.values());
+        default_fields.extend(
+            fields.custom.iter().filter(|(custom, _)| custom.kind == FieldKind::Text && custom.indexed).map(|(_, field)| *field),
+        );
+        index.tokenizers().register(ANALYZER, Analyzer::default().build());
 
+        for code in language::stemmed_languages() {
+            if let Some(analyzer) = language::analyzer(code) {
+                index.tokenizers().register(&language::analyzer_name(code), analyzer);
+            }
+        }
 
         let reader = index.reader()?;
         let writer = Arc::new(RwLock::new(index.writer(50_000_000)?));
 
-        Ok(Self { index, writer, reader, fields, options: Options { default_fields } })
+        Ok(Self { index, writer, reader, fields, options: Options { default_fields, synonyms: BTreeMap::new(), boosts: Boosts::default(), max_results: None } })
+    }
+}
+
+/// Fails with `IncompatibleSchema` when the index at the path (if any) can't be opened by this
+/// version of doks with these custom fields and this analyzer.
+pub fn check_schema(path: &Path, custom: &[CustomField], analyzer: &Analyzer) -> anyhow::Result<()> {
+    check_index_schema(path, &build_schema(custom)?.0, analyzer)
+}
+
+fn check_index_schema(path: &Path, schema: &Schema, analyzer: &Analyzer) -> anyhow::Result<()> {
+    if !path.join("meta.json").exists() {
+        return Ok(());
+    }
+
+    let version = schema_version(path);
+    // The indexes stamped before the analyzer was are assumed to be built with the configured one
+    let other_analyzer = analyzer_settings(path).is_some_and(|settings| settings != analyzer.settings());
+
+    if version != Some(SCHEMA_VERSION) || other_analyzer || Index::open_in_dir(path)?.schema() != *schema {
+        return Err(IncompatibleSchema { path: path.to_path_buf(), version }.into());
+    }
+
+    Ok(())
+}
+
+fn schema_version(path: &Path) -> Option<u32> {
+    match std::fs::read_to_string(path.join(SCHEMA_VERSION_FILE)) {
+        Ok(stamp) => stamp.lines().next().and_then(|version| version.trim().parse().ok()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Some(1),
+        Err(_) => None,
+    }
+}
+
+fn analyzer_settings(path: &Path) -> Option<String> {
+    let stamp = std::fs::read_to_string(path.join(SCHEMA_VERSION_FILE)).ok()?;
+    stamp.lines().nth(1).map(|settings| settings.trim().to_string())
+}
+
+/// The documents stored in the index at the path, whatever the version of doks that built it, to
+/// migrate them to a new index.
+pub fn stored_documents(path: &Path) -> anyhow::Result<Vec<Document>> {
+    let index = Index::open_in_dir(path)?;
+    let schema = index.schema();
+    let stored = |name: &str| schema.get_field(name).filter(|field| schema.get_field_entry(*field).is_stored());
+    let required = |name: &str| stored(name).with_context(|| format!("The index doesn't store the {} of the documents", name));
+    let (id, source, title, link, content) = (required("id")?, required("source")?, required("title")?, required("link")?, required("content")?);
+    let (metadata, tags, modified) = (stored("metadata"), stored("tags"), stored("modified"));
+
+    let searcher = index.reader()?.searcher();
+    let mut documents = vec![];
+
+    for (segment_ord, segment_reader) in searcher.segment_readers().iter().enumerate() {
+        for doc_id in (0..segment_reader.max_doc()).filter(|doc_id| !segment_reader.is_deleted(*doc_id)) {
+            let doc = searcher.doc(DocAddress::new(segment_ord as u32, doc_id))?;
+            let text = |field: Field| doc.get_first(field).and_then(stored_text).unwrap_or_default().to_string();
+            let all = |field: Option<Field>| field.into_iter().flat_map(|field| doc.get_all(field)).filter_map(Value::text).collect::<Vec<_>>();
+
+            documents.push(Document {
+                id: text(id),
+                source: text(source),
+                title: text(title),
+                link: text(link),
+                content: text(content),
+                metadata: all(metadata).into_iter().filter_map(parse_metadata_term).collect(),
+                tags: all(tags).into_iter().map(str::to_string).collect(),
+                modified: modified
+                    .and_then(|field| doc.get_first(field))
+                    .and_then(Value::i64_value)
+                    .and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single()),
+            });
+        }
+    }
+
+    Ok(documents)
+}
+
+/// Whether the fields can be added to the schema: valid and unused names, fast numeric fields.
+pub fn check_fields(custom: &[CustomField]) -> anyhow::Result<()> {
+    build_schema(custom).map(|_| ())
+}
+
+fn build_schema(custom: &[CustomField]) -> anyhow::Result<(Schema, SchemaFields)> {
+    let mut schema_builder = SchemaBuilder::new();
+    let id = schema_builder.add_text_field("id", STRING | STORED);
+    let analyzed = TextOptions::default().set_indexing_options(
+        TextFieldIndexing::default().set_tokenizer(ANALYZER).set_index_option(IndexRecordOption::WithFreqsAndPositions),
+    );
+    let title = schema_builder.add_text_field("title", analyzed.clone() | STORED);
+    let link = schema_builder.add_text_field("link", STRING | STORED);
+    let content = schema_builder.add_text_field("content", analyzed | STORED);
+    let source = schema_builder.add_text_field("source", STRING | STORED);
+    let metadata = schema_builder.add_text_field("metadata", STRING | STORED);
+    let tags = schema_builder.add_text_field("tags", STRING | STORED);
+    let facets = schema_builder.add_facet_field("facets", INDEXED);
+    let modified = schema_builder.add_i64_field("modified", INDEXED | STORED | FAST);
+
+    let stemmed_content = language::stemmed_languages()
+        .map(|code| {
+            let indexing = TextFieldIndexing::default()
+                .set_tokenizer(&language::analyzer_name(code))
+                .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+            let field = schema_builder.add_text_field(&format!("content_{}", code), TextOptions::default().set_indexing_options(indexing));
+
+            (code, field)
+        })
+        .collect();
+
+    let custom = custom
+        .iter()
+        .map(|field| {
+            if field.name.is_empty() || !field.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                bail!("Invalid field name: '{}' (expected letters, digits and underscores)", field.name)
+            }
+
+            Ok((field.clone(), field.add_to(&mut schema_builder)?))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let schema = schema_builder.build();
+
+    for (field, _) in &custom {
+        if schema.fields().filter(|(_, entry)| entry.name() == field.name).count() > 1 {
+            bail!("The field name '{}' is already used", field.name)
+        }
     }
+
+    Ok((schema, SchemaFields { title, id, link, content, source, metadata, tags, facets, modified, stemmed_content, custom }))
 }
 
 #[async_trait]
@@ -77,13 +574,61 @@ impl SearchEngine for TantivySearchEngine {
             for document in documents {
                 log::info!("Indexing document: {} (source: {})", document.link, document.source);
 
-                writer.read().unwrap().add_document(doc!(
+                let writer = writer.read().unwrap();
+
+                // Drop the previous version of the document (if any) so reindexing doesn't duplicate it
+                writer.delete_term(Term::from_field_text(fields.id, &document.id));
+
+                let is_code = code_tokenizer::is_code(&document.link);
+                let mut tantivy_doc = doc!(
+                    fields.facets => Facet::from_path(["source", document.source.as_str()]),
                     fields.title => document.title,
                     fields.id => document.id,
-                    fields.content => document.content,
                     fields.link => document.link,
                     fields.source => document.source,
-                ));
+                );
+
+                // Identifiers are split in their words so that `IndexWriter` finds `index_writer`
+                if is_code {
+                    tantivy_doc.add_pre_tokenized_text(fields.content, &code_tokenizer::pre_tokenize(document.content));
+                } else {
+                    // Also indexed with the stemmer of its language, so that `restarting` finds
+                    // `restart` (and `redémarrage` finds `redémarrer`)
+                    let stemmed = language::detect(&document.content).and_then(|code| fields.stemmed_content.get(code));
+
+                    if let Some(field) = stemmed {
+                        tantivy_doc.add_text(*field, &document.content);
+                    }
+
+                    tantivy_doc.add_text(fields.content, document.content);
+                }
+
+                if let Some(modified) = document.modified {
+                    tantivy_doc.add_i64(fields.modified, modified.timestamp());
+                }
+
+                for (key, value) in &document.metadata {
+                    tantivy_doc.add_text(fields.metadata, metadata_term(key, value));
+                    tantivy_doc.add_facet(fields.facets, Facet::from_path(["meta", key.as_str(), value.as_str()]));
+                }
+
+                for tag in &document.tags {
+                    tantivy_doc.add_text(fields.tags, tag);
+                }
+
+                for (custom, field) in &fields.custom {
+                    if let Some(value) = document.metadata.get(&custom.name) {
+                        custom.add(*field, value, &mut tantivy_doc);
+                    }
+                }
+
+                // Extra title values: matches in the headings weigh like matches in the title
+                // (only the first value is returned as the title)
+                for heading in document.metadata.get("headings").iter().flat_map(|headings| headings.lines()) {
+                    tantivy_doc.add_text(fields.title, heading);
+                }
+
+                writer.add_document(tantivy_doc);
             }
 
             writer.write().unwrap().commit()?;
@@ -91,135 +636,1405 @@ impl SearchEngine for TantivySearchEngine {
             Ok(())
         });
 
-        task.await?
+        task.await??;
+
+        // Make the documents visible to searches right away rather than on the next reload
+        self.reader.reload()?;
+
+        Ok(())
     }
 
-    fn search(&self, query: &str) -> SearchResult {
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        let (query, request) = self.parse_query(request)?;
+        let query = with_filters(query, &request, &self.fields)?;
+        let request = self.capped(request)?;
+        let searcher = self.reader.searcher();
+        let collector = top_docs(&searcher, &request, &self.fields)?;
+
+        stream_results(searcher, query, collector, request, self.fields.clone())
+    }
+
+    async fn documents(&self) -> anyhow::Result<DocStream> {
         let searcher = self.reader.searcher();
-        let query_parser = QueryParser::for_index(
-            &self.index,
-            self.options.default_fields.clone(),
-        );
-        let query = query_parser.parse_query(query)?;
-        let (results_tx, results_rx) = tokio::sync::mpsc::channel(64);
         let fields = self.fields.clone();
+        let (documents_tx, documents_rx) = tokio::sync::mpsc::channel(64);
 
-        // TODO: Is it possible that this leaks?
-        // When `rx` is dropped, `send_blocking` should fail making this task stop?
-        tokio::task::spawn_blocking(|| -> anyhow::Result<()> {
-            // Reassignments to move the values
-            let results_tx = results_tx;
-            let searcher = searcher;
-            let fields = fields;
-            let query = query;
-
-            let snippet_generator = SnippetGenerator::create(
-                &searcher,
-                &*query,
-                fields.content.clone(),
-            )?;
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            for (segment_ord, segment_reader) in searcher.segment_readers().iter().enumerate() {
+                for doc_id in (0..segment_reader.max_doc()).filter(|doc_id| !segment_reader.is_deleted(*doc_id)) {
+                    let doc = searcher.doc(DocAddress::new(segment_ord as u32, doc_id))?;
+                    documents_tx.blocking_send(Ok(tantivy_doc_to_document(&doc, &fields)))?;
+                }
+            }
 
-            let top_docs = searcher.search(
-                query.borrow(),
-                &TopDocs::with_limit(10),
-            )?;
+            Ok(())
+        });
 
-            for (score, doc_address) in top_docs {
-                let doc = searcher.doc(doc_address)?;
-                let doc = tantivy_doc_to_found_item(
-                    doc,
-                    score.abs(),
-                    &fields,
-                    &snippet_generator,
-                )?;
+        Ok(Box::pin(tokio_stream::wrappers::ReceiverStream::new(documents_rx)))
+    }
+
+    async fn document(&self, id: &str) -> anyhow::Result<Option<Document>> {
+        let searcher = self.reader.searcher();
+        let id_query = TermQuery::new(Term::from_field_text(self.fields.id, id), IndexRecordOption::Basic);
+
+        match searcher.search(&id_query, &TopDocs::with_limit(1))?.first() {
+            Some((_, address)) => Ok(Some(tantivy_doc_to_document(&searcher.doc(*address)?, &self.fields))),
+            None => Ok(None),
+        }
+    }
+
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        let writer = self.writer.clone();
+        let terms = ids.iter().map(|id| Term::from_field_text(self.fields.id, id)).collect::<Vec<_>>();
 
-                results_tx.blocking_send(Ok(doc))?;
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            let mut writer = writer.write().unwrap();
+
+            for term in terms {
+                writer.delete_term(term);
             }
 
+            writer.commit()?;
             Ok(())
-        });
+        }).await??;
 
+        self.reader.reload()?;
 
-        Ok(Box::pin(tokio_stream::wrappers::ReceiverStream::new(results_rx)))
+        Ok(())
     }
-}
 
-fn tantivy_doc_to_found_item(
-    tantivy_doc: TantivyDoc,
-    score: f32,
-    fields: &SchemaFields,
-    snippet_generator: &SnippetGenerator,
-) -> anyhow::Result<FoundItem> {
-    let snippet = snippet_generator.snippet_from_doc(&tantivy_doc
-    );
+    async fn explain(&self, request: &SearchRequest) -> anyhow::Result<SearchExplanation> {
+        let (query, request) = self.parse_query(request)?;
+        let query = with_filters(query, &request, &self.fields)?;
+        let request = self.capped(request)?;
+        let searcher = self.reader.searcher();
+        let collector = top_docs(&searcher, &request, &self.fields)?;
+        let fields = self.fields.clone();
 
-    Ok(
-        FoundItem {
-            id: tantivy_doc.get_first(fields.id)
-                .and_then(|f| f.text())
-                .expect("Field id of type text not found")
-                .to_string(),
-            title: tantivy_doc.get_first(fields.title)
-                .and_then(|f| f.text())
-                .expect("Field title of type text not found")
-                .to_string(),
-            link: tantivy_doc.get_first(fields.link)
-                .and_then(|f| f.text())
-                .expect("Field link of type text not found")
-                .to_string(),
-            snippet: snippet.to_html(),
-            source: tantivy_doc.get_first(fields.source)
-                .and_then(|f| f.text())
-                .expect("Field source of type text not found")
-                .to_string(),
-            score,
-        }
-    )
-}
+        tokio::task::spawn_blocking(move || -> anyhow::Result<SearchExplanation> {
+            let snippet_generator = snippet_generator(&searcher, &*query, &fields)?;
+            let mut results = vec![];
 
-#[cfg(test)]
-mod tests {
-    use std::collections::HashMap;
+            let top_docs = searcher.search(query.borrow(), &collector)?;
 
-    use tempdir::TempDir;
-    use tokio_stream::StreamExt;
+            for (score, doc_address) in requested_page(&searcher, top_docs, &request, &fields)? {
+                let item = tantivy_doc_to_found_item(searcher.doc(doc_address)?, score.abs(), &fields, &snippet_generator)?;
 
-    use crate::model::Document;
-    use crate::search::SearchEngine;
-    use crate::search::tantivy_impl::TantivySearchEngine;
+                // Only serialization gives access to the explanation tree
+                let explanation = serde_json::to_value(query.explain(&searcher, doc_address)?)?;
+                let explanation: ScoreExplanation = serde_json::from_value(explanation)?;
 
-    #[tokio::test]
-    async fn test_tantivy_search_engine() -> anyhow::Result<()> {
-        let index_path = TempDir::new("tantivy_index")?;
+                let explanation = match request.source_boosts.get(&item.source) {
+                    Some(boost) => ScoreExplanation {
+                        value: explanation.value * boost,
+                        description: "product of:".to_string(),
+                        details: vec![
+                            explanation,
+                            ScoreExplanation { value: *boost, description: format!("boost of the source {}", item.source), details: vec![] },
+                        ],
+                    },
+                    None => explanation,
+                };
 
-        let engine = TantivySearchEngine::new(index_path.path())?;
+                results.push((item, explanation));
+            }
 
-        let document1 = Document {
-            title: "Hello world".to_string(),
-            content: "Hello content".to_string(),
-            source: "My source".to_string(),
-            link: "link1".to_string(),
-            metadata: HashMap::new(),
-            id: "1".to_string(),
-        };
+            Ok(SearchExplanation { query: readable_query(&format!("{:?}", query), searcher.schema()), results })
+        }).await?
+    }
 
-        let document2 = Document {
-            title: "Computer science".to_string(),
-            content: "Computer science content".to_string(),
-            source: "My source".to_string(),
-            link: "link2".to_string(),
-            metadata: HashMap::new(),
-            id: "2".to_string(),
-        };
+    async fn facets(&self, request: &SearchRequest, metadata_keys: &[String]) -> anyhow::Result<Facets> {
+        let (query, request) = self.parse_query(request)?;
+        let query = with_filters(query, &request, &self.fields)?;
+        let searcher = self.reader.searcher();
+        let facets_field = self.fields.facets;
+        let metadata_keys = metadata_keys.to_vec();
 
-        engine.index(vec![document1, document2.clone()]).await?;
+        tokio::task::spawn_blocking(move || -> anyhow::Result<Facets> {
+            let mut collector = FacetCollector::for_field(facets_field);
+            collector.add_facet(Facet::from_path(["source"]));
 
-        engine.reader.reload()?;
+            for key in &metadata_keys {
+                collector.add_facet(Facet::from_path(["meta", key.as_str()]));
+            }
 
-        let results = engine.search("computer")?.collect::<Result<Vec<_>, _>>().await?;
+            let counts = searcher.search(query.borrow(), &collector)?;
+            let values = |facet: Facet| {
+                counts
+                    .get(facet)
+                    .map(|(facet, count)| (facet.to_path().last().copied().unwrap_or_default().to_string(), count))
+                    .collect::<BTreeMap<_, _>>()
+            };
 
-        assert_eq!(results.len(), 1);
-        assert_eq!(results.get(0).unwrap().id, document2.id);
+            Ok(Facets {
+                sources: values(Facet::from_path(["source"])),
+                metadata: metadata_keys.iter().map(|key| (key.clone(), values(Facet::from_path(["meta", key.as_str()])))).collect(),
+            })
+        }).await?
+    }
+
+    /// Corrects the words found in none of the titles and contents: each is replaced by the
+    /// indexed word within the fuzzy search distance that is found in the most documents.
+    async fn suggest(&self, query: &str) -> anyhow::Result<Option<String>> {
+        let parsed = query::ParsedQuery::parse(query)?;
+        let searcher = self.reader.searcher();
+        let mut unknown = vec![];
+
+        for clause in parsed.clauses.iter().filter(|clause| clause.occur != query::Occur::MustNot) {
+            for word in clause.text.split_whitespace() {
+                // Only the words analyzed in a single term: `IndexWriter::new` isn't a typo
+                if let [term] = self.terms(self.fields.content, word)?.as_slice() {
+                    let title_term = Term::from_field_text(self.fields.title, term.text());
+
+                    if searcher.doc_freq(term)? + searcher.doc_freq(&title_term)? == 0 {
+                        unknown.push((word.to_string(), term.text().to_string()));
+                    }
+                }
+            }
+        }
+
+        let fields = [self.fields.title, self.fields.content];
+        let query = query.to_string();
+
+        tokio::task::spawn_blocking(move || -> anyhow::Result<Option<String>> {
+            let mut corrected = query.clone();
+
+            for (word, text) in unknown {
+                if let Some(closest) = closest_term(&searcher, &fields, &text)? {
+                    let pattern = Regex::new(&format!(r"\b{}\b", regex::escape(&word)))?;
+                    corrected = pattern.replace_all(&corrected, NoExpand(&closest)).into_owned();
+                }
+            }
+
+            Ok(Some(corrected).filter(|corrected| *corrected != query))
+        }).await?
+    }
+
+    async fn similar(&self, id: &str, limit: usize) -> SearchResult {
+        let searcher = self.reader.searcher();
+        let id_query = TermQuery::new(Term::from_field_text(self.fields.id, id), IndexRecordOption::Basic);
+
+        let document = match searcher.search(&id_query, &TopDocs::with_limit(1))?.first() {
+            Some((_, address)) => searcher.doc(*address)?,
+            None => bail!("Document not found: {}", id),
+        };
+
+        // Only the text fields describe the document: the link or the source would match nothing or everything
+        let text_fields = [self.fields.title, self.fields.content]
+            .iter()
+            .map(|field| (*field, document.get_all(*field).map(|value| FieldValue::new(*field, value.clone())).collect()))
+            .collect();
+
+        // The defaults only consider the terms found in at least 5 documents, too strict for small indexes
+        let more_like_this = MoreLikeThisQuery::builder()
+            .with_min_doc_frequency(1)
+            .with_min_term_frequency(1)
+            .with_document_fields(text_fields);
+
+        let query: Box<dyn Query> = Box::new(BooleanQuery::new(vec![
+            (Occur::Must, Box::new(more_like_this)),
+            (Occur::MustNot, Box::new(id_query)),
+        ]));
+
+        let request = self.capped(SearchRequest { limit, ..SearchRequest::new("") })?;
+        let collector = top_docs(&searcher, &request, &self.fields)?;
+
+        stream_results(searcher, query, collector, request, self.fields.clone())
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        let writer = self.writer.clone();
+
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            let mut writer = writer.write().unwrap();
+            writer.delete_all_documents()?;
+            writer.commit()?;
+            Ok(())
+        }).await??;
+
+        self.reader.reload()?;
+
+        Ok(())
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        let writer = self.writer.clone();
+        let term = Term::from_field_text(self.fields.source, source);
+
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            let mut writer = writer.write().unwrap();
+            writer.delete_term(term);
+            writer.commit()?;
+            Ok(())
+        }).await??;
+
+        self.reader.reload()?;
+
+        Ok(())
+    }
+
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        let searcher = self.reader.searcher();
+        let source_field = self.fields.source;
+
+        tokio::task::spawn_blocking(move || -> anyhow::Result<IndexStats> {
+            let mut sources = BTreeSet::new();
+
+            for segment_reader in searcher.segment_readers() {
+                let inverted_index = segment_reader.inverted_index(source_field)?;
+                let mut terms = inverted_index.terms().stream()?;
+
+                while terms.advance() {
+                    sources.insert(String::from_utf8_lossy(terms.key()).to_string());
+                }
+            }
+
+            // Terms of deleted documents are still listed: count the live documents of each source
+            let mut counts = BTreeMap::new();
+
+            for source in sources {
+                let query = TermQuery::new(Term::from_field_text(source_field, &source), IndexRecordOption::Basic);
+                let count = searcher.search(&query, &Count)? as u64;
+
+                if count > 0 {
+                    counts.insert(source, count);
+                }
+            }
+
+            Ok(IndexStats::from_sources(counts))
+        }).await?
+    }
+}
+
+/// The requested page of the top documents, their scores multiplied by the boosts of their sources.
+/// When sorted by date, they are ranked by their modification dates first, then by their scores.
+/// When sorted by title or source, all the matching documents are collected for `requested_page`
+/// to sort them on their stored texts.
+fn top_docs(
+    searcher: &Searcher,
+    request: &SearchRequest,
+    fields: &SchemaFields,
+) -> anyhow::Result<impl Collector<Fruit=Vec<((i64, Score), DocAddress)>>> {
+    let mut boosts: HashMap<SegmentId, HashMap<DocId, Score>> = HashMap::new();
+
+    for (source, boost) in &request.source_boosts {
+        let term = Term::from_field_text(fields.source, source);
+
+        for segment_reader in searcher.segment_readers() {
+            let postings = segment_reader.inverted_index(fields.source)?.read_postings(&term, IndexRecordOption::Basic)?;
+
+            if let Some(mut postings) = postings {
+                let segment_boosts = boosts.entry(segment_reader.segment_id()).or_default();
+                let mut doc = postings.doc();
+
+                while doc != TERMINATED {
+                    segment_boosts.insert(doc, *boost);
+                    doc = postings.advance();
+                }
+            }
+        }
+    }
+
+    let sort = request.sort;
+    let modified = fields.modified;
+    let top_docs = match sort {
+        SortOrder::Title | SortOrder::Source => TopDocs::with_limit(searcher.num_docs().max(1) as usize),
+        _ => TopDocs::with_limit(request.limit).and_offset(request.offset),
+    };
+
+    Ok(top_docs.tweak_score(move |segment_reader: &SegmentReader| {
+        let segment_boosts = boosts.get(&segment_reader.segment_id()).cloned().unwrap_or_default();
+        let dates = segment_reader.fast_fields().i64(modified).ok().filter(|_| sort == SortOrder::Date);
+
+        move |doc: DocId, score: Score| {
+            let rank = dates.as_ref().map_or(0, |dates| dates.get(doc));
+
+            (rank, score * segment_boosts.get(&doc).copied().unwrap_or(1.0))
+        }
+    }))
+}
+
+/// The documents of the requested page among the `top_docs`, sorted alphabetically (case
+/// insensitively) by title or source when requested, the most relevant first among the same texts.
+fn requested_page(
+    searcher: &Searcher,
+    top_docs: Vec<((i64, Score), DocAddress)>,
+    request: &SearchRequest,
+    fields: &SchemaFields,
+) -> anyhow::Result<Vec<(Score, DocAddress)>> {
+    let field = match request.sort {
+        SortOrder::Title => fields.title,
+        SortOrder::Source => fields.source,
+        _ => return Ok(top_docs.into_iter().map(|((_, score), doc_address)| (score, doc_address)).collect()),
+    };
+
+    let mut sorted = top_docs
+        .into_iter()
+        .map(|((_, score), doc_address)| {
+            let doc = searcher.doc(doc_address)?;
+            let text = doc.get_first(field).and_then(stored_text).unwrap_or_default().to_lowercase();
+
+            Ok((text, score, doc_address))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    sorted.sort_by(|(text, score, _), (other_text, other_score, _)| {
+        text.cmp(other_text).then_with(|| other_score.total_cmp(score))
+    });
+
+    Ok(sorted.into_iter().skip(request.offset).take(request.limit).map(|(_, score, doc_address)| (score, doc_address)).collect())
+}
+
+fn stream_results(
+    searcher: LeasedItem<Searcher>,
+    query: Box<dyn Query>,
+    collector: impl Collector<Fruit=Vec<((i64, Score), DocAddress)>> + 'static,
+    request: SearchRequest,
+    fields: SchemaFields,
+) -> SearchResult {
+    let (results_tx, results_rx) = tokio::sync::mpsc::channel(64);
+
+    // TODO: Is it possible that this leaks?
+    // When `rx` is dropped, `send_blocking` should fail making this task stop?
+    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        let snippet_generator = snippet_generator(&searcher, &*query, &fields)?;
+
+        let top_docs = searcher.search(
+            query.borrow(),
+            &collector,
+        )?;
+
+        for (score, doc_address) in requested_page(&searcher, top_docs, &request, &fields)? {
+            let doc = searcher.doc(doc_address)?;
+            let doc = tantivy_doc_to_found_item(
+                doc,
+                score.abs(),
+                &fields,
+                &snippet_generator,
+            )?;
+
+            results_tx.blocking_send(Ok(doc))?;
+        }
+
+        Ok(())
+    });
+
+    Ok(Box::pin(tokio_stream::wrappers::ReceiverStream::new(results_rx)))
+}
+
+/// The debug representation of the queries shows the terms as bytes: they are replaced with
+/// `field:text`.
+fn readable_query(query: &str, schema: &Schema) -> String {
+    let term = Regex::new(r"Term\(field=(\d+),\s*bytes=\[([\d, ]*)\]\)").unwrap();
+
+    term.replace_all(query, |captures: &Captures| {
+        let field = captures[1].parse().map(|field| schema.get_field_name(Field::from_field_id(field)));
+        let bytes = captures[2].split(',').filter_map(|byte| byte.trim().parse().ok()).collect::<Vec<u8>>();
+
+        match field {
+            Ok(field) => format!("{}:{}", field, String::from_utf8_lossy(&bytes)),
+            Err(_) => captures[0].to_string(),
+        }
+    }).to_string()
+}
+
+/// `key=value`, the `=` (and `\`) of the key escaped with a `\`.
+fn metadata_term(key: &str, value: &str) -> String {
+    format!("{}={}", key.replace('\\', "\\\\").replace('=', "\\="), value)
+}
+
+/// The key and value of a `metadata_term`.
+fn parse_metadata_term(term: &str) -> Option<(String, String)> {
+    let mut key = String::new();
+    let mut chars = term.char_indices();
+
+    while let Some((position, c)) = chars.next() {
+        match c {
+            '\\' => key.extend(chars.next().map(|(_, escaped)| escaped)),
+            '=' => return Some((key, term[position + 1..].to_string())),
+            other => key.push(other),
+        }
+    }
+
+    None
+}
+
+/// Restricts the query to the documents matching the source, metadata, path, tag and id filters
+/// (if any).
+fn with_filters(query: Box<dyn Query>, request: &SearchRequest, fields: &SchemaFields) -> anyhow::Result<Box<dyn Query>> {
+    let term_query = |field: Field, text: &str| -> Box<dyn Query> {
+        Box::new(TermQuery::new(Term::from_field_text(field, text), IndexRecordOption::Basic))
+    };
+
+    let mut clauses = vec![(Occur::Must, query)];
+
+    if !request.source_filter.is_empty() {
+        let sources = request.source_filter.iter().map(|source| (Occur::Should, term_query(fields.source, source))).collect();
+        clauses.push((Occur::Must, Box::new(BooleanQuery::new(sources))));
+    }
+
+    for (key, value) in &request.metadata_filter {
+        clauses.push((Occur::Must, term_query(fields.metadata, &metadata_term(key, value))));
+    }
+
+    if let Some(path) = &request.path_filter {
+        let pattern = regex::escape(&metadata_term("path", "")) + &glob_to_regex(path);
+        clauses.push((Occur::Must, Box::new(RegexQuery::from_pattern(&pattern, fields.metadata)?)));
+    }
+
+    for tag in &request.tag_filter {
+        clauses.push((Occur::Must, term_query(fields.tags, tag)));
+    }
+
+    if !request.id_filter.is_empty() {
+        let ids = request.id_filter.iter().map(|id| (Occur::Should, term_query(fields.id, id))).collect();
+        clauses.push((Occur::Must, Box::new(BooleanQuery::new(ids))));
+    }
+
+    if let Some(since) = request.modified_since {
+        clauses.push((Occur::Must, Box::new(RangeQuery::new_i64(fields.modified, since.timestamp()..i64::MAX))));
+    }
+
+    match clauses.len() {
+        1 => Ok(clauses.remove(0).1),
+        _ => Ok(Box::new(BooleanQuery::new(clauses))),
+    }
+}
+
+/// Like the `AUTO` fuzziness of Elasticsearch: no typos in the words of 1 or 2 letters, and at
+/// most one in the words of 3 to 5 letters, as they would match too many other words otherwise.
+fn max_typos(word: &str) -> u8 {
+    match word.chars().count() {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    }
+}
+
+/// The indexed term of the fields closest to the text (within `max_typos`), the one found in the
+/// most documents among the closest ones.
+fn closest_term(searcher: &Searcher, fields: &[Field], text: &str) -> anyhow::Result<Option<String>> {
+    let max_distance = max_typos(text) as usize;
+    let length = text.chars().count();
+    let mut candidates: HashMap<String, u64> = HashMap::new();
+
+    if max_distance == 0 {
+        return Ok(None);
+    }
+
+    for segment_reader in searcher.segment_readers() {
+        for field in fields {
+            let inverted_index = segment_reader.inverted_index(*field)?;
+            let mut terms = inverted_index.terms().stream()?;
+
+            while terms.advance() {
+                let candidate = match std::str::from_utf8(terms.key()) {
+                    Ok(candidate) => candidate,
+                    Err(_) => continue,
+                };
+
+                if candidate.chars().count().abs_diff(length) <= max_distance && osa_distance(candidate, text) <= max_distance {
+                    *candidates.entry(candidate.to_string()).or_default() += u64::from(terms.value().doc_freq);
+                }
+            }
+        }
+    }
+
+    Ok(
+        candidates
+            .into_iter()
+            .min_by_key(|(candidate, doc_freq)| (osa_distance(candidate, text), Reverse(*doc_freq), candidate.clone()))
+            .map(|(candidate, _)| candidate)
+    )
+}
+
+/// The text of a stored value, the code documents content being pre-tokenized.
+fn stored_text(value: &Value) -> Option<&str> {
+    value.text().or_else(|| value.tokenized_text().map(|tokenized| tokenized.text.as_str()))
+}
+
+fn tantivy_doc_to_document(tantivy_doc: &TantivyDoc, fields: &SchemaFields) -> Document {
+    let text = |field: Field| tantivy_doc.get_first(field).and_then(stored_text).unwrap_or_default().to_string();
+
+    Document {
+        id: text(fields.id),
+        source: text(fields.source),
+        title: text(fields.title),
+        link: text(fields.link),
+        content: text(fields.content),
+        metadata: tantivy_doc.get_all(fields.metadata).filter_map(Value::text).filter_map(parse_metadata_term).collect(),
+        tags: tantivy_doc.get_all(fields.tags).filter_map(Value::text).map(str::to_string).collect(),
+        modified: tantivy_doc
+            .get_first(fields.modified)
+            .and_then(Value::i64_value)
+            .and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single()),
+    }
+}
+
+fn snippet_generator(searcher: &Searcher, query: &dyn Query, fields: &SchemaFields) -> tantivy::Result<SnippetGenerator> {
+    let mut snippet_generator = SnippetGenerator::create(searcher, query, fields.content)?;
+    snippet_generator.set_max_num_chars(SNIPPET_SIZE);
+
+    Ok(snippet_generator)
+}
+
+fn tantivy_doc_to_found_item(
+    tantivy_doc: TantivyDoc,
+    score: f32,
+    fields: &SchemaFields,
+    snippet_generator: &SnippetGenerator,
+) -> anyhow::Result<FoundItem> {
+    let content = tantivy_doc.get_first(fields.content).and_then(stored_text).unwrap_or_default();
+    let snippet = snippet_generator.snippet(content).to_html();
+
+    // Documents matching on their title or with typos have no highlighted fragment: their
+    // beginning is shown instead
+    let snippet = match snippet.is_empty() {
+        true => escape_html(content.chars().take(SNIPPET_SIZE).collect::<String>().trim()),
+        false => snippet,
+    };
+
+    Ok(
+        FoundItem {
+            id: tantivy_doc.get_first(fields.id)
+                .and_then(|f| f.text())
+                .expect("Field id of type text not found")
+                .to_string(),
+            title: tantivy_doc.get_first(fields.title)
+                .and_then(|f| f.text())
+                .expect("Field title of type text not found")
+                .to_string(),
+            link: tantivy_doc.get_first(fields.link)
+                .and_then(|f| f.text())
+                .expect("Field link of type text not found")
+                .to_string(),
+            snippet,
+            source: tantivy_doc.get_first(fields.source)
+                .and_then(|f| f.text())
+                .expect("Field source of type text not found")
+                .to_string(),
+            score,
+        }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use chrono::{DateTime, TimeZone, Utc};
+    use tantivy::tokenizer::Language;
+    use tempdir::TempDir;
+    use tokio_stream::StreamExt;
+
+    use crate::model::Document;
+    use crate::search::tantivy_impl::{check_fields, Analyzer, Boosts, CustomField, FieldKind, StopWords, TantivySearchEngine};
+    use crate::search::{SearchEngine, SearchRequest, SortOrder};
+
+    #[tokio::test]
+    async fn test_tantivy_search_engine() -> anyhow::Result<()> {
+        let index_path = TempDir::new("tantivy_index")?;
+
+        let engine = TantivySearchEngine::open(index_path.path(), &[], &Analyzer::default())?;
+
+        let document1 = Document {
+            title: "Hello world".to_string(),
+            content: "Hello content".to_string(),
+            source: "My source".to_string(),
+            link: "link1".to_string(),
+            metadata: HashMap::new(),
+            id: "1".to_string(),
+            tags: vec![],
+            modified: None,
+        };
+
+        let document2 = Document {
+            title: "Computer science".to_string(),
+            content: "Computer science content".to_string(),
+            source: "My source".to_string(),
+            link: "link2".to_string(),
+            metadata: HashMap::new(),
+            id: "2".to_string(),
+            tags: vec![],
+            modified: None,
+        };
+
+        engine.index(vec![document1, document2.clone()]).await?;
+
+        engine.reader.reload()?;
+
+        let results = engine.search(&SearchRequest::new("computer")).await?.collect::<Result<Vec<_>, _>>().await?;
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results.get(0).unwrap().id, document2.id);
+
+        Ok(())
+    }
+
+    /// The documents of the filter tests: only the second one has metadata and tags.
+    async fn filtered_engine() -> anyhow::Result<TantivySearchEngine> {
+        let engine = TantivySearchEngine::in_memory()?;
+
+        engine.index(vec![Document {
+            title: "Hello world".to_string(),
+            content: "Hello content".to_string(),
+            source: "My source".to_string(),
+            link: "link1".to_string(),
+            metadata: HashMap::new(),
+            id: "1".to_string(),
+            tags: vec![],
+            modified: None,
+        }]).await?;
+
+        engine.index(vec![Document {
+            title: "Computer science".to_string(),
+            content: "Computer science content".to_string(),
+            source: "My source".to_string(),
+            link: "link2".to_string(),
+            metadata: HashMap::from([("lang".to_string(), "rust".to_string()), ("path".to_string(), "docs/cs.md".to_string())]),
+            id: "2".to_string(),
+            tags: vec!["ops".to_string(), "db".to_string()],
+            modified: None,
+        }]).await?;
+
+        Ok(engine)
+    }
+
+    async fn ids(engine: &TantivySearchEngine, request: SearchRequest) -> anyhow::Result<Vec<String>> {
+        let results = engine.search(&request).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+        Ok(results.into_iter().map(|item| item.id).collect())
+    }
+
+    #[tokio::test]
+    async fn test_filters() -> anyhow::Result<()> {
+        let engine = filtered_engine().await?;
+
+        let request = SearchRequest { source_filter: vec!["Other source".to_string()], ..SearchRequest::new("content") };
+        assert!(ids(&engine, request).await?.is_empty());
+
+        let metadata_filter = BTreeMap::from([("lang".to_string(), "rust".to_string())]);
+        assert_eq!(ids(&engine, SearchRequest { metadata_filter, ..SearchRequest::new("content") }).await?, vec!["2"]);
+
+        for (path, expected) in [("docs/**", 1), ("docs/*.txt", 0), ("cs.md", 0)] {
+            let request = SearchRequest { path_filter: Some(path.to_string()), ..SearchRequest::new("content") };
+            assert_eq!(ids(&engine, request).await?.len(), expected, "{}", path);
+        }
+
+        let request = SearchRequest { id_filter: vec!["2".to_string(), "unknown".to_string()], ..SearchRequest::new("content") };
+        assert_eq!(ids(&engine, request).await?, vec!["2"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tags() -> anyhow::Result<()> {
+        let engine = filtered_engine().await?;
+
+        for (tags, expected) in [(vec!["ops"], 1), (vec!["ops", "db"], 1), (vec!["ops", "web"], 0)] {
+            let request = SearchRequest { tag_filter: tags.iter().map(|t| t.to_string()).collect(), ..SearchRequest::new("content") };
+            assert_eq!(ids(&engine, request).await?.len(), expected, "{:?}", tags);
+        }
+
+        assert_eq!(ids(&engine, SearchRequest::new("content tag:db")).await?, vec!["2"]);
+        assert_eq!(engine.document("2").await?.map(|document| document.tags), Some(vec!["ops".to_string(), "db".to_string()]));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_syntax() -> anyhow::Result<()> {
+        let engine = filtered_engine().await?;
+
+        for (query, expected) in [
+            ("content -computer", vec!["1"]),
+            ("title:content", vec![]),
+            ("+title:\"computer science\" hello", vec!["2"]),
+            ("-hello", vec!["2"]),
+            ("content source:\"My source\" meta:lang=rust path:docs/**", vec!["2"]),
+        ] {
+            assert_eq!(ids(&engine, SearchRequest::new(query)).await?, expected, "{}", query);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_search_engine() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+
+        let document = Document {
+            title: "Runbook".to_string(),
+            content: "Restart the database".to_string(),
+            source: "My source".to_string(),
+            link: "link1".to_string(),
+            metadata: HashMap::new(),
+            id: "1".to_string(),
+            tags: vec![],
+            modified: None,
+        };
+
+        engine.index(vec![document.clone()]).await?;
+
+        let results = engine.search(&SearchRequest::new("database")).await?.collect::<Result<Vec<_>, _>>().await?;
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, document.id);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_code_documents() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+
+        let document = Document {
+            title: "writer.rs".to_string(),
+            content: "IndexWriter::new()".to_string(),
+            source: "code".to_string(),
+            link: "writer.rs".to_string(),
+            metadata: HashMap::new(),
+            id: "1".to_string(),
+            tags: vec![],
+            modified: None,
+        };
+
+        engine.index(vec![document.clone()]).await?;
+
+        let results = engine.search(&SearchRequest::new("index_writer")).await?.collect::<Result<Vec<_>, _>>().await?;
+        assert_eq!(results.len(), 1);
+
+        assert_eq!(engine.document("1").await?.map(|document| document.content), Some(document.content));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_analyzer() -> anyhow::Result<()> {
+        let document = Document {
+            title: "Databases".to_string(),
+            content: "Restart".to_string(),
+            source: "docs".to_string(),
+            link: "1".to_string(),
+            metadata: HashMap::new(),
+            id: "1".to_string(),
+            tags: vec![],
+            modified: None,
+        };
+
+        let count = |engine: TantivySearchEngine, query: &str| {
+            let (document, request) = (document.clone(), SearchRequest::new(query));
+            async move {
+                engine.index(vec![document]).await?;
+                anyhow::Ok(engine.search(&request).await?.collect::<anyhow::Result<Vec<_>>>().await?.len())
+            }
+        };
+
+        // The inflected forms only match once stemmed
+        assert_eq!(count(TantivySearchEngine::in_memory()?, "database").await?, 0);
+        let stemmed = Analyzer { stemmer: Some(Language::English), ..Analyzer::default() };
+        assert_eq!(count(TantivySearchEngine::in_memory()?.with_analyzer(&stemmed), "database").await?, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_synonyms() -> anyhow::Result<()> {
+        let synonyms = BTreeMap::from([
+            ("k8s".to_string(), vec!["kubernetes".to_string()]),
+            ("ES".to_string(), vec!["elastic search".to_string()]),
+        ]);
+        let engine = TantivySearchEngine::in_memory()?.with_analyzer(&Analyzer { synonyms, ..Analyzer::default() });
+
+        let document = |id: &str, content: &str| Document {
+            title: id.to_string(),
+            content: content.to_string(),
+            source: "docs".to_string(),
+            link: id.to_string(),
+            metadata: HashMap::new(),
+            id: id.to_string(),
+            tags: vec![],
+            modified: None,
+        };
+
+        for (id, content) in [("1", "Kubernetes pods"), ("2", "Elastic search nodes"), ("3", "Search elastic")] {
+            engine.index(vec![document(id, content)]).await?;
+        }
+
+        let engine = &engine;
+        let ids = |query: &str| {
+            let request = SearchRequest::new(query);
+            async move {
+                let results = engine.search(&request).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+                anyhow::Ok(results.into_iter().map(|result| result.id).collect::<Vec<_>>())
+            }
+        };
+
+        assert_eq!(ids("k8s").await?, vec!["1"]);
+        assert_eq!(ids("es nodes").await?, vec!["2"]);
+        assert_eq!(ids("+kubernetes").await?, vec!["1"]);
+        assert!(ids("k8").await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fuzzy_search() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+
+        for (id, content) in [("1", "Kubernetes pods"), ("2", "Elastic search nodes")] {
+            engine.index(vec![Document {
+                title: id.to_string(),
+                content: content.to_string(),
+                source: "docs".to_string(),
+                link: id.to_string(),
+                metadata: HashMap::new(),
+                id: id.to_string(),
+                tags: vec![],
+                modified: None,
+            }]).await?;
+        }
+
+        let engine = &engine;
+        let ids = |query: &str, fuzzy: Option<u8>| {
+            let request = SearchRequest { fuzzy, ..SearchRequest::new(query) };
+            async move {
+                let results = engine.search(&request).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+                anyhow::Ok(results.into_iter().map(|result| result.id).collect::<Vec<_>>())
+            }
+        };
+
+        assert!(ids("kuberentes", None).await?.is_empty());
+        assert_eq!(ids("kuberentes", Some(2)).await?, vec!["1"]);
+        // A single typo in the words of 3 to 5 letters, none in the shorter ones
+        assert_eq!(ids("nodez", Some(2)).await?, vec!["2"]);
+        assert!(ids("nds", Some(2)).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_boosts() -> anyhow::Result<()> {
+        let score = |boosts: Boosts| async move {
+            let engine = TantivySearchEngine::in_memory()?.with_boosts(boosts);
+            let document = Document {
+                title: "Failover".to_string(),
+                content: "Promote the replica".to_string(),
+                source: "docs".to_string(),
+                link: "failover.md".to_string(),
+                metadata: HashMap::new(),
+                id: "1".to_string(),
+                tags: vec![],
+                modified: None,
+            };
+
+            engine.index(vec![document]).await?;
+            let results = engine.search(&SearchRequest::new("failover replica")).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+
+            anyhow::Ok(results[0].score)
+        };
+
+        let plain = score(Boosts::default()).await?;
+        let title_only = score(Boosts { title: 1.0, content: 0.0001 }).await?;
+        let boosted = score(Boosts { title: 3.0, content: 0.0001 }).await?;
+
+        assert!(title_only < plain);
+        assert!((boosted - 3.0 * title_only).abs() < 0.001, "{} {}", boosted, title_only);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_snippets() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+        let document = Document {
+            title: "Failover".to_string(),
+            content: format!("{}Restart the <primary> database.", "Check the replication & lag. ".repeat(10)),
+            source: "docs".to_string(),
+            link: "failover.md".to_string(),
+            metadata: HashMap::new(),
+            id: "1".to_string(),
+            tags: vec![],
+            modified: None,
+        };
+
+        engine.index(vec![document]).await?;
+        let snippet = |query: &str| {
+            let engine = &engine;
+            let request = SearchRequest::new(query);
+            async move {
+                let results = engine.search(&request).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+                anyhow::Ok(results[0].snippet.clone())
+            }
+        };
+
+        let matched = snippet("restart").await?;
+        assert!(matched.ends_with("<b>Restart</b> the &lt;primary&gt; database"), "{}", matched);
+        assert!(matched.len() < 250, "{}", matched);
+
+        // Matching the title only
+        let beginning = snippet("failover").await?;
+        assert!(beginning.starts_with("Check the replication &amp; lag."), "{}", beginning);
+        assert!(!beginning.contains("Restart"), "{}", beginning);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_facets() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+        let document = |id: &str, source: &str, lang: &str, content: &str| Document {
+            title: id.to_string(),
+            content: content.to_string(),
+            source: source.to_string(),
+            link: id.to_string(),
+            metadata: HashMap::from([("lang".to_string(), lang.to_string())]),
+            id: id.to_string(),
+            tags: vec![],
+            modified: None,
+        };
+
+        engine.index(vec![document("1", "docs", "rust", "Restart the service")]).await?;
+        engine.index(vec![document("2", "github", "rust", "Restart the node")]).await?;
+        engine.index(vec![document("3", "github", "go", "Restart")]).await?;
+        engine.index(vec![document("4", "github", "go", "Deploy")]).await?;
+
+        let facets = engine.facets(&SearchRequest { limit: 1, ..SearchRequest::new("restart") }, &["lang".to_string()]).await?;
+        let counts = |counts: &[(&str, u64)]| counts.iter().map(|(value, count)| (value.to_string(), *count)).collect::<BTreeMap<_, _>>();
+
+        assert_eq!(facets.sources, counts(&[("docs", 1), ("github", 2)]));
+        assert_eq!(facets.metadata["lang"], counts(&[("go", 1), ("rust", 2)]));
+
+        let facets = engine.facets(&SearchRequest::new("restart source:github"), &[]).await?;
+        assert_eq!(facets.sources, counts(&[("github", 2)]));
+        assert!(facets.metadata.is_empty());
+
+        // The metadata is stored along with the facets
+        let stored = engine.document("2").await?.map(|document| document.metadata);
+        assert_eq!(stored, Some(HashMap::from([("lang".to_string(), "rust".to_string())])));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_metadata() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+        let metadata = HashMap::from([
+            ("a=b".to_string(), "c=d".to_string()),
+            ("dir\\".to_string(), "=".to_string()),
+        ]);
+
+        engine.index(vec![Document {
+            title: "Runbook".to_string(),
+            content: "Restart".to_string(),
+            source: "docs".to_string(),
+            link: "1".to_string(),
+            metadata: metadata.clone(),
+            id: "1".to_string(),
+            tags: vec![],
+            modified: None,
+        }]).await?;
+
+        assert_eq!(engine.document("1").await?.map(|document| document.metadata), Some(metadata.clone()));
+
+        let documents = engine.documents().await?.collect::<anyhow::Result<Vec<_>>>().await?;
+        assert_eq!(documents.into_iter().map(|document| document.metadata).collect::<Vec<_>>(), vec![metadata]);
+
+        let metadata_filter = BTreeMap::from([("a=b".to_string(), "c=d".to_string())]);
+        let results = engine.search(&SearchRequest { metadata_filter, ..SearchRequest::new("restart") }).await?.collect::<Vec<_>>().await;
+        assert_eq!(results.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_results() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?.with_max_results(2);
+
+        for id in ["1", "2", "3"] {
+            engine.index(vec![Document {
+                title: id.to_string(),
+                content: "Restart".to_string(),
+                source: "docs".to_string(),
+                link: id.to_string(),
+                metadata: HashMap::new(),
+                id: id.to_string(),
+                tags: vec![],
+                modified: None,
+            }]).await?;
+        }
+
+        let engine = &engine;
+        let count = |request: SearchRequest| async move {
+            anyhow::Ok(engine.search(&request).await?.collect::<Result<Vec<_>, _>>().await?.len())
+        };
+
+        assert_eq!(count(SearchRequest::new("restart")).await?, 2);
+        assert_eq!(count(SearchRequest { offset: 1, ..SearchRequest::new("restart") }).await?, 1);
+        assert!(count(SearchRequest { offset: 2, ..SearchRequest::new("restart") }).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_explain() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+
+        for (id, title, content) in [("1", "Setup", "Install the database"), ("2", "Database", "Restart the database")] {
+            engine.index(vec![Document {
+                title: title.to_string(),
+                content: content.to_string(),
+                source: "docs".to_string(),
+                link: id.to_string(),
+                metadata: HashMap::new(),
+                id: id.to_string(),
+                tags: vec![],
+                modified: None,
+            }]).await?;
+        }
+
+        let request = SearchRequest::new("database");
+        let results = engine.search(&request).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+        let explanation = engine.explain(&request).await?;
+
+        // The results are explained in the order and with the scores of the search
+        assert!(explanation.query.contains("title:database"), "{}", explanation.query);
+        assert_eq!(
+            explanation.results.iter().map(|(item, score)| (item.id.as_str(), score.value)).collect::<Vec<_>>(),
+            results.iter().map(|item| (item.id.as_str(), item.score)).collect::<Vec<_>>(),
+        );
+        assert_eq!(explanation.results[0].0.id, "2");
+        assert!(!explanation.results[0].1.details.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pages() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+
+        for id in ["1", "2", "3", "4", "5"] {
+            engine.index(vec![Document {
+                title: id.to_string(),
+                content: "Restart".to_string(),
+                source: "docs".to_string(),
+                link: id.to_string(),
+                metadata: HashMap::new(),
+                id: id.to_string(),
+                tags: vec![],
+                modified: None,
+            }]).await?;
+        }
+
+        let engine = &engine;
+        let page = |page: usize| async move {
+            let request = SearchRequest { limit: 2, offset: (page - 1) * 2, sort: SortOrder::Title, ..SearchRequest::new("restart") };
+            let results = engine.search(&request).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+            anyhow::Ok(results.into_iter().map(|result| result.id).collect::<Vec<_>>())
+        };
+
+        assert_eq!(page(1).await?, vec!["1", "2"]);
+        assert_eq!(page(2).await?, vec!["3", "4"]);
+        assert_eq!(page(3).await?, vec!["5"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_modified_dates() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+        let document = |id: &str, title: &str, modified: Option<DateTime<Utc>>| Document {
+            title: title.to_string(),
+            content: "Restart the database".to_string(),
+            source: "docs".to_string(),
+            link: id.to_string(),
+            metadata: HashMap::new(),
+            id: id.to_string(),
+            tags: vec![],
+            modified,
+        };
+
+        engine.index(vec![document("old", "Database runbook", Some(Utc.ymd(2020, 1, 1).and_hms(0, 0, 0)))]).await?;
+        engine.index(vec![document("new", "Runbook", Some(Utc.ymd(2024, 1, 1).and_hms(0, 0, 0)))]).await?;
+        engine.index(vec![document("unknown", "Database runbook", None)]).await?;
+
+        let ids = |request: SearchRequest| {
+            let engine = &engine;
+            async move {
+                let results = engine.search(&request).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+                anyhow::Ok(results.into_iter().map(|item| item.id).collect::<Vec<_>>())
+            }
+        };
+
+        assert_eq!(ids(SearchRequest { sort: SortOrder::Date, ..SearchRequest::new("database") }).await?, vec!["new", "old", "unknown"]);
+
+        let since = Some(Utc.ymd(2022, 1, 1).and_hms(0, 0, 0));
+        assert_eq!(ids(SearchRequest { modified_since: since, ..SearchRequest::new("database") }).await?, vec!["new"]);
+
+        assert_eq!(engine.document("old").await?.and_then(|document| document.modified), Some(Utc.ymd(2020, 1, 1).and_hms(0, 0, 0)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sort_orders() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+
+        for (id, source, title) in [("1", "docs", "Zookeeper restart"), ("2", "wiki", "Restart the api"), ("3", "github", "restart")] {
+            engine.index(vec![Document {
+                title: title.to_string(),
+                content: "Restart the service".to_string(),
+                source: source.to_string(),
+                link: id.to_string(),
+                metadata: HashMap::new(),
+                id: id.to_string(),
+                tags: vec![],
+                modified: None,
+            }]).await?;
+        }
+
+        for (sort, expected) in [(SortOrder::Title, vec!["3", "2", "1"]), (SortOrder::Source, vec!["1", "3", "2"])] {
+            let request = SearchRequest { sort, ..SearchRequest::new("restart") };
+            let results = engine.search(&request).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+            assert_eq!(results.iter().map(|item| item.id.as_str()).collect::<Vec<_>>(), expected, "{:?}", sort);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sort_by_title_with_shared_prefix() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+
+        // The most relevant document comes last alphabetically
+        for (id, title, content) in [("1", "Runbook: database", "runbook runbook"), ("2", "Runbook: api", "runbook")] {
+            engine.index(vec![Document {
+                title: title.to_string(),
+                content: content.to_string(),
+                source: "docs".to_string(),
+                link: id.to_string(),
+                metadata: HashMap::new(),
+                id: id.to_string(),
+                tags: vec![],
+                modified: None,
+            }]).await?;
+        }
+
+        let request = SearchRequest { sort: SortOrder::Title, ..SearchRequest::new("runbook") };
+        let results = engine.search(&request).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+        assert_eq!(results.iter().map(|item| item.id.as_str()).collect::<Vec<_>>(), vec!["2", "1"]);
+
+        let request = SearchRequest { limit: 1, offset: 1, ..request };
+        let results = engine.search(&request).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+        assert_eq!(results.iter().map(|item| item.id.as_str()).collect::<Vec<_>>(), vec!["1"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_custom_fields() -> anyhow::Result<()> {
+        let index_path = TempDir::new("tantivy_index")?;
+        let field = |name: &str, kind: FieldKind| CustomField { name: name.to_string(), kind, stored: true, indexed: true, fast: false };
+        let custom = vec![field("summary", FieldKind::Text), field("priority", FieldKind::U64)];
+
+        let engine = TantivySearchEngine::open(index_path.path(), &custom, &Analyzer::default())?;
+        engine.index(vec![Document {
+            title: "Runbook".to_string(),
+            content: "Restart the database".to_string(),
+            source: "docs".to_string(),
+            link: "runbook.md".to_string(),
+            metadata: HashMap::from([("summary".to_string(), "Failover steps".to_string()), ("priority".to_string(), "high".to_string())]),
+            id: "runbook".to_string(),
+            tags: vec![],
+            modified: None,
+        }]).await?;
+
+        // The text fields are searched along with the title and content
+        let results = engine.search(&SearchRequest::new("failover")).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+        assert_eq!(results.iter().map(|item| item.id.as_str()).collect::<Vec<_>>(), vec!["runbook"]);
+        drop(engine);
+
+        let error = TantivySearchEngine::open(index_path.path(), &[], &Analyzer::default()).err().map(|err| err.to_string());
+        assert!(error.is_some_and(|err| err.contains("with other fields or analyzer")));
+
+        assert!(check_fields(&[field("title", FieldKind::String)]).is_err());
+        assert!(check_fields(&[field("team-name", FieldKind::String)]).is_err());
+        assert!(check_fields(&[CustomField { fast: true, ..field("team", FieldKind::String) }]).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_other_analyzer() -> anyhow::Result<()> {
+        let index_path = TempDir::new("tantivy_index")?;
+        let stemmed = || Analyzer { stemmer: Some(Language::English), ..Analyzer::default() };
+
+        drop(TantivySearchEngine::open(index_path.path(), &[], &stemmed())?);
+
+        // The synonyms only apply to the queries
+        let synonyms = BTreeMap::from([("k8s".to_string(), vec!["kubernetes".to_string()])]);
+        drop(TantivySearchEngine::open(index_path.path(), &[], &Analyzer { synonyms, ..stemmed() })?);
+
+        let error = TantivySearchEngine::open(index_path.path(), &[], &Analyzer::default()).err().map(|err| err.to_string());
+        assert!(error.is_some_and(|err| err.contains("with other fields or analyzer")));
+        assert!(TantivySearchEngine::open(index_path.path(), &[], &Analyzer { stop_words: Some(StopWords::English), ..stemmed() }).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_suggest() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+
+        for (id, content) in [("1", "Restart the database"), ("2", "Backup the database"), ("3", "Rotate the databases")] {
+            engine.index(vec![Document {
+                title: "Runbook".to_string(),
+                content: content.to_string(),
+                source: "docs".to_string(),
+                link: id.to_string(),
+                metadata: HashMap::new(),
+                id: id.to_string(),
+                tags: vec![],
+                modified: None,
+            }]).await?;
+        }
+
+        assert_eq!(engine.suggest("databse restrat").await?.as_deref(), Some("database restart"));
+        assert_eq!(engine.suggest("Runbok -databse").await?.as_deref(), Some("runbook -databse"));
+        assert_eq!(engine.suggest("restart database").await?, None);
+        assert_eq!(engine.suggest("xyzzy").await?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_source_boosts() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+        let document = |id: &str, source: &str, title: &str| Document {
+            title: title.to_string(),
+            content: "Promote the replica".to_string(),
+            source: source.to_string(),
+            link: format!("{}.md", id),
+            metadata: HashMap::new(),
+            id: id.to_string(),
+            tags: vec![],
+            modified: None,
+        };
+
+        engine.index(vec![document("1", "blog", "Failover failover")]).await?;
+        engine.index(vec![document("2", "runbooks", "Failover")]).await?;
+
+        let engine = &engine;
+        let sources = |request: SearchRequest| async move {
+            let results = engine.search(&request).await?.collect::<anyhow::Result<Vec<_>>>().await?;
+            anyhow::Ok(results.into_iter().map(|item| item.source).collect::<Vec<_>>())
+        };
+
+        assert_eq!(sources(SearchRequest::new("failover")).await?, vec!["blog", "runbooks"]);
+
+        let request = SearchRequest { source_boosts: BTreeMap::from([("runbooks".to_string(), 10.0)]), ..SearchRequest::new("failover") };
+        assert_eq!(sources(request.clone()).await?, vec!["runbooks", "blog"]);
+
+        let explanation = engine.explain(&request).await?;
+        let (item, score) = &explanation.results[0];
+        assert!((item.score - score.value).abs() < 0.001, "{} {}", item.score, score.value);
+        assert_eq!(score.details[1].value, 10.0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reindexing_replaces_documents() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+
+        let document = Document {
+            title: "Runbook".to_string(),
+            content: "Restart the database".to_string(),
+            source: "My source".to_string(),
+            link: "link1".to_string(),
+            metadata: HashMap::new(),
+            id: "1".to_string(),
+            tags: vec![],
+            modified: None,
+        };
+
+        engine.index(vec![document.clone()]).await?;
+        engine.index(vec![Document { title: "Updated runbook".to_string(), ..document }]).await?;
+
+        let results = engine.search(&SearchRequest::new("database")).await?.collect::<Result<Vec<_>, _>>().await?;
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].title, "Updated runbook");
+
+        let stats = engine.stats().await?;
+        assert_eq!(stats.documents, 1);
+        assert_eq!(stats.sources.get("My source"), Some(&1));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_purge() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+
+        let document = |id: &str, source: &str| Document {
+            title: "Runbook".to_string(),
+            content: "Restart the database".to_string(),
+            source: source.to_string(),
+            link: id.to_string(),
+            metadata: HashMap::new(),
+            id: id.to_string(),
+            tags: vec![],
+            modified: None,
+        };
+
+        engine.index(vec![document("1", "wiki"), document("2", "wiki"), document("3", "github")]).await?;
+
+        let documents = engine.documents().await?.collect::<Result<Vec<_>, _>>().await?;
+        assert_eq!(documents.len(), 3);
+        assert!(documents.contains(&document("3", "github")));
+
+        engine.delete(&["1".to_string()]).await?;
+        assert_eq!(engine.stats().await?.sources.get("wiki"), Some(&1));
+
+        engine.purge_source("wiki").await?;
+
+        let stats = engine.stats().await?;
+        assert_eq!(stats.documents, 1);
+        assert_eq!(stats.sources.get("wiki"), None);
+
+        engine.purge().await?;
+
+        assert_eq!(engine.stats().await?.documents, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_similar() -> anyhow::Result<()> {
+        let engine = TantivySearchEngine::in_memory()?;
+
+        let document = |id: &str, title: &str, content: &str| Document {
+            title: title.to_string(),
+            content: content.to_string(),
+            source: "wiki".to_string(),
+            link: id.to_string(),
+            metadata: HashMap::new(),
+            id: id.to_string(),
+            tags: vec![],
+            modified: None,
+        };
+
+        engine.index(vec![
+            document("1", "Database runbook", "Restart the postgres database when replication lags"),
+            document("2", "Replication runbook", "Check the postgres replication lag before restarting"),
+            document("3", "Onboarding", "Welcome to the team, read the handbook"),
+        ]).await?;
+
+        let results = engine.similar("1", 10).await?.collect::<Result<Vec<_>, _>>().await?;
+        assert_eq!(results[0].id, "2");
+        assert!(results.iter().all(|r| r.id != "1"));
+
+        assert!(engine.similar("unknown", 10).await.is_err());
 
         Ok(())
     }
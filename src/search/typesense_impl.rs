@@ -0,0 +1,403 @@
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use reqwest::{Client, Method, RequestBuilder};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+
+use crate::model::Document;
+use crate::search::{html_snippet, FoundItem, IndexStats, SearchEngine, SearchRequest, SearchResult, HIGHLIGHT_END, HIGHLIGHT_START};
+use crate::sources::DocStream;
+use crate::utils::json::get_array;
+use crate::utils::streams::channel_stream;
+
+/// Number of documents deleted per request, as their ids are passed in the url.
+const DELETE_BATCH_SIZE: usize = 100;
+
+pub struct TypesenseSearchEngine {
+    client: Client,
+    endpoint: String,
+    collection: String,
+    api_key: String,
+    collection_created: OnceCell<()>,
+}
+
+impl TypesenseSearchEngine {
+    pub fn new(endpoint: &str, collection: &str, api_key: String) -> Self {
+        Self {
+            client: Client::new(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            collection: collection.to_string(),
+            api_key,
+            collection_created: OnceCell::new(),
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.client
+            .request(method, format!("{}/{}", self.endpoint, path))
+            .header("X-TYPESENSE-API-KEY", &self.api_key)
+    }
+
+    async fn ensure_collection(&self) -> anyhow::Result<()> {
+        let exists = self.request(Method::GET, &format!("collections/{}", self.collection)).send().await?;
+
+        if exists.status().is_success() {
+            return Ok(());
+        }
+
+        log::info!("Creating typesense collection: {}", &self.collection);
+
+        self.request(Method::POST, "collections")
+            .json(&json!({
+                "name": self.collection,
+                "fields": [
+                    { "name": "doc_id", "type": "string" },
+                    { "name": "source", "type": "string", "facet": true },
+                    { "name": "title", "type": "string" },
+                    { "name": "link", "type": "string", "index": false, "optional": true },
+                    { "name": "content", "type": "string" }
+                ]
+            }))
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Couldn't create typesense collection: {}", self.collection))?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SearchEngine for TypesenseSearchEngine {
+    async fn index(&self, documents: Vec<Document>) -> anyhow::Result<()> {
+        self.collection_created.get_or_try_init(|| self.ensure_collection()).await?;
+
+        let response = self
+            .request(Method::POST, &format!("collections/{}/documents/import", self.collection))
+            .query(&[("action", "upsert")])
+            .body(import_body(&documents)?)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        check_import_response(&response)
+    }
+
+    async fn search(&self, request: &SearchRequest) -> SearchResult {
+        let (parsed, request) = request.parse_query()?;
+        request.ensure_no_document_filters()?;
+        request.ensure_no_dates()?;
+        request.ensure_relevance_sort()?;
+        request.ensure_no_source_boosts()?;
+
+        let query = parsed.text();
+        let mut http_request = self
+            .request(Method::GET, &format!("collections/{}/documents/search", self.collection))
+            .query(&[
+                ("q", query.as_str()),
+                ("query_by", "title,content"),
+                ("limit", &request.limit.to_string()),
+                ("offset", &request.offset.to_string()),
+                ("highlight_fields", "content"),
+                ("highlight_start_tag", HIGHLIGHT_START),
+                ("highlight_end_tag", HIGHLIGHT_END),
+            ]);
+
+        if !request.source_filter.is_empty() {
+            http_request = http_request.query(&[("filter_by", source_filter(&request.source_filter))]);
+        }
+
+        if let Some(distance) = request.fuzzy {
+            http_request = http_request.query(&[("num_typos", distance.to_string())]);
+        }
+
+        let stream = channel_stream(|tx| async move {
+            let response: Value = http_request
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            for item in parse_hits(&response)? {
+                tx.send(Ok(item)).await?;
+            }
+
+            Ok(())
+        });
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn purge(&self) -> anyhow::Result<()> {
+        let response = self.request(Method::DELETE, &format!("collections/{}", self.collection)).send().await?;
+
+        if response.status() != reqwest::StatusCode::NOT_FOUND {
+            response.error_for_status()?;
+        }
+
+        Ok(())
+    }
+
+    async fn purge_source(&self, source: &str) -> anyhow::Result<()> {
+        self.request(Method::DELETE, &format!("collections/{}/documents", self.collection))
+            .query(&[("filter_by", source_filter(&[source.to_string()]))])
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn documents(&self) -> anyhow::Result<DocStream> {
+        let response = self
+            .request(Method::GET, &format!("collections/{}/documents/export", self.collection))
+            .send()
+            .await?;
+
+        // Nothing was indexed yet
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(Box::pin(tokio_stream::empty()));
+        }
+
+        let documents = parse_export(&response.error_for_status()?.text().await?)?;
+
+        Ok(Box::pin(tokio_stream::iter(documents.into_iter().map(Ok))))
+    }
+
+    async fn delete(&self, ids: &[String]) -> anyhow::Result<()> {
+        for batch in ids.chunks(DELETE_BATCH_SIZE) {
+            self.request(Method::DELETE, &format!("collections/{}/documents", self.collection))
+                .query(&[("filter_by", id_filter(batch))])
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+
+        Ok(())
+    }
+
+    async fn stats(&self) -> anyhow::Result<IndexStats> {
+        let response: Value = self
+            .request(Method::GET, &format!("collections/{}/documents/search", self.collection))
+            .query(&[
+                ("q", "*"),
+                ("query_by", "title"),
+                ("per_page", "0"),
+                ("facet_by", "source"),
+                ("max_facet_values", "1000"),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        parse_stats(&response)
+    }
+}
+
+/// Builds the JSONL payload of the import endpoint. Typesense ids are restricted so a hash of the
+/// document id is used instead (the original id is kept in `doc_id`). The metadata isn't in the
+/// schema: it's stored but not indexed.
+fn import_body(documents: &[Document]) -> anyhow::Result<String> {
+    let mut body = String::new();
+
+    for document in documents {
+        log::info!("Indexing document: {} (source: {})", document.link, document.source);
+
+        body.push_str(&serde_json::to_string(&json!({
+            "id": typesense_id(&document.id),
+            "doc_id": document.id,
+            "source": document.source,
+            "title": document.title,
+            "link": document.link,
+            "content": document.content,
+            "metadata": document.metadata,
+        }))?);
+        body.push('\n');
+    }
+
+    Ok(body)
+}
+
+fn typesense_id(id: &str) -> String {
+    hex::encode(Sha256::digest(id.as_bytes()))
+}
+
+fn id_filter(ids: &[String]) -> String {
+    let ids = ids.iter().map(|id| typesense_id(id)).collect::<Vec<_>>();
+    format!("id:[{}]", ids.join(","))
+}
+
+/// Parses the JSONL of the export endpoint back into the indexed documents.
+fn parse_export(response: &str) -> anyhow::Result<Vec<Document>> {
+    response
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let document: Value = serde_json::from_str(line)?;
+            let field = |name: &str| document.get(name).and_then(|f| f.as_str()).unwrap_or_default().to_string();
+            let id = document.get("doc_id").and_then(|f| f.as_str()).with_context(|| format!("Unexpected document: {}", line))?;
+
+            Ok(Document {
+                id: id.to_string(),
+                source: field("source"),
+                title: field("title"),
+                link: field("link"),
+                content: field("content"),
+                metadata: document.get("metadata").cloned().map(serde_json::from_value).transpose()?.unwrap_or_default(),
+                tags: vec![],
+                modified: None,
+            })
+        })
+        .collect()
+}
+
+/// Backticks allow filtering on values containing commas or spaces.
+fn source_filter(sources: &[String]) -> String {
+    let sources = sources.iter().map(|s| format!("`{}`", s)).collect::<Vec<_>>();
+    format!("source:=[{}]", sources.join(","))
+}
+
+fn check_import_response(response: &str) -> anyhow::Result<()> {
+    for line in response.lines().filter(|l| !l.trim().is_empty()) {
+        let result: Value = serde_json::from_str(line)?;
+
+        if !result.get("success").and_then(|s| s.as_bool()).unwrap_or(false) {
+            bail!("Import failed for some documents. First error: {}", result);
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_stats(response: &Value) -> anyhow::Result<IndexStats> {
+    let sources = get_array(response, &["facet_counts"])?
+        .iter()
+        .filter(|facet| facet.get("field_name").and_then(|f| f.as_str()) == Some("source"))
+        .flat_map(|facet| facet.get("counts").and_then(|c| c.as_array()).cloned().unwrap_or_default())
+        .map(|count| {
+            let source = count.get("value").and_then(|v| v.as_str());
+            let value = count.get("count").and_then(|c| c.as_u64());
+
+            match (source, value) {
+                (Some(source), Some(value)) => Ok((source.to_string(), value)),
+                _ => bail!("Unexpected facet count: {}", count),
+            }
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok(IndexStats {
+        documents: response.get("found").and_then(|f| f.as_u64()).unwrap_or_default(),
+        sources,
+    })
+}
+
+fn parse_hits(response: &Value) -> anyhow::Result<Vec<FoundItem>> {
+    get_array(response, &["hits"])?
+        .iter()
+        .map(|hit| {
+            let field = |name: &str| -> anyhow::Result<String> {
+                hit.pointer(&format!("/document/{}", name))
+                    .and_then(|f| f.as_str())
+                    .map(|f| f.to_string())
+                    .with_context(|| format!("Field {} of type text not found in hit: {}", name, hit))
+            };
+
+            let snippet = hit.get("highlights")
+                .and_then(|h| h.as_array())
+                .and_then(|highlights| {
+                    highlights
+                        .iter()
+                        .find(|h| h.get("field").and_then(|f| f.as_str()) == Some("content"))
+                })
+                .and_then(|h| h.get("snippet"))
+                .and_then(|s| s.as_str())
+                .map(html_snippet)
+                .unwrap_or_default();
+
+            Ok(FoundItem {
+                id: field("doc_id")?,
+                score: hit.get("text_match").and_then(|s| s.as_f64()).unwrap_or_default() as f32,
+                source: field("source")?,
+                title: field("title")?,
+                link: field("link")?,
+                snippet,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::search::typesense_impl::{check_import_response, id_filter, parse_export, parse_hits, parse_stats, typesense_id};
+
+    #[test]
+    fn test_check_import_response() {
+        assert!(check_import_response("{\"success\": true}\n{\"success\": true}").is_ok());
+        assert!(check_import_response("{\"success\": true}\n{\"success\": false, \"error\": \"Bad JSON.\"}").is_err());
+    }
+
+    #[test]
+    fn test_parse_hits() -> anyhow::Result<()> {
+        let response = json!({
+            "found": 1,
+            "hits": [{
+                "document": { "id": "abc", "doc_id": "1", "source": "src", "title": "Hello", "link": "link1", "content": "Hello content" },
+                "highlights": [{ "field": "content", "snippet": "\u{2}Hello\u{3} <content>" }],
+                "text_match": 578730123365187705u64
+            }]
+        });
+
+        let hits = parse_hits(&response)?;
+
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, "1");
+        assert_eq!(hits[0].snippet, "<b>Hello</b> &lt;content&gt;");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_stats() -> anyhow::Result<()> {
+        let response = json!({
+            "found": 3,
+            "hits": [],
+            "facet_counts": [{
+                "field_name": "source",
+                "counts": [{ "value": "github", "count": 2 }, { "value": "fs", "count": 1 }]
+            }]
+        });
+
+        let stats = parse_stats(&response)?;
+
+        assert_eq!(stats.documents, 3);
+        assert_eq!(stats.sources.get("github"), Some(&2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_export() -> anyhow::Result<()> {
+        let export = concat!(
+            "{\"id\": \"abc\", \"doc_id\": \"1\", \"source\": \"src\", \"title\": \"Hello\", \"link\": \"link1\", \"content\": \"Hello\", \"metadata\": {\"repository\": \"doks\"}}\n",
+            "{\"id\": \"def\", \"doc_id\": \"2\", \"source\": \"src\", \"title\": \"World\", \"content\": \"World\"}\n",
+        );
+
+        let documents = parse_export(export)?;
+
+        assert_eq!(documents.iter().map(|document| document.id.as_str()).collect::<Vec<_>>(), vec!["1", "2"]);
+        assert_eq!(documents[0].metadata.get("repository").map(String::as_str), Some("doks"));
+        assert!(documents[1].metadata.is_empty());
+
+        assert_eq!(id_filter(&["1".to_string(), "2".to_string()]), format!("id:[{},{}]", typesense_id("1"), typesense_id("2")));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+
+use chrono::{TimeZone, Utc};
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+use tonic::service::Interceptor;
+use tonic::transport::Server;
+
+use proto::doks_server::{Doks, DoksServer};
+
+use crate::model::Document;
+use crate::search::{FoundItem, IndexStats, SearchEngine, SearchRequest, SortOrder};
+use crate::server::metrics::metrics;
+use crate::server::token_matches;
+
+pub mod proto {
+    tonic::include_proto!("doks.v1");
+}
+
+/// Serves the engine over gRPC until interrupted, with the same bearer token check as the HTTP API.
+pub async fn serve(engine: Arc<dyn SearchEngine>, address: &SocketAddr, token: Option<String>) -> anyhow::Result<()> {
+    log::info!("Listening for gRPC on: {}", address);
+
+    Server::builder()
+        .add_service(DoksServer::with_interceptor(GrpcService { engine }, Authentication { token }))
+        .serve_with_shutdown(*address, async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[derive(Clone)]
+struct Authentication {
+    token: Option<String>,
+}
+
+impl Interceptor for Authentication {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(token) = &self.token {
+            let authorized = request.metadata()
+                .get("authorization")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .is_some_and(|provided| token_matches(provided, token));
+
+            if !authorized {
+                return Err(Status::unauthenticated("Invalid or missing bearer token"));
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+struct GrpcService {
+    engine: Arc<dyn SearchEngine>,
+}
+
+type FoundItemStream = Pin<Box<dyn Stream<Item=Result<proto::FoundItem, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Doks for GrpcService {
+    type SearchStream = FoundItemStream;
+
+    async fn search(&self, request: Request<proto::SearchRequest>) -> Result<Response<Self::SearchStream>, Status> {
+        let started = Instant::now();
+        let results = self.engine.search(&request.into_inner().into()).await;
+        metrics().search(started.elapsed(), results.is_ok());
+
+        let results = results.map_err(internal)?;
+        // The stream items are dictated by tonic
+        #[allow(clippy::result_large_err)]
+        let items = results.map(|item| item.map(proto::FoundItem::from).map_err(internal));
+
+        Ok(Response::new(Box::pin(items)))
+    }
+
+    async fn index(&self, request: Request<proto::IndexRequest>) -> Result<Response<proto::IndexResponse>, Status> {
+        let documents = request.into_inner().documents.into_iter().map(Document::from).collect::<Vec<_>>();
+        let sources = documents.iter().map(|document| document.source.clone()).collect::<Vec<_>>();
+        self.engine.index(documents).await.map_err(internal)?;
+
+        for source in sources {
+            metrics().documents_indexed(&source, 1);
+        }
+
+        Ok(Response::new(proto::IndexResponse {}))
+    }
+
+    async fn stats(&self, _request: Request<proto::StatsRequest>) -> Result<Response<proto::IndexStats>, Status> {
+        Ok(Response::new(self.engine.stats().await.map_err(internal)?.into()))
+    }
+
+    async fn purge(&self, request: Request<proto::PurgeRequest>) -> Result<Response<proto::PurgeResponse>, Status> {
+        match request.into_inner().source {
+            Some(source) => self.engine.purge_source(&source).await.map_err(internal)?,
+            None => self.engine.purge().await.map_err(internal)?,
+        }
+
+        Ok(Response::new(proto::PurgeResponse {}))
+    }
+}
+
+/// Reports engine failures to the client as internal errors.
+fn internal(err: anyhow::Error) -> Status {
+    log::error!("Request failed: {:#}", err);
+    Status::internal(format!("{:#}", err))
+}
+
+impl From<proto::SearchRequest> for SearchRequest {
+    fn from(request: proto::SearchRequest) -> Self {
+        let defaults = SearchRequest::new(&request.query);
+
+        SearchRequest {
+            limit: request.limit.map_or(defaults.limit, |limit| limit as usize),
+            offset: request.offset as usize,
+            source_filter: request.sources,
+            metadata_filter: request.metadata.into_iter().collect(),
+            path_filter: request.path,
+            tag_filter: request.tags,
+            fuzzy: request.fuzzy.map(|distance| distance.min(u8::MAX.into()) as u8),
+            modified_since: request.modified_since.and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single()),
+            sort: match proto::SortOrder::from_i32(request.sort) {
+                Some(proto::SortOrder::Date) => SortOrder::Date,
+                Some(proto::SortOrder::Title) => SortOrder::Title,
+                Some(proto::SortOrder::Source) => SortOrder::Source,
+                _ => SortOrder::Relevance,
+            },
+            ..defaults
+        }
+    }
+}
+
+impl From<FoundItem> for proto::FoundItem {
+    fn from(item: FoundItem) -> Self {
+        Self { id: item.id, score: item.score, source: item.source, title: item.title, link: item.link, snippet: item.snippet }
+    }
+}
+
+impl From<proto::Document> for Document {
+    fn from(document: proto::Document) -> Self {
+        Self {
+            id: document.id,
+            source: document.source,
+            title: document.title,
+            link: document.link,
+            content: document.content,
+            metadata: document.metadata,
+            tags: document.tags,
+            modified: document.modified.and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single()),
+        }
+    }
+}
+
+impl From<IndexStats> for proto::IndexStats {
+    fn from(stats: IndexStats) -> Self {
+        Self { documents: stats.documents, sources: stats.sources.into_iter().collect() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use tokio_stream::StreamExt;
+    use tonic::{Code, Request};
+    use tonic::service::Interceptor;
+
+    use crate::search::tantivy_impl::TantivySearchEngine;
+    use crate::server::grpc::{Authentication, GrpcService, proto};
+    use crate::server::grpc::proto::doks_server::Doks;
+
+    #[tokio::test]
+    async fn test_grpc_service() -> anyhow::Result<()> {
+        let service = GrpcService { engine: Arc::new(TantivySearchEngine::in_memory()?) };
+
+        let document = proto::Document {
+            id: "runbook".to_string(),
+            source: "docs".to_string(),
+            title: "Runbook".to_string(),
+            link: "/docs/runbook.md".to_string(),
+            content: "Restart the database".to_string(),
+            metadata: HashMap::new(),
+            tags: vec![],
+            modified: None,
+        };
+        service.index(Request::new(proto::IndexRequest { documents: vec![document] })).await?;
+
+        let search = proto::SearchRequest { query: "database".to_string(), ..proto::SearchRequest::default() };
+        let results = service.search(Request::new(search)).await?.into_inner().collect::<Result<Vec<_>, _>>().await?;
+        assert_eq!(results.iter().map(|item| item.id.as_str()).collect::<Vec<_>>(), vec!["runbook"]);
+
+        let stats = service.stats(Request::new(proto::StatsRequest {})).await?.into_inner();
+        assert_eq!(stats.sources.get("docs"), Some(&1));
+
+        let mut authentication = Authentication { token: Some("secret".to_string()) };
+        let mut request = Request::new(());
+        assert_eq!(authentication.call(Request::new(())).unwrap_err().code(), Code::Unauthenticated);
+        request.metadata_mut().insert("authorization", "Bearer secret".parse()?);
+        assert!(authentication.call(request).is_ok());
+        assert!(Authentication { token: None }.call(Request::new(())).is_ok());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use axum::http::header::CONTENT_TYPE;
+use axum::response::IntoResponse;
+use prometheus::{Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder};
+
+/// The metrics of the process, served on `/metrics` by `doks serve` and `doks daemon`.
+pub struct Metrics {
+    registry: Registry,
+    documents_indexed: IntCounterVec,
+    index_runs: IntCounterVec,
+    index_duration: HistogramVec,
+    last_success: IntGaugeVec,
+    search_duration: Histogram,
+    search_errors: IntCounter,
+}
+
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+    METRICS.get_or_init(|| Metrics::new().expect("Invalid metrics definitions"))
+}
+
+impl Metrics {
+    fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new_custom(Some("doks".to_string()), None)?;
+
+        let documents_indexed = IntCounterVec::new(
+            Opts::new("documents_indexed_total", "Documents indexed, by source"),
+            &["source"],
+        )?;
+        let index_runs = IntCounterVec::new(
+            Opts::new("index_runs_total", "Index runs of a source, by source and result (success or failure)"),
+            &["source", "result"],
+        )?;
+        let index_duration = HistogramVec::new(
+            HistogramOpts::new("index_duration_seconds", "Duration of the index runs, by source")
+                .buckets(prometheus::exponential_buckets(1.0, 4.0, 8)?),
+            &["source"],
+        )?;
+        let last_success = IntGaugeVec::new(
+            Opts::new("last_successful_index_timestamp_seconds", "Time of the last successful index run, by source"),
+            &["source"],
+        )?;
+        let search_duration = Histogram::with_opts(HistogramOpts::new("search_duration_seconds", "Latency of the searches"))?;
+        let search_errors = IntCounter::new("search_errors_total", "Failed searches")?;
+
+        registry.register(Box::new(documents_indexed.clone()))?;
+        registry.register(Box::new(index_runs.clone()))?;
+        registry.register(Box::new(index_duration.clone()))?;
+        registry.register(Box::new(last_success.clone()))?;
+        registry.register(Box::new(search_duration.clone()))?;
+        registry.register(Box::new(search_errors.clone()))?;
+
+        Ok(Self { registry, documents_indexed, index_runs, index_duration, last_success, search_duration, search_errors })
+    }
+
+    pub fn documents_indexed(&self, source: &str, documents: u64) {
+        self.documents_indexed.with_label_values(&[source]).inc_by(documents);
+    }
+
+    /// Records a run of `doks daemon`, its documents being recorded as they are indexed.
+    pub fn index_run(&self, source: &str, duration: Duration, success: bool) {
+        let result = if success { "success" } else { "failure" };
+
+        self.index_runs.with_label_values(&[source, result]).inc();
+        self.index_duration.with_label_values(&[source]).observe(duration.as_secs_f64());
+
+        if success {
+            self.last_success.with_label_values(&[source]).set(chrono::Utc::now().timestamp());
+        }
+    }
+
+    pub fn search(&self, duration: Duration, success: bool) {
+        self.search_duration.observe(duration.as_secs_f64());
+
+        if !success {
+            self.search_errors.inc();
+        }
+    }
+
+    /// The metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buffer = vec![];
+
+        // Encoding to a vector only fails on invalid metrics, which are caught when registering them
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer).unwrap_or_default();
+
+        String::from_utf8_lossy(&buffer).to_string()
+    }
+}
+
+/// The `/metrics` endpoint.
+pub async fn serve_metrics() -> impl IntoResponse {
+    ([(CONTENT_TYPE, TextEncoder::new().format_type().to_string())], metrics().render())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::server::metrics::Metrics;
+
+    #[test]
+    fn test_metrics() -> anyhow::Result<()> {
+        let metrics = Metrics::new()?;
+
+        metrics.documents_indexed("docs", 12);
+        metrics.index_run("docs", Duration::from_secs(3), true);
+        metrics.index_run("wiki", Duration::from_secs(5), false);
+        metrics.search(Duration::from_millis(20), false);
+
+        let rendered = metrics.render();
+
+        assert!(rendered.contains(r#"doks_documents_indexed_total{source="docs"} 12"#));
+        assert!(rendered.contains(r#"doks_index_runs_total{result="failure",source="wiki"} 1"#));
+        assert!(rendered.contains(r#"doks_last_successful_index_timestamp_seconds{source="docs"}"#));
+        assert!(!rendered.contains(r#"doks_last_successful_index_timestamp_seconds{source="wiki"}"#));
+        assert!(rendered.contains("doks_search_duration_seconds_count 1"));
+        assert!(rendered.contains("doks_search_errors_total 1"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::body::{Bytes, StreamBody};
+use axum::extract::{Json, Query, State};
+use axum::http::header::{AUTHORIZATION, CONTENT_TYPE};
+use axum::http::{Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use axum::routing::{get, post};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio_stream::StreamExt;
+
+use crate::model::Document;
+use crate::search::{IndexStats, SearchEngine, SearchRequest, SortOrder};
+use crate::server::metrics::{metrics, serve_metrics};
+
+pub mod grpc;
+pub mod metrics;
+
+#[derive(Clone)]
+struct ServerState {
+    engine: Arc<dyn SearchEngine>,
+    token: Option<Arc<String>>,
+}
+
+/// Serves the engine over HTTP until interrupted. This is the API the `remote` engine talks to.
+pub async fn serve(engine: Arc<dyn SearchEngine>, address: &SocketAddr, token: Option<String>) -> anyhow::Result<()> {
+    let server = axum::Server::try_bind(address)?.serve(router(engine, token).into_make_service());
+
+    log::info!("Listening on: {}", server.local_addr());
+
+    server
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await?;
+
+    Ok(())
+}
+
+fn router(engine: Arc<dyn SearchEngine>, token: Option<String>) -> Router {
+    let state = ServerState { engine, token: token.map(Arc::new) };
+
+    Router::new()
+        .route("/index", post(index))
+        .route("/search", get(search_params).post(search))
+        .route("/stats", get(stats))
+        .route("/purge", post(purge))
+        .route("/documents", get(documents))
+        .route("/delete", post(delete))
+        .route("/metrics", get(serve_metrics))
+        // Health checks don't need the token
+        .route_layer(middleware::from_fn_with_state(state.clone(), authenticate))
+        .route("/healthz", get(|| async { "ok" }))
+        .with_state(state)
+}
+
+/// Comparing digests rather than the tokens doesn't leak the token through timing.
+pub(crate) fn token_matches(provided: &str, token: &str) -> bool {
+    Sha256::digest(provided.as_bytes()) == Sha256::digest(token.as_bytes())
+}
+
+async fn authenticate<B>(State(state): State<ServerState>, request: Request<B>, next: Next<B>) -> Response {
+    if let Some(token) = &state.token {
+        let authorized = request.headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|provided| token_matches(provided, token));
+
+        if !authorized {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+async fn index(State(state): State<ServerState>, Json(documents): Json<Vec<Document>>) -> Result<StatusCode, ServerError> {
+    let sources = documents.iter().map(|document| document.source.clone()).collect::<Vec<_>>();
+    state.engine.index(documents).await?;
+
+    for source in sources {
+        metrics().documents_indexed(&source, 1);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Query string flavour of the search for quick use from a browser or curl.
+#[derive(Deserialize)]
+struct SearchParams {
+    q: String,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    source: Option<String>,
+    tag: Option<String>,
+    fuzzy: Option<u8>,
+    sort: Option<SortOrder>,
+}
+
+async fn search_params(state: State<ServerState>, Query(params): Query<SearchParams>) -> Result<Response, ServerError> {
+    let mut request = SearchRequest::new(&params.q);
+    request.limit = params.limit.unwrap_or(request.limit);
+    request.offset = params.offset.unwrap_or(request.offset);
+    request.source_filter = params.source.into_iter().collect();
+    request.tag_filter = params.tag.into_iter().collect();
+    request.fuzzy = params.fuzzy;
+    request.sort = params.sort.unwrap_or_default();
+
+    search(state, Json(request)).await
+}
+
+/// Streams the results as JSON lines as soon as the engine produces them.
+async fn search(State(state): State<ServerState>, Json(request): Json<SearchRequest>) -> Result<Response, ServerError> {
+    let started = Instant::now();
+    let results = state.engine.search(&request).await;
+    metrics().search(started.elapsed(), results.is_ok());
+
+    let results = results?;
+
+    let lines = results.map(|item| -> anyhow::Result<Bytes> {
+        let mut line = serde_json::to_vec(&item?)?;
+        line.push(b'\n');
+
+        Ok(Bytes::from(line))
+    });
+
+    Ok(([(CONTENT_TYPE, "application/x-ndjson")], StreamBody::new(lines)).into_response())
+}
+
+async fn stats(State(state): State<ServerState>) -> Result<Json<IndexStats>, ServerError> {
+    Ok(Json(state.engine.stats().await?))
+}
+
+#[derive(Deserialize)]
+struct PurgeParams {
+    source: Option<String>,
+}
+
+async fn purge(State(state): State<ServerState>, Query(params): Query<PurgeParams>) -> Result<StatusCode, ServerError> {
+    match params.source {
+        Some(source) => state.engine.purge_source(&source).await?,
+        None => state.engine.purge().await?,
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Streams the indexed documents as JSON lines.
+async fn documents(State(state): State<ServerState>) -> Result<Response, ServerError> {
+    let lines = state.engine.documents().await?.map(|document| -> anyhow::Result<Bytes> {
+        let mut line = serde_json::to_vec(&document?)?;
+        line.push(b'\n');
+
+        Ok(Bytes::from(line))
+    });
+
+    Ok(([(CONTENT_TYPE, "application/x-ndjson")], StreamBody::new(lines)).into_response())
+}
+
+async fn delete(State(state): State<ServerState>, Json(ids): Json<Vec<String>>) -> Result<StatusCode, ServerError> {
+    state.engine.delete(&ids).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Reports engine failures to the client as internal errors.
+struct ServerError(anyhow::Error);
+
+impl<E: Into<anyhow::Error>> From<E> for ServerError {
+    fn from(err: E) -> Self {
+        Self(err.into())
+    }
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        log::error!("Request failed: {:#}", self.0);
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", self.0)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use tokio_stream::StreamExt;
+
+    use crate::model::Document;
+    use crate::search::remote_impl::RemoteSearchEngine;
+    use crate::search::tantivy_impl::TantivySearchEngine;
+    use crate::search::{SearchEngine, SearchRequest};
+    use crate::server::router;
+
+    #[tokio::test]
+    async fn test_serve_remote_engine() -> anyhow::Result<()> {
+        let engine = Arc::new(TantivySearchEngine::in_memory()?);
+        let server = axum::Server::try_bind(&SocketAddr::from(([127, 0, 0, 1], 0)))?
+            .serve(router(engine, Some("secret".to_string())).into_make_service());
+        let endpoint = format!("http://{}", server.local_addr());
+
+        tokio::spawn(server);
+
+        let remote = RemoteSearchEngine::new(&endpoint, Some("secret".to_string()));
+
+        remote.index(vec![Document {
+            id: "1".to_string(),
+            source: "runbooks".to_string(),
+            title: "Database".to_string(),
+            link: "link1".to_string(),
+            content: "Restart the database".to_string(),
+            metadata: HashMap::new(),
+            tags: vec![],
+            modified: None,
+        }]).await?;
+
+        let results = remote.search(&SearchRequest::new("database")).await?.collect::<Result<Vec<_>, _>>().await?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, "1");
+
+        assert_eq!(remote.stats().await?.sources.get("runbooks"), Some(&1));
+
+        let documents = remote.documents().await?.collect::<Result<Vec<_>, _>>().await?;
+        assert_eq!(documents.iter().map(|document| document.id.as_str()).collect::<Vec<_>>(), vec!["1"]);
+        remote.delete(&["1".to_string()]).await?;
+        assert_eq!(remote.stats().await?.documents, 0);
+        remote.index(documents).await?;
+
+        remote.purge_source("runbooks").await?;
+        assert_eq!(remote.stats().await?.documents, 0);
+
+        let unauthorized = RemoteSearchEngine::new(&endpoint, None);
+        assert!(unauthorized.stats().await.is_err());
+        assert_eq!(reqwest::get(format!("{}/healthz", endpoint)).await?.text().await?, "ok");
+
+        Ok(())
+    }
+}
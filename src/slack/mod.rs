@@ -0,0 +1,217 @@
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use futures::{SinkExt, StreamExt, TryStreamExt};
+use regex::Regex;
+use serde_json::{json, Value};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::cli::output::plain_snippet;
+use crate::search::{FoundItem, SearchEngine, SearchRequest};
+
+const SLACK_API: &str = "https://slack.com/api";
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Answers the `/doks <query>` slash commands and the mentions of the bot with the top results of
+/// the engine. Connects to Slack with Socket Mode so no public endpoint is needed: `app_token` is
+/// an app level token (`xapp-...`) with the `connections:write` scope, and `bot_token` a bot token
+/// (`xoxb-...`) with the `chat:write` scope to answer the mentions. Runs until interrupted.
+pub async fn run(engine: &dyn SearchEngine, app_token: &str, bot_token: &str, limit: usize) -> anyhow::Result<()> {
+    let bot = SlackBot { engine, client: reqwest::Client::new(), bot_token, limit };
+
+    loop {
+        // Slack regularly asks the clients to reconnect, and connections may drop
+        if let Err(err) = bot.connect(app_token).await {
+            log::warn!("Slack connection lost, reconnecting in {:?}: {:#}", RECONNECT_DELAY, err);
+        }
+
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+struct SlackBot<'a> {
+    engine: &'a dyn SearchEngine,
+    client: reqwest::Client,
+    bot_token: &'a str,
+    limit: usize,
+}
+
+impl SlackBot<'_> {
+    async fn connect(&self, app_token: &str) -> anyhow::Result<()> {
+        let response = self.call("apps.connections.open", app_token, &json!({})).await?;
+        let url = response["url"].as_str().context("No Socket Mode url returned by Slack")?;
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await?;
+        log::info!("Connected to Slack");
+
+        while let Some(message) = socket.next().await {
+            let envelope: Value = match message? {
+                Message::Text(text) => serde_json::from_str(&text)?,
+                Message::Close(_) => break,
+                _ => continue,
+            };
+
+            if envelope["type"] == "disconnect" {
+                break;
+            }
+
+            let envelope_id = match envelope["envelope_id"].as_str() {
+                Some(envelope_id) => envelope_id,
+                None => continue,
+            };
+
+            // Every envelope must be acknowledged within 3 seconds: slash commands with their
+            // answer, events right away as their answer is posted separately
+            if envelope["type"] == "slash_commands" {
+                let payload = self.answer_command(&envelope["payload"]).await.unwrap_or_else(|err| {
+                    log::error!("Couldn't answer the Slack command: {:#}", err);
+                    json!({ "text": format!("Search failed: {:#}", err) })
+                });
+
+                socket.send(Message::Text(json!({ "envelope_id": envelope_id, "payload": payload }).to_string())).await?;
+            } else {
+                socket.send(Message::Text(json!({ "envelope_id": envelope_id }).to_string())).await?;
+
+                if let Err(err) = self.answer_event(&envelope["payload"]).await {
+                    log::error!("Couldn't answer the Slack event: {:#}", err);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The answer to a slash command, sent back in its acknowledgement.
+    async fn answer_command(&self, payload: &Value) -> anyhow::Result<Value> {
+        let query = payload["text"].as_str().unwrap_or_default().trim();
+        let blocks = result_blocks(query, &self.search(query).await?);
+
+        Ok(json!({ "response_type": "in_channel", "text": fallback_text(query), "blocks": blocks }))
+    }
+
+    /// Mentions are answered in their thread, the other events are ignored.
+    async fn answer_event(&self, payload: &Value) -> anyhow::Result<()> {
+        let event = &payload["event"];
+
+        if event["type"] != "app_mention" {
+            return Ok(());
+        }
+
+        let query = mention_query(event["text"].as_str().unwrap_or_default());
+        let message = json!({
+            "channel": event["channel"],
+            "thread_ts": event.get("thread_ts").unwrap_or(&event["ts"]),
+            "text": fallback_text(&query),
+            "blocks": result_blocks(&query, &self.search(&query).await?),
+        });
+
+        self.call("chat.postMessage", self.bot_token, &message).await?;
+
+        Ok(())
+    }
+
+    async fn search(&self, query: &str) -> anyhow::Result<Vec<FoundItem>> {
+        if query.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let request = SearchRequest { limit: self.limit, ..SearchRequest::new(query) };
+
+        self.engine.search(&request).await?.try_collect().await
+    }
+
+    async fn call(&self, method: &str, token: &str, body: &Value) -> anyhow::Result<Value> {
+        let response: Value = self.client
+            .post(format!("{}/{}", SLACK_API, method))
+            .bearer_auth(token)
+            .json(body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if response["ok"] != true {
+            bail!("Slack API call '{}' failed: {}", method, response["error"]);
+        }
+
+        Ok(response)
+    }
+}
+
+/// The query of a message mentioning the bot, without the mentions.
+fn mention_query(text: &str) -> String {
+    let mentions = Regex::new(r"<@[A-Z0-9]+(\|[^>]*)?>").unwrap();
+
+    mentions.replace_all(text, "").trim().to_string()
+}
+
+/// Shown in the notifications, where blocks aren't rendered.
+fn fallback_text(query: &str) -> String {
+    format!("doks results for: {}", query)
+}
+
+/// The results as Block Kit blocks: a section per result, with the title linking to the document
+/// when it's a web page, and the source in a context block.
+fn result_blocks(query: &str, items: &[FoundItem]) -> Value {
+    if query.is_empty() {
+        return json!([section("Usage: `/doks <query>` or mention me with a query")]);
+    }
+
+    if items.is_empty() {
+        return json!([section(&format!("No results found for *{}*", escape(query)))]);
+    }
+
+    let mut blocks = vec![section(&format!("Top results for *{}*", escape(query))), json!({ "type": "divider" })];
+
+    for item in items {
+        let title = match item.link.starts_with("http://") || item.link.starts_with("https://") {
+            true => format!("*<{}|{}>*", item.link, escape(&item.title)),
+            false => format!("*{}*\n`{}`", escape(&item.title), escape(&item.link)),
+        };
+
+        blocks.push(section(&format!("{}\n{}", title, escape(&plain_snippet(item)))));
+        blocks.push(json!({ "type": "context", "elements": [{ "type": "mrkdwn", "text": escape(&item.source) }] }));
+    }
+
+    Value::Array(blocks)
+}
+
+fn section(text: &str) -> Value {
+    json!({ "type": "section", "text": { "type": "mrkdwn", "text": text } })
+}
+
+/// The characters Slack requires to be escaped in message texts.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::search::FoundItem;
+    use crate::slack::{mention_query, result_blocks};
+
+    #[test]
+    fn test_result_blocks() {
+        let item = |link: &str| FoundItem {
+            id: link.to_string(),
+            score: 1.0,
+            source: "docs".to_string(),
+            title: "Runbook <draft>".to_string(),
+            link: link.to_string(),
+            snippet: "Restart the <b>database</b>".to_string(),
+        };
+
+        let blocks = result_blocks("database", &[item("https://wiki/runbook"), item("/docs/runbook.md")]);
+
+        assert_eq!(blocks.as_array().unwrap().len(), 6);
+        assert_eq!(blocks[2]["text"]["text"], "*<https://wiki/runbook|Runbook &lt;draft&gt;>*\nRestart the database");
+        assert_eq!(blocks[4]["text"]["text"], "*Runbook &lt;draft&gt;*\n`/docs/runbook.md`\nRestart the database");
+        assert_eq!(blocks[5]["elements"][0]["text"], "docs");
+
+        assert_eq!(result_blocks("database", &[])[0]["text"]["text"], "No results found for *database*");
+
+        assert_eq!(mention_query("<@U024BE7LH> restart the database"), "restart the database");
+        assert_eq!(mention_query("<@U024BE7LH|doks>  "), "");
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::model::Document;
+use crate::sources::{DocStream, DocumentSource};
+use crate::utils::streams::channel_stream;
+
+pub struct AirtableSource {
+    pub source_id: String,
+    pub endpoint: String,
+    pub token: String,
+    pub tables: Vec<AirtableTable>,
+}
+
+/// Describes a table to read and how its fields map to documents.
+#[derive(Debug, Clone)]
+pub struct AirtableTable {
+    pub base: String,
+    pub table: String,
+    pub view: Option<String>,
+    pub title_field: String,
+    pub content_fields: Vec<String>,
+    pub metadata_fields: Vec<String>,
+}
+
+impl DocumentSource for AirtableSource {
+    fn fetch(&self) -> DocStream {
+        let client = reqwest::Client::new();
+        let source_id = self.source_id.clone();
+        let endpoint = self.endpoint.trim_end_matches('/').to_string();
+        let token = self.token.clone();
+        let tables = self.tables.clone();
+
+        Box::pin(
+            channel_stream(|tx| async move {
+                for table in tables {
+                    log::info!("Fetching records of airtable table: {}/{}", &table.base, &table.table);
+
+                    let mut offset: Option<String> = None;
+
+                    loop {
+                        let mut params = vec![("pageSize", "100".to_string())];
+                        if let Some(view) = &table.view {
+                            params.push(("view", view.clone()));
+                        }
+                        if let Some(offset) = offset.take() {
+                            params.push(("offset", offset));
+                        }
+
+                        let page: RecordsPage = client
+                            .get(format!("{}/{}/{}", endpoint, table.base, table.table))
+                            .bearer_auth(&token)
+                            .query(&params)
+                            .send()
+                            .await?
+                            .error_for_status()?
+                            .json()
+                            .await
+                            .with_context(|| format!("Couldn't list records of airtable table: {}/{}", table.base, table.table))?;
+
+                        for record in page.records {
+                            tx.send(Ok(record_to_document(&source_id, &table, record))).await?;
+                        }
+
+                        match page.offset {
+                            Some(next) => offset = Some(next),
+                            None => break,
+                        }
+                    }
+                }
+
+                Ok(())
+            })
+        )
+    }
+}
+
+fn record_to_document(source_id: &str, table: &AirtableTable, record: Record) -> Document {
+    let content = table.content_fields
+        .iter()
+        .filter_map(|field| record.fields.get(field).map(field_to_string))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let metadata = table.metadata_fields
+        .iter()
+        .filter_map(|field| record.fields.get(field).map(|v| (field.clone(), field_to_string(v))))
+        .collect::<HashMap<_, _>>();
+
+    let link = format!("https://airtable.com/{}/{}/{}", table.base, table.table, record.id);
+
+    Document {
+        id: link.clone(),
+        source: source_id.to_string(),
+        title: record.fields
+            .get(&table.title_field)
+            .map(field_to_string)
+            .unwrap_or(record.id),
+        link,
+        content,
+        metadata,
+        tags: vec![],
+        modified: None,
+    }
+}
+
+fn field_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(values) => values.iter().map(field_to_string).collect::<Vec<_>>().join(", "),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct RecordsPage {
+    records: Vec<Record>,
+    offset: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Record {
+    id: String,
+    fields: Map<String, Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::sources::airtable::{AirtableTable, record_to_document, RecordsPage};
+
+    #[test]
+    fn test_record_to_document() -> anyhow::Result<()> {
+        let table = AirtableTable {
+            base: "app1".to_string(),
+            table: "Runbooks".to_string(),
+            view: None,
+            title_field: "Name".to_string(),
+            content_fields: vec!["Steps".to_string(), "Notes".to_string()],
+            metadata_fields: vec!["Owners".to_string()],
+        };
+
+        let page = serde_json::from_value::<RecordsPage>(json!({
+            "records": [{
+                "id": "rec1",
+                "fields": {
+                    "Name": "Restart the queue",
+                    "Steps": "1. Drain\n2. Restart",
+                    "Owners": ["infra", "sre"]
+                }
+            }]
+        }))?;
+
+        let document = record_to_document("airtable", &table, page.records.into_iter().next().unwrap());
+
+        assert_eq!(document.title, "Restart the queue");
+        assert_eq!(document.link, "https://airtable.com/app1/Runbooks/rec1");
+        assert_eq!(document.content, "1. Drain\n2. Restart");
+        assert_eq!(document.metadata.get("Owners").map(|s| s.as_str()), Some("infra, sre"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::model::Document;
+use crate::sources::{DocStream, DocumentSource};
+use crate::utils::json::parse_json;
+use crate::utils::streams::channel_stream;
+
+pub struct AsanaSource {
+    pub source_id: String,
+    pub endpoint: String,
+    pub token: String,
+    pub projects: Vec<String>,
+}
+
+impl DocumentSource for AsanaSource {
+    fn fetch(&self) -> DocStream {
+        let source_id = self.source_id.clone();
+        let client = AsanaClient {
+            client: reqwest::Client::new(),
+            endpoint: self.endpoint.clone(),
+            token: self.token.clone(),
+        };
+        let projects = self.projects.clone();
+
+        let stream = channel_stream(|tx| async move {
+            for project in projects {
+                log::info!("Fetching tasks of asana project: {}", &project);
+
+                let mut offset: Option<String> = None;
+
+                loop {
+                    let mut params = vec![
+                        ("opt_fields", "name,notes,permalink_url,completed,assignee.name,modified_at".to_string()),
+                        ("limit", "100".to_string()),
+                    ];
+                    if let Some(offset) = offset.take() {
+                        params.push(("offset", offset));
+                    }
+
+                    let page = client
+                        .get(&format!("projects/{}/tasks", project), &params)
+                        .await
+                        .with_context(|| format!("Couldn't list tasks of asana project: {}", project))?;
+
+                    let tasks: Vec<AsanaTask> = parse_json(&page, &["data"])?;
+
+                    for task in tasks {
+                        let comments = client.comments(&task.gid).await?;
+
+                        tx.send(Ok(task_to_document(&source_id, &project, task, comments))).await?;
+                    }
+
+                    let next_page = parse_json::<Option<NextPage>>(&page, &["next_page"])
+                        .with_context(|| format!("Couldn't read the next page of the tasks of asana project: {}", project))?;
+
+                    match next_page {
+                        Some(next_page) => offset = Some(next_page.offset),
+                        None => break,
+                    }
+                }
+            }
+
+            Ok(())
+        });
+
+        Box::pin(stream)
+    }
+}
+
+struct AsanaClient {
+    client: reqwest::Client,
+    endpoint: String,
+    token: String,
+}
+
+impl AsanaClient {
+    async fn get(&self, path: &str, params: &[(&str, String)]) -> anyhow::Result<Value> {
+        let response = self.client
+            .get(format!("{}/{}", self.endpoint.trim_end_matches('/'), path))
+            .bearer_auth(&self.token)
+            .query(params)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Value>()
+            .await?;
+
+        Ok(response)
+    }
+
+    async fn comments(&self, task_gid: &str) -> anyhow::Result<Vec<String>> {
+        let stories = self
+            .get(
+                &format!("tasks/{}/stories", task_gid),
+                &[("opt_fields", "type,text".to_string())],
+            )
+            .await
+            .with_context(|| format!("Couldn't fetch comments of asana task: {}", task_gid))?;
+
+        let stories: Vec<AsanaStory> = parse_json(&stories, &["data"])?;
+
+        Ok(
+            stories
+                .into_iter()
+                .filter(|story| story.story_type == "comment")
+                .filter_map(|story| story.text)
+                .collect()
+        )
+    }
+}
+
+fn task_to_document(source_id: &str, project: &str, task: AsanaTask, comments: Vec<String>) -> Document {
+    let mut content = task.notes.unwrap_or_default();
+
+    for comment in comments {
+        content.push_str("\n\n");
+        content.push_str(&comment);
+    }
+
+    let mut metadata = HashMap::new();
+    metadata.insert("project".to_string(), project.to_string());
+    metadata.insert("completed".to_string(), task.completed.to_string());
+
+    if let Some(assignee) = task.assignee {
+        metadata.insert("assignee".to_string(), assignee.name);
+    }
+
+    Document {
+        id: task.permalink_url.clone(),
+        source: source_id.to_string(),
+        title: task.name,
+        link: task.permalink_url,
+        content,
+        metadata,
+        tags: vec![],
+        modified: task.modified_at,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct AsanaTask {
+    gid: String,
+    name: String,
+    notes: Option<String>,
+    permalink_url: String,
+    #[serde(default)]
+    completed: bool,
+    assignee: Option<AsanaUser>,
+    modified_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct AsanaUser {
+    name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct AsanaStory {
+    #[serde(rename = "type")]
+    story_type: String,
+    text: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct NextPage {
+    offset: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+
+    use crate::sources::asana::{AsanaTask, task_to_document};
+    use crate::utils::json::parse_json;
+
+    #[test]
+    fn test_task_to_document() -> anyhow::Result<()> {
+        let page = json!({
+            "data": [{
+                "gid": "42",
+                "name": "Write the runbook",
+                "notes": "Describe the failover procedure",
+                "permalink_url": "https://app.asana.com/0/1/42",
+                "completed": false,
+                "assignee": { "name": "Jane" },
+                "modified_at": "2022-03-01T10:00:00.000Z"
+            }],
+            "next_page": null
+        });
+
+        let mut tasks: Vec<AsanaTask> = parse_json(&page, &["data"])?;
+        let document = task_to_document(
+            "asana",
+            "1",
+            tasks.remove(0),
+            vec!["Done for region A".to_string()],
+        );
+
+        assert_eq!(document.id, "https://app.asana.com/0/1/42");
+        assert_eq!(document.title, "Write the runbook");
+        assert_eq!(document.content, "Describe the failover procedure\n\nDone for region A");
+        assert_eq!(document.metadata.get("assignee").map(|s| s.as_str()), Some("Jane"));
+        assert_eq!(document.modified, Some(Utc.ymd(2022, 3, 1).and_hms(10, 0, 0)));
+
+        Ok(())
+    }
+}
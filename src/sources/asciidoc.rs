@@ -0,0 +1,201 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+/// The text of an AsciiDoc document, without its markup.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct AsciidocText {
+    /// The document title: its level 0 section (`= Title`).
+    pub title: Option<String>,
+    pub text: String,
+    pub headings: Vec<String>,
+}
+
+/// The delimited blocks whose content is kept verbatim (or dropped), as opposed to the compound
+/// blocks (examples, sidebars, quotes...) holding regular AsciiDoc.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Block {
+    Listing,
+    Comment,
+    Passthrough,
+    Table,
+}
+
+static HEADING: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(={1,6})\s+(.+?)(\s+=+)?$").unwrap());
+static ATTRIBUTE_ENTRY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^:!?[\w-]+!?:").unwrap());
+static BLOCK_MACRO: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[a-z]+::\S*\[.*\]$").unwrap());
+static LIST_MARKER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(\*+|-|\.+|\d+\.|<\d+>)\s+").unwrap());
+static ADMONITION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(NOTE|TIP|IMPORTANT|WARNING|CAUTION):\s+").unwrap());
+
+/// The inline markup, replaced in order: images, then links and cross references (keeping their
+/// text), anchors, and the unconstrained (`**bold**`) then constrained (`*bold*`) formatting.
+static INLINE: LazyLock<Vec<(Regex, &str)>> = LazyLock::new(|| {
+    let mut replacements = vec![
+        (r"image:[^\s\[]+\[[^\]]*\]".to_string(), ""),
+        (r"(?:(?:link|mailto|xref):[^\s\[]+|\w+://[^\s\[]+)\[([^\]]*)\]".to_string(), "$1"),
+        (r"\b(?:kbd|btn|menu|pass|footnote):\[([^\]]*)\]".to_string(), "$1"),
+        (r"<<[^,>]+,\s*([^>]+)>>".to_string(), "$1"),
+        (r"<<([^>]+)>>".to_string(), "$1"),
+        (r"\[\[[^\]]*\]\]".to_string(), ""),
+    ];
+
+    for mark in ["*", "_", "`", "#"].map(regex::escape) {
+        replacements.push((format!(r"{0}{0}(.+?){0}{0}", mark), "$1"));
+        replacements.push((format!(r"(^|\W){0}(\S|\S.*?\S){0}(\W|$)", mark), "$1$2$3"));
+    }
+
+    replacements.into_iter().map(|(pattern, replacement)| (Regex::new(&pattern).unwrap(), replacement)).collect()
+});
+
+pub fn is_asciidoc(path: &str) -> bool {
+    let path = path.to_lowercase();
+
+    [".adoc", ".asciidoc", ".asc"].iter().any(|extension| path.ends_with(extension))
+}
+
+/// Extracts the text of the AsciiDoc document: the attributes, comments, block macros (images,
+/// includes...) and markup are dropped, and so is the content of the listing blocks when
+/// `strip_code_blocks`.
+pub fn extract_text(asciidoc: &str, strip_code_blocks: bool) -> AsciidocText {
+    let mut extracted = AsciidocText::default();
+    let mut lines = vec![];
+    // The delimited block the line is in, with its delimiter
+    let mut block: Option<(Block, &str)> = None;
+    // The author and revision lines following the title
+    let mut header = false;
+
+    for line in asciidoc.lines() {
+        let trimmed = line.trim_end();
+
+        if let Some((kind, delimiter)) = block {
+            match kind {
+                _ if trimmed == delimiter => block = None,
+                Block::Listing if !strip_code_blocks => lines.push(trimmed.to_string()),
+                Block::Table => {
+                    let cells = trimmed.split('|').map(str::trim).filter(|cell| !cell.is_empty()).collect::<Vec<_>>();
+                    lines.push(inline(&cells.join(" ")));
+                }
+                _ => {}
+            }
+
+            continue;
+        }
+
+        if header {
+            header = !trimmed.is_empty();
+            continue;
+        }
+
+        if let Some(kind) = delimited_block(trimmed) {
+            block = Some((kind, trimmed));
+            continue;
+        }
+
+        if trimmed.starts_with("//") || trimmed == "+" || ATTRIBUTE_ENTRY.is_match(trimmed) || BLOCK_MACRO.is_match(trimmed) {
+            continue;
+        }
+
+        // Block attributes (`[source,java]`) and anchors, compound block delimiters
+        if (trimmed.starts_with('[') && trimmed.ends_with(']')) || is_compound_delimiter(trimmed) {
+            continue;
+        }
+
+        if let Some(heading) = HEADING.captures(trimmed) {
+            let text = inline(&heading[2]);
+
+            if heading[1].len() == 1 && extracted.title.is_none() && lines.iter().all(|line: &String| line.is_empty()) {
+                extracted.title = Some(text);
+                header = true;
+            } else {
+                extracted.headings.push(text.clone());
+                lines.push(text);
+            }
+
+            continue;
+        }
+
+        // Block titles (`.Title`)
+        let line = match trimmed.strip_prefix('.') {
+            Some(title) if title.starts_with(|c: char| !c.is_whitespace() && c != '.') => title,
+            _ => trimmed,
+        };
+
+        let line = LIST_MARKER.replace(line, "");
+        let line = ADMONITION.replace(&line, "");
+        lines.push(inline(line.trim()));
+    }
+
+    extracted.text = lines.join("\n").trim().to_string();
+    extracted
+}
+
+fn delimited_block(line: &str) -> Option<Block> {
+    let repeated = |c: char| line.len() >= 4 && line.chars().all(|other| other == c);
+
+    match line {
+        _ if repeated('-') || repeated('.') || line.starts_with("```") => Some(Block::Listing),
+        _ if repeated('/') => Some(Block::Comment),
+        _ if repeated('+') => Some(Block::Passthrough),
+        _ if line.starts_with("|===") => Some(Block::Table),
+        _ => None,
+    }
+}
+
+/// Examples, sidebars, quotes and open blocks: their content is regular AsciiDoc.
+fn is_compound_delimiter(line: &str) -> bool {
+    line == "--" || ['=', '*', '_'].iter().any(|c| line.len() >= 4 && line.chars().all(|other| other == *c))
+}
+
+fn inline(text: &str) -> String {
+    INLINE
+        .iter()
+        .fold(text.to_string(), |text, (pattern, replacement)| pattern.replace_all(&text, *replacement).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sources::asciidoc::extract_text;
+
+    #[test]
+    fn test_extract_text() {
+        let asciidoc = r#"= Database *runbook*
+Jane Doe <jane@example.com>
+v1.0, 2021-03-04
+:toc:
+:source-highlighter: rouge
+
+// Keep in sync with the alerts
+image::lag.png[Lag]
+
+== Restart
+
+NOTE: Check the https://grafana.example.com/lag[replication lag] first, see <<failover,the failover>>.
+
+.Restart command
+[source,shell]
+----
+systemctl restart postgresql
+----
+
+* The _primary_ first
+* Then the `replicas`
+
+|===
+| Host | Role
+| db-1 | primary
+|===
+"#;
+
+        let extracted = extract_text(asciidoc, false);
+        assert_eq!(extracted.title.as_deref(), Some("Database runbook"));
+        assert_eq!(extracted.headings, vec!["Restart"]);
+        assert_eq!(
+            extracted.text,
+            "Restart\n\nCheck the replication lag first, see the failover.\n\nRestart command\nsystemctl restart postgresql\n\n\
+             The primary first\nThen the replicas\n\nHost Role\ndb-1 primary"
+        );
+
+        let extracted = extract_text(asciidoc, true);
+        assert!(extracted.text.contains("Restart command\n\nThe primary first"));
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::model::Document;
+use crate::sources::{DocStream, DocumentSource};
+use crate::utils::streams::channel_stream;
+
+const PAGE_SIZE: usize = 100;
+
+/// Indexes entities from a Backstage software catalog.
+pub struct BackstageSource {
+    pub source_id: String,
+    pub endpoint: String,
+    pub frontend: String,
+    pub token: Option<String>,
+    pub kinds: Vec<String>,
+}
+
+impl DocumentSource for BackstageSource {
+    fn fetch(&self) -> DocStream {
+        let client = reqwest::Client::new();
+        let source_id = self.source_id.clone();
+        let endpoint = self.endpoint.trim_end_matches('/').to_string();
+        let frontend = self.frontend.trim_end_matches('/').to_string();
+        let token = self.token.clone();
+        let filter = self.kinds
+            .iter()
+            .map(|kind| format!("kind={}", kind))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        Box::pin(
+            channel_stream(|tx| async move {
+                let mut offset = 0;
+
+                loop {
+                    let mut request = client
+                        .get(format!("{}/api/catalog/entities", endpoint))
+                        .query(&[("limit", PAGE_SIZE), ("offset", offset)]);
+
+                    if !filter.is_empty() {
+                        request = request.query(&[("filter", &filter)]);
+                    }
+                    if let Some(token) = &token {
+                        request = request.bearer_auth(token);
+                    }
+
+                    let entities: Vec<Entity> = request
+                        .send()
+                        .await?
+                        .error_for_status()?
+                        .json()
+                        .await
+                        .context("Couldn't list backstage catalog entities")?;
+
+                    let count = entities.len();
+
+                    for entity in entities {
+                        tx.send(Ok(entity_to_document(&source_id, &frontend, entity))).await?;
+                    }
+
+                    if count < PAGE_SIZE {
+                        break;
+                    }
+
+                    offset += count;
+                }
+
+                Ok(())
+            })
+        )
+    }
+}
+
+fn entity_to_document(source_id: &str, frontend: &str, entity: Entity) -> Document {
+    let namespace = entity.metadata.namespace.unwrap_or_else(|| "default".to_string());
+    let kind = entity.kind.to_lowercase();
+    let link = format!("{}/catalog/{}/{}/{}", frontend, namespace, kind, entity.metadata.name);
+
+    let mut metadata = HashMap::new();
+    metadata.insert("kind".to_string(), kind.clone());
+    metadata.insert("namespace".to_string(), namespace.clone());
+
+    for key in &["type", "lifecycle", "owner", "system"] {
+        if let Some(Value::String(value)) = entity.spec.get(*key) {
+            metadata.insert(key.to_string(), value.clone());
+        }
+    }
+
+    if entity.metadata.annotations.contains_key("backstage.io/techdocs-ref") {
+        metadata.insert(
+            "techdocs".to_string(),
+            format!("{}/docs/{}/{}/{}", frontend, namespace, kind, entity.metadata.name),
+        );
+    }
+
+    let mut content = entity.metadata.description.unwrap_or_default();
+
+    // APIs carry their (OpenAPI, AsyncAPI, ...) definition inline
+    if let Some(Value::String(definition)) = entity.spec.get("definition") {
+        content.push_str("\n\n");
+        content.push_str(definition);
+    }
+
+    Document {
+        id: link.clone(),
+        source: source_id.to_string(),
+        title: entity.metadata.title.unwrap_or(entity.metadata.name),
+        link,
+        content,
+        metadata,
+        tags: entity.metadata.tags,
+        modified: None,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Entity {
+    kind: String,
+    metadata: EntityMetadata,
+    #[serde(default)]
+    spec: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct EntityMetadata {
+    name: String,
+    namespace: Option<String>,
+    title: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    annotations: HashMap<String, String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::sources::backstage::{Entity, entity_to_document};
+
+    #[test]
+    fn test_entity_to_document() -> anyhow::Result<()> {
+        let entity = serde_json::from_value::<Entity>(json!({
+            "apiVersion": "backstage.io/v1alpha1",
+            "kind": "Component",
+            "metadata": {
+                "name": "payments",
+                "description": "Handles card payments",
+                "tags": ["java"],
+                "annotations": { "backstage.io/techdocs-ref": "dir:." }
+            },
+            "spec": { "type": "service", "owner": "team-billing", "lifecycle": "production" }
+        }))?;
+
+        let document = entity_to_document("backstage", "https://backstage.example.com", entity);
+
+        assert_eq!(document.title, "payments");
+        assert_eq!(document.link, "https://backstage.example.com/catalog/default/component/payments");
+        assert_eq!(document.content, "Handles card payments");
+        assert_eq!(document.metadata.get("owner").map(|s| s.as_str()), Some("team-billing"));
+        assert_eq!(document.tags, vec!["java".to_string()]);
+        assert_eq!(
+            document.metadata.get("techdocs").map(|s| s.as_str()),
+            Some("https://backstage.example.com/docs/default/component/payments"),
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::{BTreeMap, HashMap};
+use std::pin::Pin;
+
+use anyhow::Context;
+use async_walkdir::WalkDir;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio_stream::{Stream, StreamExt};
+
+use crate::model::Document;
+use crate::sources::{DocStream, DocumentSource};
+use crate::utils::streams::channel_stream;
+
+pub struct DiscordSource {
+    pub source_id: String,
+    pub loader: Box<dyn DiscordChannelLoader>,
+}
+
+impl DocumentSource for DiscordSource {
+    fn fetch(&self) -> DocStream {
+        let mut channels = self.loader.load();
+        let source_id = self.source_id.clone();
+
+        Box::pin(
+            channel_stream(|tx| async move {
+                while let Some(channel) = channels.next().await {
+                    for document in channel_to_documents(&source_id, channel?) {
+                        tx.send(Ok(document)).await?;
+                    }
+                }
+
+                Ok(())
+            })
+        )
+    }
+}
+
+/// A channel (or thread) along with its messages in chronological order.
+#[derive(Debug, Clone)]
+pub struct DiscordChannel {
+    pub guild_id: String,
+    pub id: String,
+    pub name: String,
+    pub is_thread: bool,
+    pub messages: Vec<DiscordMessage>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiscordMessage {
+    pub id: String,
+    pub author: String,
+    pub timestamp: String,
+    pub content: String,
+}
+
+pub trait DiscordChannelLoader {
+    fn load(&self) -> Pin<Box<dyn Stream<Item=anyhow::Result<DiscordChannel>> + Send>>;
+}
+
+/// Threads are indexed as a single document while regular channels are split in one document per day.
+fn channel_to_documents(source_id: &str, channel: DiscordChannel) -> Vec<Document> {
+    let mut groups = BTreeMap::<String, Vec<&DiscordMessage>>::new();
+
+    for message in channel.messages.iter().filter(|m| !m.content.is_empty()) {
+        let key = if channel.is_thread {
+            String::new()
+        } else {
+            message.timestamp.chars().take(10).collect()
+        };
+
+        groups.entry(key).or_default().push(message);
+    }
+
+    groups
+        .into_iter()
+        .filter_map(|(day, messages)| {
+            let first = messages.first()?;
+            let link = format!("https://discord.com/channels/{}/{}/{}", channel.guild_id, channel.id, first.id);
+
+            let mut metadata = HashMap::new();
+            metadata.insert("guild".to_string(), channel.guild_id.clone());
+            metadata.insert("channel".to_string(), channel.name.clone());
+
+            let title = if channel.is_thread {
+                channel.name.clone()
+            } else {
+                metadata.insert("date".to_string(), day.clone());
+                format!("#{} ({})", channel.name, day)
+            };
+
+            Some(Document {
+                id: link.clone(),
+                source: source_id.to_string(),
+                title,
+                link,
+                content: messages
+                    .iter()
+                    .map(|m| format!("{}: {}", m.author, m.content))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                metadata,
+                tags: vec![],
+                modified: messages
+                    .last()
+                    .and_then(|m| DateTime::parse_from_rfc3339(&m.timestamp).ok())
+                    .map(|timestamp| timestamp.with_timezone(&Utc)),
+            })
+        })
+        .collect()
+}
+
+/// Reads channel exports produced by DiscordChatExporter (JSON format).
+pub struct DiscordExportFiles {
+    pub paths: Vec<String>,
+}
+
+impl DiscordChannelLoader for DiscordExportFiles {
+    fn load(&self) -> Pin<Box<dyn Stream<Item=anyhow::Result<DiscordChannel>> + Send>> {
+        let paths = self.paths.clone();
+
+        Box::pin(
+            channel_stream(|tx| async move {
+                for path in paths {
+                    let mut files = vec![];
+
+                    if tokio::fs::metadata(&path).await?.is_dir() {
+                        let mut entries = WalkDir::new(&path);
+                        while let Some(entry) = entries.next().await {
+                            let entry = entry?;
+                            if entry.path().extension().map(|e| e == "json").unwrap_or(false) {
+                                files.push(entry.path());
+                            }
+                        }
+                    } else {
+                        files.push(path.into());
+                    }
+
+                    for file in files {
+                        log::debug!("Processing discord export: {:?}", &file);
+
+                        let content = tokio::fs::read_to_string(&file).await?;
+                        let export = serde_json::from_str::<ExportFile>(&content)
+                            .with_context(|| format!("Couldn't parse discord export: {:?}", file))?;
+
+                        tx.send(Ok(export.into())).await?;
+                    }
+                }
+
+                Ok(())
+            })
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ExportFile {
+    guild: ExportGuild,
+    channel: ExportChannel,
+    messages: Vec<ExportMessage>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ExportGuild {
+    id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ExportChannel {
+    id: String,
+    #[serde(rename = "type")]
+    channel_type: String,
+    name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ExportMessage {
+    id: String,
+    timestamp: String,
+    content: String,
+    author: ExportAuthor,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ExportAuthor {
+    name: String,
+}
+
+impl From<ExportFile> for DiscordChannel {
+    fn from(export: ExportFile) -> Self {
+        DiscordChannel {
+            guild_id: export.guild.id,
+            id: export.channel.id,
+            name: export.channel.name,
+            is_thread: export.channel.channel_type.contains("Thread"),
+            messages: export.messages
+                .into_iter()
+                .map(|m| DiscordMessage {
+                    id: m.id,
+                    author: m.author.name,
+                    timestamp: m.timestamp,
+                    content: m.content,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Fetches the messages of live channels using a bot token.
+pub struct DiscordApiChannels {
+    pub endpoint: String,
+    pub token: String,
+    pub channels: Vec<String>,
+}
+
+impl DiscordChannelLoader for DiscordApiChannels {
+    fn load(&self) -> Pin<Box<dyn Stream<Item=anyhow::Result<DiscordChannel>> + Send>> {
+        let client = reqwest::Client::new();
+        let endpoint = self.endpoint.trim_end_matches('/').to_string();
+        let token = self.token.clone();
+        let channels = self.channels.clone();
+
+        Box::pin(
+            channel_stream(|tx| async move {
+                for channel_id in channels {
+                    log::info!("Fetching messages of discord channel: {}", &channel_id);
+
+                    let channel: ApiChannel = client
+                        .get(format!("{}/channels/{}", endpoint, channel_id))
+                        .header("Authorization", format!("Bot {}", token))
+                        .send()
+                        .await?
+                        .error_for_status()?
+                        .json()
+                        .await
+                        .with_context(|| format!("Couldn't fetch discord channel: {}", channel_id))?;
+
+                    let mut messages = Vec::<ApiMessage>::new();
+
+                    loop {
+                        let mut request = client
+                            .get(format!("{}/channels/{}/messages", endpoint, channel_id))
+                            .header("Authorization", format!("Bot {}", token))
+                            .query(&[("limit", "100")]);
+
+                        if let Some(last) = messages.last() {
+                            request = request.query(&[("before", last.id.as_str())]);
+                        }
+
+                        let page: Vec<ApiMessage> = request
+                            .send()
+                            .await?
+                            .error_for_status()?
+                            .json()
+                            .await?;
+
+                        let done = page.len() < 100;
+                        messages.extend(page);
+
+                        if done {
+                            break;
+                        }
+                    }
+
+                    // The API returns the most recent messages first
+                    messages.reverse();
+
+                    tx.send(Ok(DiscordChannel {
+                        guild_id: channel.guild_id.unwrap_or_else(|| "@me".to_string()),
+                        id: channel.id,
+                        name: channel.name.unwrap_or_default(),
+                        // 10, 11 and 12 are the thread channel types
+                        is_thread: matches!(channel.channel_type, 10..=12),
+                        messages: messages
+                            .into_iter()
+                            .map(|m| DiscordMessage {
+                                id: m.id,
+                                author: m.author.username,
+                                timestamp: m.timestamp,
+                                content: m.content,
+                            })
+                            .collect(),
+                    })).await?;
+                }
+
+                Ok(())
+            })
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ApiChannel {
+    id: String,
+    #[serde(rename = "type")]
+    channel_type: u8,
+    guild_id: Option<String>,
+    name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ApiMessage {
+    id: String,
+    timestamp: String,
+    content: String,
+    author: ApiAuthor,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ApiAuthor {
+    username: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::sources::discord::{channel_to_documents, DiscordChannel, ExportFile};
+
+    #[test]
+    fn test_export_grouped_by_day() -> anyhow::Result<()> {
+        let export = r#"
+            {
+              "guild": { "id": "1", "name": "My guild" },
+              "channel": { "id": "2", "type": "GuildTextChat", "name": "general" },
+              "messages": [
+                { "id": "10", "timestamp": "2022-03-01T10:00:00+00:00", "content": "hello", "author": { "name": "alice" } },
+                { "id": "11", "timestamp": "2022-03-01T11:00:00+00:00", "content": "hi", "author": { "name": "bob" } },
+                { "id": "12", "timestamp": "2022-03-02T09:00:00+00:00", "content": "deploy is done", "author": { "name": "alice" } }
+              ]
+            }
+        "#;
+
+        let channel: DiscordChannel = serde_json::from_str::<ExportFile>(export)?.into();
+        let documents = channel_to_documents("discord", channel);
+
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0].title, "#general (2022-03-01)");
+        assert_eq!(documents[0].link, "https://discord.com/channels/1/2/10");
+        assert_eq!(documents[0].content, "alice: hello\nbob: hi");
+        assert_eq!(documents[1].link, "https://discord.com/channels/1/2/12");
+        assert_eq!(documents[0].modified, Some(Utc.ymd(2022, 3, 1).and_hms(11, 0, 0)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use anyhow::Context;
+use serde_json::Value;
+
+use crate::model::Document;
+
+/// The metadata block at the top of a markdown file: YAML between `---` lines or TOML between
+/// `+++` lines, as used by most static site generators.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Frontmatter {
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub date: Option<String>,
+}
+
+/// Splits the content in its frontmatter (if any) and the rest of the content.
+pub fn extract(content: &str) -> anyhow::Result<(Option<Frontmatter>, &str)> {
+    let (delimiter, parse): (&str, fn(&str) -> anyhow::Result<Value>) = match content.lines().next().map(str::trim_end) {
+        Some("---") => ("---", |block| Ok(serde_json::to_value(serde_yaml::from_str::<serde_yaml::Value>(block)?)?)),
+        Some("+++") => ("+++", |block| Ok(toml_to_json(block.parse()?))),
+        _ => return Ok((None, content)),
+    };
+
+    let body = content.split_once('\n').map(|(_, body)| body).unwrap_or_default();
+    let mut offset = 0;
+
+    for line in body.split_inclusive('\n') {
+        if line.trim_end() == delimiter {
+            let fields = parse(&body[..offset]).context("Invalid frontmatter")?;
+            return Ok((Some(Frontmatter::from_fields(&fields)), &body[offset + line.len()..]));
+        }
+
+        offset += line.len();
+    }
+
+    // Not closed: a markdown file starting with a horizontal rule
+    Ok((None, content))
+}
+
+impl Frontmatter {
+    fn from_fields(fields: &Value) -> Self {
+        let text = |value: &Value| match value {
+            Value::String(text) => Some(text.trim().to_string()),
+            Value::Number(number) => Some(number.to_string()),
+            _ => None,
+        };
+
+        let tags = match &fields["tags"] {
+            Value::Array(tags) => tags.iter().filter_map(text).collect(),
+            Value::String(tags) => tags.split(',').map(|tag| tag.trim().to_string()).collect(),
+            _ => vec![],
+        };
+
+        Self {
+            title: text(&fields["title"]).filter(|title| !title.is_empty()),
+            tags: tags.into_iter().filter(|tag: &String| !tag.is_empty()).collect(),
+            date: text(&fields["date"]),
+        }
+    }
+
+    /// The title replaces the file name, the tags are added to the ones of the document and the
+    /// date goes to the `date` metadata.
+    pub fn apply(self, document: &mut Document) {
+        if let Some(title) = self.title {
+            document.title = title;
+        }
+
+        document.tags.extend(self.tags);
+
+        if let Some(date) = self.date {
+            document.metadata.insert("date".to_string(), date);
+        }
+    }
+}
+
+/// TOML dates are kept as their RFC 3339 representation.
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(text) => Value::String(text),
+        toml::Value::Integer(number) => Value::from(number),
+        toml::Value::Float(number) => Value::from(number),
+        toml::Value::Boolean(boolean) => Value::Bool(boolean),
+        toml::Value::Datetime(date) => Value::String(date.to_string()),
+        toml::Value::Array(values) => Value::Array(values.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(table.into_iter().map(|(key, value)| (key, toml_to_json(value))).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sources::frontmatter::{extract, Frontmatter};
+
+    #[test]
+    fn test_extract() -> anyhow::Result<()> {
+        let yaml = "---\ntitle: Database runbook\ntags: [ops, database]\ndate: 2021-03-04\n---\n# Restart\nRestart the database\n";
+        let (frontmatter, content) = extract(yaml)?;
+
+        assert_eq!(
+            frontmatter,
+            Some(Frontmatter {
+                title: Some("Database runbook".to_string()),
+                tags: vec!["ops".to_string(), "database".to_string()],
+                date: Some("2021-03-04".to_string()),
+            })
+        );
+        assert_eq!(content, "# Restart\nRestart the database\n");
+
+        let toml = "+++\ntitle = \"Setup\"\ntags = \"install, linux\"\ndate = 2021-03-04T10:00:00Z\n+++\nInstall it";
+        let (frontmatter, content) = extract(toml)?;
+
+        assert_eq!(frontmatter.as_ref().and_then(|f| f.title.as_deref()), Some("Setup"));
+        assert_eq!(frontmatter.as_ref().map(|f| f.tags.clone()), Some(vec!["install".to_string(), "linux".to_string()]));
+        assert_eq!(frontmatter.and_then(|f| f.date), Some("2021-03-04T10:00:00Z".to_string()));
+        assert_eq!(content, "Install it");
+
+        let unclosed = "---\nA document starting with a rule";
+        assert_eq!(extract(unclosed)?, (None, unclosed));
+        assert_eq!(extract("# Title")?, (None, "# Title"));
+        assert!(extract("---\ntitle: [unclosed\n---\n").is_err());
+
+        Ok(())
+    }
+}
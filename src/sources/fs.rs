@@ -1,32 +1,215 @@
 use std::collections::HashMap;
+use std::path::Path;
 
-use async_walkdir::WalkDir;
+use anyhow::Context;
+use async_walkdir::{Filtering, WalkDir};
+use chrono::{DateTime, Utc};
 use regex::Regex;
 use tokio_stream::StreamExt;
 
 use crate::model::Document;
 use crate::sources::DocStream;
+use crate::sources::office::OfficeFormat;
+use crate::sources::{asciidoc, frontmatter, html, markdown, notebook, sections};
+use crate::sources::ignore::IgnoreFiles;
+use crate::sources::sections::Sections;
 use crate::utils::streams::channel_stream;
 
 use super::DocumentSource;
 
+/// Extensions of the files that are never text (images, archives, binaries, fonts...), skipped
+/// without being read.
+const BINARY_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "bmp", "ico", "webp", "tiff", "psd", "zip", "gz", "tgz", "bz2", "xz", "7z", "rar", "tar",
+    "jar", "war", "class", "exe", "dll", "so", "dylib", "o", "a", "bin", "pyc", "wasm", "mp3", "mp4", "wav", "ogg", "mov",
+    "avi", "woff", "woff2", "ttf", "otf", "eot", "pdf", "sqlite", "db",
+];
+
+/// Bytes of the files looked at for a null byte, which text files don't have.
+const BINARY_SNIFF_SIZE: usize = 8000;
+
+/// The directory where git stores the history of a repository, never indexed.
+const GIT_DIR: &str = ".git";
+
+#[derive(Clone)]
 pub struct FileSystemDocumentSource {
     pub source_id: String,
     pub paths: Vec<String>,
     pub include: Vec<Regex>,
     pub exclude: Vec<Regex>,
+    /// Matches the include/exclude patterns against the paths relative to the indexed directory
+    /// rather than the absolute ones (glob patterns).
+    pub relative_patterns: bool,
+    pub strip_code_blocks: bool,
+    pub strip_notebook_outputs: bool,
+    /// Indexes each section of the markdown and HTML files as its own document.
+    pub sections: bool,
+    /// Files larger than this number of bytes are skipped.
+    pub max_file_size: Option<u64>,
+    /// Skips the files matched by the `.gitignore` and `.doksignore` files of the directories.
+    pub ignore_files: bool,
+}
+
+impl FileSystemDocumentSource {
+    /// Whether the file at this path is part of the source according to the include/exclude patterns.
+    pub fn accepts(&self, path: &str) -> bool {
+        let path = match self.relative_patterns {
+            true => self.paths
+                .iter()
+                .find_map(|root| Path::new(path).strip_prefix(root).ok())
+                .map(|relative| relative.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.to_string()),
+            false => path.to_string(),
+        };
+
+        let matching = self.include
+            .iter()
+            .any(|r| {
+                r.is_match(&path)
+            });
+
+        matching && self.exclude
+            .iter()
+            .all(|r| !r.is_match(&path))
+    }
+
+    /// Whether the file is skipped by the ignore files of the indexed directory it's in.
+    pub async fn ignores(&self, path: &Path) -> bool {
+        match self.paths.iter().find(|root| path.starts_with(root)) {
+            Some(root) if self.ignore_files => IgnoreFiles::new(root).is_ignored(path, false).await,
+            _ => false,
+        }
+    }
+
+    /// The documents of the file: the file itself, or each of its sections when `sections` is set.
+    pub async fn load(&self, path: &Path) -> anyhow::Result<Vec<Document>> {
+        let link = path.to_string_lossy().to_string();
+        let file_metadata = tokio::fs::metadata(path).await?;
+
+        if let Some(max) = self.max_file_size.filter(|max| file_metadata.len() > *max) {
+            log::info!("Skipping {}: {} bytes, above the max file size of the source ({} bytes)", link, file_metadata.len(), max);
+            return Ok(vec![]);
+        }
+
+        let (content, title) = match OfficeFormat::of(&link) {
+            Some(format) => {
+                let bytes = tokio::fs::read(path).await?;
+                let extracted = format.extract(&bytes).with_context(|| format!("Reading the document {}", link))?;
+                (extracted.text, extracted.title)
+            }
+            None if has_binary_extension(&link) => {
+                log::debug!("Skipping binary file: {}", link);
+                return Ok(vec![]);
+            }
+            None => {
+                let bytes = tokio::fs::read(path).await?;
+
+                if bytes.iter().take(BINARY_SNIFF_SIZE).any(|byte| *byte == 0) {
+                    log::debug!("Skipping binary file: {}", link);
+                    return Ok(vec![]);
+                }
+
+                // Text in another encoding than UTF-8 (e.g. latin-1) is indexed with its invalid bytes replaced
+                (String::from_utf8(bytes).unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned()), None)
+            }
+        };
+
+        // Relative to the indexed directory, so that `--path` filters don't depend on where it is
+        let relative = self.paths.iter().find_map(|root| path.strip_prefix(root).ok()).unwrap_or(path);
+        let metadata = HashMap::from([("path".to_string(), relative.to_string_lossy().to_string())]);
+        let modified = file_metadata.modified().ok().map(DateTime::<Utc>::from);
+
+        let mut document = Document {
+            id: link.clone(),
+            source: self.source_id.to_string(),
+            title: title.unwrap_or_else(|| path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default()),
+            link,
+            content,
+            metadata,
+            tags: vec![],
+            modified,
+        };
+        let mut sections = Sections::default();
+
+        if markdown::is_markdown(&document.link) {
+            match frontmatter::extract(&document.content) {
+                Ok((Some(frontmatter), content)) => {
+                    document.content = content.to_string();
+                    frontmatter.apply(&mut document);
+                }
+                Ok((None, _)) => {}
+                Err(err) => log::warn!("Indexing {} with its frontmatter: {:#}", document.link, err),
+            }
+
+            // Indexing the markup would match queries on link urls, badges, html tags...
+            let extracted = markdown::extract_text(&document.content, self.strip_code_blocks);
+            document.content = extracted.text;
+            sections = extracted.sections;
+
+            if !extracted.headings.is_empty() {
+                document.metadata.insert("headings".to_string(), extracted.headings.join("\n"));
+            }
+        } else if asciidoc::is_asciidoc(&document.link) {
+            let extracted = asciidoc::extract_text(&document.content, self.strip_code_blocks);
+            document.content = extracted.text;
+
+            if let Some(title) = extracted.title {
+                document.title = title;
+            }
+
+            if !extracted.headings.is_empty() {
+                document.metadata.insert("headings".to_string(), extracted.headings.join("\n"));
+            }
+        } else if notebook::is_notebook(&document.link) {
+            if let Err(err) = notebook::process(&mut document, self.strip_code_blocks, self.strip_notebook_outputs) {
+                log::warn!("Indexing {} as is: {:#}", document.link, err);
+            }
+        } else {
+            sections = html::process(&mut document);
+        }
+
+        match self.sections {
+            true => Ok(sections::split(document, sections)),
+            false => Ok(vec![document]),
+        }
+    }
+}
+
+fn has_binary_extension(path: &str) -> bool {
+    match path.rsplit_once('.') {
+        Some((_, extension)) => BINARY_EXTENSIONS.contains(&extension.to_lowercase().as_str()),
+        None => false,
+    }
 }
 
 impl DocumentSource for FileSystemDocumentSource {
     fn fetch(&self) -> DocStream {
-        let paths = self.paths.clone();
-        let source_id = self.source_id.clone();
-        let include = self.include.clone();
-        let exclude = self.exclude.clone();
+        let source = self.clone();
 
         let stream = channel_stream(|tx| async move {
-            for path in paths {
-                let mut files = WalkDir::new(path);
+            for path in &source.paths {
+                let ignores = IgnoreFiles::new(path);
+                let ignore_files = source.ignore_files;
+
+                // The ignored directories (node_modules, target...) and the git internals of the
+                // clones are not walked at all
+                let mut files = WalkDir::new(path).filter(move |entry| {
+                    let ignores = ignores.clone();
+
+                    async move {
+                        let is_dir = entry.file_type().await.map(|kind| kind.is_dir()).unwrap_or(false);
+
+                        if is_dir && entry.file_name() == GIT_DIR {
+                            return Filtering::IgnoreDir;
+                        }
+
+                        match ignore_files && ignores.is_ignored(&entry.path(), is_dir).await {
+                            true if is_dir => Filtering::IgnoreDir,
+                            true => Filtering::Ignore,
+                            false => Filtering::Continue,
+                        }
+                    }
+                });
 
                 while let Some(file) = files.next().await {
                     let file = file?;
@@ -38,31 +221,14 @@ impl DocumentSource for FileSystemDocumentSource {
 
                     log::debug!("Processing: {}", &path);
 
-                    let matching = (&include)
-                        .iter()
-                        .any(|r| {
-                            r.is_match(path.as_ref())
-                        });
-
-                    let matching = matching && exclude.is_empty() && (&exclude)
-                        .iter()
-                        .all(|r| !r.is_match(path.as_ref()));
-
-                    if !matching {
+                    if !source.accepts(&path) {
                         log::debug!("Ignoring file: {}", &path);
                         continue;
                     }
 
-                    let content = tokio::fs::read_to_string(file.path()).await?;
-
-                    tx.send(Ok(Document {
-                        id: path.clone(),
-                        source: source_id.to_string(),
-                        title: file.file_name().to_string_lossy().to_string(),
-                        link: path,
-                        content,
-                        metadata: HashMap::default(),
-                    })).await?;
+                    for document in source.load(&file.path()).await? {
+                        tx.send(Ok(document)).await?;
+                    }
                 }
             }
 
@@ -75,12 +241,12 @@ impl DocumentSource for FileSystemDocumentSource {
 
 #[cfg(test)]
 mod tests {
-    use anyhow::anyhow;
     use regex::Regex;
     use tempdir::TempDir;
     use tokio_stream::StreamExt;
 
     use crate::sources::fs::FileSystemDocumentSource;
+    use crate::utils::glob::glob_to_regex;
 
     use super::DocumentSource;
 
@@ -109,8 +275,14 @@ mod tests {
         let source = FileSystemDocumentSource {
             include: vec![Regex::new(".*.txt")?],
             exclude: vec![],
+            relative_patterns: false,
             paths: vec![root.path().to_string_lossy().to_string()],
             source_id: String::from("source1"),
+            strip_code_blocks: false,
+            strip_notebook_outputs: false,
+            sections: false,
+            max_file_size: None,
+            ignore_files: false,
         };
 
         let mut collected = (&source).fetch()
@@ -125,6 +297,168 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_sections() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let path = root.path().join("README.md");
+        tokio::fs::write(&path, "# Service\n\nAn API.\n\n## Deployment\n\nRun the playbook.").await?;
+
+        let source = FileSystemDocumentSource {
+            include: vec![Regex::new(".*")?],
+            exclude: vec![],
+            relative_patterns: false,
+            paths: vec![root.path().to_string_lossy().to_string()],
+            source_id: String::from("docs"),
+            strip_code_blocks: false,
+            strip_notebook_outputs: false,
+            sections: true,
+            max_file_size: None,
+            ignore_files: false,
+        };
+
+        let documents = source.load(&path).await?;
+        let link = path.to_string_lossy();
+
+        assert_eq!(
+            documents.iter().map(|document| (document.link.clone(), document.title.as_str())).collect::<Vec<_>>(),
+            vec![(format!("{}#service", link), "README.md › Service"), (format!("{}#deployment", link), "README.md › Deployment")]
+        );
+        assert_eq!(documents[1].content, "Deployment\nRun the playbook.");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_binary_files() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        tokio::fs::write(root.path().join("logo.PNG"), "not even an image").await?;
+        tokio::fs::write(root.path().join("data"), b"ELF\x00\x01\x02").await?;
+        tokio::fs::write(root.path().join("notes.txt"), b"caf\xe9").await?;
+
+        let source = FileSystemDocumentSource {
+            include: vec![Regex::new(".*")?],
+            exclude: vec![],
+            relative_patterns: false,
+            paths: vec![root.path().to_string_lossy().to_string()],
+            source_id: String::from("docs"),
+            strip_code_blocks: false,
+            strip_notebook_outputs: false,
+            sections: false,
+            max_file_size: None,
+            ignore_files: false,
+        };
+
+        let documents = source.fetch().collect::<anyhow::Result<Vec<_>>>().await?;
+
+        assert_eq!(documents.iter().map(|document| document.title.as_str()).collect::<Vec<_>>(), vec!["notes.txt"]);
+        assert_eq!(documents[0].content, "caf\u{FFFD}");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_file_size() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        tokio::fs::write(root.path().join("small.log"), "ok").await?;
+        tokio::fs::write(root.path().join("large.log"), "x".repeat(2048)).await?;
+
+        let source = FileSystemDocumentSource {
+            include: vec![Regex::new(".*")?],
+            exclude: vec![],
+            relative_patterns: false,
+            paths: vec![root.path().to_string_lossy().to_string()],
+            source_id: String::from("logs"),
+            strip_code_blocks: false,
+            strip_notebook_outputs: false,
+            sections: false,
+            max_file_size: Some(1024),
+            ignore_files: false,
+        };
+
+        let documents = source.fetch().collect::<anyhow::Result<Vec<_>>>().await?;
+        assert_eq!(documents.iter().map(|document| document.title.as_str()).collect::<Vec<_>>(), vec!["small.log"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ignore_files() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        tokio::fs::create_dir_all(root.path().join("node_modules/lib")).await?;
+        tokio::fs::create_dir_all(root.path().join(".git")).await?;
+        tokio::fs::write(root.path().join(".git/HEAD.md"), "ref: refs/heads/main").await?;
+        tokio::fs::write(root.path().join(".gitignore"), "node_modules/\n").await?;
+        tokio::fs::write(root.path().join(".doksignore"), "*.txt\n").await?;
+        tokio::fs::write(root.path().join("node_modules/lib/README.md"), "A dependency").await?;
+        tokio::fs::write(root.path().join("notes.txt"), "Scratch").await?;
+        tokio::fs::write(root.path().join("README.md"), "A service").await?;
+
+        let source = FileSystemDocumentSource {
+            include: vec![Regex::new(".*\\.(md|txt)$")?],
+            exclude: vec![],
+            relative_patterns: false,
+            paths: vec![root.path().to_string_lossy().to_string()],
+            source_id: String::from("docs"),
+            strip_code_blocks: false,
+            strip_notebook_outputs: false,
+            sections: false,
+            max_file_size: None,
+            ignore_files: true,
+        };
+
+        let documents = source.fetch().collect::<anyhow::Result<Vec<_>>>().await?;
+        assert_eq!(documents.iter().map(|document| document.title.as_str()).collect::<Vec<_>>(), vec!["README.md"]);
+        assert!(source.ignores(&root.path().join("node_modules/lib/README.md")).await);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_accepts() -> anyhow::Result<()> {
+        let glob = |glob: &str| Regex::new(&format!("^{}$", glob_to_regex(glob)));
+        let source = FileSystemDocumentSource {
+            include: vec![glob("**/*.md")?],
+            exclude: vec![glob("drafts/**")?],
+            relative_patterns: true,
+            paths: vec!["/home/docs".to_string()],
+            source_id: String::from("docs"),
+            strip_code_blocks: false,
+            strip_notebook_outputs: false,
+            sections: false,
+            max_file_size: None,
+            ignore_files: false,
+        };
+
+        assert!(source.accepts("/home/docs/README.md"));
+        assert!(source.accepts("/home/docs/ops/runbook.md"));
+        assert!(!source.accepts("/home/docs/drafts/todo.md"));
+        assert!(!source.accepts("/home/docs/setup.txt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclude() -> anyhow::Result<()> {
+        // Any exclude pattern used to reject all the files
+        let source = FileSystemDocumentSource {
+            include: vec![Regex::new(".*\\.md$")?],
+            exclude: vec![Regex::new("/drafts/")?],
+            relative_patterns: false,
+            paths: vec!["/home/docs".to_string()],
+            source_id: String::from("docs"),
+            strip_code_blocks: false,
+            strip_notebook_outputs: false,
+            sections: false,
+            max_file_size: None,
+            ignore_files: false,
+        };
+
+        assert!(source.accepts("/home/docs/README.md"));
+        assert!(!source.accepts("/home/docs/drafts/todo.md"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_regex() -> anyhow::Result<()> {
         let regex = Regex::new(".*.txt")?;
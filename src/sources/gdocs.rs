@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use serde_json::Value;
+
+use crate::model::Document;
+use crate::sources::{DocStream, DocumentSource};
+use crate::utils::json::{get_array, get_path};
+use crate::utils::streams::channel_stream;
+
+/// Pulls documents through the Google Docs API and indexes every heading section separately so
+/// that links point to the matching heading anchor.
+pub struct GoogleDocsSource {
+    pub source_id: String,
+    pub endpoint: String,
+    pub token: String,
+    pub documents: Vec<String>,
+}
+
+impl DocumentSource for GoogleDocsSource {
+    fn fetch(&self) -> DocStream {
+        let client = reqwest::Client::new();
+        let source_id = self.source_id.clone();
+        let endpoint = self.endpoint.trim_end_matches('/').to_string();
+        let token = self.token.clone();
+        let documents = self.documents.clone();
+
+        Box::pin(
+            channel_stream(|tx| async move {
+                for document_id in documents {
+                    log::info!("Fetching google document: {}", &document_id);
+
+                    let document: Value = client
+                        .get(format!("{}/documents/{}", endpoint, document_id))
+                        .bearer_auth(&token)
+                        .send()
+                        .await?
+                        .error_for_status()?
+                        .json()
+                        .await
+                        .with_context(|| format!("Couldn't fetch google document: {}", document_id))?;
+
+                    for section in document_to_sections(&source_id, &document)? {
+                        tx.send(Ok(section)).await?;
+                    }
+                }
+
+                Ok(())
+            })
+        )
+    }
+}
+
+struct Section {
+    heading: Option<String>,
+    heading_id: Option<String>,
+    content: String,
+}
+
+fn document_to_sections(source_id: &str, document: &Value) -> anyhow::Result<Vec<Document>> {
+    let document_id = get_path(document, &["documentId"])?.as_str().unwrap_or_default();
+    let title = get_path(document, &["title"])?.as_str().unwrap_or_default();
+    let base_link = format!("https://docs.google.com/document/d/{}/edit", document_id);
+
+    let mut sections = vec![Section { heading: None, heading_id: None, content: String::new() }];
+
+    for element in get_array(document, &["body", "content"])? {
+        let style = element
+            .pointer("/paragraph/paragraphStyle/namedStyleType")
+            .and_then(|s| s.as_str())
+            .unwrap_or_default();
+
+        if style.starts_with("HEADING_") || style == "TITLE" {
+            sections.push(Section {
+                heading: Some(extract_text(element).trim().to_string()),
+                heading_id: element
+                    .pointer("/paragraph/paragraphStyle/headingId")
+                    .and_then(|s| s.as_str())
+                    .map(|s| s.to_string()),
+                content: String::new(),
+            });
+            continue;
+        }
+
+        if let Some(section) = sections.last_mut() {
+            section.content.push_str(&extract_text(element));
+        }
+    }
+
+    Ok(
+        sections
+            .into_iter()
+            .enumerate()
+            .filter(|(_, section)| section.heading.is_some() || !section.content.trim().is_empty())
+            .map(|(position, section)| {
+                let link = match &section.heading_id {
+                    Some(heading_id) => format!("{}#heading={}", base_link, heading_id),
+                    None => base_link.clone(),
+                };
+                // The headings without an id link to the document, but mustn't replace the intro
+                let id = match (&section.heading, &section.heading_id) {
+                    (Some(_), None) => format!("{}#section-{}", base_link, position),
+                    _ => link.clone(),
+                };
+
+                let mut metadata = HashMap::new();
+                metadata.insert("document".to_string(), title.to_string());
+
+                Document {
+                    id,
+                    source: source_id.to_string(),
+                    title: match section.heading {
+                        Some(heading) => format!("{} › {}", title, heading),
+                        None => title.to_string(),
+                    },
+                    link,
+                    content: section.content,
+                    metadata,
+                    tags: vec![],
+                    modified: None,
+                }
+            })
+            .collect()
+    )
+}
+
+/// Extracts the text of a structural element (paragraph, table or table of contents).
+fn extract_text(element: &Value) -> String {
+    let mut text = String::new();
+
+    if let Some(elements) = element.pointer("/paragraph/elements").and_then(|e| e.as_array()) {
+        for element in elements {
+            if let Some(content) = element.pointer("/textRun/content").and_then(|c| c.as_str()) {
+                text.push_str(content);
+            }
+        }
+    }
+
+    if let Some(rows) = element.pointer("/table/tableRows").and_then(|r| r.as_array()) {
+        for row in rows {
+            let cells = row
+                .get("tableCells")
+                .and_then(|c| c.as_array())
+                .map(|cells| {
+                    cells
+                        .iter()
+                        .map(|cell| {
+                            cell.get("content")
+                                .and_then(|c| c.as_array())
+                                .map(|content| content.iter().map(extract_text).collect::<String>())
+                                .unwrap_or_default()
+                                .trim()
+                                .to_string()
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+
+            text.push_str(&cells.join(" | "));
+            text.push('\n');
+        }
+    }
+
+    if let Some(content) = element.pointer("/tableOfContents/content").and_then(|c| c.as_array()) {
+        for element in content {
+            text.push_str(&extract_text(element));
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::sources::gdocs::document_to_sections;
+
+    #[test]
+    fn test_document_to_sections() -> anyhow::Result<()> {
+        let document = json!({
+            "documentId": "abc",
+            "title": "Oncall handbook",
+            "body": {
+                "content": [
+                    { "sectionBreak": {} },
+                    { "paragraph": { "elements": [{ "textRun": { "content": "Intro text\n" } }] } },
+                    {
+                        "paragraph": {
+                            "elements": [{ "textRun": { "content": "Escalation\n" } }],
+                            "paragraphStyle": { "namedStyleType": "HEADING_1", "headingId": "h.123" }
+                        }
+                    },
+                    {
+                        "paragraph": {
+                            "elements": [{ "textRun": { "content": "Contacts\n" } }],
+                            "paragraphStyle": { "namedStyleType": "HEADING_2" }
+                        }
+                    },
+                    {
+                        "table": {
+                            "tableRows": [{
+                                "tableCells": [
+                                    { "content": [{ "paragraph": { "elements": [{ "textRun": { "content": "Level 1\n" } }] } }] },
+                                    { "content": [{ "paragraph": { "elements": [{ "textRun": { "content": "Team lead\n" } }] } }] }
+                                ]
+                            }]
+                        }
+                    }
+                ]
+            }
+        });
+
+        let sections = document_to_sections("gdocs", &document)?;
+
+        assert_eq!(sections.len(), 3);
+        assert_eq!(sections[0].title, "Oncall handbook");
+        assert_eq!(sections[0].content, "Intro text\n");
+        assert_eq!(sections[1].title, "Oncall handbook › Escalation");
+        assert_eq!(sections[1].link, "https://docs.google.com/document/d/abc/edit#heading=h.123");
+        assert_eq!(sections[2].title, "Oncall handbook › Contacts");
+        assert_eq!(sections[2].content, "Level 1 | Team lead\n");
+
+        // A heading without id doesn't share the id of the intro
+        assert_eq!(sections[2].link, "https://docs.google.com/document/d/abc/edit");
+        assert_eq!(sections[2].id, "https://docs.google.com/document/d/abc/edit#section-2");
+        assert_eq!(sections[0].id, "https://docs.google.com/document/d/abc/edit");
+
+        Ok(())
+    }
+}
@@ -1,26 +1,41 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 
 use anyhow::Context;
+use chrono::{TimeZone, Utc};
+use fs2::FileExt;
 use git2::build::RepoBuilder;
+use git2::{Repository, ResetType};
+use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
 use regex::Regex;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
-use tempdir::TempDir;
 use tokio::task::JoinHandle;
 use tokio_stream::{Stream, StreamExt};
 
 use fs::FileSystemDocumentSource;
 
 use crate::sources::{DocStream, DocumentSource, fs};
-use crate::utils::json::get_array;
 use crate::utils::json::parse_json;
 use crate::utils::streams::channel_stream;
 
+/// The characters escaped in the segments of the paths of the links to the files on GitHub.
+const PATH_ESCAPES: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'#').add(b'%').add(b'<').add(b'>').add(b'?').add(b'`').add(b'{').add(b'}');
+
 pub struct GithubSource {
     pub source_id: String,
     pub lister: Box<dyn GitRepositoryLister>,
     pub include: Vec<Regex>,
     pub exclude: Vec<Regex>,
+    pub relative_patterns: bool,
+    pub strip_code_blocks: bool,
+    pub strip_notebook_outputs: bool,
+    pub sections: bool,
+    pub max_file_size: Option<u64>,
+    pub ignore_files: bool,
+    /// Where the repositories are cloned, and kept to only fetch their new commits on the next runs.
+    pub clone_dir: PathBuf,
 }
 
 impl DocumentSource for GithubSource {
@@ -29,38 +44,77 @@ impl DocumentSource for GithubSource {
         let source_id = self.source_id.clone();
         let include = self.include.clone();
         let exclude = self.exclude.clone();
+        let relative_patterns = self.relative_patterns;
+        let strip_code_blocks = self.strip_code_blocks;
+        let strip_notebook_outputs = self.strip_notebook_outputs;
+        let sections = self.sections;
+        let max_file_size = self.max_file_size;
+        let ignore_files = self.ignore_files;
+        let clone_dir = self.clone_dir.clone();
 
         Box::pin(
-            channel_stream(|tx| async move {
+            channel_stream(move |tx| async move {
                 while let Some(repository) = repositories.next().await {
-                    // Clone the repo
+                    // Clone the repo, or update its clone from a previous run
                     let repository = repository?;
-                    let dest = TempDir::new("cloned")?;
+                    let name = repository.name.clone();
+                    let topics = repository.topics.clone();
+                    let web_url = repository.web_url();
+                    let dest = clone_path(&clone_dir, &repository);
 
-                    let path = dest.path().to_owned();
+                    let path = dest.clone();
+                    // The files of a clone are all as old as the clone: the date of the last commit
+                    // stands for their modification date
                     let clone_task: JoinHandle<anyhow::Result<_>> = tokio::task::spawn_blocking(move || {
-                        log::info!("Cloning repository '{}' into {:?}", &repository.clone_url, &path);
-                        RepoBuilder::default().clone(&repository.clone_url, &path)?;
-                        std::fs::remove_dir_all(path.join(".git"))?;
-                        Ok(())
+                        let cloned = sync_clone(&repository.clone_url, &path)?;
+                        let head = cloned.head()?;
+                        let branch = head.shorthand().unwrap_or("HEAD").to_string();
+                        let last_commit = head.peel_to_commit()?.time();
+                        Ok((branch, Utc.timestamp_opt(last_commit.seconds(), 0).single()))
                     });
 
-                    clone_task
+                    let (branch, modified) = clone_task
                         .await
                         .context("Clone task panicked!")?
                         .context("Error while cloning repository")?;
 
-
                     let source = FileSystemDocumentSource {
                         source_id: source_id.clone(),
-                        paths: vec![dest.path().to_string_lossy().to_string()],
+                        paths: vec![dest.to_string_lossy().to_string()],
                         include: include.clone(),
                         exclude: exclude.clone(),
+                        relative_patterns,
+                        strip_code_blocks,
+                        strip_notebook_outputs,
+                        sections,
+                        max_file_size,
+                        ignore_files,
                     };
 
                     let mut documents = source.fetch();
 
                     while let Some(document) = documents.next().await {
+                        let document = document.map(|mut document| {
+                            // The documents link to GitHub rather than to the local clone
+                            let path = document.metadata.get("path").cloned().unwrap_or_default();
+                            let blob_link = |link: &str| blob_link(&web_url, &branch, &dest, &path, link);
+                            document.id = blob_link(&document.id);
+                            document.link = blob_link(&document.link);
+
+                            if let Some(parent_id) = document.metadata.get_mut("parent_id") {
+                                *parent_id = blob_link(parent_id);
+                            }
+
+                            document.metadata.insert("repository".to_string(), name.clone());
+                            for topic in &topics {
+                                if !document.tags.contains(topic) {
+                                    document.tags.push(topic.clone());
+                                }
+                            }
+                            document.modified = modified;
+                            document
+                        });
+
                         tx.send(document).await?;
                     }
                 }
@@ -75,44 +129,31 @@ pub trait GitRepositoryLister {
     fn list(&self) -> Pin<Box<dyn Stream<Item=anyhow::Result<RepositoryInfo>> + Send>>;
 }
 
-pub struct GithubStarsLister {
-    client: octocrab::Octocrab,
-    starred_by: String,
+/// The repositories of the config, with their topics fetched from the REST API of `endpoint`.
+#[derive(Clone)]
+pub struct GithubRepoStaticList {
+    pub list: Vec<RepositoryInfo>,
+    pub endpoint: String,
+    pub token: Option<String>,
 }
 
-impl GitRepositoryLister for GithubStarsLister {
+impl GitRepositoryLister for GithubRepoStaticList {
     fn list(&self) -> Pin<Box<dyn Stream<Item=anyhow::Result<RepositoryInfo>> + Send>> {
-        let client = self.client.clone();
-        let starred_by = self.starred_by.clone();
+        let list = self.list.clone();
+        let endpoint = self.endpoint.clone();
+        let token = self.token.clone();
 
         let stream = channel_stream(|tx| async move {
-            let mut page_info: Option<PageInfo> = None;
-
-            loop {
-                let query = gh_starred_gql_query(
-                    starred_by.as_str(),
-                    page_info.take().map(|v| v.end_cursor),
-                );
-
-                let page: Value = client.graphql(&query).await?;
-
-                let nodes = get_array(&page, &["data", "user", "starredRepositories", "nodes"])?;
-                let current_page_info: PageInfo = parse_json(
-                    &page, &["data", "user", "starredRepositories", "pageInfo"],
-                )?;
-
-                for item in nodes {
-                    let parsed = serde_json::from_value::<RepositoryInfo>(item.clone())
-                        .with_context(|| format!("Couldn't parse json into a repository: {}", item));
+            let client = reqwest::Client::new();
 
-                    tx.send(parsed).await?;
+            for mut repository in list {
+                // The repository is still indexed, without tags, when its topics can't be fetched
+                match repository_topics(&client, &endpoint, token.as_deref(), &repository.name).await {
+                    Ok(topics) => repository.topics = topics,
+                    Err(err) => log::warn!("Couldn't fetch the topics of repository {}: {:#}", repository.name, err),
                 }
 
-                if !&current_page_info.has_next_page {
-                    break;
-                }
-
-                page_info.replace(current_page_info);
+                tx.send(Ok(repository)).await?;
             }
 
             Ok(())
@@ -122,56 +163,20 @@ impl GitRepositoryLister for GithubStarsLister {
     }
 }
 
-fn gh_starred_gql_query(starred_by: &str, start_cursor: Option<String>) -> String {
-    format!(
-        r#"query {{
-          user(login:"{}") {{
-            starredRepositories(
-                first: 2,
-                orderBy: {{ field: STARRED_AT, direction:DESC }},
-                after: "{}"
-            ) {{
-              pageInfo {{
-                startCursor
-                endCursor
-                hasNextPage
-              }}
-              nodes {{
-                sshUrl
-                url
-                name
-              }}
-            }}
-          }}
-        }}
-        "#,
-        starred_by,
-        start_cursor.unwrap_or_default(),
-    )
-}
-
-#[derive(Clone)]
-pub struct GithubRepoStaticList {
-    pub list: Vec<RepositoryInfo>,
-}
+/// `GET /repos/{owner}/{repo}/topics`
+async fn repository_topics(client: &reqwest::Client, endpoint: &str, token: Option<&str>, name: &str) -> anyhow::Result<Vec<String>> {
+    let mut request = client
+        .get(format!("{}/repos/{}/topics", endpoint.trim_end_matches('/'), name))
+        .header("Accept", "application/vnd.github+json")
+        .header("User-Agent", "doks");
 
-impl GitRepositoryLister for GithubRepoStaticList {
-    fn list(&self) -> Pin<Box<dyn Stream<Item=anyhow::Result<RepositoryInfo>> + Send>> {
-        Box::pin(tokio_stream::iter(
-            self.list
-                .iter()
-                .map(|e| Ok(e.clone()))
-                .collect::<Vec<_>>()
-        ))
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
     }
-}
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct PageInfo {
-    has_next_page: bool,
-    start_cursor: String,
-    end_cursor: String,
+    let response: Value = request.send().await?.error_for_status()?.json().await?;
+
+    parse_json(&response, &["names"])
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -180,4 +185,274 @@ pub struct RepositoryInfo {
     pub name: String,
     #[serde(alias = "url")]
     pub clone_url: String,
+    /// The topics of the repository, added to the tags of its documents.
+    #[serde(default, rename = "repositoryTopics", deserialize_with = "topic_names")]
+    pub topics: Vec<String>,
+}
+
+impl RepositoryInfo {
+    /// The url of the page of the repository (`https://github.com/<owner>/<name>`), from its ssh
+    /// (`git@github.com:<owner>/<name>.git`) or https clone url.
+    pub fn web_url(&self) -> String {
+        let url = self.clone_url.trim_end_matches('/');
+        let url = url.strip_suffix(".git").unwrap_or(url);
+
+        match url.strip_prefix("git@").and_then(|rest| rest.split_once(':')) {
+            Some((host, path)) => format!("https://{}/{}", host, path),
+            None => url.replacen("ssh://git@", "https://", 1),
+        }
+    }
+}
+
+/// The directory of the clone of the repository in `clone_dir`: `<host>/<owner>/<name>`.
+fn clone_path(clone_dir: &Path, repository: &RepositoryInfo) -> PathBuf {
+    let web_url = repository.web_url();
+    let key = web_url.split_once("://").map_or(web_url.as_str(), |(_, rest)| rest);
+
+    key.split('/')
+        .filter(|part| !part.is_empty() && *part != "." && *part != "..")
+        .map(|part| part.replace(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | '.')), "_"))
+        .fold(clone_dir.to_path_buf(), |path, part| path.join(part))
+}
+
+/// Clones the repository into `path`, or updates the clone left there by a previous run: fetches
+/// its remote and resets the checked out branch to it. A clone that can't be updated (interrupted,
+/// corrupted...) is deleted and cloned again. The clone is locked meanwhile (with `<path>.lock`), as
+/// other runs or processes may sync it at the same time.
+fn sync_clone(clone_url: &str, path: &Path) -> anyhow::Result<Repository> {
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let lock = File::create(&lock_path).with_context(|| format!("Couldn't create the lock file {:?}", lock_path))?;
+    lock.lock_exclusive().with_context(|| format!("Couldn't lock the clone at {:?}", path))?;
+
+    if path.join(".git").exists() {
+        match fetch_and_reset(clone_url, path) {
+            Ok(repository) => return Ok(repository),
+            Err(err) => log::warn!("Cloning '{}' again, its clone at {:?} couldn't be updated: {:#}", clone_url, path, err),
+        }
+    }
+
+    if path.exists() {
+        std::fs::remove_dir_all(path)?;
+    }
+
+    log::info!("Cloning repository '{}' into {:?}", clone_url, path);
+    Ok(RepoBuilder::default().clone(clone_url, path)?)
+}
+
+fn fetch_and_reset(clone_url: &str, path: &Path) -> anyhow::Result<Repository> {
+    let repository = Repository::open(path)?;
+
+    {
+        log::info!("Fetching repository '{}' into {:?}", clone_url, path);
+        repository.remote_set_url("origin", clone_url)?;
+        repository.find_remote("origin")?.fetch(&[] as &[&str], None, None)?;
+
+        let branch = repository.head()?.shorthand().context("No branch is checked out")?.to_string();
+        let target = repository.revparse_single(&format!("refs/remotes/origin/{}", branch))?;
+        repository.reset(&target, ResetType::Hard, None)?;
+    }
+
+    Ok(repository)
+}
+
+/// The link on GitHub (`<web_url>/blob/<branch>/<path>`) of a document of the clone at `root`,
+/// given its local link and the path of its file in the clone. The anchor the sections add after
+/// the path of the file is kept, a `#` in the name of a file is escaped.
+fn blob_link(web_url: &str, branch: &str, root: &Path, path: &str, link: &str) -> String {
+    let local = root.join(path).to_string_lossy().to_string();
+
+    let anchor = match link.strip_prefix(local.as_str()) {
+        Some("") => None,
+        Some(rest) if rest.starts_with('#') => Some(&rest[1..]),
+        _ => return link.to_string(),
+    };
+
+    let segments = path.split('/').map(|segment| utf8_percent_encode(segment, PATH_ESCAPES).to_string()).collect::<Vec<_>>();
+    let mut blob = format!("{}/blob/{}/{}", web_url, branch, segments.join("/"));
+
+    if let Some(anchor) = anchor {
+        blob.push('#');
+        blob.push_str(anchor);
+    }
+
+    blob
+}
+
+/// The names of the topics as returned by the GraphQL API: `{ "nodes": [{ "topic": { "name": ... } }] }`.
+fn topic_names<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let topics = Value::deserialize(deserializer)?;
+
+    Ok(
+        topics["nodes"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|node| node["topic"]["name"].as_str())
+            .map(str::to_string)
+            .collect()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::path::Path;
+
+    use axum::extract::Path as UrlPath;
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use git2::{Repository, Signature};
+    use regex::Regex;
+    use serde_json::json;
+    use tempdir::TempDir;
+    use tokio_stream::StreamExt;
+
+    use crate::sources::gh::{blob_link, clone_path, sync_clone, GithubRepoStaticList, GithubSource, RepositoryInfo};
+    use crate::sources::DocumentSource;
+
+    #[test]
+    fn test_repository_topics() -> anyhow::Result<()> {
+        let repository = serde_json::from_value::<RepositoryInfo>(json!({
+            "name": "doks",
+            "url": "https://github.com/wlezzar/doks",
+            "repositoryTopics": { "nodes": [{ "topic": { "name": "search" } }, { "topic": { "name": "rust" } }] }
+        }))?;
+
+        assert_eq!(repository.topics, vec!["search".to_string(), "rust".to_string()]);
+
+        let repository = serde_json::from_value::<RepositoryInfo>(json!({ "name": "doks", "url": "https://github.com/wlezzar/doks" }))?;
+        assert!(repository.topics.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_blob_link() {
+        let repository = |clone_url: &str| RepositoryInfo { name: "wlezzar/doks".to_string(), clone_url: clone_url.to_string(), topics: vec![] };
+
+        assert_eq!(repository("git@github.com:wlezzar/doks.git").web_url(), "https://github.com/wlezzar/doks");
+        assert_eq!(repository("https://github.com/wlezzar/doks.git").web_url(), "https://github.com/wlezzar/doks");
+        assert_eq!(repository("https://github.com/wlezzar/doks").web_url(), "https://github.com/wlezzar/doks");
+
+        let link = |path: &str, link: &str| blob_link("https://github.com/wlezzar/doks", "main", Path::new("/tmp/cloned.x1"), path, link);
+
+        assert_eq!(link("docs/setup.md", "/tmp/cloned.x1/docs/setup.md"), "https://github.com/wlezzar/doks/blob/main/docs/setup.md");
+        assert_eq!(link("README.md", "/tmp/cloned.x1/README.md#deployment"), "https://github.com/wlezzar/doks/blob/main/README.md#deployment");
+        assert_eq!(link("docs/release notes.md", "/tmp/cloned.x1/docs/release notes.md"), "https://github.com/wlezzar/doks/blob/main/docs/release%20notes.md");
+        assert_eq!(link("docs/C#.md", "/tmp/cloned.x1/docs/C#.md"), "https://github.com/wlezzar/doks/blob/main/docs/C%23.md");
+        assert_eq!(link("docs/C#.md", "/tmp/cloned.x1/docs/C#.md#usage"), "https://github.com/wlezzar/doks/blob/main/docs/C%23.md#usage");
+    }
+
+    fn commit(repository: &Repository, file: &str, content: &str) -> anyhow::Result<()> {
+        std::fs::write(repository.workdir().expect("Not a bare repository").join(file), content)?;
+
+        let mut index = repository.index()?;
+        index.add_path(Path::new(file))?;
+        index.write()?;
+
+        let tree = repository.find_tree(index.write_tree()?)?;
+        let signature = Signature::now("doks", "doks@example.com")?;
+        let parent = repository.head().ok().and_then(|head| head.peel_to_commit().ok());
+        repository.commit(Some("HEAD"), &signature, &signature, "Update the docs", &tree, &parent.iter().collect::<Vec<_>>())?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_clone() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let upstream = Repository::init(root.path().join("upstream"))?;
+        commit(&upstream, "README.md", "Version 1")?;
+
+        let repository = RepositoryInfo {
+            name: "wlezzar/doks".to_string(),
+            clone_url: "git@github.com:wlezzar/doks.git".to_string(),
+            topics: vec![],
+        };
+        assert_eq!(clone_path(root.path(), &repository), root.path().join("github.com/wlezzar/doks"));
+
+        let clone_url = root.path().join("upstream").to_string_lossy().to_string();
+        let clone = root.path().join("clones/doks");
+        sync_clone(&clone_url, &clone)?;
+        assert_eq!(std::fs::read_to_string(clone.join("README.md"))?, "Version 1");
+
+        // The next runs fetch the new commits into the same clone rather than cloning it again
+        std::fs::write(clone.join("untracked"), "")?;
+        std::fs::write(clone.join(".git/doks-marker"), "")?;
+        commit(&upstream, "README.md", "Version 2")?;
+        sync_clone(&clone_url, &clone)?;
+
+        assert_eq!(std::fs::read_to_string(clone.join("README.md"))?, "Version 2");
+        assert!(clone.join("untracked").exists());
+        assert!(clone.join(".git/doks-marker").exists());
+
+        // Concurrent syncs of the clone wait for each other
+        commit(&upstream, "README.md", "Version 3")?;
+        let syncs = (0..4)
+            .map(|_| {
+                let (clone_url, clone) = (clone_url.clone(), clone.clone());
+                std::thread::spawn(move || sync_clone(&clone_url, &clone).map(|_| ()))
+            })
+            .collect::<Vec<_>>();
+
+        for sync in syncs {
+            sync.join().expect("Sync panicked")?;
+        }
+
+        assert_eq!(std::fs::read_to_string(clone.join("README.md"))?, "Version 3");
+        assert!(clone.join(".git/doks-marker").exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_topics_tags() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let upstream = Repository::init(root.path().join("upstream"))?;
+        commit(&upstream, "README.md", "Restart the database")?;
+
+        let api = Router::new().route(
+            "/repos/:owner/:name/topics",
+            get(|UrlPath((owner, name)): UrlPath<(String, String)>| async move {
+                Json(json!({ "names": if (owner.as_str(), name.as_str()) == ("wlezzar", "doks") { vec!["search", "rust"] } else { vec![] } }))
+            }),
+        );
+        let server = axum::Server::try_bind(&SocketAddr::from(([127, 0, 0, 1], 0)))?.serve(api.into_make_service());
+        let endpoint = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+
+        let source = GithubSource {
+            source_id: "github".to_string(),
+            lister: Box::new(GithubRepoStaticList {
+                list: vec![RepositoryInfo {
+                    name: "wlezzar/doks".to_string(),
+                    clone_url: root.path().join("upstream").to_string_lossy().to_string(),
+                    topics: vec![],
+                }],
+                endpoint,
+                token: None,
+            }),
+            include: vec![Regex::new(".*\\.md$")?],
+            exclude: vec![],
+            relative_patterns: false,
+            strip_code_blocks: false,
+            strip_notebook_outputs: false,
+            sections: false,
+            max_file_size: None,
+            ignore_files: true,
+            clone_dir: root.path().join("clones"),
+        };
+
+        let documents = source.fetch().collect::<anyhow::Result<Vec<_>>>().await?;
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].tags, vec!["search".to_string(), "rust".to_string()]);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,199 @@
+use scraper::{ElementRef, Html, Node, Selector};
+
+use crate::model::Document;
+use crate::sources::sections::{HeadingMark, Sections};
+
+/// Elements whose content is never part of the text: code, styles, and the boilerplate repeated
+/// on every page of a site (navigation, headers, footers...).
+const SKIPPED: &[&str] = &[
+    "script", "style", "noscript", "template", "svg", "iframe", "object", "form", "button", "nav", "header", "footer", "aside",
+];
+
+/// Elements starting a new line in the text.
+const BLOCKS: &[&str] = &[
+    "address", "article", "blockquote", "br", "dd", "div", "dl", "dt", "figcaption", "h1", "h2", "h3", "h4", "h5", "h6",
+    "hr", "li", "main", "ol", "p", "pre", "section", "table", "td", "th", "tr", "ul",
+];
+
+const HEADINGS: &[&str] = &["h1", "h2", "h3", "h4", "h5", "h6"];
+
+/// The text of an HTML page, without its markup and boilerplate.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct HtmlText {
+    pub title: Option<String>,
+    pub text: String,
+    pub headings: Vec<String>,
+    /// Cut at the headings having an `id`, the only ones that can be linked to.
+    pub sections: Sections,
+}
+
+/// Whether the document is an HTML page, according to its link or to its content.
+pub fn is_html(document: &Document) -> bool {
+    let link = document.link.to_lowercase();
+    let start = document.content.trim_start().chars().take(15).collect::<String>().to_lowercase();
+
+    [".html", ".htm", ".xhtml"].iter().any(|extension| link.ends_with(extension))
+        || start.starts_with("<!doctype html")
+        || start.starts_with("<html")
+}
+
+/// Replaces the content of the HTML documents by their text, and their title by the page title
+/// (if any). The headings go to the `headings` metadata. Returns the sections of the page.
+pub fn process(document: &mut Document) -> Sections {
+    if !is_html(document) {
+        return Sections::default();
+    }
+
+    let extracted = extract_text(&document.content);
+
+    if let Some(title) = extracted.title {
+        document.title = title;
+    }
+
+    if !extracted.headings.is_empty() {
+        document.metadata.insert("headings".to_string(), extracted.headings.join("\n"));
+    }
+
+    document.content = extracted.text;
+    extracted.sections
+}
+
+/// Extracts the text of the main content of the page: its `main` or `article` element when it
+/// has one, its whole body otherwise, without the scripts, styles and navigation elements.
+pub fn extract_text(html: &str) -> HtmlText {
+    let html = Html::parse_document(html);
+    let selector = |selector: &str| Selector::parse(selector).unwrap();
+
+    let title = html
+        .select(&selector("title"))
+        .next()
+        .map(|title| normalize(&title.text().collect::<String>()))
+        .filter(|title| !title.is_empty());
+
+    let root = ["main", "article", "[role=main]", "body"]
+        .iter()
+        .find_map(|candidate| html.select(&selector(candidate)).next())
+        .unwrap_or_else(|| html.root_element());
+
+    let mut extracted = HtmlText { title, ..HtmlText::default() };
+    let mut text = String::new();
+    let mut marks = vec![];
+    collect_text(root, &mut text, &mut extracted.headings, &mut marks);
+
+    let mut sections = Sections::cut(&text, marks);
+    sections.intro = remove_blank_lines(&sections.intro);
+    for section in &mut sections.sections {
+        section.text = remove_blank_lines(&section.text);
+    }
+
+    extracted.text = remove_blank_lines(&text);
+    extracted.sections = sections;
+    extracted
+}
+
+fn remove_blank_lines(text: &str) -> String {
+    text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn collect_text(element: ElementRef, text: &mut String, headings: &mut Vec<String>, marks: &mut Vec<HeadingMark>) {
+    let name = element.value().name();
+
+    if SKIPPED.contains(&name) {
+        return;
+    }
+
+    if HEADINGS.contains(&name) {
+        let heading = normalize(&element.text().collect::<String>());
+
+        if !heading.is_empty() {
+            if let Some(id) = element.value().id() {
+                marks.push(HeadingMark { offset: text.len(), heading: heading.clone(), id: Some(id.to_string()) });
+            }
+
+            headings.push(heading);
+        }
+    }
+
+    let block = BLOCKS.contains(&name);
+
+    if block {
+        text.push('\n');
+    }
+
+    for child in element.children() {
+        match (ElementRef::wrap(child), child.value()) {
+            (Some(child), _) => collect_text(child, text, headings, marks),
+            (None, Node::Text(content)) => {
+                // Whitespaces are not significant in HTML, except at the boundaries of elements
+                if content.starts_with(char::is_whitespace) {
+                    text.push(' ');
+                }
+
+                text.push_str(&normalize(content));
+
+                if content.ends_with(char::is_whitespace) {
+                    text.push(' ');
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if block {
+        text.push('\n');
+    }
+}
+
+/// Collapses the whitespaces.
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sources::html::extract_text;
+
+    #[test]
+    fn test_extract_text() {
+        let html = r#"
+<!DOCTYPE html>
+<html>
+<head><title>Runbook | Docs</title><style>body { color: red }</style></head>
+<body>
+  <nav><a href="/">Home</a> <a href="/docs">Docs</a></nav>
+  <main>
+    <h1>Database   runbook</h1>
+    <p>Restart the <b>database</b>, then check the <a href="/lag">replication lag</a>.</p>
+    <script>track("page")</script>
+    <ul><li>Primary</li><li>Replica</li></ul>
+  </main>
+  <footer>Copyright</footer>
+</body>
+</html>
+"#;
+
+        let extracted = extract_text(html);
+
+        assert_eq!(extracted.title.as_deref(), Some("Runbook | Docs"));
+        assert_eq!(extracted.headings, vec!["Database runbook"]);
+        assert_eq!(extracted.text, "Database runbook\nRestart the database, then check the replication lag.\nPrimary\nReplica");
+    }
+
+    #[test]
+    fn test_sections() {
+        let html = r#"<body><p>Intro</p><h2 id="restart">Restart</h2><p>Run it.</p><h3>Notes</h3><p>None.</p><h2 id="backup">Backup</h2></body>"#;
+
+        let sections = extract_text(html).sections;
+
+        assert_eq!(sections.intro, "Intro");
+        assert_eq!(
+            sections.sections.iter().map(|section| (section.anchor.as_str(), section.text.as_str())).collect::<Vec<_>>(),
+            vec![("restart", "Restart\nRun it.\nNotes\nNone."), ("backup", "Backup")]
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use regex::Regex;
+
+use crate::utils::glob::glob_to_regex;
+
+/// The files listing, with the syntax of `.gitignore`, the paths to skip in their directory and
+/// below. `.doksignore` applies after `.gitignore`, so it can also bring back the ignored files.
+pub const IGNORE_FILES: &[&str] = &[".gitignore", ".doksignore"];
+
+/// A line of an ignore file.
+#[derive(Debug)]
+struct Pattern {
+    /// Matches the paths relative to the directory of the ignore file.
+    regex: Regex,
+    /// `!pattern`: brings back the paths ignored by the previous patterns.
+    negated: bool,
+    /// `pattern/`: only matches directories.
+    directory: bool,
+}
+
+impl Pattern {
+    /// `None` for the blank lines and the comments.
+    fn parse(line: &str) -> Option<anyhow::Result<Pattern>> {
+        let line = line.trim_end();
+
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (directory, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+
+        // Without a slash, the pattern matches the names at any depth
+        let regex = match line.strip_prefix('/') {
+            Some(anchored) => format!("^{}$", glob_to_regex(anchored)),
+            None if line.contains('/') => format!("^{}$", glob_to_regex(line)),
+            None => format!("^(.*/)?{}$", glob_to_regex(line)),
+        };
+
+        Some(Regex::new(&regex).map(|regex| Pattern { regex, negated, directory }).map_err(Into::into))
+    }
+}
+
+/// The patterns of the ignore files found under a directory, read once per directory.
+#[derive(Clone)]
+pub struct IgnoreFiles {
+    root: PathBuf,
+    patterns: Arc<Mutex<HashMap<PathBuf, Arc<Vec<Pattern>>>>>,
+}
+
+impl IgnoreFiles {
+    /// Ignore files above the root are not read.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        IgnoreFiles { root: root.into(), patterns: Arc::default() }
+    }
+
+    /// Whether the path, under the root, is ignored by the ignore files of its directory and of
+    /// the ones above it, or is in an ignored directory.
+    pub async fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let relative = match path.strip_prefix(&self.root) {
+            Ok(relative) => relative,
+            Err(_) => return false,
+        };
+
+        let mut current = self.root.clone();
+        let mut components = relative.components().peekable();
+
+        while let Some(component) = components.next() {
+            current.push(component);
+            let last = components.peek().is_none();
+
+            if self.matches(&current, !last || is_dir).await {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// The last pattern matching the path decides, looking at the deepest ignore files first.
+    async fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        for directory in path.ancestors().skip(1).take_while(|directory| directory.starts_with(&self.root)) {
+            let relative = match path.strip_prefix(directory) {
+                Ok(relative) => relative.to_string_lossy(),
+                Err(_) => continue,
+            };
+
+            let patterns = self.patterns(directory).await;
+            let matching = patterns.iter().rev().find(|pattern| (is_dir || !pattern.directory) && pattern.regex.is_match(&relative));
+
+            if let Some(pattern) = matching {
+                return !pattern.negated;
+            }
+        }
+
+        false
+    }
+
+    async fn patterns(&self, directory: &Path) -> Arc<Vec<Pattern>> {
+        if let Some(patterns) = self.patterns.lock().unwrap().get(directory) {
+            return patterns.clone();
+        }
+
+        let mut patterns = vec![];
+
+        for file in IGNORE_FILES {
+            let path = directory.join(file);
+
+            let content = match tokio::fs::read_to_string(&path).await {
+                Ok(content) => content,
+                Err(_) => continue,
+            };
+
+            for pattern in content.lines().filter_map(Pattern::parse) {
+                match pattern {
+                    Ok(pattern) => patterns.push(pattern),
+                    Err(err) => log::warn!("Skipping a pattern of {:?}: {:#}", path, err),
+                }
+            }
+        }
+
+        let patterns = Arc::new(patterns);
+        self.patterns.lock().unwrap().insert(directory.to_path_buf(), patterns.clone());
+        patterns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use crate::sources::ignore::IgnoreFiles;
+
+    #[tokio::test]
+    async fn test_ignore_files() -> anyhow::Result<()> {
+        let root = TempDir::new("doks-tests")?;
+        let root = root.path();
+        std::fs::create_dir_all(root.join("docs/build"))?;
+        std::fs::write(root.join(".gitignore"), "# dependencies\nnode_modules/\n*.log\n/target\n!keep.log\n")?;
+        std::fs::write(root.join("docs/.doksignore"), "build\ndrafts/*.md\n")?;
+
+        let ignores = IgnoreFiles::new(root);
+        let ignored = |path: &str, is_dir: bool| {
+            let ignores = ignores.clone();
+            let path = root.join(path);
+            async move { ignores.is_ignored(&path, is_dir).await }
+        };
+
+        assert!(ignored("node_modules", true).await);
+        assert!(ignored("web/node_modules/react/README.md", false).await);
+        assert!(!ignored("node_modules", false).await);
+        assert!(ignored("docs/debug.log", false).await);
+        assert!(!ignored("docs/keep.log", false).await);
+        assert!(ignored("target", true).await);
+        assert!(!ignored("docs/target", true).await);
+        assert!(ignored("docs/build/index.md", false).await);
+        assert!(ignored("docs/drafts/todo.md", false).await);
+        assert!(!ignored("drafts/todo.md", false).await);
+        assert!(!ignored("docs/README.md", false).await);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag};
+
+use crate::sources::sections::{HeadingMark, Sections};
+
+/// The text of a markdown document, without its markup.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct MarkdownText {
+    pub text: String,
+    pub headings: Vec<String>,
+    pub sections: Sections,
+}
+
+pub fn is_markdown(path: &str) -> bool {
+    let path = path.to_lowercase();
+
+    [".md", ".markdown", ".mdx"].iter().any(|extension| path.ends_with(extension))
+}
+
+/// Extracts the text of the markdown document: the link destinations, images (badges mostly) and
+/// raw HTML are dropped, and so is the content of the code blocks when `strip_code_blocks`.
+pub fn extract_text(markdown: &str, strip_code_blocks: bool) -> MarkdownText {
+    let mut extracted = MarkdownText::default();
+    let mut heading: Option<String> = None;
+    let mut marks = vec![];
+    let mut mark = HeadingMark { offset: 0, heading: String::new(), id: None };
+    // Nested images (e.g. `![![a](b)](c)`) are skipped as a whole
+    let mut skipped = 0;
+
+    let push = |extracted: &mut MarkdownText, heading: &mut Option<String>, text: &str| {
+        extracted.text.push_str(text);
+
+        if let Some(heading) = heading {
+            heading.push_str(text);
+        }
+    };
+
+    for event in Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_HEADING_ATTRIBUTES) {
+        match event {
+            Event::Start(Tag::Image(..)) | Event::Start(Tag::Link(LinkType::Autolink, ..)) => skipped += 1,
+            Event::End(Tag::Image(..)) | Event::End(Tag::Link(LinkType::Autolink, ..)) => skipped -= 1,
+            Event::Start(Tag::CodeBlock(_)) if strip_code_blocks => skipped += 1,
+            Event::End(Tag::CodeBlock(_)) if strip_code_blocks => skipped -= 1,
+            _ if skipped > 0 => {}
+            Event::Start(Tag::Heading(_, id, _)) => {
+                heading = Some(String::new());
+                mark = HeadingMark { offset: extracted.text.len(), heading: String::new(), id: id.map(str::to_string) };
+            }
+            Event::End(Tag::Heading(..)) => {
+                if let Some(heading) = heading.take().filter(|heading| !heading.trim().is_empty()) {
+                    extracted.headings.push(heading.trim().to_string());
+                    marks.push(HeadingMark { heading: heading.trim().to_string(), ..mark.clone() });
+                }
+
+                extracted.text.push('\n');
+            }
+            Event::Text(text) | Event::Code(text) => push(&mut extracted, &mut heading, &text),
+            Event::SoftBreak | Event::HardBreak => push(&mut extracted, &mut heading, " "),
+            Event::End(Tag::Paragraph | Tag::CodeBlock(_) | Tag::Item | Tag::TableRow | Tag::TableHead | Tag::BlockQuote) => {
+                extracted.text.push('\n')
+            }
+            Event::End(Tag::TableCell) => extracted.text.push(' '),
+            _ => {}
+        }
+    }
+
+    extracted.sections = Sections::cut(&extracted.text, marks);
+    extracted.text = extracted.text.trim().to_string();
+    extracted
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sources::markdown::extract_text;
+
+    #[test]
+    fn test_extract_text() {
+        let markdown = r#"
+# The **doks** project
+
+[![Build](https://img.shields.io/badge/build-passing-green)](https://ci.example.com)
+
+Search your [documentation](https://wiki.example.com/docs) from `doks`, see <https://doks.dev>.
+<div align="center">centered</div>
+
+```shell
+cargo install doks
+```
+"#;
+
+        let extracted = extract_text(markdown, false);
+        assert_eq!(extracted.headings, vec!["The doks project"]);
+        assert_eq!(
+            extracted.text,
+            "The doks project\n\nSearch your documentation from doks, see .\ncargo install doks"
+        );
+
+        let extracted = extract_text(markdown, true);
+        assert!(extracted.text.ends_with("see ."));
+    }
+
+    #[test]
+    fn test_sections() {
+        let markdown = "Intro\n\n## Deployment\n\nRun `make deploy`.\n\n### Rollback {#undo}\n\nRevert it.\n\n## Deployment\n\nAgain.";
+
+        let sections = extract_text(markdown, false).sections;
+        assert_eq!(sections.intro, "Intro");
+        assert_eq!(
+            sections.sections.iter().map(|section| (section.anchor.as_str(), section.text.as_str())).collect::<Vec<_>>(),
+            vec![
+                ("deployment", "Deployment\nRun make deploy."),
+                ("undo", "Rollback\nRevert it."),
+                ("deployment-1", "Deployment\nAgain."),
+            ]
+        );
+    }
+}
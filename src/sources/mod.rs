@@ -7,6 +7,20 @@ use crate::model::Document;
 pub mod static_list;
 pub mod fs;
 pub mod gh;
+pub mod asana;
+pub mod discord;
+pub mod gdocs;
+pub mod airtable;
+pub mod backstage;
+pub mod frontmatter;
+pub mod markdown;
+pub mod html;
+pub mod office;
+pub mod notebook;
+pub mod asciidoc;
+pub mod tagged;
+pub mod sections;
+pub mod ignore;
 
 // Send is required to use `batched(...)` on the stream.
 pub type DocStream = Pin<Box<dyn Stream<Item=anyhow::Result<Document>> + Send>>;
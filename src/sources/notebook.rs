@@ -0,0 +1,134 @@
+use anyhow::Context;
+use serde_json::Value;
+
+use crate::model::Document;
+use crate::sources::markdown;
+
+pub fn is_notebook(path: &str) -> bool {
+    path.to_lowercase().ends_with(".ipynb")
+}
+
+/// Replaces the JSON of a Jupyter notebook by the text of its cells: the markdown cells without
+/// their markup, the code cells with their outputs unless `strip_outputs` (images are dropped).
+///
+/// The headings of the markdown cells go to the `headings` metadata, the number of cells of each
+/// type to the `markdown_cells` and `code_cells` metadata, and the kernel language (if set) to
+/// the `language` metadata.
+pub fn process(document: &mut Document, strip_code_blocks: bool, strip_outputs: bool) -> anyhow::Result<()> {
+    let notebook: Value = serde_json::from_str(&document.content).context("Invalid notebook")?;
+    let cells = notebook["cells"].as_array().context("Invalid notebook: no cells")?;
+
+    let mut blocks = vec![];
+    let mut headings = vec![];
+    let (mut markdown_cells, mut code_cells) = (0, 0);
+
+    for cell in cells {
+        let source = text(&cell["source"]);
+
+        match cell["cell_type"].as_str() {
+            Some("markdown") => {
+                let extracted = markdown::extract_text(&source, strip_code_blocks);
+                blocks.push(extracted.text);
+                headings.extend(extracted.headings);
+                markdown_cells += 1;
+            }
+            Some("code") => {
+                blocks.push(source);
+
+                if !strip_outputs {
+                    blocks.extend(cell["outputs"].as_array().into_iter().flatten().map(output));
+                }
+
+                code_cells += 1;
+            }
+            // Raw cells are mostly templating directives
+            _ => {}
+        }
+    }
+
+    document.content = blocks.iter().map(|block| block.trim()).filter(|block| !block.is_empty()).collect::<Vec<_>>().join("\n\n");
+    document.metadata.insert("markdown_cells".to_string(), markdown_cells.to_string());
+    document.metadata.insert("code_cells".to_string(), code_cells.to_string());
+
+    if !headings.is_empty() {
+        document.metadata.insert("headings".to_string(), headings.join("\n"));
+    }
+
+    if let Some(language) = notebook.pointer("/metadata/kernelspec/language").and_then(Value::as_str) {
+        document.metadata.insert("language".to_string(), language.to_string());
+    }
+
+    Ok(())
+}
+
+/// The text of a cell output: the printed text, or the text representation of the result.
+fn output(output: &Value) -> String {
+    match output["output_type"].as_str() {
+        Some("stream") => text(&output["text"]),
+        Some("execute_result" | "display_data") => text(&output["data"]["text/plain"]),
+        Some("error") => format!("{}: {}", output["ename"].as_str().unwrap_or_default(), output["evalue"].as_str().unwrap_or_default()),
+        _ => String::new(),
+    }
+}
+
+/// The notebooks texts are either strings or arrays of lines.
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::model::Document;
+    use crate::sources::notebook::process;
+
+    #[test]
+    fn test_process() -> anyhow::Result<()> {
+        let notebook = r##"{
+            "metadata": {"kernelspec": {"language": "python", "name": "python3"}},
+            "cells": [
+                {"cell_type": "markdown", "source": ["# Replication lag\n", "Plot the **lag** of the replicas"]},
+                {"cell_type": "code", "source": "lag = fetch_lag()\nlag.plot()", "outputs": [
+                    {"output_type": "stream", "text": ["fetched 3 replicas\n"]},
+                    {"output_type": "display_data", "data": {"image/png": "iVBORw0KGgo=", "text/plain": ["<Figure>"]}}
+                ]},
+                {"cell_type": "raw", "source": "{{ template }}"}
+            ]
+        }"##;
+        let document = || Document {
+            id: "lag.ipynb".to_string(),
+            source: "notebooks".to_string(),
+            title: "lag.ipynb".to_string(),
+            link: "lag.ipynb".to_string(),
+            content: notebook.to_string(),
+            metadata: HashMap::new(),
+            tags: vec![],
+            modified: None,
+        };
+
+        let mut processed = document();
+        process(&mut processed, false, false)?;
+        assert_eq!(
+            processed.content,
+            "Replication lag\nPlot the lag of the replicas\n\nlag = fetch_lag()\nlag.plot()\n\nfetched 3 replicas\n\n<Figure>"
+        );
+        assert_eq!(processed.metadata["headings"], "Replication lag");
+        assert_eq!(processed.metadata["markdown_cells"], "1");
+        assert_eq!(processed.metadata["code_cells"], "1");
+        assert_eq!(processed.metadata["language"], "python");
+
+        let mut processed = document();
+        process(&mut processed, false, true)?;
+        assert!(processed.content.ends_with("lag.plot()"));
+
+        let mut invalid = Document { content: "{".to_string(), ..document() };
+        assert!(process(&mut invalid, false, false).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+use std::io::{Cursor, Read};
+
+use anyhow::Context;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use zip::ZipArchive;
+
+/// The office documents formats: zip archives of XML files.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OfficeFormat {
+    Docx,
+    Odt,
+    Pptx,
+}
+
+/// The text of an office document, with the title from its properties (if set).
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct OfficeText {
+    pub title: Option<String>,
+    pub text: String,
+}
+
+/// The elements of an XML file holding its text.
+struct Markup {
+    /// The elements containing the text, every element when empty.
+    text: &'static [&'static [u8]],
+    /// The elements ending with a new line.
+    paragraphs: &'static [&'static [u8]],
+    /// The empty elements standing for a space (tabulations...).
+    spaces: &'static [&'static [u8]],
+    /// The empty elements standing for a new line.
+    breaks: &'static [&'static [u8]],
+}
+
+/// `<w:p><w:r><w:t>text</w:t></w:r></w:p>`
+const DOCX: Markup = Markup { text: &[b"t"], paragraphs: &[b"p"], spaces: &[b"tab"], breaks: &[b"br", b"cr"] };
+
+/// `<a:p><a:r><a:t>text</a:t></a:r></a:p>` in each slide.
+const PPTX: Markup = Markup { text: &[b"t"], paragraphs: &[b"p"], spaces: &[], breaks: &[b"br"] };
+
+/// `<text:p>text <text:span>styled</text:span></text:p>`
+const ODT: Markup = Markup { text: &[], paragraphs: &[b"p", b"h"], spaces: &[b"s", b"tab"], breaks: &[b"line-break"] };
+
+/// `<dc:title>title</dc:title>` in the document properties.
+const TITLE: Markup = Markup { text: &[b"title"], paragraphs: &[], spaces: &[], breaks: &[] };
+
+impl OfficeFormat {
+    pub fn of(path: &str) -> Option<Self> {
+        match path.rsplit_once('.').map(|(_, extension)| extension.to_lowercase()).as_deref() {
+            Some("docx") => Some(Self::Docx),
+            Some("odt") => Some(Self::Odt),
+            Some("pptx") => Some(Self::Pptx),
+            _ => None,
+        }
+    }
+
+    pub fn extract(self, bytes: &[u8]) -> anyhow::Result<OfficeText> {
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).context("Not a zip archive")?;
+
+        let (entries, properties, markup) = match self {
+            Self::Docx => (vec!["word/document.xml".to_string()], "docProps/core.xml", &DOCX),
+            Self::Odt => (vec!["content.xml".to_string()], "meta.xml", &ODT),
+            Self::Pptx => (slides(&archive), "docProps/core.xml", &PPTX),
+        };
+
+        let mut text = String::new();
+
+        for entry in entries {
+            text.push_str(&extract_xml(&read_entry(&mut archive, &entry)?, markup).with_context(|| format!("Invalid {}", entry))?);
+            text.push('\n');
+        }
+
+        // The properties are optional, and only used for the title
+        let title = read_entry(&mut archive, properties)
+            .and_then(|xml| extract_xml(&xml, &TITLE))
+            .map(|title| title.trim().to_string())
+            .ok()
+            .filter(|title| !title.is_empty());
+
+        let text = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>().join("\n");
+
+        Ok(OfficeText { title, text })
+    }
+}
+
+/// The slides of a presentation, in order: `ppt/slides/slide<n>.xml`.
+fn slides(archive: &ZipArchive<Cursor<&[u8]>>) -> Vec<String> {
+    let mut slides = archive
+        .file_names()
+        .filter_map(|name| {
+            let position = name.strip_prefix("ppt/slides/slide")?.strip_suffix(".xml")?.parse::<usize>().ok()?;
+            Some((position, name.to_string()))
+        })
+        .collect::<Vec<_>>();
+
+    slides.sort();
+    slides.into_iter().map(|(_, name)| name).collect()
+}
+
+fn read_entry(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> anyhow::Result<String> {
+    let mut content = String::new();
+    archive.by_name(name).with_context(|| format!("Missing {}", name))?.read_to_string(&mut content)?;
+
+    Ok(content)
+}
+
+fn extract_xml(xml: &str, markup: &Markup) -> anyhow::Result<String> {
+    let mut reader = Reader::from_str(xml);
+    let mut text = String::new();
+    // Depth in the elements containing the text
+    let mut depth = 0;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) if markup.text.contains(&element.local_name().as_ref()) => depth += 1,
+            Event::End(element) => {
+                let name = element.local_name();
+
+                if markup.text.contains(&name.as_ref()) {
+                    depth -= 1;
+                }
+
+                if markup.paragraphs.contains(&name.as_ref()) {
+                    text.push('\n');
+                }
+            }
+            Event::Empty(element) if markup.spaces.contains(&element.local_name().as_ref()) => text.push(' '),
+            Event::Empty(element) if markup.breaks.contains(&element.local_name().as_ref()) => text.push('\n'),
+            Event::Text(content) if markup.text.is_empty() || depth > 0 => text.push_str(&content.unescape()?),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    use crate::sources::office::OfficeFormat;
+
+    fn archive(entries: &[(&str, &str)]) -> anyhow::Result<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+
+        for (name, content) in entries {
+            writer.start_file(*name, SimpleFileOptions::default())?;
+            writer.write_all(content.as_bytes())?;
+        }
+
+        Ok(writer.finish()?.into_inner())
+    }
+
+    #[test]
+    fn test_extract() -> anyhow::Result<()> {
+        assert_eq!(OfficeFormat::of("/docs/Runbook.DOCX"), Some(OfficeFormat::Docx));
+        assert_eq!(OfficeFormat::of("/docs/runbook.md"), None);
+
+        let docx = archive(&[
+            (
+                "word/document.xml",
+                r#"<w:document xmlns:w="w"><w:body>
+                    <w:p><w:r><w:t>Restart the </w:t></w:r><w:r><w:t>database</w:t></w:r></w:p>
+                    <w:p><w:r><w:instrText>PAGE</w:instrText><w:t>Check the lag</w:t><w:tab/><w:t>&amp; wait</w:t></w:r></w:p>
+                </w:body></w:document>"#,
+            ),
+            ("docProps/core.xml", r#"<cp:coreProperties xmlns:cp="cp" xmlns:dc="dc"><dc:title>Runbook</dc:title></cp:coreProperties>"#),
+        ])?;
+        let extracted = OfficeFormat::Docx.extract(&docx)?;
+        assert_eq!(extracted.title.as_deref(), Some("Runbook"));
+        assert_eq!(extracted.text, "Restart the database\nCheck the lag & wait");
+
+        let odt = archive(&[(
+            "content.xml",
+            r#"<office:document-content xmlns:office="o" xmlns:text="t"><office:body><office:text><text:h>Setup</text:h><text:p>Install <text:span>doks</text:span><text:line-break/>then index</text:p></office:text></office:body></office:document-content>"#,
+        )])?;
+        let extracted = OfficeFormat::Odt.extract(&odt)?;
+        assert_eq!(extracted.title, None);
+        assert_eq!(extracted.text, "Setup\nInstall doks\nthen index");
+
+        let slide = |text: &str| format!(r#"<p:sld xmlns:p="p" xmlns:a="a"><a:p><a:r><a:t>{}</a:t></a:r></a:p></p:sld>"#, text);
+        let pptx = archive(&[("ppt/slides/slide10.xml", &slide("Last")), ("ppt/slides/slide2.xml", &slide("First"))])?;
+        assert_eq!(OfficeFormat::Pptx.extract(&pptx)?.text, "First\nLast");
+
+        assert!(OfficeFormat::Docx.extract(b"not a zip").is_err());
+
+        Ok(())
+    }
+}
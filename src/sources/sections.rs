@@ -0,0 +1,163 @@
+use std::collections::HashSet;
+
+use crate::model::Document;
+
+/// Separates the title of a document from the heading of its sections.
+const TITLE_SEPARATOR: &str = " › ";
+
+/// The text following a heading, up to the next one (whatever their levels).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Section {
+    pub heading: String,
+    /// The fragment of the link to the heading (`deployment` in `README.md#deployment`).
+    pub anchor: String,
+    /// The text of the section, its heading included.
+    pub text: String,
+}
+
+/// The text of a document cut at its headings.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct Sections {
+    /// The text before the first heading.
+    pub intro: String,
+    pub sections: Vec<Section>,
+}
+
+/// Where a heading starts in the text of a document, and its explicit anchor if any (`{#id}` in
+/// markdown, the `id` attribute in HTML).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct HeadingMark {
+    pub offset: usize,
+    pub heading: String,
+    pub id: Option<String>,
+}
+
+impl Sections {
+    /// Cuts the text at the heading marks, sorted by offset.
+    pub fn cut(text: &str, marks: Vec<HeadingMark>) -> Self {
+        let mut taken = HashSet::new();
+        let ends = marks.iter().skip(1).map(|mark| mark.offset).chain(std::iter::once(text.len())).collect::<Vec<_>>();
+
+        let sections = marks
+            .iter()
+            .zip(ends)
+            .map(|(mark, end)| Section {
+                heading: mark.heading.clone(),
+                anchor: match &mark.id {
+                    Some(id) => {
+                        taken.insert(id.clone());
+                        id.clone()
+                    }
+                    None => anchor(&mark.heading, &mut taken),
+                },
+                text: text[mark.offset..end].trim().to_string(),
+            })
+            .collect();
+
+        Sections { intro: text[..marks.first().map_or(text.len(), |mark| mark.offset)].trim().to_string(), sections }
+    }
+}
+
+/// The anchor generated for a heading the way GitHub does: lowercased, the punctuation removed
+/// and the spaces replaced by dashes. `taken` holds the anchors already used in the document,
+/// the duplicates get a `-1`, `-2`... suffix.
+pub fn anchor(heading: &str, taken: &mut HashSet<String>) -> String {
+    let slug = heading
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect::<String>();
+
+    let anchor = (0..)
+        .map(|n| if n == 0 { slug.clone() } else { format!("{}-{}", slug, n) })
+        .find(|anchor| !taken.contains(anchor))
+        .unwrap_or(slug);
+
+    taken.insert(anchor.clone());
+    anchor
+}
+
+/// One document per section: the section id and link are the ones of the document followed by
+/// the anchor (`README.md#deployment`), its heading is its only `headings` metadata, and its
+/// `parent_id` metadata is the id of the document. The text before the first heading keeps the
+/// id and link of the document. Returns the document as is when it has no sections.
+pub fn split(document: Document, sections: Sections) -> Vec<Document> {
+    if sections.sections.is_empty() {
+        return vec![document];
+    }
+
+    let mut documents = vec![];
+
+    if !sections.intro.is_empty() {
+        let mut metadata = document.metadata.clone();
+        metadata.remove("headings");
+
+        documents.push(Document { content: sections.intro.clone(), metadata, ..document.clone() });
+    }
+
+    for section in sections.sections {
+        let mut metadata = document.metadata.clone();
+        metadata.insert("parent_id".to_string(), document.id.clone());
+        metadata.insert("headings".to_string(), section.heading.clone());
+
+        documents.push(Document {
+            id: format!("{}#{}", document.id, section.anchor),
+            title: format!("{}{}{}", document.title, TITLE_SEPARATOR, section.heading),
+            link: format!("{}#{}", document.link, section.anchor),
+            content: section.text,
+            metadata,
+            ..document.clone()
+        });
+    }
+
+    documents
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use crate::model::Document;
+    use crate::sources::sections::{anchor, split, HeadingMark, Sections};
+
+    #[test]
+    fn test_anchor() {
+        let mut taken = HashSet::new();
+
+        assert_eq!(anchor("Deployment", &mut taken), "deployment");
+        assert_eq!(anchor("What's new in v2.0?", &mut taken), "whats-new-in-v20");
+        assert_eq!(anchor("snake_case & kebab-case", &mut taken), "snake_case--kebab-case");
+        assert_eq!(anchor("Deployment", &mut taken), "deployment-1");
+        assert_eq!(anchor("Deployment", &mut taken), "deployment-2");
+    }
+
+    #[test]
+    fn test_split() {
+        let document = Document {
+            id: "/docs/README.md".to_string(),
+            source: "docs".to_string(),
+            title: "README.md".to_string(),
+            link: "/docs/README.md".to_string(),
+            content: "A service\nDeployment\nRun the playbook".to_string(),
+            metadata: HashMap::from([("headings".to_string(), "Deployment".to_string())]),
+            tags: vec![],
+            modified: None,
+        };
+        let marks = vec![HeadingMark { offset: 10, heading: "Deployment".to_string(), id: None }];
+        let sections = Sections::cut(&document.content, marks);
+
+        let documents = split(document.clone(), sections);
+
+        assert_eq!(documents.len(), 2);
+        assert_eq!((documents[0].id.as_str(), documents[0].content.as_str()), ("/docs/README.md", "A service"));
+        assert_eq!(documents[1].id, "/docs/README.md#deployment");
+        assert_eq!(documents[1].link, "/docs/README.md#deployment");
+        assert_eq!(documents[1].title, "README.md › Deployment");
+        assert_eq!(documents[1].content, "Deployment\nRun the playbook");
+        assert_eq!(documents[1].metadata["parent_id"], "/docs/README.md");
+
+        assert_eq!(split(document.clone(), Sections::cut(&document.content, vec![])), vec![document]);
+    }
+}
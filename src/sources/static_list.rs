@@ -2,7 +2,7 @@ use crate::model::Document;
 use crate::sources::{DocStream, DocumentSource};
 
 pub struct StaticDocumentSource {
-    documents: Vec<Document>,
+    pub documents: Vec<Document>,
 }
 
 impl DocumentSource for StaticDocumentSource {
@@ -34,6 +34,8 @@ mod tests {
                 content: "content1".to_string(),
                 title: "title1".to_string(),
                 metadata: HashMap::new(),
+                tags: vec![],
+                modified: None,
             },
             Document {
                 id: "doc2".to_string(),
@@ -42,6 +44,8 @@ mod tests {
                 content: "content2".to_string(),
                 title: "title2".to_string(),
                 metadata: HashMap::new(),
+                tags: vec![],
+                modified: None,
             },
         ];
 
@@ -0,0 +1,64 @@
+use tokio_stream::StreamExt;
+
+use crate::sources::{DocStream, DocumentSource};
+
+/// Adds the tags of the source configuration to all the documents of the wrapped source, along
+/// with the ones they already have (frontmatter tags, GitHub topics...).
+pub struct TaggedSource {
+    pub source: Box<dyn DocumentSource>,
+    pub tags: Vec<String>,
+}
+
+impl DocumentSource for TaggedSource {
+    fn fetch(&self) -> DocStream {
+        let tags = self.tags.clone();
+
+        Box::pin(self.source.fetch().map(move |document| {
+            document.map(|mut document| {
+                for tag in &tags {
+                    if !document.tags.contains(tag) {
+                        document.tags.push(tag.clone());
+                    }
+                }
+
+                document
+            })
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tokio_stream::StreamExt;
+
+    use crate::model::Document;
+    use crate::sources::DocumentSource;
+    use crate::sources::static_list::StaticDocumentSource;
+    use crate::sources::tagged::TaggedSource;
+
+    #[tokio::test]
+    async fn test_tagged_source() -> anyhow::Result<()> {
+        let document = Document {
+            id: "runbook".to_string(),
+            source: "docs".to_string(),
+            title: "Runbook".to_string(),
+            link: "/docs/runbook.md".to_string(),
+            content: "Restart the database".to_string(),
+            metadata: HashMap::new(),
+            tags: vec!["database".to_string(), "ops".to_string()],
+            modified: None,
+        };
+
+        let source = TaggedSource {
+            source: Box::new(StaticDocumentSource { documents: vec![document] }),
+            tags: vec!["ops".to_string(), "internal".to_string()],
+        };
+
+        let documents = source.fetch().collect::<anyhow::Result<Vec<_>>>().await?;
+        assert_eq!(documents[0].tags, vec!["database".to_string(), "ops".to_string(), "internal".to_string()]);
+
+        Ok(())
+    }
+}
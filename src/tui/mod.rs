@@ -0,0 +1,235 @@
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Position};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use tokio_stream::StreamExt;
+
+use crate::search::{FoundItem, SearchEngine, SearchRequest};
+
+/// Time to wait after the last keystroke before running the search.
+const DEBOUNCE: Duration = Duration::from_millis(150);
+const MAX_RESULTS: usize = 50;
+
+/// Interactive search: results are refreshed while typing, Up/Down select a result and Enter
+/// opens its link. Esc or Ctrl-C exits.
+pub async fn run(engine: &dyn SearchEngine, query: &str) -> anyhow::Result<()> {
+    let mut terminal = ratatui::init();
+    let result = App::new(query).run(&mut terminal, engine).await;
+    ratatui::restore();
+
+    result
+}
+
+struct App {
+    query: String,
+    results: Vec<FoundItem>,
+    list: ListState,
+    status: String,
+    /// Set when the query changed and wasn't searched yet.
+    changed_at: Option<Instant>,
+}
+
+impl App {
+    fn new(query: &str) -> Self {
+        Self {
+            query: query.to_string(),
+            results: vec![],
+            list: ListState::default(),
+            status: "Type to search".to_string(),
+            changed_at: Some(Instant::now() - DEBOUNCE),
+        }
+    }
+
+    async fn run(mut self, terminal: &mut DefaultTerminal, engine: &dyn SearchEngine) -> anyhow::Result<()> {
+        loop {
+            if self.changed_at.is_some_and(|at| at.elapsed() >= DEBOUNCE) {
+                self.changed_at = None;
+                self.search(engine).await;
+            }
+
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(Duration::from_millis(50))? {
+                continue;
+            }
+
+            let key = match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => key,
+                _ => continue,
+            };
+
+            match (key.code, key.modifiers) {
+                (KeyCode::Esc, _) | (KeyCode::Char('c'), KeyModifiers::CONTROL) => return Ok(()),
+                (KeyCode::Enter, _) => self.open_selected(),
+                (KeyCode::Up, _) => self.list.select_previous(),
+                (KeyCode::Down, _) => self.list.select_next(),
+                (KeyCode::Backspace, _) => {
+                    self.query.pop();
+                    self.changed_at = Some(Instant::now());
+                }
+                (KeyCode::Char(c), _) => {
+                    self.query.push(c);
+                    self.changed_at = Some(Instant::now());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    async fn search(&mut self, engine: &dyn SearchEngine) {
+        if self.query.trim().is_empty() {
+            self.results.clear();
+            self.list.select(None);
+            self.status = "Type to search".to_string();
+            return;
+        }
+
+        let request = SearchRequest { limit: MAX_RESULTS, ..SearchRequest::new(&self.query) };
+        let results = match engine.search(&request).await {
+            Ok(stream) => stream.collect::<anyhow::Result<Vec<_>>>().await,
+            Err(err) => Err(err),
+        };
+
+        // Keep the previous results while the query is being typed (e.g. unbalanced quotes)
+        match results {
+            Ok(results) => {
+                self.status = format!("{} results", results.len());
+                self.list.select(if results.is_empty() { None } else { Some(0) });
+                self.results = results;
+            }
+            Err(err) => self.status = format!("Search failed: {:#}", err),
+        }
+    }
+
+    fn open_selected(&mut self) {
+        if let Some(item) = self.list.selected().and_then(|i| self.results.get(i)) {
+            self.status = match open::that_detached(&item.link) {
+                Ok(_) => format!("Opened: {}", item.link),
+                Err(err) => format!("Couldn't open {}: {}", item.link, err),
+            };
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [query_area, body_area, status_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ]).areas(frame.area());
+
+        let [list_area, preview_area] = Layout::horizontal([
+            Constraint::Percentage(40),
+            Constraint::Percentage(60),
+        ]).areas(body_area);
+
+        frame.render_widget(Paragraph::new(self.query.as_str()).block(Block::bordered().title(" Search ")), query_area);
+        frame.set_cursor_position(Position::new(query_area.x + 1 + self.query.chars().count() as u16, query_area.y + 1));
+
+        let items = self.results
+            .iter()
+            .map(|item| ListItem::new(Line::from(vec![Span::raw(item.title.as_str()), Span::raw(format!("  {}", item.source)).dim()])))
+            .collect::<Vec<_>>();
+
+        let list = List::new(items)
+            .block(Block::bordered().title(" Results "))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+
+        frame.render_stateful_widget(list, list_area, &mut self.list);
+
+        let preview = match self.list.selected().and_then(|i| self.results.get(i)) {
+            Some(item) => vec![
+                Line::from(item.title.as_str()).bold(),
+                Line::from(item.link.as_str()).underlined(),
+                Line::from(format!("source: {}  score: {:.3}", item.source, item.score)).dim(),
+                Line::default(),
+                Line::from(highlighted_spans(&item.snippet)),
+            ],
+            None => vec![],
+        };
+
+        frame.render_widget(
+            Paragraph::new(preview).wrap(Wrap { trim: true }).block(Block::bordered().title(" Preview ")),
+            preview_area,
+        );
+        frame.render_widget(Line::from(self.status.as_str()).dim(), status_area);
+    }
+}
+
+/// Converts an html snippet as returned by the engines into spans, making the highlighted parts
+/// bold.
+fn highlighted_spans(snippet: &str) -> Vec<Span<'static>> {
+    snippet_parts(snippet)
+        .into_iter()
+        .map(|(text, highlighted)| if highlighted { Span::raw(text).bold() } else { Span::raw(text) })
+        .collect()
+}
+
+/// Splits an html snippet as returned by the engines into its unescaped texts, each flagged when
+/// highlighted (`<b>`, `<em>` or `<mark>`).
+pub(crate) fn snippet_parts(snippet: &str) -> Vec<(String, bool)> {
+    let mut parts = vec![];
+    let mut highlighted = false;
+    let mut rest = snippet;
+
+    while !rest.is_empty() {
+        let tag = ["<b>", "</b>", "<em>", "</em>", "<mark>", "</mark>"]
+            .iter()
+            .filter_map(|tag| rest.find(tag).map(|position| (position, *tag)))
+            .min();
+
+        let (text, next) = match tag {
+            Some((position, tag)) => (&rest[..position], Some(tag)),
+            None => (rest, None),
+        };
+
+        if !text.is_empty() {
+            parts.push((unescape_html(text), highlighted));
+        }
+
+        match next {
+            Some(tag) => {
+                highlighted = !tag.starts_with("</");
+                rest = &rest[text.len() + tag.len()..];
+            }
+            None => break,
+        }
+    }
+
+    parts
+}
+
+pub(crate) fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::style::Stylize;
+    use ratatui::text::Span;
+
+    use crate::tui::highlighted_spans;
+
+    #[test]
+    fn test_highlighted_spans() {
+        assert_eq!(
+            highlighted_spans("Restart <b>the</b> <em>db</em> &amp; <mark>x</mark>"),
+            vec![
+                Span::raw("Restart "),
+                Span::raw("the").bold(),
+                Span::raw(" "),
+                Span::raw("db").bold(),
+                Span::raw(" & "),
+                Span::raw("x").bold(),
+            ]
+        );
+        assert!(highlighted_spans("").is_empty());
+    }
+}
@@ -0,0 +1,77 @@
+/// Translates a path glob into an unanchored regex: `**` matches across directories, `*` and `?`
+/// within a single one (`docs/**/*.md` matches `docs/setup.md` and `docs/ops/runbook.md`), and
+/// `[...]` one of the listed characters (`[!...]` one that isn't).
+pub fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::new();
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                let class = chars.clone().take_while(|c| *c != ']').collect::<String>();
+                let members = class.strip_prefix('!').unwrap_or(&class);
+
+                // An unterminated or empty class is a literal `[`
+                if members.is_empty() || !chars.clone().any(|c| c == ']') {
+                    regex.push_str("\\[");
+                    continue;
+                }
+
+                // Consumes the class and its `]`
+                chars.nth(class.chars().count());
+                regex.push_str(if class.starts_with('!') { "[^" } else { "[" });
+
+                for c in members.chars() {
+                    if matches!(c, '[' | '\\' | '&' | '~') {
+                        regex.push('\\');
+                    }
+
+                    regex.push(c);
+                }
+
+                regex.push(']');
+            }
+            other => regex.push_str(&regex::escape(&other.to_string())),
+        }
+    }
+
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use crate::utils::glob::glob_to_regex;
+
+    #[test]
+    fn test_glob_to_regex() {
+        let matches = |glob: &str, path: &str| Regex::new(&format!("^{}$", glob_to_regex(glob))).unwrap().is_match(path);
+
+        assert!(matches("docs/**", "docs/ops/runbook.md"));
+        assert!(!matches("docs/**", "src/docs/runbook.md"));
+        assert!(matches("docs/**/*.md", "docs/setup.md"));
+        assert!(matches("docs/**/*.md", "docs/ops/runbook.md"));
+        assert!(!matches("docs/*.md", "docs/ops/runbook.md"));
+        assert!(matches("README.?d", "README.md"));
+        assert!(!matches("README.md", "READMEamd"));
+        assert!(matches("*.py[cod]", "cache.pyc"));
+        assert!(!matches("*.py[cod]", "main.py"));
+        assert!(matches("[!_]*.md", "setup.md"));
+        assert!(!matches("[!_]*.md", "_draft.md"));
+        assert!(matches("[id", "[id"));
+        assert!(matches("[]", "[]"));
+    }
+}
@@ -1,5 +1,7 @@
+pub mod glob;
 pub mod json;
 pub mod streams;
+pub mod table;
 
 use std::mem;
 
@@ -0,0 +1,53 @@
+/// Formats rows as left-aligned columns separated by two spaces. The first row is the header.
+pub fn format_table<R: AsRef<[String]>>(rows: &[R]) -> Vec<String> {
+    let columns = rows.iter().map(|row| row.as_ref().len()).max().unwrap_or(0);
+    let widths = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| row.as_ref().get(column))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+
+    rows.iter()
+        .map(|row| {
+            let row = row.as_ref();
+            let cells = row.iter().enumerate().map(|(column, cell)| {
+                // The last column isn't padded to avoid trailing spaces
+                if column == row.len() - 1 {
+                    cell.to_string()
+                } else {
+                    format!("{:width$}", cell, width = widths[column])
+                }
+            });
+
+            cells.collect::<Vec<_>>().join("  ")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::table::format_table;
+
+    #[test]
+    fn test_format_table() {
+        let rows = vec![
+            vec!["ID".to_string(), "TYPE".to_string(), "LINK".to_string()],
+            vec!["github".to_string(), "github".to_string(), "https://github.com".to_string()],
+            vec!["docs".to_string(), "fs".to_string(), "/docs".to_string()],
+        ];
+
+        assert_eq!(
+            format_table(&rows),
+            vec![
+                "ID      TYPE    LINK",
+                "github  github  https://github.com",
+                "docs    fs      /docs",
+            ]
+        );
+        assert!(format_table::<Vec<String>>(&[]).is_empty());
+    }
+}